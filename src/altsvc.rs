@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use url::{Origin, Url};
+
+use clock::{Clock, SystemClock};
+use header::AltSvc;
+use header::item::AltService;
+use version::Version;
+
+// The longest freshness lifetime stored, the largest delta-seconds
+// value caches must handle. [RFC7234 Section 1.2.1]
+const MAX_AGE: u64 = 2_147_483_648;
+
+/// A cache of alternative services advertised by origins,
+/// [RFC7838 Section 2.2]
+///
+/// Each origin keeps the alternatives of its latest `Alt-Svc` header
+/// field until they expire. Clients use it to move to a faster
+/// protocol like HTTP/3 for later requests.
+#[derive(Clone, Debug)]
+pub struct AltSvcCache<C: Clock = SystemClock> {
+    clock: C,
+    entries: HashMap<Origin, Vec<(AltService, SystemTime)>>,
+}
+
+impl AltSvcCache {
+    /// Constructs an empty cache using the system clock.
+    pub fn new() -> AltSvcCache {
+        AltSvcCache::with_clock(SystemClock)
+    }
+}
+
+impl Default for AltSvcCache {
+    fn default() -> AltSvcCache {
+        AltSvcCache::new()
+    }
+}
+
+impl<C: Clock> AltSvcCache<C> {
+    /// Constructs an empty cache with a clock.
+    pub fn with_clock(clock: C) -> AltSvcCache<C> {
+        AltSvcCache {
+            clock,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Stores the alternatives an origin advertised in a response.
+    ///
+    /// They replace all alternatives stored for the origin before.
+    /// Freshness lifetimes are capped at 2^31 seconds. Origins with a scheme other than `http` and `https` are ignored.
+    pub fn update(&mut self, origin: &Url, alt_svc: &AltSvc) {
+        let origin = match origin.scheme() {
+            "http" | "https" => origin.origin(),
+            _ => return,
+        };
+        match *alt_svc {
+            AltSvc::Clear => {
+                self.entries.remove(&origin);
+            }
+            AltSvc::Services(ref services) => {
+                let now = self.clock.now();
+                let services = services.iter()
+                    .filter_map(|x| {
+                        let max_age = Duration::from_secs(x.max_age.min(MAX_AGE));
+                        now.checked_add(max_age).map(|expires| (x.clone(), expires))
+                    })
+                    .collect();
+                self.entries.insert(origin, services);
+            }
+        }
+    }
+
+    /// Returns the fresh alternatives of an origin in order of
+    /// preference.
+    pub fn get(&self, origin: &Url) -> Vec<&AltService> {
+        let now = self.clock.now();
+        self.entries
+            .get(&origin.origin())
+            .map_or_else(Vec::new, |services| {
+                services.iter().filter(|x| x.1 > now).map(|x| &x.0).collect()
+            })
+    }
+
+    /// Selects the most preferred fresh alternative with one of the
+    /// supported protocol versions.
+    pub fn select(&self, origin: &Url, supported: &[Version]) -> Option<&AltService> {
+        self.get(origin)
+            .into_iter()
+            .find(|x| x.version().is_some_and(|version| supported.contains(&version)))
+    }
+
+    /// Removes the alternatives of an origin, e.g. after connecting
+    /// to one failed.
+    pub fn remove(&mut self, origin: &Url) {
+        self.entries.remove(&origin.origin());
+    }
+
+    /// Removes the alternatives not marked as `persist` when the
+    /// client's network changed.
+    pub fn network_changed(&mut self) {
+        for services in self.entries.values_mut() {
+            services.retain(|x| x.0.persist);
+        }
+        self.entries.retain(|_, services| !services.is_empty());
+    }
+
+    /// Removes expired alternatives.
+    pub fn prune(&mut self) {
+        let now = self.clock.now();
+        for services in self.entries.values_mut() {
+            services.retain(|x| x.1 > now);
+        }
+        self.entries.retain(|_, services| !services.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use url::Url;
+
+    use header::AltSvc;
+    use header::item::AltService;
+    use version::Version;
+    use super::{AltSvcCache, MAX_AGE};
+
+    #[test]
+    fn test_update() {
+        let now = Cell::new(UNIX_EPOCH + Duration::from_secs(1_000_000));
+        let mut cache = AltSvcCache::with_clock(|| now.get());
+        let origin = Url::parse("https://example.com/").unwrap();
+        let mut h3 = AltService::new("h3", None, 443);
+        h3.max_age = 60;
+        let h2 = AltService::new("h2", None, 8443);
+        cache.update(&origin, &AltSvc::Services(vec![h3.clone(), h2.clone()]));
+        assert_eq!(cache.get(&origin), vec![&h3, &h2]);
+        assert_eq!(cache.select(&origin, &[Version::Http20]), Some(&h2));
+
+        now.set(now.get() + Duration::from_secs(60));
+        assert_eq!(cache.get(&origin), vec![&h2]);
+        cache.update(&origin, &AltSvc::Clear);
+        assert!(cache.get(&origin).is_empty());
+
+        let other = Url::parse("ftp://example.com/").unwrap();
+        cache.update(&other, &AltSvc::Services(vec![h2]));
+        assert!(cache.get(&other).is_empty());
+    }
+
+    #[test]
+    fn test_max_age_overflow() {
+        let now = Cell::new(UNIX_EPOCH);
+        let mut cache = AltSvcCache::with_clock(|| now.get());
+        let origin = Url::parse("https://example.com/").unwrap();
+        let service: AltService = "h3=\":443\"; ma=18446744073709551615".parse().unwrap();
+        cache.update(&origin, &AltSvc::Services(vec![service.clone()]));
+        assert_eq!(cache.get(&origin), vec![&service]);
+
+        now.set(UNIX_EPOCH + Duration::from_secs(MAX_AGE));
+        assert!(cache.get(&origin).is_empty());
+        cache.prune();
+        assert!(cache.entries.is_empty());
+
+        // Adding the lifetime to the current time overflows.
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(u64::MAX / 2);
+        let mut cache = AltSvcCache::with_clock(move || now);
+        cache.update(&origin, &AltSvc::Services(vec![service]));
+        assert!(cache.get(&origin).is_empty());
+    }
+}
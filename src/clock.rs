@@ -0,0 +1,41 @@
+use std::time::SystemTime;
+
+/// A source of the current time.
+///
+/// Types that expire or schedule things take a clock so tests can
+/// control time. Closures returning a `SystemTime` are clocks too.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+impl<F: Fn() -> SystemTime> Clock for F {
+    fn now(&self) -> SystemTime {
+        self()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{Clock, SystemClock};
+
+    #[test]
+    fn test_clock() {
+        let fixed = || UNIX_EPOCH + Duration::from_secs(60);
+        assert_eq!(fixed.now(), UNIX_EPOCH + Duration::from_secs(60));
+        let before = SystemTime::now();
+        assert!(SystemClock.now() >= before);
+    }
+}
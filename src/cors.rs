@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use url::Origin;
+
+use header::{self, Headers};
+use header::item::HeaderField;
+use Method;
+
+/// The origins allowed by a [`CorsPolicy`](struct.CorsPolicy.html).
+#[derive(Clone, Debug)]
+pub enum AllowedOrigins {
+    /// Every origin is allowed.
+    ///
+    /// With credentials the origin of the request is echoed
+    /// because the wildcard `*` is not permitted.
+    Any,
+    /// Only the listed origins are allowed.
+    List(Vec<Origin>),
+}
+
+/// A policy for Cross-Origin Resource Sharing.
+///
+/// The policy evaluates preflight requests and actual requests and
+/// returns the header fields to add to the response. It enforces the
+/// rules of the [Fetch standard](https://fetch.spec.whatwg.org/#http-cors-protocol),
+/// in particular wildcards are never sent for requests with credentials.
+///
+/// Methods are compared case-sensitively, header field names
+/// case-insensitively. An unregistered method `*` in `allowed_methods`
+/// or a header field `*` in `allowed_headers` allows all methods or
+/// header fields for requests without credentials.
+#[derive(Clone, Debug)]
+pub struct CorsPolicy {
+    /// Origins allowed to read responses.
+    pub allowed_origins: AllowedOrigins,
+    /// Methods allowed in addition to the safelisted `GET`, `HEAD`
+    /// and `POST`.
+    pub allowed_methods: Vec<Method>,
+    /// Request header fields allowed in addition to the safelisted ones.
+    pub allowed_headers: Vec<HeaderField>,
+    /// Response header fields scripts are allowed to read.
+    pub exposed_headers: Vec<HeaderField>,
+    /// Allow requests with cookies and authentication.
+    pub allow_credentials: bool,
+    /// Time a preflight result may be cached.
+    pub max_age: Option<Duration>,
+}
+
+/// The outcome of evaluating a request with a
+/// [`CorsPolicy`](struct.CorsPolicy.html).
+#[derive(Clone, Debug)]
+pub struct CorsResponse {
+    /// Set if the request is allowed.
+    pub allowed: bool,
+    /// The header fields to add to the response.
+    ///
+    /// They are also set for rejected requests as they may
+    /// contain `Vary: Origin`.
+    pub headers: Headers,
+}
+
+impl CorsPolicy {
+    /// Constructs a policy that allows the given origins
+    /// to make simple requests without credentials.
+    pub fn new(allowed_origins: AllowedOrigins) -> CorsPolicy {
+        CorsPolicy {
+            allowed_origins,
+            allowed_methods: Vec::new(),
+            allowed_headers: Vec::new(),
+            exposed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Checks if the origin is allowed.
+    ///
+    /// Opaque origins serialized as `null` are only allowed if
+    /// any origin is allowed and no credentials are used.
+    pub fn is_origin_allowed(&self, origin: &Origin) -> bool {
+        match self.allowed_origins {
+            AllowedOrigins::Any => origin.is_tuple() || !self.allow_credentials,
+            AllowedOrigins::List(ref origins) => {
+                origin.is_tuple() && origins.iter().any(|x| x == origin)
+            }
+        }
+    }
+
+    /// Checks if the method is allowed.
+    pub fn is_method_allowed(&self, method: &Method) -> bool {
+        matches!(*method, Method::Get | Method::Head | Method::Post) ||
+        self.allowed_methods.contains(method) ||
+        (!self.allow_credentials && self.allowed_methods.contains(&wildcard_method()))
+    }
+
+    /// Checks if the request header field is allowed.
+    ///
+    /// `Authorization` is never covered by the wildcard.
+    pub fn is_header_allowed(&self, name: &HeaderField) -> bool {
+        self.allowed_headers.contains(name) ||
+        (!self.allow_credentials && self.allowed_headers.contains(&"*".into()) &&
+         *name != "Authorization".into())
+    }
+
+    /// Evaluates a preflight `OPTIONS` request.
+    ///
+    /// The arguments are the values of the `Origin`,
+    /// `Access-Control-Request-Method` and
+    /// `Access-Control-Request-Headers` header fields.
+    pub fn preflight(&self,
+                     origin: &Origin,
+                     method: &Method,
+                     request_headers: &[HeaderField])
+                     -> CorsResponse {
+        let mut response = self.response(origin);
+        if !response.allowed {
+            return response;
+        }
+        if !self.is_method_allowed(method) ||
+           !request_headers.iter().all(|x| self.is_header_allowed(x)) {
+            return self.rejected();
+        }
+        let mut methods = self.allowed_methods.clone();
+        if self.allow_credentials || !methods.contains(&wildcard_method()) {
+            methods.retain(|x| *x != wildcard_method());
+            if !methods.contains(method) {
+                methods.push(method.clone());
+            }
+        }
+        response.headers.set_valid(&header::AccessControlAllowMethods::from(methods));
+        if !request_headers.is_empty() {
+            response.headers
+                .set_valid(&header::AccessControlAllowHeaders::from(request_headers.to_vec()));
+        }
+        if let Some(max_age) = self.max_age {
+            response.headers.set_valid(&header::AccessControlMaxAge::from(max_age));
+        }
+        response
+    }
+
+    /// Evaluates an actual request.
+    ///
+    /// The origin is the value of the `Origin` header field if present.
+    /// Requests without an origin are not cross-origin requests.
+    pub fn actual(&self, origin: Option<&Origin>) -> CorsResponse {
+        let origin = match origin {
+            Some(origin) => origin,
+            None => {
+                let mut response = self.rejected();
+                response.allowed = true;
+                return response;
+            }
+        };
+        let mut response = self.response(origin);
+        if !response.allowed {
+            return response;
+        }
+        let mut exposed = self.exposed_headers.clone();
+        if self.allow_credentials {
+            exposed.retain(|x| *x != "*".into());
+        }
+        if !exposed.is_empty() {
+            response.headers.set_valid(&header::AccessControlExposeHeaders::from(exposed));
+        }
+        response
+    }
+
+    // Checks the origin and creates the header fields common to all
+    // allowed responses.
+    fn response(&self, origin: &Origin) -> CorsResponse {
+        if !self.is_origin_allowed(origin) {
+            return self.rejected();
+        }
+        let mut response = self.rejected();
+        response.allowed = true;
+        let allow_origin = match self.allowed_origins {
+            AllowedOrigins::Any if !self.allow_credentials => {
+                header::AccessControlAllowOrigin::Any
+            }
+            _ => header::AccessControlAllowOrigin::Origin(origin.clone()),
+        };
+        response.headers.set_valid(&allow_origin);
+        if self.allow_credentials {
+            response.headers.set_valid(&header::AccessControlAllowCredentials);
+        }
+        response
+    }
+
+    fn rejected(&self) -> CorsResponse {
+        let mut headers = Headers::new();
+        if self.varies_by_origin() {
+            headers.set_valid(&header::Vary::from(vec!["Origin".into()]));
+        }
+        CorsResponse {
+            allowed: false,
+            headers,
+        }
+    }
+
+    // The response depends on the origin unless the wildcard is sent.
+    fn varies_by_origin(&self) -> bool {
+        match self.allowed_origins {
+            AllowedOrigins::Any => self.allow_credentials,
+            AllowedOrigins::List(_) => true,
+        }
+    }
+}
+
+fn wildcard_method() -> Method {
+    Method::Unregistered("*".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use url::{Origin, Url};
+
+    use Method;
+    use super::{AllowedOrigins, CorsPolicy};
+
+    fn origin(s: &str) -> Origin {
+        Url::parse(s).unwrap().origin()
+    }
+
+    fn policy(credentials: bool) -> CorsPolicy {
+        let mut policy = CorsPolicy::new(AllowedOrigins::List(vec![origin("https://a.example")]));
+        policy.allowed_methods = vec![Method::Put];
+        policy.allowed_headers = vec!["X-Token".into()];
+        policy.exposed_headers = vec!["ETag".into()];
+        policy.allow_credentials = credentials;
+        policy.max_age = Some(Duration::from_secs(600));
+        policy
+    }
+
+    #[test]
+    fn test_preflight() {
+        let response = policy(true).preflight(&origin("https://a.example"), &Method::Put, &["x-token".into()]);
+        assert!(response.allowed);
+        let headers = response.headers;
+        assert_eq!(headers.get_raw("Access-Control-Allow-Origin"), Some(&[b"https://a.example".to_vec()][..]));
+        assert_eq!(headers.get_raw("Access-Control-Allow-Credentials"), Some(&[b"true".to_vec()][..]));
+        assert_eq!(headers.get_raw("Access-Control-Allow-Methods"), Some(&[b"PUT".to_vec()][..]));
+        assert_eq!(headers.get_raw("Access-Control-Max-Age"), Some(&[b"600".to_vec()][..]));
+        assert_eq!(headers.get_raw("Vary"), Some(&[b"Origin".to_vec()][..]));
+
+        let response = policy(false).preflight(&origin("https://a.example"), &Method::Delete, &[]);
+        assert!(!response.allowed);
+        assert!(response.headers.get_raw("Access-Control-Allow-Origin").is_none());
+        let response = policy(false).preflight(&origin("https://b.example"), &Method::Get, &[]);
+        assert!(!response.allowed);
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let mut policy = CorsPolicy::new(AllowedOrigins::Any);
+        policy.allowed_headers = vec!["*".into()];
+        let response = policy.actual(Some(&origin("https://b.example")));
+        assert_eq!(response.headers.get_raw("Access-Control-Allow-Origin"), Some(&[b"*".to_vec()][..]));
+        assert!(response.headers.get_raw("Vary").is_none());
+        assert!(policy.is_header_allowed(&"X-Anything".into()));
+        assert!(!policy.is_header_allowed(&"Authorization".into()));
+
+        policy.allow_credentials = true;
+        assert!(!policy.is_header_allowed(&"X-Anything".into()));
+        let response = policy.actual(Some(&origin("https://b.example")));
+        assert_eq!(response.headers.get_raw("Access-Control-Allow-Origin"),
+                   Some(&[b"https://b.example".to_vec()][..]));
+        assert!(!policy.actual(Some(&Url::parse("data:,x").unwrap().origin())).allowed);
+        assert!(policy.actual(None).allowed);
+    }
+}
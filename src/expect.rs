@@ -0,0 +1,197 @@
+use std::time::{Duration, SystemTime};
+
+use clock::{Clock, SystemClock};
+use header::{Expect, Headers};
+use {Status, Version};
+
+/// How a server answers the expectation of a request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExpectAction {
+    /// Send the interim response `100 Continue`, then read the content.
+    Continue,
+    /// Send a response with this final status without reading the
+    /// content.
+    ///
+    /// Afterwards the server closes the connection or reads and
+    /// discards the content.
+    Reject(Status),
+    /// Send no interim response and read the content.
+    Ignore,
+}
+
+/// Decides how a server answers the `Expect` header field of a request,
+/// [RFC7231 Section 5.1.1]
+///
+/// `final_status` is the status the server would respond with before
+/// looking at the content, like `401 Unauthorized` or `413 Payload Too
+/// Large`, or `None` if the request is acceptable so far.
+///
+/// Unknown expectations are rejected with `417 Expectation Failed`.
+/// HTTP/1.0 clients do not know about expectations so the field is
+/// ignored in their requests.
+pub fn check_expect(version: Version, headers: &Headers, final_status: Option<Status>) -> ExpectAction {
+    if version == Version::Http10 || !headers.has::<Expect>() {
+        return ExpectAction::Ignore;
+    }
+    match headers.get_without_base::<Expect>() {
+        Some(Ok(Expect::Continue)) => match final_status {
+            Some(status) => ExpectAction::Reject(status),
+            None => ExpectAction::Continue,
+        },
+        _ => ExpectAction::Reject(Status::EXPECTATION_FAILED),
+    }
+}
+
+/// The state of a client waiting for `100 Continue`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ContinueState {
+    /// No response yet, wait at most the given time before
+    /// sending the content.
+    Waiting(Duration),
+    /// Send the content.
+    SendBody,
+    /// A final response arrived, do not send (the rest of) the content.
+    ///
+    /// On `417 Expectation Failed` the request is repeated without
+    /// the `Expect` header field.
+    Response(Status),
+}
+
+/// A client waiting for `100 Continue` before sending the content,
+/// [RFC7231 Section 5.1.1]
+///
+/// Servers may not know about expectations, so the client sends the
+/// content anyway after a timeout of one second by default.
+#[derive(Clone, Debug)]
+pub struct ContinueWait<C: Clock = SystemClock> {
+    clock: C,
+    start: SystemTime,
+    timeout: Duration,
+    state: ContinueState,
+}
+
+impl ContinueWait {
+    /// Starts waiting using the system clock, right after sending
+    /// the request header.
+    pub fn new() -> ContinueWait {
+        ContinueWait::with_clock(SystemClock)
+    }
+}
+
+impl Default for ContinueWait {
+    fn default() -> ContinueWait {
+        ContinueWait::new()
+    }
+}
+
+impl<C: Clock> ContinueWait<C> {
+    /// Starts waiting using the given clock.
+    pub fn with_clock(clock: C) -> ContinueWait<C> {
+        let start = clock.now();
+        let timeout = Duration::from_secs(1);
+        ContinueWait {
+            clock,
+            start,
+            timeout,
+            state: ContinueState::Waiting(timeout),
+        }
+    }
+
+    /// Sets the time to wait before sending the content.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the current state, sending the content once the
+    /// timeout has passed.
+    pub fn poll(&mut self) -> ContinueState {
+        if let ContinueState::Waiting(_) = self.state {
+            let elapsed = self.clock.now().duration_since(self.start).unwrap_or_default();
+            self.state = match self.timeout.checked_sub(elapsed) {
+                Some(remaining) if remaining > Duration::from_secs(0) => {
+                    ContinueState::Waiting(remaining)
+                }
+                _ => ContinueState::SendBody,
+            };
+        }
+        self.state
+    }
+
+    /// Updates the state with the status of a received response.
+    ///
+    /// Informational responses other than `100 Continue` are ignored.
+    /// After a final response the client stops sending the content
+    /// if it already started.
+    pub fn receive(&mut self, status: Status) -> ContinueState {
+        if status == Status::CONTINUE {
+            if let ContinueState::Waiting(_) = self.state {
+                self.state = ContinueState::SendBody;
+            }
+        } else if !status.is_informational() {
+            self.state = ContinueState::Response(status);
+        }
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use header::{Expect, Headers};
+    use {Status, Version};
+    use super::{ContinueState, ContinueWait, ExpectAction, check_expect};
+
+    #[test]
+    fn test_check_expect() {
+        let mut headers = Headers::new();
+        assert_eq!(check_expect(Version::Http11, &headers, None), ExpectAction::Ignore);
+        headers.set(&Expect::Continue).unwrap();
+        assert_eq!(check_expect(Version::Http11, &headers, None), ExpectAction::Continue);
+        assert_eq!(check_expect(Version::Http11, &headers, Some(Status::UNAUTHORIZED)),
+                   ExpectAction::Reject(Status::UNAUTHORIZED));
+        assert_eq!(check_expect(Version::Http10, &headers, None), ExpectAction::Ignore);
+
+        headers.set_raw("Expect", vec![b"100-Continue, 100-continue".to_vec()]);
+        assert_eq!(check_expect(Version::Http11, &headers, None), ExpectAction::Continue);
+        headers.set_raw("Expect", vec![b"100-continue, foo=bar".to_vec()]);
+        assert_eq!(check_expect(Version::Http11, &headers, None),
+                   ExpectAction::Reject(Status::EXPECTATION_FAILED));
+        headers.set_raw("Expect", vec![b"".to_vec()]);
+        assert_eq!(check_expect(Version::Http11, &headers, None),
+                   ExpectAction::Reject(Status::EXPECTATION_FAILED));
+    }
+
+    #[test]
+    fn test_continue_wait() {
+        let now = Cell::new(UNIX_EPOCH);
+        let mut wait = ContinueWait::with_clock(|| now.get());
+        assert_eq!(wait.poll(), ContinueState::Waiting(Duration::from_secs(1)));
+        now.set(UNIX_EPOCH + Duration::from_millis(400));
+        assert_eq!(wait.poll(), ContinueState::Waiting(Duration::from_millis(600)));
+        assert_eq!(wait.receive(Status::PROCESSING),
+                   ContinueState::Waiting(Duration::from_millis(600)));
+        assert_eq!(wait.receive(Status::CONTINUE), ContinueState::SendBody);
+        assert_eq!(wait.poll(), ContinueState::SendBody);
+        assert_eq!(wait.receive(Status::OK), ContinueState::Response(Status::OK));
+        assert_eq!(wait.receive(Status::CONTINUE), ContinueState::Response(Status::OK));
+    }
+
+    #[test]
+    fn test_continue_timeout() {
+        let now = Cell::new(UNIX_EPOCH);
+        let mut wait = ContinueWait::with_clock(|| now.get());
+        wait.set_timeout(Duration::from_secs(2));
+        now.set(UNIX_EPOCH + Duration::from_secs(2));
+        assert_eq!(wait.poll(), ContinueState::SendBody);
+        assert_eq!(wait.receive(Status::EXPECTATION_FAILED),
+                   ContinueState::Response(Status::EXPECTATION_FAILED));
+
+        // A clock going backwards does not extend the timeout.
+        let now = Cell::new(UNIX_EPOCH + Duration::from_secs(10));
+        let mut wait = ContinueWait::with_clock(|| now.get());
+        now.set(UNIX_EPOCH);
+        assert_eq!(wait.poll(), ContinueState::Waiting(Duration::from_secs(1)));
+    }
+}
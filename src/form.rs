@@ -0,0 +1,453 @@
+//! Codec for `application/x-www-form-urlencoded` bodies.
+//!
+//! HTML forms without file uploads are submitted as a list of
+//! name-value pairs. Parsing and serialization follow the
+//! [URL standard](https://url.spec.whatwg.org/#application/x-www-form-urlencoded).
+//!
+//! The names and values are percent-encoded bytes in the encoding of
+//! the form, which is given by the `charset` parameter of the
+//! `Content-Type` or a field named `_charset_`. UTF-8 and windows-1252
+//! (including the labels `iso-8859-1` and `us-ascii`) are supported.
+
+use std::fmt::{self, Display};
+use std::mem;
+
+use media_types::{self, Application};
+
+use header::item::MediaType;
+
+/// The encoding of the names and values of a form.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    /// UTF-8, the default encoding.
+    Utf8,
+    /// windows-1252, used for the labels `iso-8859-1`, `latin1`
+    /// and `us-ascii` as well.
+    Windows1252,
+}
+
+impl Encoding {
+    /// Looks up the encoding for a label like `utf-8` or `latin1`.
+    pub fn for_label(label: &str) -> Option<Encoding> {
+        let label = label.trim_matches(|c| c == ' ' || c == '\t' || c == '\n' || c == '\r');
+        let labels: &[(&str, Encoding)] = &[("unicode-1-1-utf-8", Encoding::Utf8),
+                                            ("unicode11utf8", Encoding::Utf8),
+                                            ("unicode20utf8", Encoding::Utf8),
+                                            ("utf-8", Encoding::Utf8),
+                                            ("utf8", Encoding::Utf8),
+                                            ("x-unicode20utf8", Encoding::Utf8),
+                                            ("ansi_x3.4-1968", Encoding::Windows1252),
+                                            ("ascii", Encoding::Windows1252),
+                                            ("cp1252", Encoding::Windows1252),
+                                            ("cp819", Encoding::Windows1252),
+                                            ("ibm819", Encoding::Windows1252),
+                                            ("iso-8859-1", Encoding::Windows1252),
+                                            ("iso-ir-100", Encoding::Windows1252),
+                                            ("iso8859-1", Encoding::Windows1252),
+                                            ("iso88591", Encoding::Windows1252),
+                                            ("iso_8859-1", Encoding::Windows1252),
+                                            ("iso_8859-1:1987", Encoding::Windows1252),
+                                            ("l1", Encoding::Windows1252),
+                                            ("latin1", Encoding::Windows1252),
+                                            ("us-ascii", Encoding::Windows1252),
+                                            ("windows-1252", Encoding::Windows1252),
+                                            ("x-cp1252", Encoding::Windows1252)];
+        labels.iter().find(|x| x.0.eq_ignore_ascii_case(label)).map(|x| x.1)
+    }
+
+    /// Returns the canonical name of the encoding.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Decodes bytes, invalid sequences are replaced with U+FFFD.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match *self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Windows1252 => {
+                bytes.iter()
+                    .map(|&c| match c {
+                        0x80..=0x9F => WINDOWS_1252[(c - 0x80) as usize],
+                        _ => c as char,
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Encodes a string, unmappable characters are written as
+    /// numeric character references like `&#8364;`.
+    pub fn encode(&self, s: &str) -> Vec<u8> {
+        match *self {
+            Encoding::Utf8 => s.as_bytes().to_vec(),
+            Encoding::Windows1252 => {
+                let mut bytes = Vec::with_capacity(s.len());
+                for c in s.chars() {
+                    match c as u32 {
+                        0x00..=0x7F | 0xA0..=0xFF => bytes.push(c as u8),
+                        _ => {
+                            match WINDOWS_1252.iter().position(|&x| x == c) {
+                                Some(i) => bytes.push(0x80 + i as u8),
+                                None => bytes.extend_from_slice(format!("&#{};", c as u32).as_bytes()),
+                            }
+                        }
+                    }
+                }
+                bytes
+            }
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Characters for the bytes 0x80 to 0x9F, unassigned bytes map to
+// the C1 control characters.
+const WINDOWS_1252: [char; 32] = ['\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}',
+                                  '\u{2026}', '\u{2020}', '\u{2021}', '\u{2C6}', '\u{2030}',
+                                  '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+                                  '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}',
+                                  '\u{2022}', '\u{2013}', '\u{2014}', '\u{2DC}', '\u{2122}',
+                                  '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}'];
+
+/// Limits enforced while parsing a form.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The maximum number of fields.
+    pub max_fields: usize,
+    /// The maximum size of an encoded name-value pair in bytes.
+    pub max_field_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_fields: 1000,
+            max_field_size: 64 * 1024,
+        }
+    }
+}
+
+/// An error encountered while parsing a form.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The content type is not `application/x-www-form-urlencoded`.
+    InvalidContentType,
+    /// The charset of the form is not supported.
+    UnsupportedCharset,
+    /// There are more fields than allowed.
+    TooManyFields,
+    /// A name-value pair is too large.
+    FieldTooLarge,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::InvalidContentType => "content type is not application/x-www-form-urlencoded",
+            Error::UnsupportedCharset => "unsupported form charset",
+            Error::TooManyFields => "too many form fields",
+            Error::FieldTooLarge => "form field too large",
+        })
+    }
+}
+
+/// Checks if the media type is `application/x-www-form-urlencoded`.
+pub fn is_urlencoded(media_type: &MediaType) -> bool {
+    media_type.type_ == Some(Application) &&
+    media_type.subtype.as_ref().is_some_and(|x| {
+        x.0 == media_types::Standards && x.1.eq_ignore_ascii_case("x-www-form-urlencoded") &&
+        x.2.is_none()
+    })
+}
+
+/// Returns the encoding given by the `charset` parameter.
+///
+/// Returns `Ok(None)` if the parameter is absent.
+pub fn charset(media_type: &MediaType) -> Result<Option<Encoding>, Error> {
+    match media_type.parameters.get("charset") {
+        Some(label) => Encoding::for_label(label).map(Some).ok_or(Error::UnsupportedCharset),
+        None => Ok(None),
+    }
+}
+
+/// Parses a complete form body.
+///
+/// The content type must be `application/x-www-form-urlencoded`.
+/// Without a `charset` parameter the value of a `_charset_` field is
+/// used as encoding, otherwise UTF-8.
+pub fn parse(media_type: &MediaType, body: &[u8], limits: Limits) -> Result<Vec<(String, String)>, Error> {
+    if !is_urlencoded(media_type) {
+        return Err(Error::InvalidContentType);
+    }
+    let mut pairs = Vec::new();
+    for field in body.split(|&c| c == b'&').filter(|x| !x.is_empty()) {
+        if pairs.len() == limits.max_fields {
+            return Err(Error::TooManyFields);
+        }
+        if field.len() > limits.max_field_size {
+            return Err(Error::FieldTooLarge);
+        }
+        pairs.push(split_field(field));
+    }
+    let encoding = match charset(media_type)? {
+        Some(encoding) => encoding,
+        None => {
+            match pairs.iter().find(|x| x.0 == b"_charset_") {
+                Some(pair) => {
+                    let label = String::from_utf8_lossy(&pair.1);
+                    Encoding::for_label(&label).ok_or(Error::UnsupportedCharset)?
+                }
+                None => Encoding::Utf8,
+            }
+        }
+    };
+    Ok(pairs.iter().map(|x| (encoding.decode(&x.0), encoding.decode(&x.1))).collect())
+}
+
+/// A streaming parser for form bodies.
+///
+/// Feed the body with [`feed`](#method.feed) and call
+/// [`next_field`](#method.next_field) until it returns `None`, then
+/// feed more data. After the last chunk call [`finish`](#method.finish).
+///
+/// Without a `charset` parameter a `_charset_` field changes the
+/// encoding of the fields following it.
+#[derive(Clone, Debug)]
+pub struct Parser {
+    encoding: Encoding,
+    charset_fixed: bool,
+    limits: Limits,
+    buffer: Vec<u8>,
+    fields: usize,
+    finished: bool,
+}
+
+impl Parser {
+    /// Constructs a parser for a form with the media type.
+    pub fn new(media_type: &MediaType, limits: Limits) -> Result<Parser, Error> {
+        if !is_urlencoded(media_type) {
+            return Err(Error::InvalidContentType);
+        }
+        let charset = charset(media_type)?;
+        Ok(Parser {
+            encoding: charset.unwrap_or(Encoding::Utf8),
+            charset_fixed: charset.is_some(),
+            limits,
+            buffer: Vec::new(),
+            fields: 0,
+            finished: false,
+        })
+    }
+
+    /// Returns the encoding used for decoding.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Appends a chunk of the body.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Marks the end of the body.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Returns the next decoded name-value pair.
+    ///
+    /// `None` is returned if more data is needed or the body is complete.
+    pub fn next_field(&mut self) -> Result<Option<(String, String)>, Error> {
+        loop {
+            let field = match self.buffer.iter().position(|&c| c == b'&') {
+                Some(end) => {
+                    let rest = self.buffer.split_off(end + 1);
+                    let mut field = mem::replace(&mut self.buffer, rest);
+                    field.pop();
+                    field
+                }
+                None if self.buffer.len() > self.limits.max_field_size => {
+                    return Err(Error::FieldTooLarge)
+                }
+                None if self.finished => mem::take(&mut self.buffer),
+                None => return Ok(None),
+            };
+            if field.is_empty() {
+                if self.buffer.is_empty() && self.finished {
+                    return Ok(None);
+                }
+                continue;
+            }
+            if field.len() > self.limits.max_field_size {
+                return Err(Error::FieldTooLarge);
+            }
+            if self.fields == self.limits.max_fields {
+                return Err(Error::TooManyFields);
+            }
+            self.fields += 1;
+            let (name, value) = split_field(&field);
+            if !self.charset_fixed && name == b"_charset_" {
+                let label = String::from_utf8_lossy(&value);
+                self.encoding = Encoding::for_label(&label).ok_or(Error::UnsupportedCharset)?;
+            }
+            return Ok(Some((self.encoding.decode(&name), self.encoding.decode(&value))));
+        }
+    }
+}
+
+// Splits a field at the first `=` and percent-decodes both parts.
+fn split_field(field: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    match field.iter().position(|&c| c == b'=') {
+        Some(i) => (percent_decode(&field[..i]), percent_decode(&field[i + 1..])),
+        None => (percent_decode(field), Vec::new()),
+    }
+}
+
+fn percent_decode(input: &[u8]) -> Vec<u8> {
+    fn hex(c: u8) -> Option<u8> {
+        (c as char).to_digit(16).map(|x| x as u8)
+    }
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'+' => output.push(b' '),
+            b'%' => {
+                let high = input.get(i + 1).cloned().and_then(hex);
+                let low = input.get(i + 2).cloned().and_then(hex);
+                match (high, low) {
+                    (Some(high), Some(low)) => {
+                        output.push(high << 4 | low);
+                        i += 2;
+                    }
+                    _ => output.push(b'%'),
+                }
+            }
+            c => output.push(c),
+        }
+        i += 1;
+    }
+    output
+}
+
+/// Serializes name-value pairs as a form body.
+pub fn serialize<'a, I, N, V>(pairs: I, encoding: Encoding) -> String
+    where I: IntoIterator<Item = &'a (N, V)>,
+          N: AsRef<str> + 'a,
+          V: AsRef<str> + 'a
+{
+    let mut output = String::new();
+    for pair in pairs {
+        if !output.is_empty() {
+            output.push('&');
+        }
+        percent_encode(&encoding.encode(pair.0.as_ref()), &mut output);
+        output.push('=');
+        percent_encode(&encoding.encode(pair.1.as_ref()), &mut output);
+    }
+    output
+}
+
+fn percent_encode(input: &[u8], output: &mut String) {
+    for &c in input {
+        match c {
+            b' ' => output.push('+'),
+            b'*' | b'-' | b'.' | b'_' => output.push(c as char),
+            _ if c.is_ascii_alphanumeric() => output.push(c as char),
+            _ => output.push_str(&format!("%{:02X}", c)),
+        }
+    }
+}
+
+/// Returns the media type for a form body in the encoding.
+///
+/// The `charset` parameter is only added for encodings other than UTF-8.
+pub fn media_type(encoding: Encoding) -> MediaType {
+    let mut media_type = MediaType::new(Application, media_types::Standards, "x-www-form-urlencoded");
+    if encoding != Encoding::Utf8 {
+        media_type.parameters.insert("charset".into(), encoding.name().into());
+    }
+    media_type
+}
+
+#[cfg(test)]
+mod tests {
+    use header::item::MediaType;
+    use super::{Encoding, Error, Limits, Parser, media_type, parse, serialize};
+
+    fn urlencoded(charset: Option<&str>) -> MediaType {
+        match charset {
+            Some(charset) => format!("application/x-www-form-urlencoded; charset={}", charset),
+            None => "application/x-www-form-urlencoded".to_owned(),
+        }.parse().unwrap()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|x| (x.0.to_owned(), x.1.to_owned())).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let body = b"a=1&&b=x+y%21&c&=d&e=%zz%4";
+        assert_eq!(parse(&urlencoded(None), body, Limits::default()).unwrap(),
+                   pairs(&[("a", "1"), ("b", "x y!"), ("c", ""), ("", "d"), ("e", "%zz%4")]));
+        assert_eq!(parse(&urlencoded(Some("latin1")), b"n=%E9%80", Limits::default()).unwrap(),
+                   pairs(&[("n", "\u{e9}\u{20ac}")]));
+        assert_eq!(parse(&urlencoded(None), b"n=%E9&_charset_=windows-1252", Limits::default()).unwrap(),
+                   pairs(&[("n", "\u{e9}"), ("_charset_", "windows-1252")]));
+        assert_eq!(parse(&urlencoded(None), b"n=%E9", Limits::default()).unwrap(),
+                   pairs(&[("n", "\u{fffd}")]));
+        assert_eq!(parse(&urlencoded(Some("koi8-r")), b"", Limits::default()), Err(Error::UnsupportedCharset));
+        assert_eq!(parse(&"text/plain".parse().unwrap(), b"", Limits::default()),
+                   Err(Error::InvalidContentType));
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits { max_fields: 2, max_field_size: 4 };
+        assert_eq!(parse(&urlencoded(None), b"a=1&b=2&c=3", limits), Err(Error::TooManyFields));
+        assert_eq!(parse(&urlencoded(None), b"a=123", limits), Err(Error::FieldTooLarge));
+        let mut parser = Parser::new(&urlencoded(None), limits).unwrap();
+        parser.feed(b"abcde");
+        assert_eq!(parser.next_field(), Err(Error::FieldTooLarge));
+    }
+
+    #[test]
+    fn test_streaming() {
+        let body = b"a=%C3%A9&_charset_=latin1&b=%E9&c=";
+        let mut parser = Parser::new(&urlencoded(None), Limits::default()).unwrap();
+        let mut fields = Vec::new();
+        for chunk in body.chunks(3) {
+            parser.feed(chunk);
+            while let Some(field) = parser.next_field().unwrap() {
+                fields.push(field);
+            }
+        }
+        parser.finish();
+        while let Some(field) = parser.next_field().unwrap() {
+            fields.push(field);
+        }
+        assert_eq!(fields, pairs(&[("a", "\u{e9}"), ("_charset_", "latin1"), ("b", "\u{e9}"), ("c", "")]));
+        assert_eq!(parser.encoding(), Encoding::Windows1252);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let fields = [("name", "J\u{f6}rg & co."), ("sym", "\u{20ac}\u{2603}*")];
+        assert_eq!(serialize(&fields, Encoding::Utf8),
+                   "name=J%C3%B6rg+%26+co.&sym=%E2%82%AC%E2%98%83*");
+        assert_eq!(serialize(&fields, Encoding::Windows1252),
+                   "name=J%F6rg+%26+co.&sym=%80%26%239731%3B*");
+        assert_eq!(media_type(Encoding::Utf8).to_string(), "application/x-www-form-urlencoded");
+        assert_eq!(Encoding::for_label(" ISO-8859-1\n"), Some(Encoding::Windows1252));
+        assert_eq!(Encoding::for_label("utf-16"), None);
+    }
+}
@@ -1,11 +1,6 @@
-use std::str;
-use std::time::SystemTime;
-
-use httpdate::{parse_http_date, fmt_http_date};
-
 use header::{RequestHeader, ResponseHeader, parse_value, serialize_value,
     parse_list1_star, serialize_list_star};
-use header::item::EntityTag;
+use header::item::{EntityTag, HttpDate};
 
 header!{
     /// `ETag` header, [RFC7232 Section 2.3]
@@ -37,19 +32,15 @@ header!{
 
 header!{
     /// `If-Modified-Since` header, [RFC7232 Section 3.3]
-    pub struct IfModifiedSince(SystemTime);
+    pub struct IfModifiedSince(HttpDate);
     (RequestHeader);
     NAME = "If-Modified-Since";
     SENSITIVE = false;
     parse(s, _base) {
-        if s.len() != 1 {
-            return Err(());
-        }
-        let raw = str::from_utf8(&s[0]).map_err(|_| ())?;
-        parse_http_date(raw).map(Into::into)
+        parse_value::<HttpDate>(s).map(Into::into)
     }
     serialize(self, iter) {
-        serialize_value(iter, fmt_http_date(self.0))
+        serialize_value(iter, self.0)
     }
 }
 
@@ -69,36 +60,54 @@ header!{
 
 header!{
     /// `If-Unmodified-Since` header, [RFC7232 Section 3.4]
-    pub struct IfUnmodifiedSince(SystemTime);
+    pub struct IfUnmodifiedSince(HttpDate);
     (RequestHeader);
     NAME = "If-Unmodified-Since";
     SENSITIVE = false;
     parse(s, _base) {
-        if s.len() != 1 {
-            return Err(());
-        }
-        let raw = str::from_utf8(&s[0]).map_err(|_| ())?;
-        parse_http_date(raw).map(Into::into)
+        parse_value::<HttpDate>(s).map(Into::into)
     }
     serialize(self, iter) {
-        serialize_value(iter, fmt_http_date(self.0))
+        serialize_value(iter, self.0)
     }
 }
 
 header!{
     /// `Last-Modified` header, [RFC7232 Section 2.2]
-    pub struct LastModified(SystemTime);
+    pub struct LastModified(HttpDate);
     (ResponseHeader);
     NAME = "Last-Modified";
     SENSITIVE = false;
     parse(s, _base) {
-        if s.len() != 1 {
-            return Err(());
-        }
-        let raw = str::from_utf8(&s[0]).map_err(|_| ())?;
-        parse_http_date(raw).map(Into::into)
+        parse_value::<HttpDate>(s).map(Into::into)
     }
     serialize(self, iter) {
-        serialize_value(iter, fmt_http_date(self.0))
+        serialize_value(iter, self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use header::headers;
+    use header::item::HttpDate;
+    use super::{IfModifiedSince, LastModified};
+
+    #[test]
+    fn test_dates() {
+        let mut headers = headers("If-Modified-Since", "Sunday, 06-Nov-94 08:49:37 GMT");
+        let date: HttpDate = headers.get_without_base::<IfModifiedSince>().unwrap().unwrap().into();
+        assert_eq!(SystemTime::from(date), UNIX_EPOCH + Duration::from_secs(784_111_777));
+
+        // Sub-second parts are truncated, so the file is not newer.
+        let modified = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+        assert!(HttpDate::from(modified) <= date);
+        headers.set(&LastModified::from(HttpDate::from(modified))).unwrap();
+        assert_eq!(headers.get_raw("Last-Modified").unwrap(),
+                   &[b"Sun, 06 Nov 1994 08:49:37 GMT".to_vec()][..]);
+
+        headers.set_raw("If-Modified-Since", vec![b"Sun, 06 Nov 1960 08:49:37 GMT".to_vec()]);
+        assert!(headers.get_without_base::<IfModifiedSince>().unwrap().is_err());
     }
 }
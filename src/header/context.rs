@@ -1,8 +1,8 @@
 use std::str;
 
 use header::{RequestHeader, ResponseHeader, parse_value, serialize_value, parse_list0,
-             serialize_list};
-use header::item::Url;
+             parse_list1, serialize_list, parse_word_list1, serialize_word_list};
+use header::item::{ForwardedElement, HostPort, LinkValue, Node, Url};
 use Method;
 
 header!{
@@ -80,3 +80,257 @@ header!{
         serialize_value(iter, &self.0)
     }
 }
+
+header!{
+    /// `Forwarded` header, [RFC7239 Section 4]
+    ///
+    /// Each proxy appends an element to the list, so the rightmost
+    /// element was added by the proxy closest to the server.
+    pub struct Forwarded(Vec<ForwardedElement>);
+    (RequestHeader);
+    NAME = "Forwarded";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `X-Forwarded-For` header
+    ///
+    /// Legacy predecessor of the `for` parameter in `Forwarded`.
+    /// Nodes are listed from the original client to the last proxy.
+    pub struct XForwardedFor(Vec<Node>);
+    (RequestHeader);
+    NAME = "X-Forwarded-For";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `X-Forwarded-Proto` header
+    ///
+    /// Legacy predecessor of the `proto` parameter in `Forwarded`.
+    /// Some proxy chains append a value per hop.
+    pub struct XForwardedProto(Vec<String>);
+    (RequestHeader);
+    NAME = "X-Forwarded-Proto";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_word_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_word_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `X-Forwarded-Host` header
+    ///
+    /// Legacy predecessor of the `host` parameter in `Forwarded`.
+    /// Some proxy chains append a value per hop.
+    pub struct XForwardedHost(Vec<String>);
+    (RequestHeader);
+    NAME = "X-Forwarded-Host";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Host` header, [RFC7230 Section 5.4]
+    ///
+    /// The value is `None` if the field is empty, as sent for
+    /// request targets without an authority component.
+    pub struct Host(Option<HostPort>);
+    (RequestHeader);
+    NAME = "Host";
+    SENSITIVE = false;
+    parse(s, _base) {
+        if s.len() != 1 {
+            return Err(());
+        }
+        let raw = str::from_utf8(&s[0]).map_err(|_| ())?.trim();
+        if raw.is_empty() {
+            return Ok(None.into());
+        }
+        raw.parse().map(|x| Some(x).into())
+    }
+    serialize(self, iter) {
+        match self.0 {
+            Some(ref host) => serialize_value(iter, host),
+            None => serialize_value(iter, ""),
+        }
+    }
+}
+
+header!{
+    /// `Link` header, [RFC8288 Section 3]
+    ///
+    /// Multiple links may be sent in a single header field.
+    pub struct Link(Vec<LinkValue>);
+    (ResponseHeader);
+    NAME = "Link";
+    SENSITIVE = false;
+    parse(s, base) {
+        let mut links = Vec::new();
+        for line in s {
+            let line = str::from_utf8(line).map_err(|_| ())?;
+            links.extend(LinkValue::parse_list(line, &base)?);
+        }
+        Ok(links.into())
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+impl Link {
+    /// Returns the first link with the given registered relation type.
+    pub fn find(&self, rel: &str) -> Option<&LinkValue> {
+        self.0.iter().find(|x| x.has_rel(rel))
+    }
+
+    /// Returns the target of the `next` link.
+    pub fn next(&self) -> Option<&Url> {
+        self.find("next").map(|x| &x.target)
+    }
+
+    /// Returns the target of the `prev` or `previous` link.
+    pub fn prev(&self) -> Option<&Url> {
+        self.find("prev").or_else(|| self.find("previous")).map(|x| &x.target)
+    }
+
+    /// Returns the target of the `first` link.
+    pub fn first(&self) -> Option<&Url> {
+        self.find("first").map(|x| &x.target)
+    }
+
+    /// Returns the target of the `last` link.
+    pub fn last(&self) -> Option<&Url> {
+        self.find("last").map(|x| &x.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use url::Url;
+
+    use header::{Headers, base, headers};
+    use header::item::{ForwardedElement, HostName, HostPort, NodeName, NodePort};
+    use super::{Forwarded, Host, Link, XForwardedFor, XForwardedProto};
+
+    #[test]
+    fn test_forwarded() {
+        let headers = headers("Forwarded",
+                              "for=192.0.2.60;proto=http;by=203.0.113.43, \
+                               For=\"[2001:db8:cafe::17]:4711\", for=_hidden;host=\"a b\"");
+        let elements: Vec<ForwardedElement> =
+            headers.get::<Forwarded>(base()).unwrap().unwrap().into();
+        assert_eq!(elements.len(), 3);
+        let client = elements[0].for_.as_ref().unwrap();
+        assert_eq!(client.name, NodeName::Ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60))));
+        assert_eq!(elements[0].proto, Some("http".to_owned()));
+        let proxy = elements[1].for_.as_ref().unwrap();
+        assert_eq!(proxy.name,
+                   NodeName::Ip(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0xcafe, 0, 0, 0, 0, 0x17))));
+        assert_eq!(proxy.port, Some(NodePort::Port(4711)));
+        assert_eq!(elements[2].for_.as_ref().unwrap().name,
+                   NodeName::Obfuscated("_hidden".to_owned()));
+
+        let mut output = Headers::new();
+        output.set(&Forwarded::from(elements)).unwrap();
+        assert_eq!(output.get_raw("Forwarded").unwrap(),
+                   &[b"by=203.0.113.43;for=192.0.2.60;proto=http, \
+                       for=\"[2001:db8:cafe::17]:4711\", for=_hidden;host=\"a b\"".to_vec()][..]);
+
+        for value in &["for=192.0.2.60;for=192.0.2.61", "for=300.0.0.1", "for", "for=a b"] {
+            assert!(self::headers("Forwarded", value)
+                        .get::<Forwarded>(base())
+                        .unwrap()
+                        .is_err(),
+                    "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_x_forwarded() {
+        let headers = headers("X-Forwarded-For", "203.0.113.195, 2001:db8::1, unknown");
+        let nodes: Vec<_> = headers.get::<XForwardedFor>(base()).unwrap().unwrap().into();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[1].name, NodeName::Ip("2001:db8::1".parse().unwrap()));
+        assert_eq!(nodes[2].name, NodeName::Unknown);
+
+        let mut headers = self::headers("X-Forwarded-Proto", "https, \"http\"");
+        let protos: Vec<String> = headers.get::<XForwardedProto>(base()).unwrap().unwrap().into();
+        assert_eq!(protos, vec!["https", "http"]);
+        headers.set(&XForwardedProto::from(vec!["https".to_owned(), "a, b".to_owned()])).unwrap();
+        assert_eq!(headers.get_raw("X-Forwarded-Proto").unwrap(),
+                   &[b"https, \"a, b\"".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_host() {
+        let host: Option<HostPort> =
+            headers("Host", "Example.com:8080").get::<Host>(base()).unwrap().unwrap().into();
+        let host = host.unwrap();
+        assert_eq!(host.host, HostName::Name("example.com".to_owned()));
+        assert_eq!(host.port, Some(8080));
+        let host: Option<HostPort> =
+            headers("Host", "[::1]").get::<Host>(base()).unwrap().unwrap().into();
+        assert_eq!(host.unwrap().host, HostName::Ip("::1".parse().unwrap()));
+        let host: Option<HostPort> =
+            headers("Host", " ").get::<Host>(base()).unwrap().unwrap().into();
+        assert!(host.is_none());
+
+        for value in &["a b", "example.com:80a", "example.com:65536", "[::1", "user@example.com"] {
+            assert!(headers("Host", value).get::<Host>(base()).unwrap().is_err(), "{}", value);
+        }
+        let mut repeated = headers("Host", "example.com");
+        repeated.append_raw("Host", b"example.org".to_vec());
+        assert!(repeated.get::<Host>(base()).unwrap().is_err());
+
+        let mut output = Headers::new();
+        output.set(&Host::from(None)).unwrap();
+        assert_eq!(output.get_raw("Host").unwrap(), &[b"".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_link() {
+        let base = Url::parse("https://example.com/items?page=2").unwrap();
+        let mut headers = headers("Link",
+                                  "<?page=3>; rel=\"next last\", <https://example.com/items?page=1>; \
+                                   rel=first; title=\"First\"; title*=UTF-8'de'Erste");
+        headers.append_raw("Link", b"<?page=1>; rel=previous; foo=bar".to_vec());
+        let link: Link = headers.get(base.clone()).unwrap().unwrap();
+        assert_eq!(link.next().map(Url::as_str), Some("https://example.com/items?page=3"));
+        assert_eq!(link.last(), link.next());
+        assert_eq!(link.prev().map(Url::as_str), Some("https://example.com/items?page=1"));
+        let first = link.find("first").unwrap();
+        assert_eq!(first.title, Some("Erste".to_owned()));
+        assert_eq!(first.title_language.as_ref().map(|x| x.to_string()), Some("de".to_owned()));
+        assert_eq!(link.find("previous").unwrap().parameters[0].value, "bar");
+        let mut first = first.clone();
+        first.media = Some("screen\r\nX: y".to_owned());
+        assert!(first.to_string().ends_with("; media=\"screen%0D%0AX: y\""));
+
+        for value in &["https://example.com/", "<https://example.com/>; rel", "<a>; x=1 y"] {
+            assert!(self::headers("Link", value).get::<Link>(base.clone()).unwrap().is_err(),
+                    "{}", value);
+        }
+    }
+}
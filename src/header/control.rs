@@ -2,11 +2,112 @@ use std::io::{self, Write};
 use std::str;
 use std::time::{Duration, SystemTime};
 
-use httpdate::{parse_http_date, fmt_http_date};
+use clock::{Clock, SystemClock};
+use header::{Header, Headers, ListElements, RequestHeader, ResponseHeader, parse_value,
+            serialize_value, parse_list1, serialize_list};
+use header::item::{AltService, HeaderField, HttpDate, Preference, PreferenceKind, Url};
 
-use header::{Header, RequestHeader, ResponseHeader, parse_value, serialize_value,
-            parse_list1, serialize_list};
-use header::item::{HeaderField, Url};
+header!{
+    /// `Date` header, [RFC7231 Section 7.1.1.2]
+    ///
+    /// The time the message was created. Servers with a clock send it in
+    /// every response, see [`DateCache`](struct.DateCache.html).
+    pub struct Date(HttpDate);
+    (RequestHeader ResponseHeader);
+    NAME = "Date";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<HttpDate>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, self.0)
+    }
+}
+
+/// Formats the `Date` header field for responses.
+///
+/// The value changes once per second, so a busy server formats it
+/// once and reuses it for all responses within the same second.
+#[derive(Clone, Debug)]
+pub struct DateCache<C: Clock = SystemClock> {
+    clock: C,
+    date: HttpDate,
+    value: String,
+}
+
+impl DateCache {
+    /// Constructs a cache using the system clock.
+    pub fn new() -> DateCache {
+        DateCache::with_clock(SystemClock)
+    }
+}
+
+impl Default for DateCache {
+    fn default() -> DateCache {
+        DateCache::new()
+    }
+}
+
+impl<C: Clock> DateCache<C> {
+    /// Constructs a cache using the given clock.
+    pub fn with_clock(clock: C) -> DateCache<C> {
+        let date = HttpDate::from(clock.now());
+        DateCache {
+            clock,
+            date,
+            value: date.to_string(),
+        }
+    }
+
+    /// Returns the current date, formatted as IMF-fixdate.
+    pub fn get(&mut self) -> &str {
+        let date = HttpDate::from(self.clock.now());
+        if date != self.date {
+            self.date = date;
+            self.value = date.to_string();
+        }
+        &self.value
+    }
+
+    /// Sets the `Date` header field to the current date.
+    pub fn set(&mut self, headers: &mut Headers) {
+        let value = self.get().as_bytes().to_vec();
+        headers.set_raw(Date::NAME, vec![value]);
+    }
+}
+
+/// `Expect` header, [RFC7231 Section 5.1.1]
+///
+/// Only `100-continue` is defined, other expectations fail to parse
+/// and are answered with `417 Expectation Failed`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Expect {
+    /// `100-continue`: the client waits for `100 Continue` before
+    /// sending the content.
+    Continue,
+}
+
+impl RequestHeader for Expect {}
+
+impl Header for Expect {
+    const NAME: &'static str = "Expect";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        let mut found = false;
+        for element in ListElements::new(s) {
+            if !element?.eq_ignore_ascii_case(b"100-continue") {
+                return Err(());
+            }
+            found = true;
+        }
+        if found { Ok(Expect::Continue) } else { Err(()) }
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter, "100-continue")
+    }
+}
 
 header!{
     /// `Max-Forwards header`, [RFC7231 Section 5.1.2]
@@ -44,7 +145,7 @@ header!{
 #[derive(Clone, Debug)]
 pub enum RetryAfter {
     /// A timestamp.
-    Date(SystemTime),
+    Date(HttpDate),
     /// A duration.
     Delay(Duration),
 }
@@ -60,7 +161,7 @@ impl Header for RetryAfter {
             return Err(());
         }
         let raw = str::from_utf8(&s[0]).map_err(|_| ())?;
-        if let Ok(date) = parse_http_date(raw) {
+        if let Ok(date) = raw.parse::<HttpDate>() {
             return Ok(date.into())
         }
         let secs = raw.parse().map_err(|_| ())?;
@@ -70,16 +171,21 @@ impl Header for RetryAfter {
     fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
         use self::RetryAfter::*;
         match *self {
-            Date(x) => serialize_value(iter, fmt_http_date(x)),
+            Date(x) => serialize_value(iter, x),
             Delay(x) => serialize_value(iter, x.as_secs()),
         }
     }
 }
 
+impl From<HttpDate> for RetryAfter {
+    fn from(t: HttpDate) -> Self {
+        RetryAfter::Date(t)
+    }
+}
 
 impl From<SystemTime> for RetryAfter {
     fn from(t: SystemTime) -> Self {
-        RetryAfter::Date(t)
+        RetryAfter::Date(t.into())
     }
 }
 
@@ -102,3 +208,213 @@ header!{
         serialize_list(iter, &self.0[..])
     }
 }
+
+/// `Alt-Svc` header, [RFC7838 Section 3]
+///
+/// Advertises other protocols and authorities the origin is
+/// available at, like HTTP/2 on another port or HTTP/3.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AltSvc {
+    /// `clear`: all alternatives of the origin are invalidated.
+    Clear,
+    /// The alternatives in order of preference, replacing those
+    /// previously advertised.
+    Services(Vec<AltService>),
+}
+
+impl ResponseHeader for AltSvc {}
+
+impl Header for AltSvc {
+    const NAME: &'static str = "Alt-Svc";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        let mut elements = ListElements::new(s);
+        if let Some(Ok(b"clear")) = elements.next() {
+            // `clear` must be the only value.
+            return match elements.next() {
+                None => Ok(AltSvc::Clear),
+                Some(_) => Err(()),
+            };
+        }
+        parse_list1(s).map(AltSvc::Services)
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        match *self {
+            AltSvc::Clear => serialize_value(iter, "clear"),
+            AltSvc::Services(ref services) => serialize_list(iter, services),
+        }
+    }
+}
+
+/// `Alt-Svc-Used` header
+///
+/// Sent by clients on requests to an alternative service so the
+/// server can tell them apart from requests to the origin.
+#[derive(Clone, Copy, Debug)]
+pub struct AltSvcUsed;
+
+impl RequestHeader for AltSvcUsed {}
+
+impl Header for AltSvcUsed {
+    const NAME: &'static str = "Alt-Svc-Used";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        match ListElements::new(s).next() {
+            Some(Ok(b"1")) => Ok(AltSvcUsed),
+            _ => Err(()),
+        }
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter, "1")
+    }
+}
+
+header!{
+    /// `Prefer` header, [RFC7240 Section 2]
+    ///
+    /// Preferences the server may honor, like `return=minimal` or
+    /// `respond-async`. Only the first instance of a repeated
+    /// preference counts, see [`apply_preferences`](../fn.apply_preferences.html).
+    pub struct Prefer(Vec<Preference>);
+    (RequestHeader);
+    NAME = "Prefer";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0[..])
+    }
+}
+
+header!{
+    /// `Preference-Applied` header, [RFC7240 Section 3]
+    ///
+    /// The preferences the server honored.
+    pub struct PreferenceApplied(Vec<PreferenceKind>);
+    (ResponseHeader);
+    NAME = "Preference-Applied";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use header::{Headers, headers};
+    use header::item::{AltService, HttpDate, Preference, PreferenceKind, ReturnPreference};
+    use super::{AltSvc, AltSvcUsed, Date, DateCache, Expect, Prefer, PreferenceApplied};
+
+    #[test]
+    fn test_alt_svc() {
+        let headers = headers("Alt-Svc",
+                              "h3=\":443\"; ma=3600, h3%2D29=\"alt.example.com:8443\"; persist=1; x=y");
+        let services = match headers.get_without_base::<AltSvc>() {
+            Some(Ok(AltSvc::Services(services))) => services,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].protocol, "h3");
+        assert_eq!(services[0].host, None);
+        assert_eq!(services[0].max_age, 3600);
+        assert_eq!(services[1].protocol, "h3-29");
+        assert_eq!(services[1].port, 8443);
+        assert!(services[1].persist);
+
+        let mut output = Headers::new();
+        output.set(&AltSvc::Services(services)).unwrap();
+        assert_eq!(output.get_raw("Alt-Svc").unwrap(),
+                   &[b"h3=\":443\"; ma=3600, h3-29=\"alt.example.com:8443\"; persist=1".to_vec()][..]);
+        output.set(&AltSvc::Services(vec![AltService::new("a=b", None, 80)])).unwrap();
+        assert_eq!(output.get_raw("Alt-Svc").unwrap(), &[b"a%3Db=\":80\"".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_alt_svc_clear() {
+        assert_eq!(headers("Alt-Svc", "clear").get_without_base::<AltSvc>(),
+                   Some(Ok(AltSvc::Clear)));
+        for value in &["clear, h2=\":443\"", "h2=:443", "h2=\":http\"", "h2=\"\"", ""] {
+            assert!(headers("Alt-Svc", value).get_without_base::<AltSvc>().unwrap().is_err(),
+                    "{}", value);
+        }
+        assert!(headers("Alt-Svc-Used", "1").get_without_base::<AltSvcUsed>().unwrap().is_ok());
+        assert!(headers("Alt-Svc-Used", "0").get_without_base::<AltSvcUsed>().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_date_cache() {
+        let now = Cell::new(UNIX_EPOCH + Duration::from_millis(1_500));
+        let mut cache = DateCache::with_clock(|| now.get());
+        assert_eq!(cache.get(), "Thu, 01 Jan 1970 00:00:01 GMT");
+        now.set(UNIX_EPOCH + Duration::from_millis(1_999));
+        assert_eq!(cache.get(), "Thu, 01 Jan 1970 00:00:01 GMT");
+        now.set(UNIX_EPOCH + Duration::from_secs(86_400 * 365 + 2));
+        let mut headers = Headers::new();
+        cache.set(&mut headers);
+        assert_eq!(headers.get_raw("Date").unwrap(), &[b"Fri, 01 Jan 1971 00:00:02 GMT".to_vec()][..]);
+        let date: HttpDate = headers.get_without_base::<Date>().unwrap().unwrap().into();
+        assert_eq!(date, HttpDate::from(now.get()));
+    }
+
+    #[test]
+    fn test_expect() {
+        assert_eq!(headers("Expect", "100-Continue").get_without_base::<Expect>(),
+                   Some(Ok(Expect::Continue)));
+        let mut repeated = headers("Expect", "100-continue");
+        repeated.append_raw("Expect", b"100-continue".to_vec());
+        assert_eq!(repeated.get_without_base::<Expect>(), Some(Ok(Expect::Continue)));
+        for value in &["", "200-ok", "100-continue, 200-ok", "100-continue; x=1"] {
+            assert!(headers("Expect", value).get_without_base::<Expect>().unwrap().is_err(),
+                    "{}", value);
+        }
+        let mut output = Headers::new();
+        output.set(&Expect::Continue).unwrap();
+        assert_eq!(output.get_raw("Expect").unwrap(), &[b"100-continue".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_prefer() {
+        let mut headers = headers("Prefer", "respond-async, wait=100");
+        headers.append_raw("Prefer", b"handling=lenient, foo=\"a, b\"; p".to_vec());
+        let preferences: Vec<Preference> =
+            headers.get_without_base::<Prefer>().unwrap().unwrap().into();
+        assert_eq!(preferences.len(), 4);
+        assert_eq!(preferences[0].kind, PreferenceKind::RespondAsync);
+        assert_eq!(preferences[3].kind,
+                   PreferenceKind::Extension("foo".to_owned(), Some("a, b".to_owned())));
+        assert_eq!(preferences[3].parameter("p"), Some(None));
+        assert!(self::headers("Prefer", "").get_without_base::<Prefer>().unwrap().is_err());
+
+        let mut output = Headers::new();
+        output.set(&Prefer::from(preferences)).unwrap();
+        assert_eq!(output.get_raw("Prefer").unwrap(),
+                   &[b"respond-async, wait=100, handling=lenient, foo=\"a, b\"; p".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_preference_applied() {
+        let applied = vec![PreferenceKind::Return(ReturnPreference::Representation),
+                           PreferenceKind::Extension("x".to_owned(), None)];
+        let mut headers = Headers::new();
+        headers.set(&PreferenceApplied::from(applied.clone())).unwrap();
+        assert_eq!(headers.get_raw("Preference-Applied").unwrap(),
+                   &[b"return=representation, x".to_vec()][..]);
+        let parsed: Vec<PreferenceKind> =
+            headers.get_without_base::<PreferenceApplied>().unwrap().unwrap().into();
+        assert_eq!(parsed, applied);
+        let invalid = PreferenceApplied::from(vec![PreferenceKind::Extension("a,b".to_owned(), None)]);
+        headers.set(&invalid).unwrap();
+        assert_eq!(headers.get_raw("Preference-Applied").unwrap(), &[b"a%2Cb".to_vec()][..]);
+    }
+}
@@ -0,0 +1,269 @@
+use std::io::{self, Write};
+use std::str;
+use std::time::Duration;
+
+use url::Origin as UrlOrigin;
+
+use header::{Header, RequestHeader, ResponseHeader, parse_value, serialize_value, parse_list0,
+             serialize_list};
+use header::item::{HeaderField, Url};
+use Method;
+
+fn parse_origin(raw: &str) -> Result<UrlOrigin, ()> {
+    if raw == "null" {
+        return Ok(UrlOrigin::new_opaque());
+    }
+    let url = Url::parse(raw).map_err(|_| ())?;
+    if raw.ends_with('/') || url.path() != "/" || url.query().is_some() ||
+       url.fragment().is_some() || !url.username().is_empty() || url.password().is_some() {
+        return Err(());
+    }
+    let origin = url.origin();
+    if !origin.is_tuple() {
+        return Err(());
+    }
+    Ok(origin)
+}
+
+header!{
+    /// `Origin` header, [RFC6454 Section 7]
+    ///
+    /// Opaque origins are serialized as `null`.
+    pub struct Origin(UrlOrigin);
+    (RequestHeader);
+    NAME = "Origin";
+    SENSITIVE = false;
+    parse(s, _base) {
+        if s.len() != 1 {
+            return Err(());
+        }
+        parse_origin(str::from_utf8(&s[0]).map_err(|_| ())?.trim()).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, self.0.ascii_serialization())
+    }
+}
+
+/// `Access-Control-Allow-Origin` header, [Fetch Section 3.2.3]
+#[derive(Clone, Debug)]
+pub enum AccessControlAllowOrigin {
+    /// Any origin may read the response, serialized as `*`.
+    ///
+    /// Must not be used together with credentials.
+    Any,
+    /// Only the given origin may read the response.
+    Origin(UrlOrigin),
+}
+
+impl ResponseHeader for AccessControlAllowOrigin {}
+
+impl Header for AccessControlAllowOrigin {
+    const NAME: &'static str = "Access-Control-Allow-Origin";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        if s.len() != 1 {
+            return Err(());
+        }
+        let raw = str::from_utf8(&s[0]).map_err(|_| ())?.trim();
+        if raw == "*" {
+            return Ok(AccessControlAllowOrigin::Any);
+        }
+        parse_origin(raw).map(AccessControlAllowOrigin::Origin)
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        match *self {
+            AccessControlAllowOrigin::Any => serialize_value(iter, "*"),
+            AccessControlAllowOrigin::Origin(ref origin) => {
+                serialize_value(iter, origin.ascii_serialization())
+            }
+        }
+    }
+}
+
+header!{
+    /// `Access-Control-Allow-Methods` header, [Fetch Section 3.2.3]
+    ///
+    /// The wildcard `*` is represented as an unregistered method.
+    pub struct AccessControlAllowMethods(Vec<Method>);
+    (ResponseHeader);
+    NAME = "Access-Control-Allow-Methods";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list0(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Access-Control-Allow-Headers` header, [Fetch Section 3.2.3]
+    pub struct AccessControlAllowHeaders(Vec<HeaderField>);
+    (ResponseHeader);
+    NAME = "Access-Control-Allow-Headers";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list0(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+/// `Access-Control-Allow-Credentials` header, [Fetch Section 3.2.3]
+///
+/// The only valid value is `true`, so the header field is either
+/// present or absent.
+#[derive(Clone, Copy, Debug)]
+pub struct AccessControlAllowCredentials;
+
+impl ResponseHeader for AccessControlAllowCredentials {}
+
+impl Header for AccessControlAllowCredentials {
+    const NAME: &'static str = "Access-Control-Allow-Credentials";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        if s.len() != 1 || s[0] != b"true" {
+            return Err(());
+        }
+        Ok(AccessControlAllowCredentials)
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter, "true")
+    }
+}
+
+header!{
+    /// `Access-Control-Expose-Headers` header, [Fetch Section 3.2.3]
+    pub struct AccessControlExposeHeaders(Vec<HeaderField>);
+    (ResponseHeader);
+    NAME = "Access-Control-Expose-Headers";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list0(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Access-Control-Max-Age` header, [Fetch Section 3.2.3]
+    pub struct AccessControlMaxAge(Duration);
+    (ResponseHeader);
+    NAME = "Access-Control-Max-Age";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<u64>(s).map(Duration::from_secs).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, self.0.as_secs())
+    }
+}
+
+header!{
+    /// `Access-Control-Request-Method` header, [Fetch Section 3.2.2]
+    pub struct AccessControlRequestMethod(Method);
+    (RequestHeader);
+    NAME = "Access-Control-Request-Method";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<Method>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Access-Control-Request-Headers` header, [Fetch Section 3.2.2]
+    pub struct AccessControlRequestHeaders(Vec<HeaderField>);
+    (RequestHeader);
+    NAME = "Access-Control-Request-Headers";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list0(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use url::Origin as UrlOrigin;
+
+    use header::{Headers, base, headers};
+    use header::item::HeaderField;
+    use super::{AccessControlAllowCredentials, AccessControlAllowOrigin, AccessControlMaxAge,
+                AccessControlRequestHeaders, Origin};
+
+    #[test]
+    fn test_origin() {
+        let origin: UrlOrigin = headers("Origin", "https://Example.com:8443")
+            .get::<Origin>(base())
+            .unwrap()
+            .unwrap()
+            .into();
+        assert_eq!(origin.ascii_serialization(), "https://example.com:8443");
+        let origin: UrlOrigin =
+            headers("Origin", "null").get::<Origin>(base()).unwrap().unwrap().into();
+        assert!(!origin.is_tuple());
+
+        for value in &["https://example.com/", "https://example.com/a", "https://a@example.com",
+                       "https://example.com?x", "https://example.com#x", "example.com",
+                       "data:text/plain,x"] {
+            assert!(headers("Origin", value).get::<Origin>(base()).unwrap().is_err(),
+                    "{}", value);
+        }
+
+        let mut output = Headers::new();
+        output.set(&Origin::from(UrlOrigin::new_opaque())).unwrap();
+        assert_eq!(output.get_raw("Origin").unwrap(), &[b"null".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_allow_origin() {
+        let any = headers("Access-Control-Allow-Origin", "*");
+        assert!(matches!(any.get::<AccessControlAllowOrigin>(base()),
+                         Some(Ok(AccessControlAllowOrigin::Any))));
+        let origin = headers("Access-Control-Allow-Origin", "http://example.com");
+        match origin.get::<AccessControlAllowOrigin>(base()) {
+            Some(Ok(AccessControlAllowOrigin::Origin(origin))) => {
+                assert_eq!(origin.ascii_serialization(), "http://example.com")
+            }
+            other => panic!("{:?}", other),
+        }
+        let credentials = headers("Access-Control-Allow-Credentials", "True");
+        assert!(credentials.get::<AccessControlAllowCredentials>(base()).unwrap().is_err());
+        let credentials = headers("Access-Control-Allow-Credentials", "true");
+        assert!(credentials.get::<AccessControlAllowCredentials>(base()).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_preflight_headers() {
+        let request = headers("Access-Control-Request-Headers", "X-Custom, content-type");
+        let names: Vec<HeaderField> = request.get::<AccessControlRequestHeaders>(base())
+            .unwrap()
+            .unwrap()
+            .into();
+        assert_eq!(names, vec![HeaderField::from("x-custom"), HeaderField::from("Content-Type")]);
+
+        let max_age: Duration = headers("Access-Control-Max-Age", "600")
+            .get::<AccessControlMaxAge>(base())
+            .unwrap()
+            .unwrap()
+            .into();
+        assert_eq!(max_age, Duration::from_secs(600));
+        assert!(headers("Access-Control-Max-Age", "-1")
+                    .get::<AccessControlMaxAge>(base())
+                    .unwrap()
+                    .is_err());
+    }
+}
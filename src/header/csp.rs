@@ -0,0 +1,760 @@
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::str::{self, FromStr};
+
+use url::{Origin, Url};
+use url::percent_encoding::percent_decode;
+
+use header::{ResponseHeader, ListElements, serialize_value};
+use util::is_scheme;
+
+/// A keyword source expression like `'self'`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CspKeyword {
+    /// `'self'`, the origin of the protected resource
+    Self_,
+    /// `'unsafe-inline'`
+    UnsafeInline,
+    /// `'unsafe-eval'`
+    UnsafeEval,
+    /// `'strict-dynamic'`
+    StrictDynamic,
+    /// `'unsafe-hashes'`
+    UnsafeHashes,
+    /// `'report-sample'`
+    ReportSample,
+    /// `'unsafe-allow-redirects'`
+    UnsafeAllowRedirects,
+    /// `'wasm-unsafe-eval'`
+    WasmUnsafeEval,
+}
+
+const KEYWORDS: [(CspKeyword, &str); 8] = [(CspKeyword::Self_, "'self'"),
+                                           (CspKeyword::UnsafeInline, "'unsafe-inline'"),
+                                           (CspKeyword::UnsafeEval, "'unsafe-eval'"),
+                                           (CspKeyword::StrictDynamic, "'strict-dynamic'"),
+                                           (CspKeyword::UnsafeHashes, "'unsafe-hashes'"),
+                                           (CspKeyword::ReportSample, "'report-sample'"),
+                                           (CspKeyword::UnsafeAllowRedirects,
+                                            "'unsafe-allow-redirects'"),
+                                           (CspKeyword::WasmUnsafeEval, "'wasm-unsafe-eval'")];
+
+/// The digest algorithm of a hash source expression.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CspHashAlgorithm {
+    /// SHA-256
+    Sha256,
+    /// SHA-384
+    Sha384,
+    /// SHA-512
+    Sha512,
+}
+
+const HASH_ALGORITHMS: [(CspHashAlgorithm, &str); 3] = [(CspHashAlgorithm::Sha256, "sha256"),
+                                                        (CspHashAlgorithm::Sha384, "sha384"),
+                                                        (CspHashAlgorithm::Sha512, "sha512")];
+
+/// The port of a host source expression.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CspPort {
+    /// `*`, any port
+    Any,
+    /// A specific port.
+    Port(u16),
+}
+
+/// A host source expression like `https://*.example.com:8443/path/`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CspHostSource {
+    /// The scheme, if missing the scheme of the protected resource
+    /// is used.
+    pub scheme: Option<String>,
+    /// The host, may start with the wildcard `*.`
+    ///
+    /// A host of `*` matches all hosts.
+    pub host: String,
+    /// The port, if missing the default port of the scheme.
+    pub port: Option<CspPort>,
+    /// A path, ending with `/` to match all paths below.
+    pub path: Option<String>,
+}
+
+impl CspHostSource {
+    /// Constructs a source for a host with any scheme, port and path.
+    pub fn new<H: Into<String>>(host: H) -> CspHostSource {
+        CspHostSource {
+            scheme: None,
+            host: host.into(),
+            port: None,
+            path: None,
+        }
+    }
+}
+
+/// A source expression of a source list, [CSP3 Section 2.3.1]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CspSource {
+    /// `*`, all URLs with a network scheme
+    Any,
+    /// A keyword like `'self'`.
+    Keyword(CspKeyword),
+    /// A scheme like `https:` or `data:`, stored without the colon.
+    Scheme(String),
+    /// A host with optional scheme, port and path.
+    Host(CspHostSource),
+    /// `'nonce-<value>'`, allows elements with this nonce.
+    Nonce(String),
+    /// `'sha256-<digest>'`, allows inline content with this hash.
+    Hash(CspHashAlgorithm, String),
+}
+
+impl FromStr for CspSource {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<CspSource, ()> {
+        if s == "*" {
+            return Ok(CspSource::Any);
+        }
+        if let Some(keyword) = KEYWORDS.iter().find(|x| x.1.eq_ignore_ascii_case(s)) {
+            return Ok(CspSource::Keyword(keyword.0));
+        }
+        if s.len() > 2 && s.starts_with('\'') && s.ends_with('\'') {
+            let inner = &s[1..s.len() - 1];
+            let dash = inner.find('-').ok_or(())?;
+            let (prefix, value) = (&inner[..dash], &inner[dash + 1..]);
+            if !is_base64_value(value) {
+                return Err(());
+            }
+            if prefix.eq_ignore_ascii_case("nonce") {
+                return Ok(CspSource::Nonce(value.to_owned()));
+            }
+            return HASH_ALGORITHMS.iter()
+                .find(|x| x.1.eq_ignore_ascii_case(prefix))
+                .map(|x| CspSource::Hash(x.0, value.to_owned()))
+                .ok_or(());
+        }
+        if s.ends_with(':') && is_scheme(&s[..s.len() - 1]) {
+            return Ok(CspSource::Scheme(s[..s.len() - 1].to_ascii_lowercase()));
+        }
+        parse_host_source(s).map(CspSource::Host)
+    }
+}
+
+impl Display for CspSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CspSource::Any => f.write_str("*"),
+            CspSource::Keyword(keyword) => {
+                f.write_str(KEYWORDS.iter().find(|x| x.0 == keyword).unwrap().1)
+            }
+            CspSource::Scheme(ref scheme) => write!(f, "{}:", scheme),
+            CspSource::Host(ref source) => {
+                if let Some(ref scheme) = source.scheme {
+                    write!(f, "{}://", scheme)?;
+                }
+                f.write_str(&source.host)?;
+                match source.port {
+                    Some(CspPort::Any) => f.write_str(":*")?,
+                    Some(CspPort::Port(port)) => write!(f, ":{}", port)?,
+                    None => (),
+                }
+                if let Some(ref path) = source.path {
+                    f.write_str(path)?;
+                }
+                Ok(())
+            }
+            CspSource::Nonce(ref value) => write!(f, "'nonce-{}'", value),
+            CspSource::Hash(algorithm, ref value) => {
+                write!(f,
+                       "'{}-{}'",
+                       HASH_ALGORITHMS.iter().find(|x| x.0 == algorithm).unwrap().1,
+                       value)
+            }
+        }
+    }
+}
+
+fn is_base64_value(s: &str) -> bool {
+    let data = s.trim_end_matches('=');
+    !data.is_empty() && s.len() - data.len() <= 2 &&
+    data.chars().all(|c| c.is_ascii_alphanumeric() || "+/-_".contains(c))
+}
+
+fn parse_host_source(s: &str) -> Result<CspHostSource, ()> {
+    let (scheme, rest) = match s.find("://") {
+        Some(i) if is_scheme(&s[..i]) => (Some(s[..i].to_ascii_lowercase()), &s[i + 3..]),
+        Some(_) => return Err(()),
+        None => (None, s),
+    };
+    let host_end = rest.find([':', '/']).unwrap_or(rest.len());
+    let host = &rest[..host_end];
+    let labels = host.strip_prefix("*.").unwrap_or(host).trim_end_matches('.');
+    let is_label = |x: &str| {
+        !x.is_empty() && x.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if host != "*" && !labels.split('.').all(is_label) {
+        return Err(());
+    }
+    let mut rest = &rest[host_end..];
+    let port = if let Some(after) = rest.strip_prefix(':') {
+        let end = after.find('/').unwrap_or(after.len());
+        let port = &after[..end];
+        rest = &after[end..];
+        if port == "*" {
+            Some(CspPort::Any)
+        } else if !port.is_empty() && port.bytes().all(|c| c.is_ascii_digit()) {
+            Some(CspPort::Port(port.parse().map_err(|_| ())?))
+        } else {
+            return Err(());
+        }
+    } else {
+        None
+    };
+    if rest.contains(['?', '#', ';', ',']) {
+        return Err(());
+    }
+    Ok(CspHostSource {
+        scheme,
+        host: host.to_ascii_lowercase(),
+        port,
+        path: if rest.is_empty() { None } else { Some(rest.to_owned()) },
+    })
+}
+
+/// The value of a directive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CspValue {
+    /// A source list, empty for `'none'`.
+    Sources(Vec<CspSource>),
+    /// Other directives like `sandbox` or `report-to` have tokens.
+    Tokens(Vec<String>),
+}
+
+/// A directive of a policy like `script-src 'self'`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CspDirective {
+    /// The lowercase name.
+    pub name: String,
+    /// The value.
+    pub value: CspValue,
+}
+
+const SOURCE_LIST_DIRECTIVES: [&str; 19] = ["base-uri",
+                                            "child-src",
+                                            "connect-src",
+                                            "default-src",
+                                            "font-src",
+                                            "form-action",
+                                            "frame-ancestors",
+                                            "frame-src",
+                                            "img-src",
+                                            "manifest-src",
+                                            "media-src",
+                                            "object-src",
+                                            "script-src",
+                                            "script-src-attr",
+                                            "script-src-elem",
+                                            "style-src",
+                                            "style-src-attr",
+                                            "style-src-elem",
+                                            "worker-src"];
+
+impl CspDirective {
+    /// Constructs a directive with a source list.
+    pub fn sources<N: Into<String>>(name: N, sources: Vec<CspSource>) -> CspDirective {
+        CspDirective {
+            name: name.into().to_ascii_lowercase(),
+            value: CspValue::Sources(sources),
+        }
+    }
+
+    /// Constructs a directive with tokens.
+    pub fn tokens<N: Into<String>>(name: N, tokens: Vec<String>) -> CspDirective {
+        CspDirective {
+            name: name.into().to_ascii_lowercase(),
+            value: CspValue::Tokens(tokens),
+        }
+    }
+
+    /// Checks if a load of the URL is allowed by the source list,
+    /// [CSP3 Section 6.7.2.5]
+    ///
+    /// The origin is the origin of the protected resource. Nonces,
+    /// hashes and keywords other than `'self'` never match URLs.
+    /// Directives without a source list allow all URLs.
+    pub fn allows(&self, url: &Url, origin: &Origin) -> bool {
+        match self.value {
+            CspValue::Sources(ref sources) => sources.iter().any(|x| matches_source(x, url, origin)),
+            CspValue::Tokens(_) => true,
+        }
+    }
+}
+
+impl Display for CspDirective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        match self.value {
+            CspValue::Sources(ref sources) if sources.is_empty() => f.write_str(" 'none'"),
+            CspValue::Sources(ref sources) => {
+                for source in sources {
+                    write!(f, " {}", source)?;
+                }
+                Ok(())
+            }
+            CspValue::Tokens(ref tokens) => {
+                for token in tokens {
+                    write!(f, " {}", token)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn matches_source(source: &CspSource, url: &Url, origin: &Origin) -> bool {
+    let (origin_scheme, origin_host, origin_port) = match *origin {
+        Origin::Tuple(ref scheme, ref host, port) => (&scheme[..], Some(host.to_string()), port),
+        Origin::Opaque(_) => ("", None, 0),
+    };
+    match *source {
+        CspSource::Any => {
+            matches!(url.scheme(), "http" | "https") || url.scheme() == origin_scheme
+        }
+        CspSource::Scheme(ref scheme) => scheme_matches(scheme, url.scheme()),
+        CspSource::Host(ref source) => {
+            let host = match url.host_str() {
+                Some(host) => host.to_ascii_lowercase(),
+                None => return false,
+            };
+            let scheme = source.scheme.as_ref().map_or(origin_scheme, |x| &x[..]);
+            if !scheme_matches(scheme, url.scheme()) {
+                return false;
+            }
+            let host_matches = match source.host.strip_prefix('*') {
+                Some("") => true,
+                Some(suffix) => host.ends_with(suffix) && url.domain().is_some(),
+                None => source.host.trim_end_matches('.') == host.trim_end_matches('.'),
+            };
+            if !host_matches {
+                return false;
+            }
+            let port_matches = match source.port {
+                Some(CspPort::Any) => true,
+                Some(CspPort::Port(port)) => Some(port) == url.port_or_known_default(),
+                None => url.port().is_none(),
+            };
+            port_matches && source.path.as_ref().is_none_or(|x| path_matches(x, url.path()))
+        }
+        CspSource::Keyword(CspKeyword::Self_) => {
+            if url.origin() == *origin {
+                return true;
+            }
+            origin_host.is_some() && url.host_str().map(|x| x.to_string()) == origin_host &&
+            (url.port_or_known_default() == Some(origin_port) ||
+             (url.port().is_none() && default_port(origin_scheme) == Some(origin_port))) &&
+            (matches!(url.scheme(), "https" | "wss") ||
+             (origin_scheme == "http" && matches!(url.scheme(), "http" | "ws")))
+        }
+        CspSource::Keyword(_) | CspSource::Nonce(_) | CspSource::Hash(..) => false,
+    }
+}
+
+// Secure variants of a scheme match too. [CSP3 Section 6.7.2.9]
+fn scheme_matches(pattern: &str, scheme: &str) -> bool {
+    pattern.eq_ignore_ascii_case(scheme) ||
+    matches!((&pattern.to_ascii_lowercase()[..], scheme),
+             ("http", "https") | ("ws", "wss") | ("ws", "http") | ("ws", "https") |
+             ("wss", "https"))
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+// A path ending in `/` matches all paths below, others only
+// themselves. Segments are compared percent-decoded.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let decode = |s: &str| percent_decode(s.as_bytes()).decode_utf8_lossy().into_owned();
+    let pattern = decode(pattern);
+    let path = decode(path);
+    if pattern == "/" {
+        return true;
+    }
+    if pattern.ends_with('/') {
+        path.starts_with(&pattern)
+    } else {
+        path == pattern
+    }
+}
+
+/// The directives consulted for a fetch directive, [CSP3 Section 6.8.3]
+fn fallback_list(name: &str) -> &'static [&'static str] {
+    match name {
+        "script-src-elem" => &["script-src-elem", "script-src", "default-src"],
+        "script-src-attr" => &["script-src-attr", "script-src", "default-src"],
+        "style-src-elem" => &["style-src-elem", "style-src", "default-src"],
+        "style-src-attr" => &["style-src-attr", "style-src", "default-src"],
+        "worker-src" => &["worker-src", "child-src", "script-src", "default-src"],
+        "frame-src" => &["frame-src", "child-src", "default-src"],
+        "script-src" => &["script-src", "default-src"],
+        "style-src" => &["style-src", "default-src"],
+        "child-src" => &["child-src", "default-src"],
+        "connect-src" => &["connect-src", "default-src"],
+        "font-src" => &["font-src", "default-src"],
+        "img-src" => &["img-src", "default-src"],
+        "manifest-src" => &["manifest-src", "default-src"],
+        "media-src" => &["media-src", "default-src"],
+        "object-src" => &["object-src", "default-src"],
+        _ => &[],
+    }
+}
+
+/// A Content Security Policy, [CSP3 Section 2.2]
+///
+/// A policy is a list of directives with unique names.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CspPolicy {
+    /// The directives in order.
+    pub directives: Vec<CspDirective>,
+}
+
+impl CspPolicy {
+    /// Constructs an empty policy.
+    pub fn new() -> CspPolicy {
+        CspPolicy::default()
+    }
+
+    /// Returns the directive with the name.
+    pub fn get(&self, name: &str) -> Option<&CspDirective> {
+        self.directives.iter().find(|x| x.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Inserts a directive, replacing a directive with the same name.
+    pub fn set(&mut self, directive: CspDirective) {
+        match self.directives.iter_mut().find(|x| x.name == directive.name) {
+            Some(present) => *present = directive,
+            None => self.directives.push(directive),
+        }
+    }
+
+    /// Returns the directive that governs a fetch directive.
+    ///
+    /// Missing fetch directives fall back to more general directives
+    /// like `default-src`.
+    pub fn effective(&self, name: &str) -> Option<&CspDirective> {
+        let name = name.to_ascii_lowercase();
+        match fallback_list(&name) {
+            [] => self.get(&name),
+            list => list.iter().filter_map(|x| self.get(x)).next(),
+        }
+    }
+
+    /// Checks if the policy allows loading the URL for a fetch
+    /// directive like `img-src`.
+    ///
+    /// The origin is the origin of the protected resource.
+    pub fn allows(&self, name: &str, url: &Url, origin: &Origin) -> bool {
+        self.effective(name).is_none_or(|x| x.allows(url, origin))
+    }
+
+    /// Merges another policy into this one.
+    ///
+    /// The result allows everything either policy allows. A directive
+    /// restricts the result only if both policies restrict it: missing
+    /// fetch directives are compared through their fallbacks and
+    /// source lists are combined. Source lists whose union cannot be
+    /// expressed, like `'strict-dynamic'` in only one of them, allow
+    /// everything. Nonces and hashes are replaced by `*` if the other
+    /// list allows all inline content, as they would disable
+    /// `'unsafe-inline'`.
+    pub fn merge(&mut self, other: &CspPolicy) {
+        let mut merged = CspPolicy::new();
+        for name in &FETCH_DIRECTIVES {
+            let sources = match (self.effective(name), other.effective(name)) {
+                (Some(a), Some(b)) => union_sources(source_list(a), source_list(b)),
+                _ => continue,
+            };
+            if merged.effective(name).map(source_list) != Some(&sources[..]) {
+                merged.directives.push(CspDirective::sources(*name, sources));
+            }
+        }
+        for directive in &self.directives {
+            if !FETCH_DIRECTIVES.contains(&&directive.name[..]) {
+                if let Some(directive) = merge_directive(directive, other.get(&directive.name)) {
+                    merged.directives.push(directive);
+                }
+            }
+        }
+        for name in &["report-to", "report-uri"] {
+            if let (None, Some(directive)) = (self.get(name), other.get(name)) {
+                merged.directives.push(directive.clone());
+            }
+        }
+        *self = merged;
+    }
+}
+
+// The fetch directives, each after the directives it falls back to.
+const FETCH_DIRECTIVES: [&str; 16] = ["default-src",
+                                      "child-src",
+                                      "connect-src",
+                                      "font-src",
+                                      "img-src",
+                                      "manifest-src",
+                                      "media-src",
+                                      "object-src",
+                                      "script-src",
+                                      "style-src",
+                                      "frame-src",
+                                      "script-src-attr",
+                                      "script-src-elem",
+                                      "style-src-attr",
+                                      "style-src-elem",
+                                      "worker-src"];
+
+fn source_list(directive: &CspDirective) -> &[CspSource] {
+    match directive.value {
+        CspValue::Sources(ref sources) => sources,
+        CspValue::Tokens(_) => &[],
+    }
+}
+
+// Merges a directive without fallback with the one of the other
+// policy. Reporting directives are kept from this policy.
+fn merge_directive(directive: &CspDirective, other: Option<&CspDirective>) -> Option<CspDirective> {
+    if matches!(&directive.name[..], "report-to" | "report-uri") {
+        return Some(directive.clone());
+    }
+    let value = match (&directive.value, &other?.value) {
+        (CspValue::Sources(a), CspValue::Sources(b)) => CspValue::Sources(union_sources(a, b)),
+        (CspValue::Tokens(a), CspValue::Tokens(b)) => {
+            let mut tokens = a.clone();
+            for token in b {
+                if !tokens.contains(token) {
+                    tokens.push(token.clone());
+                }
+            }
+            CspValue::Tokens(tokens)
+        }
+        _ => return None,
+    };
+    Some(CspDirective {
+        name: directive.name.clone(),
+        value,
+    })
+}
+
+fn union_sources(a: &[CspSource], b: &[CspSource]) -> Vec<CspSource> {
+    let has = |list: &[CspSource], keyword| list.contains(&CspSource::Keyword(keyword));
+    let is_nonce_or_hash = |x: &CspSource| matches!(*x, CspSource::Nonce(_) | CspSource::Hash(..));
+    let allows_inline = |list: &[CspSource]| {
+        has(list, CspKeyword::UnsafeInline) && !has(list, CspKeyword::StrictDynamic) &&
+        !list.iter().any(is_nonce_or_hash)
+    };
+    if has(a, CspKeyword::StrictDynamic) != has(b, CspKeyword::StrictDynamic) {
+        return allow_all();
+    }
+    let mut sources = a.to_vec();
+    for source in b {
+        if !sources.contains(source) {
+            sources.push(source.clone());
+        }
+    }
+    if (allows_inline(a) || allows_inline(b)) && sources.iter().any(is_nonce_or_hash) {
+        // Nonces and hashes also match external resources.
+        sources.retain(|x| !is_nonce_or_hash(x));
+        if !sources.contains(&CspSource::Any) {
+            sources.push(CspSource::Any);
+        }
+    }
+    sources
+}
+
+fn allow_all() -> Vec<CspSource> {
+    let mut sources = vec![CspSource::Any];
+    sources.extend(["data", "blob", "filesystem", "mediastream"]
+        .iter()
+        .map(|x| CspSource::Scheme((*x).to_owned())));
+    sources.extend([CspKeyword::UnsafeInline, CspKeyword::UnsafeEval, CspKeyword::WasmUnsafeEval]
+        .iter()
+        .map(|x| CspSource::Keyword(*x)));
+    sources
+}
+
+impl FromStr for CspPolicy {
+    type Err = ();
+
+    /// Parses a serialized policy, [CSP3 Section 2.2.1]
+    ///
+    /// Directives with an invalid name or repeated directives are
+    /// ignored, as are invalid source expressions.
+    fn from_str(s: &str) -> Result<CspPolicy, ()> {
+        if s.contains(',') {
+            return Err(());
+        }
+        let mut policy = CspPolicy::new();
+        for token in s.split(';') {
+            let mut parts = token.split(|c: char| c.is_ascii_whitespace()).filter(|x| !x.is_empty());
+            let name = match parts.next() {
+                Some(name) => name.to_ascii_lowercase(),
+                None => continue,
+            };
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') ||
+               policy.get(&name).is_some() {
+                continue;
+            }
+            let directive = if SOURCE_LIST_DIRECTIVES.contains(&&name[..]) {
+                let values: Vec<&str> = parts.collect();
+                let sources = if values.len() == 1 && values[0].eq_ignore_ascii_case("'none'") {
+                    Vec::new()
+                } else {
+                    values.iter().filter_map(|x| x.parse().ok()).collect()
+                };
+                CspDirective::sources(name, sources)
+            } else {
+                CspDirective::tokens(name, parts.map(ToOwned::to_owned).collect())
+            };
+            policy.directives.push(directive);
+        }
+        Ok(policy)
+    }
+}
+
+impl Display for CspPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, directive) in self.directives.iter().enumerate() {
+            if i != 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", directive)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_policies(s: &[Vec<u8>]) -> Result<Vec<CspPolicy>, ()> {
+    let mut policies = Vec::new();
+    for element in ListElements::new(s) {
+        policies.push(str::from_utf8(element?).map_err(|_| ())?.parse()?);
+    }
+    if policies.is_empty() {
+        return Err(());
+    }
+    Ok(policies)
+}
+
+fn serialize_policies<I, W>(iter: I, policies: &[CspPolicy]) -> io::Result<()>
+    where I: Iterator<Item = W>,
+          W: Write
+{
+    let policies: Vec<String> = policies.iter().map(ToString::to_string).collect();
+    serialize_value(iter, policies.join(", "))
+}
+
+header!{
+    /// `Content-Security-Policy` header, [CSP3 Section 3.1]
+    ///
+    /// All listed policies are enforced, so a load must be allowed
+    /// by each of them.
+    pub struct ContentSecurityPolicy(Vec<CspPolicy>);
+    (ResponseHeader);
+    NAME = "Content-Security-Policy";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_policies(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_policies(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Content-Security-Policy-Report-Only` header, [CSP3 Section 3.2]
+    ///
+    /// Violations of the policies are reported but not blocked.
+    pub struct ContentSecurityPolicyReportOnly(Vec<CspPolicy>);
+    (ResponseHeader);
+    NAME = "Content-Security-Policy-Report-Only";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_policies(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_policies(iter, &self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::{CspKeyword, CspPolicy, CspSource};
+
+    fn merge(a: &str, b: &str) -> String {
+        let mut policy: CspPolicy = a.parse().unwrap();
+        policy.merge(&b.parse().unwrap());
+        policy.to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        let policy: CspPolicy = "default-src 'self'; IMG-SRC * data:; script-src 'none'; img-src x"
+            .parse()
+            .unwrap();
+        assert_eq!(policy.to_string(), "default-src 'self'; img-src * data:; script-src 'none'");
+        assert!("default-src 'self', img-src *".parse::<CspPolicy>().is_err());
+        assert_eq!("'nonce-abc='".parse(), Ok(CspSource::Nonce("abc=".to_owned())));
+        assert_eq!("'UNSAFE-INLINE'".parse(), Ok(CspSource::Keyword(CspKeyword::UnsafeInline)));
+        assert!("'nonce-'".parse::<CspSource>().is_err());
+        assert!("https://exa mple.com".parse::<CspSource>().is_err());
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy: CspPolicy = "default-src 'self'; img-src https://*.example.com:* example.org/a/"
+            .parse()
+            .unwrap();
+        let origin = Url::parse("https://example.org/").unwrap().origin();
+        let allows = |name, url| policy.allows(name, &Url::parse(url).unwrap(), &origin);
+        assert!(allows("script-src", "https://example.org/app.js"));
+        assert!(!allows("script-src", "https://example.com/app.js"));
+        assert!(allows("img-src", "https://cdn.example.com:8443/x.png"));
+        assert!(!allows("img-src", "http://cdn.example.com/x.png"));
+        assert!(!allows("img-src", "https://example.org/x.png"));
+        assert!(allows("img-src", "https://example.org/a/x.png"));
+        assert!(allows("sandbox", "https://example.com/"));
+    }
+
+    #[test]
+    fn test_merge_union() {
+        assert_eq!(merge("img-src a.com", "img-src b.com"), "img-src a.com b.com");
+        assert_eq!(merge("script-src 'none'", "script-src a.com"), "script-src a.com");
+        // Only restricted by one of the policies.
+        assert_eq!(merge("img-src a.com", "script-src b.com"), "");
+        assert_eq!(merge("default-src 'self'", "img-src a.com"), "img-src 'self' a.com");
+        assert_eq!(merge("default-src 'self'; img-src a.com", "default-src b.com"),
+                   "default-src 'self' b.com; img-src a.com b.com");
+        assert_eq!(merge("default-src a.com", "default-src a.com; script-src b.com"),
+                   "default-src a.com; script-src a.com b.com");
+        assert_eq!(merge("sandbox allow-scripts; base-uri 'self'", "sandbox allow-forms"),
+                   "sandbox allow-scripts allow-forms");
+        assert_eq!(merge("img-src a.com", "img-src b.com; report-to csp"),
+                   "img-src a.com b.com; report-to csp");
+    }
+
+    #[test]
+    fn test_merge_keywords() {
+        assert_eq!(merge("script-src 'unsafe-inline'", "script-src 'nonce-abc' 'self'"),
+                   "script-src 'unsafe-inline' 'self' *");
+        assert_eq!(merge("script-src 'nonce-abc'", "script-src 'sha256-abc'"),
+                   "script-src 'nonce-abc' 'sha256-abc'");
+        assert_eq!(merge("script-src 'nonce-abc' 'strict-dynamic'", "script-src 'self'"),
+                   "script-src * data: blob: filesystem: mediastream: 'unsafe-inline' \
+                    'unsafe-eval' 'wasm-unsafe-eval'");
+    }
+}
@@ -0,0 +1,191 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::slice;
+
+use url::Url;
+
+use header::Header;
+use header::util::ListElements;
+
+/// A collection of header fields.
+///
+/// Fields are stored in their raw form as one or more lines per name.
+/// Names are compared case-insensitively and keep the order in which
+/// they were first inserted. Typed header fields are parsed and
+/// serialized on access.
+#[derive(Clone, Debug, Default)]
+pub struct Headers {
+    fields: Vec<(String, Vec<Vec<u8>>)>,
+}
+
+impl Headers {
+    /// Constructs an empty collection.
+    pub fn new() -> Headers {
+        Headers::default()
+    }
+
+    /// Parses a typed header field.
+    ///
+    /// Returns `None` if the field is not present.
+    pub fn get<H: Header>(&self, base: Url) -> Option<Result<H, ()>> {
+        self.get_raw(H::NAME).map(|x| H::parse(x, base))
+    }
+
+    /// Parses a typed header field without a base URL.
+    ///
+    /// Use this for fields without URI references. Relative references
+    /// like `Location: /index.html` cannot be resolved and fail to parse.
+    pub fn get_without_base<H: Header>(&self) -> Option<Result<H, ()>> {
+        self.get(Url::parse("about:blank").expect("valid URL"))
+    }
+
+    /// Serializes a typed header field and replaces present values.
+    pub fn set<H: Header>(&mut self, header: &H) -> io::Result<()> {
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        header.serialize((0..).map(|index| {
+                lines.borrow_mut().push(Vec::new());
+                LineWriter {
+                    lines: lines.clone(),
+                    index,
+                }
+            }))?;
+        let lines = lines.borrow().clone();
+        self.set_raw(H::NAME, lines);
+        Ok(())
+    }
+
+    // Sets a typed header field built from values that always
+    // serialize, like numbers and fixed tokens.
+    pub(crate) fn set_valid<H: Header>(&mut self, header: &H) {
+        self.set(header).expect("header field value is valid");
+    }
+
+    /// Checks if the typed header field is present.
+    pub fn has<H: Header>(&self) -> bool {
+        self.contains(H::NAME)
+    }
+
+    /// Removes a typed header field.
+    pub fn remove<H: Header>(&mut self) -> Option<Vec<Vec<u8>>> {
+        self.remove_raw(H::NAME)
+    }
+
+    /// Returns the raw lines of a header field.
+    pub fn get_raw(&self, name: &str) -> Option<&[Vec<u8>]> {
+        self.fields.iter().find(|x| x.0.eq_ignore_ascii_case(name)).map(|x| &x.1[..])
+    }
+
+    /// Replaces the raw lines of a header field.
+    pub fn set_raw<N: Into<String>>(&mut self, name: N, lines: Vec<Vec<u8>>) {
+        let name = name.into();
+        match self.fields.iter_mut().find(|x| x.0.eq_ignore_ascii_case(&name)) {
+            Some(field) => field.1 = lines,
+            None => self.fields.push((name, lines)),
+        }
+    }
+
+    /// Appends a raw line to a header field.
+    pub fn append_raw<N: Into<String>>(&mut self, name: N, line: Vec<u8>) {
+        let name = name.into();
+        match self.fields.iter_mut().find(|x| x.0.eq_ignore_ascii_case(&name)) {
+            Some(field) => field.1.push(line),
+            None => self.fields.push((name, vec![line])),
+        }
+    }
+
+    /// Removes a header field and returns its raw lines.
+    pub fn remove_raw(&mut self, name: &str) -> Option<Vec<Vec<u8>>> {
+        let position = self.fields.iter().position(|x| x.0.eq_ignore_ascii_case(name))?;
+        Some(self.fields.remove(position).1)
+    }
+
+    /// Checks if a header field is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get_raw(name).is_some()
+    }
+
+    /// Checks if a list header field like `Connection` contains
+    /// the token, ignoring case.
+    pub fn contains_token(&self, name: &str, token: &str) -> bool {
+        self.get_raw(name).is_some_and(|lines| {
+            ListElements::new(lines).any(|x| x.is_ok_and(|x| x.eq_ignore_ascii_case(token.as_bytes())))
+        })
+    }
+
+    /// Returns the number of distinct header fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Checks if the collection contains no header fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Iterates over the names and raw lines of all header fields.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { inner: self.fields.iter() }
+    }
+}
+
+/// Iterator over the header fields in a collection.
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (String, Vec<Vec<u8>>)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a [Vec<u8>]);
+
+    fn next(&mut self) -> Option<(&'a str, &'a [Vec<u8>])> {
+        self.inner.next().map(|x| (&x.0[..], &x.1[..]))
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a str, &'a [Vec<u8>]);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+// Writes into a line of the shared list of lines.
+struct LineWriter {
+    lines: Rc<RefCell<Vec<Vec<u8>>>>,
+    index: usize,
+}
+
+impl Write for LineWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lines.borrow_mut()[self.index].extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Location, MaxForwards};
+    use super::Headers;
+
+    #[test]
+    fn test_get_without_base() {
+        let mut headers = Headers::new();
+        headers.set_raw("Max-Forwards", vec![b"10".to_vec()]);
+        headers.set_raw("Location", vec![b"/index.html".to_vec()]);
+        let max_forwards: MaxForwards = headers.get_without_base().unwrap().unwrap();
+        assert_eq!(u32::from(max_forwards), 10);
+        assert!(headers.get_without_base::<Location>().unwrap().is_err());
+
+        headers.set_raw("Location", vec![b"https://example.com/index.html".to_vec()]);
+        assert!(headers.get_without_base::<Location>().unwrap().is_ok());
+        headers.remove_raw("Max-Forwards");
+        assert!(headers.get_without_base::<MaxForwards>().is_none());
+    }
+}
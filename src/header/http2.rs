@@ -0,0 +1,54 @@
+use header::{RequestHeader, serialize_value};
+use http2::Settings;
+use util::{BASE64_URL, decode_base64, encode_base64};
+
+header!{
+    /// `HTTP2-Settings` header, [RFC7540 Section 3.2.1]
+    ///
+    /// The `SETTINGS` payload of a client upgrading to HTTP/2, encoded
+    /// in base64url without padding.
+    pub struct Http2Settings(Settings);
+    (RequestHeader);
+    NAME = "HTTP2-Settings";
+    SENSITIVE = false;
+    parse(s, _base) {
+        if s.len() != 1 {
+            return Err(());
+        }
+        let payload = decode_base64(s[0].trim_ascii(), BASE64_URL).ok_or(())?;
+        Settings::from_payload(&payload).map(Into::into).map_err(|_| ())
+    }
+    serialize(self, iter) {
+        serialize_value(iter, encode_base64(&self.0.to_payload(), BASE64_URL, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use http2::Settings;
+    use super::Http2Settings;
+
+    #[test]
+    fn test_http2_settings() {
+        let settings = Settings {
+            max_concurrent_streams: Some(100),
+            initial_window_size: Some(65_535),
+            ..Settings::default()
+        };
+        let mut headers = Headers::new();
+        headers.set(&Http2Settings::from(settings)).unwrap();
+        assert_eq!(headers.get_raw("HTTP2-Settings").unwrap(), &[b"AAMAAABkAAQAAP__".to_vec()][..]);
+        let parsed: Settings = headers.get_without_base::<Http2Settings>().unwrap().unwrap().into();
+        assert_eq!(parsed, settings);
+
+        headers.set_raw("HTTP2-Settings", vec![b"".to_vec()]);
+        let parsed: Settings = headers.get_without_base::<Http2Settings>().unwrap().unwrap().into();
+        assert_eq!(parsed, Settings::default());
+
+        for value in &["AAMAAAB", "AAMAAABkAAQAAP//", "AAMAAABk AAQAAP__"] {
+            headers.set_raw("HTTP2-Settings", vec![value.as_bytes().to_vec()]);
+            assert!(headers.get_without_base::<Http2Settings>().unwrap().is_err(), "{}", value);
+        }
+    }
+}
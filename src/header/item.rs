@@ -5,7 +5,9 @@
 
 use std::ascii::AsciiExt;
 use std::fmt::{self, Display};
-use std::str::FromStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::{self, FromStr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature="negotiation")]
 pub use charsets::Charset;
@@ -13,6 +15,14 @@ pub use language_tags::LanguageTag;
 pub use media_types::MediaType;
 pub use url::Url;
 
+use httpdate;
+use url::percent_encoding::percent_decode;
+
+use Version;
+use header::util::{Token, Tokenizer, encode_token, find_parameter, fmt_parameters, is_quotable,
+                   parse_optional_parameter, parse_parameter, parse_parameters, quote_if_needed};
+use util::{is_tchar, is_token};
+
 /// Content coding names, [RFC 7231, Section 3.1.2.1]
 ///
 /// This shall not be used for `Transfer-Encoding`. Case is
@@ -109,20 +119,45 @@ impl PartialEq for Coding {
 /// use `Quality::new("item", 500)`. A most preferred item with
 /// the weight of 1.0 can be created with `"item".into()` if
 /// needed.
+///
+/// Parameters following the weight are kept as extension
+/// parameters, e.g. `text/html; q=0.5; level=1`.
 #[derive(Clone, Debug)]
 pub struct Quality<T> {
     item: T,
     weight: Weight,
+    parameters: Vec<Parameter>,
 }
 
 impl<T> Quality<T> {
     /// Constructs a new quality item with a given weight.
     pub fn new<I: Into<Weight>>(item: T, weight: I) -> Quality<T> {
         Quality {
-            item: item,
+            item,
             weight: weight.into(),
+            parameters: Vec::new(),
         }
     }
+
+    /// Returns the item.
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+
+    /// Returns the weight of the item.
+    pub fn weight(&self) -> &Weight {
+        &self.weight
+    }
+
+    /// Returns the extension parameters following the weight.
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// Returns a mutable reference to the extension parameters.
+    pub fn parameters_mut(&mut self) -> &mut Vec<Parameter> {
+        &mut self.parameters
+    }
 }
 
 impl<T: Display> Display for Quality<T> {
@@ -130,14 +165,15 @@ impl<T: Display> Display for Quality<T> {
         self.item.fmt(f)?;
         let weight = self.weight.0;
         if weight == 0 {
-            f.write_str("; q=0")
+            f.write_str("; q=0")?;
         } else if weight < 1000 {
             write!(f,
                    "; q=0.{}",
-                   format!("{:03}", weight).trim_right_matches('0'))
-        } else {
-            Ok(())
+                   format!("{:03}", weight).trim_end_matches('0'))?;
+        } else if !self.parameters.is_empty() {
+            f.write_str("; q=1")?;
         }
+        fmt_parameters(f, &self.parameters)
     }
 }
 
@@ -145,34 +181,43 @@ impl<T: FromStr> FromStr for Quality<T> {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Quality<T>, ()> {
-        let mut iter = s.rsplitn(2, ';').map(|x| x.trim());
-        let mut raw_weight = iter.next();
-        let raw_item = iter.next().unwrap_or_else(|| raw_weight.take().unwrap());
-        if let Some(raw_weight) = raw_weight {
-            if raw_weight.starts_with("q=") || raw_weight.starts_with("Q=") {
-                let raw_weight = &raw_weight[2..];
-                if raw_weight.len() <= 5 {
-                    if let Some(weight) = parse_weight(s) {
-                        return Ok(Quality {
-                            item: raw_item.parse::<T>().map_err(|_| ())?,
-                            weight: Weight::new(weight),
-                        });
-                    } else {
+        let mut tokens = Tokenizer::new(s);
+        loop {
+            let offset = s.len() - tokens.rest().len();
+            match tokens.next() {
+                None => return Ok(Quality::new(s.trim().parse().map_err(|_| ())?, 1000)),
+                Some(Err(())) => return Err(()),
+                Some(Ok(Token::Delimiter(';'))) => {
+                    let mut lookahead = tokens.clone();
+                    match lookahead.next() {
+                        Some(Ok(Token::Token(name))) if name.eq_ignore_ascii_case("q") &&
+                                                        lookahead.eat('=') => (),
+                        _ => continue,
+                    }
+                    let mut tokens = Tokenizer::new(&s[offset..]);
+                    let mut parameters = parse_parameters(&mut tokens)?;
+                    if tokens.peek().is_some() {
+                        return Err(());
+                    }
+                    let weight = parameters.remove(0);
+                    if weight.extended {
                         return Err(());
                     }
+                    return Ok(Quality {
+                        item: s[..offset].trim().parse().map_err(|_| ())?,
+                        weight: Weight::new(parse_weight(&weight.value).ok_or(())?),
+                        parameters,
+                    });
                 }
+                Some(Ok(_)) => (),
             }
         }
-        Ok(Quality::new(raw_item.parse().map_err(|_| ())?, 1000))
     }
 }
 
 impl<T> From<T> for Quality<T> {
     fn from(t: T) -> Quality<T> {
-        Quality {
-            item: t,
-            weight: Weight::new(1000),
-        }
+        Quality::new(t, 1000)
     }
 }
 
@@ -203,40 +248,18 @@ impl From<u16> for Weight {
     }
 }
 
+// qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )
 fn parse_weight(s: &str) -> Option<u16> {
-    let mut iter = s.chars();
-    match iter.next() {
-        Some('0') => {
-            match iter.next() {
-                Some('.') => {
-                    let mut weight = 0;
-                    while let Some(char) = iter.next() {
-                        if char <= '0' || char >= '9' {
-                            return None;
-                        }
-                        weight *= 10;
-                        weight += char as u16 - '0' as u16;
-                    }
-                    Some(weight)
-                }
-                Some(_) => None,
-                None => Some(0),
-            }
-        }
-        Some('1') => {
-            match iter.next() {
-                Some('.') => {
-                    for c in iter {
-                        if c != '0' {
-                            return None;
-                        }
-                    }
-                    Some(1000)
-                }
-                Some(_) => None,
-                None => Some(1000),
-            }
-        }
+    let mut iter = s.splitn(2, '.');
+    let integer = iter.next().unwrap();
+    let fraction = iter.next().unwrap_or("");
+    if fraction.len() > 3 || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let fraction = format!("{:0<3}", fraction).parse::<u16>().unwrap();
+    match integer {
+        "0" => Some(fraction),
+        "1" if fraction == 0 => Some(1000),
         _ => None,
     }
 }
@@ -261,6 +284,27 @@ impl Display for HeaderField {
     }
 }
 
+impl HeaderField {
+    /// Returns the name as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a str> for HeaderField {
+    fn from(s: &'a str) -> HeaderField {
+        HeaderField(s.to_owned())
+    }
+}
+
+impl PartialEq for HeaderField {
+    fn eq(&self, other: &HeaderField) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl Eq for HeaderField {}
+
 // check that each char in the slice is either:
 // 1. %x21, or
 // 2. in the range %x23 to %x7E, or
@@ -399,3 +443,1547 @@ impl FromStr for EntityTag {
         Err(())
     }
 }
+
+/// The name part of a node in a `Forwarded` header field,
+/// [RFC7239 Section 6]
+///
+/// IPv6 addresses are enclosed in square brackets when serialized.
+/// Proxies may hide the real address behind an obfuscated identifier
+/// which must start with an underscore.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NodeName {
+    /// An IPv4 or IPv6 address.
+    Ip(IpAddr),
+    /// The identifier `unknown` used if the address is not known.
+    Unknown,
+    /// An obfuscated identifier like `_hidden` or `_SEVKISEK`.
+    Obfuscated(String),
+}
+
+impl Display for NodeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::NodeName::*;
+        match *self {
+            Ip(IpAddr::V4(ref addr)) => addr.fmt(f),
+            Ip(IpAddr::V6(ref addr)) => write!(f, "[{}]", addr),
+            Unknown => f.write_str("unknown"),
+            Obfuscated(ref s) => f.write_str(s),
+        }
+    }
+}
+
+impl FromStr for NodeName {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<NodeName, ()> {
+        if s.eq_ignore_ascii_case("unknown") {
+            Ok(NodeName::Unknown)
+        } else if s.starts_with('[') && s.ends_with(']') {
+            s[1..s.len() - 1].parse::<Ipv6Addr>()
+                .map(|x| NodeName::Ip(IpAddr::V6(x)))
+                .map_err(|_| ())
+        } else if is_obfuscated(s) {
+            Ok(NodeName::Obfuscated(s.to_owned()))
+        } else {
+            s.parse::<Ipv4Addr>().map(|x| NodeName::Ip(IpAddr::V4(x))).map_err(|_| ())
+        }
+    }
+}
+
+/// The port part of a node in a `Forwarded` header field,
+/// [RFC7239 Section 6]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NodePort {
+    /// A TCP port number.
+    Port(u16),
+    /// An obfuscated port like `_8080`.
+    Obfuscated(String),
+}
+
+impl Display for NodePort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NodePort::Port(port) => port.fmt(f),
+            NodePort::Obfuscated(ref s) => f.write_str(s),
+        }
+    }
+}
+
+impl FromStr for NodePort {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<NodePort, ()> {
+        if is_obfuscated(s) {
+            return Ok(NodePort::Obfuscated(s.to_owned()));
+        }
+        if s.is_empty() || s.len() > 5 || !s.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(());
+        }
+        s.parse().map(NodePort::Port).map_err(|_| ())
+    }
+}
+
+fn is_obfuscated(s: &str) -> bool {
+    s.len() > 1 && s.starts_with('_') &&
+    s.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'.' || c == b'_' || c == b'-')
+}
+
+/// A node identifies a client or a proxy, [RFC7239 Section 6]
+///
+/// Nodes are found in the `for` and `by` parameters of the `Forwarded`
+/// header field and in the legacy `X-Forwarded-For` header field.
+///
+/// Parsing is lenient and also accepts IPv6 addresses without
+/// square brackets as they are common in `X-Forwarded-For`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    /// The IP address or identifier of the node.
+    pub name: NodeName,
+    /// The optional port of the node.
+    pub port: Option<NodePort>,
+}
+
+impl Node {
+    /// Returns the IP address of the node if it is known.
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self.name {
+            NodeName::Ip(addr) => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+impl From<IpAddr> for Node {
+    fn from(addr: IpAddr) -> Node {
+        Node {
+            name: NodeName::Ip(addr),
+            port: None,
+        }
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.name.fmt(f)?;
+        if let Some(ref port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Node {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Node, ()> {
+        if let Ok(addr) = s.parse::<IpAddr>() {
+            return Ok(addr.into());
+        }
+        let (name, port) = match s.rfind(':') {
+            Some(i) if !s.ends_with(']') => (&s[..i], Some(s[i + 1..].parse()?)),
+            _ => (s, None),
+        };
+        Ok(Node {
+            name: name.parse()?,
+            port,
+        })
+    }
+}
+
+/// A single element of the `Forwarded` header field, [RFC7239 Section 4]
+///
+/// Each proxy appends one element describing the request it received.
+/// All parameters are optional. Unknown parameters are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ForwardedElement {
+    /// The interface where the request came in to the proxy server.
+    pub by: Option<Node>,
+    /// The client that initiated the request and subsequent proxies.
+    pub for_: Option<Node>,
+    /// The `Host` request header field as received by the proxy.
+    pub host: Option<String>,
+    /// The protocol used to make the request, e.g. `http` or `https`.
+    pub proto: Option<String>,
+}
+
+impl Display for ForwardedElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        let pairs = [("by", self.by.as_ref().map(|x| x.to_string())),
+                     ("for", self.for_.as_ref().map(|x| x.to_string())),
+                     ("host", self.host.clone()),
+                     ("proto", self.proto.clone())];
+        for &(name, ref value) in &pairs {
+            if let Some(ref value) = *value {
+                if !first {
+                    f.write_str(";")?;
+                }
+                first = false;
+                write!(f, "{}={}", name, quote_if_needed(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ForwardedElement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<ForwardedElement, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let mut pairs = vec![parse_parameter(&mut tokens)?];
+        for pair in parse_parameters(&mut tokens)? {
+            if pairs.iter().any(|x| x.name == pair.name) {
+                return Err(());
+            }
+            pairs.push(pair);
+        }
+        if tokens.peek().is_some() {
+            return Err(());
+        }
+        let mut element = ForwardedElement::default();
+        for pair in pairs {
+            if pair.extended {
+                continue;
+            }
+            match &pair.name[..] {
+                "by" => element.by = Some(pair.value.parse()?),
+                "for" => element.for_ = Some(pair.value.parse()?),
+                "host" => element.host = Some(pair.value),
+                "proto" => element.proto = Some(pair.value),
+                _ => (),
+            }
+        }
+        Ok(element)
+    }
+}
+
+/// The host part of a `Host` header field, [RFC7230 Section 5.4]
+///
+/// A host is either an IP literal or a registered name like
+/// `www.example.com`. IPv6 addresses are enclosed in square
+/// brackets when serialized.
+#[derive(Clone, Debug, Eq)]
+pub enum HostName {
+    /// An IPv4 or IPv6 address.
+    Ip(IpAddr),
+    /// A registered name, usually a domain name.
+    Name(String),
+}
+
+impl Display for HostName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HostName::Ip(IpAddr::V4(ref addr)) => addr.fmt(f),
+            HostName::Ip(IpAddr::V6(ref addr)) => write!(f, "[{}]", addr),
+            HostName::Name(ref s) => f.write_str(s),
+        }
+    }
+}
+
+impl FromStr for HostName {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<HostName, ()> {
+        if s.starts_with('[') && s.ends_with(']') {
+            return s[1..s.len() - 1].parse::<Ipv6Addr>()
+                .map(|x| HostName::Ip(IpAddr::V6(x)))
+                .map_err(|_| ());
+        }
+        if let Ok(addr) = s.parse::<Ipv4Addr>() {
+            return Ok(HostName::Ip(IpAddr::V4(addr)));
+        }
+        if s.is_empty() || !is_reg_name(s) {
+            return Err(());
+        }
+        Ok(HostName::Name(s.to_owned()))
+    }
+}
+
+impl PartialEq for HostName {
+    fn eq(&self, other: &HostName) -> bool {
+        match (self, other) {
+            (&HostName::Ip(ref a), &HostName::Ip(ref b)) => a == b,
+            (&HostName::Name(ref a), &HostName::Name(ref b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+}
+
+// reg-name = *( unreserved / pct-encoded / sub-delims )
+fn is_reg_name(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                if i + 2 >= bytes.len() || !bytes[i + 1].is_ascii_hexdigit() ||
+                   !bytes[i + 2].is_ascii_hexdigit() {
+                    return false;
+                }
+                i += 2;
+            }
+            b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' |
+            b'+' | b',' | b';' | b'=' => (),
+            c if c.is_ascii_alphanumeric() => (),
+            _ => return false,
+        }
+        i += 1;
+    }
+    true
+}
+
+/// A host with an optional port as used by the `Host` header field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HostPort {
+    /// The registered name or IP address of the host.
+    pub host: HostName,
+    /// The port if it is given explicitly.
+    pub port: Option<u16>,
+}
+
+impl HostPort {
+    /// Constructs a host and port from the authority of a URL.
+    ///
+    /// Returns `None` if the URL has no host.
+    pub fn from_url(url: &Url) -> Option<HostPort> {
+        let host = match url.host() {
+            Some(::url::Host::Domain(name)) => HostName::Name(name.to_owned()),
+            Some(::url::Host::Ipv4(addr)) => HostName::Ip(IpAddr::V4(addr)),
+            Some(::url::Host::Ipv6(addr)) => HostName::Ip(IpAddr::V6(addr)),
+            None => return None,
+        };
+        Some(HostPort {
+            host,
+            port: url.port(),
+        })
+    }
+}
+
+impl Display for HostPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.host.fmt(f)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for HostPort {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<HostPort, ()> {
+        let (host, port) = match s.rfind(':') {
+            Some(i) if !s.ends_with(']') => (&s[..i], &s[i + 1..]),
+            _ => (s, ""),
+        };
+        if !port.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(());
+        }
+        Ok(HostPort {
+            host: host.parse()?,
+            port: if port.is_empty() {
+                None
+            } else {
+                Some(port.parse().map_err(|_| ())?)
+            },
+        })
+    }
+}
+
+/// A parameter of a header field value like `charset=utf-8`,
+/// [RFC7231 Section 3.1.1.1]
+///
+/// Names are case-insensitive and stored in lowercase.
+/// Extended parameters like `title*` carry their value encoded as
+/// described in [RFC8187](https://tools.ietf.org/html/rfc8187).
+/// The name of an extended parameter is stored without the asterisk,
+/// the value is decoded and may be tagged with a language.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Parameter {
+    /// The lowercased name of the parameter.
+    pub name: String,
+    /// The decoded value of the parameter.
+    pub value: String,
+    /// Set for extended parameters like `filename*`.
+    pub extended: bool,
+    /// The language of an extended value.
+    pub language: Option<LanguageTag>,
+}
+
+impl Parameter {
+    /// Constructs a new regular parameter.
+    ///
+    /// # Panics
+    /// If the name is not a token.
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Parameter {
+        let name = name.into();
+        assert!(is_token(&name), "parameter name must be a token");
+        Parameter {
+            name: name.to_ascii_lowercase(),
+            value: value.into(),
+            extended: false,
+            language: None,
+        }
+    }
+
+    /// Constructs a new extended parameter.
+    ///
+    /// The value is always serialized as UTF-8.
+    ///
+    /// # Panics
+    /// If the name is not a token.
+    pub fn extended<N, V>(name: N, value: V, language: Option<LanguageTag>) -> Parameter
+        where N: Into<String>,
+              V: Into<String>
+    {
+        let name = name.into();
+        assert!(is_token(&name), "parameter name must be a token");
+        Parameter {
+            name: name.to_ascii_lowercase(),
+            value: value.into(),
+            extended: true,
+            language,
+        }
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = encode_token(&self.name);
+        // Control characters can only be sent in an extended value.
+        if !self.extended && is_quotable(&self.value) {
+            return write!(f, "{}={}", name, quote_if_needed(&self.value));
+        }
+        write!(f, "{}*=UTF-8'", name)?;
+        if let Some(ref language) = self.language {
+            language.fmt(f)?;
+        }
+        f.write_str("'")?;
+        for &c in self.value.as_bytes() {
+            if is_attr_char(c) {
+                write!(f, "{}", c as char)?;
+            } else {
+                write!(f, "%{:02X}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Parameter {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Parameter, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let parameter = parse_parameter(&mut tokens)?;
+        if tokens.next().is_some() {
+            return Err(());
+        }
+        Ok(parameter)
+    }
+}
+
+// attr-char = ALPHA / DIGIT / "!" / "#" / "$" / "&" / "+" / "-" / "."
+//           / "^" / "_" / "`" / "|" / "~"
+fn is_attr_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() ||
+    matches!(c, b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~')
+}
+
+// Decodes an ext-value, [RFC8187 Section 3.2]
+pub(crate) fn decode_ext_value(s: &str) -> Result<(String, Option<LanguageTag>), ()> {
+    let mut iter = s.splitn(3, '\'');
+    let charset = iter.next().ok_or(())?;
+    let language = iter.next().ok_or(())?;
+    let raw = iter.next().ok_or(())?;
+    let language = if language.is_empty() {
+        None
+    } else {
+        Some(language.parse().map_err(|_| ())?)
+    };
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut iter = raw.bytes();
+    while let Some(c) = iter.next() {
+        if c == b'%' {
+            let hex = [iter.next().ok_or(())?, iter.next().ok_or(())?];
+            // `from_str_radix` would also accept a sign like `%+1`.
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return Err(());
+            }
+            let hex = str::from_utf8(&hex).map_err(|_| ())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| ())?);
+        } else if is_attr_char(c) {
+            bytes.push(c);
+        } else {
+            return Err(());
+        }
+    }
+    let value = if charset.eq_ignore_ascii_case("UTF-8") {
+        String::from_utf8(bytes).map_err(|_| ())?
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        bytes.into_iter().map(char::from).collect()
+    } else {
+        return Err(());
+    };
+    Ok((value, language))
+}
+
+/// A link relation type, [RFC8288 Section 2.1]
+///
+/// Registered relation types like `next` are case-insensitive and
+/// stored in lowercase. Extension relation types are URLs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RelationType {
+    /// A relation type from the IANA Link Relations registry.
+    Registered(String),
+    /// An extension relation type identified by a URL.
+    Extension(Url),
+}
+
+impl RelationType {
+    /// Checks if this is the given registered relation type.
+    pub fn is(&self, name: &str) -> bool {
+        match *self {
+            RelationType::Registered(ref s) => s.eq_ignore_ascii_case(name),
+            RelationType::Extension(_) => false,
+        }
+    }
+}
+
+impl Display for RelationType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RelationType::Registered(ref s) => f.write_str(s),
+            RelationType::Extension(ref url) => url.fmt(f),
+        }
+    }
+}
+
+impl FromStr for RelationType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<RelationType, ()> {
+        if s.contains(':') {
+            return Url::parse(s).map(RelationType::Extension).map_err(|_| ());
+        }
+        let mut chars = s.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() => (),
+            _ => return Err(()),
+        }
+        if !chars.all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') {
+            return Err(());
+        }
+        Ok(RelationType::Registered(s.to_ascii_lowercase()))
+    }
+}
+
+/// A single link of a `Link` header field, [RFC8288 Section 3]
+///
+/// The target and the anchor are resolved against the base URL
+/// of the request. If a target attribute occurs more than once
+/// all but the first occurrence are ignored, except for `hreflang`.
+#[derive(Clone, Debug)]
+pub struct LinkValue {
+    /// The target of the link.
+    pub target: Url,
+    /// The relation types of the link.
+    pub rel: Vec<RelationType>,
+    /// The context of the link if it is not the requested resource.
+    pub anchor: Option<Url>,
+    /// The media type of the target.
+    pub media_type: Option<MediaType>,
+    /// The languages of the target.
+    pub hreflang: Vec<LanguageTag>,
+    /// A human-readable title of the link.
+    ///
+    /// A `title*` attribute takes precedence over `title`.
+    pub title: Option<String>,
+    /// The language of the title if given by `title*`.
+    pub title_language: Option<LanguageTag>,
+    /// The media the target is intended for.
+    pub media: Option<String>,
+    /// Other target attributes.
+    pub parameters: Vec<Parameter>,
+}
+
+impl LinkValue {
+    /// Constructs a link with a target and a single relation type.
+    pub fn new(target: Url, rel: RelationType) -> LinkValue {
+        LinkValue {
+            target,
+            rel: vec![rel],
+            anchor: None,
+            media_type: None,
+            hreflang: Vec::new(),
+            title: None,
+            title_language: None,
+            media: None,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Checks if the link has the given registered relation type.
+    pub fn has_rel(&self, name: &str) -> bool {
+        self.rel.iter().any(|x| x.is(name))
+    }
+
+    /// Parses links from a `Link` header field value.
+    ///
+    /// URI references are resolved against the base URL.
+    pub fn parse_list(s: &str, base: &Url) -> Result<Vec<LinkValue>, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let mut links = Vec::new();
+        loop {
+            while tokens.eat(',') {}
+            if tokens.peek().is_none() {
+                return Ok(links);
+            }
+            if !tokens.eat('<') {
+                return Err(());
+            }
+            let target = base.join(tokens.take_until('>')?.trim()).map_err(|_| ())?;
+            let mut parameters = Vec::new();
+            loop {
+                match tokens.peek() {
+                    None | Some(Ok(Token::Delimiter(','))) => break,
+                    _ => (),
+                }
+                if !tokens.eat(';') {
+                    return Err(());
+                }
+                match tokens.peek() {
+                    None | Some(Ok(Token::Delimiter(';'))) | Some(Ok(Token::Delimiter(','))) => {
+                        continue
+                    }
+                    _ => (),
+                }
+                parameters.push(parse_parameter(&mut tokens)?);
+            }
+            links.push(LinkValue::from_parameters(target, parameters, base)?);
+        }
+    }
+
+    fn from_parameters(target: Url, parameters: Vec<Parameter>, base: &Url) -> Result<LinkValue, ()> {
+        let mut link = LinkValue {
+            target,
+            rel: Vec::new(),
+            anchor: None,
+            media_type: None,
+            hreflang: Vec::new(),
+            title: None,
+            title_language: None,
+            media: None,
+            parameters: Vec::new(),
+        };
+        let mut seen: Vec<(String, bool)> = Vec::new();
+        for parameter in parameters {
+            let key = (parameter.name.clone(), parameter.extended);
+            let first = !seen.contains(&key);
+            seen.push(key);
+            match (&parameter.name[..], parameter.extended) {
+                ("rel", false) if first => {
+                    link.rel = parameter.value
+                        .split_whitespace()
+                        .map(|x| x.parse())
+                        .collect::<Result<_, ()>>()?;
+                }
+                ("anchor", false) if first => {
+                    link.anchor = Some(base.join(&parameter.value).map_err(|_| ())?);
+                }
+                ("type", false) if first => {
+                    link.media_type = Some(parameter.value.parse().map_err(|_| ())?);
+                }
+                ("hreflang", false) => {
+                    link.hreflang.push(parameter.value.parse().map_err(|_| ())?);
+                }
+                ("title", false) if first => {
+                    if link.title.is_none() {
+                        link.title = Some(parameter.value);
+                    }
+                }
+                ("title", true) if first => {
+                    link.title = Some(parameter.value);
+                    link.title_language = parameter.language;
+                }
+                ("media", false) if first => link.media = Some(parameter.value),
+                ("rel", _) | ("anchor", _) | ("type", _) | ("title", _) | ("media", _) => (),
+                _ => link.parameters.push(parameter),
+            }
+        }
+        Ok(link)
+    }
+}
+
+impl Display for LinkValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}>", self.target)?;
+        if !self.rel.is_empty() {
+            let rel: Vec<String> = self.rel.iter().map(|x| x.to_string()).collect();
+            write!(f, "; rel={}", quote_if_needed(&rel.join(" ")))?;
+        }
+        if let Some(ref anchor) = self.anchor {
+            write!(f, "; anchor={}", quote_if_needed(anchor.as_str()))?;
+        }
+        if let Some(ref media_type) = self.media_type {
+            write!(f, "; type={}", quote_if_needed(&media_type.to_string()))?;
+        }
+        for language in &self.hreflang {
+            write!(f, "; hreflang={}", language)?;
+        }
+        if let Some(ref title) = self.title {
+            if self.title_language.is_some() || !title.bytes().all(|c| (b' '..0x7f).contains(&c)) {
+                write!(f, "; {}", Parameter::extended("title", &title[..], self.title_language.clone()))?;
+            } else {
+                write!(f, "; {}", Parameter::new("title", &title[..]))?;
+            }
+        }
+        if let Some(ref media) = self.media {
+            write!(f, "; media={}", quote_if_needed(media))?;
+        }
+        fmt_parameters(f, &self.parameters)
+    }
+}
+
+/// The type of a `Content-Disposition`, [RFC6266 Section 4.2]
+///
+/// Disposition types are case-insensitive.
+#[derive(Clone, Debug, Eq)]
+pub enum DispositionType {
+    /// Display the content inline.
+    Inline,
+    /// Save the content as a file.
+    Attachment,
+    /// A field of a `multipart/form-data` body, [RFC7578 Section 4.2]
+    FormData,
+    /// Any other disposition type, recipients should treat it like
+    /// `attachment`.
+    Extension(String),
+}
+
+impl Display for DispositionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DispositionType::Inline => "inline",
+            DispositionType::Attachment => "attachment",
+            DispositionType::FormData => "form-data",
+            DispositionType::Extension(ref s) => s,
+        })
+    }
+}
+
+impl FromStr for DispositionType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<DispositionType, ()> {
+        Ok(match s {
+            s if s.eq_ignore_ascii_case("inline") => DispositionType::Inline,
+            s if s.eq_ignore_ascii_case("attachment") => DispositionType::Attachment,
+            s if s.eq_ignore_ascii_case("form-data") => DispositionType::FormData,
+            s if is_token(s) => DispositionType::Extension(s.to_owned()),
+            _ => return Err(()),
+        })
+    }
+}
+
+impl PartialEq for DispositionType {
+    fn eq(&self, other: &DispositionType) -> bool {
+        use self::DispositionType::*;
+        match (self, other) {
+            (&Inline, &Inline) | (&Attachment, &Attachment) | (&FormData, &FormData) => true,
+            (&Extension(ref a), &Extension(ref b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+}
+
+/// The value of a `Content-Disposition` header field,
+/// [RFC6266 Section 4.1]
+///
+/// The disposition type is followed by parameters like `filename`.
+/// If both `filename` and `filename*` are present the extended
+/// parameter takes precedence.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Disposition {
+    /// The disposition type.
+    pub kind: DispositionType,
+    /// The parameters like `filename` or `name`.
+    pub parameters: Vec<Parameter>,
+}
+
+impl Disposition {
+    /// Constructs a new disposition without parameters.
+    pub fn new(kind: DispositionType) -> Disposition {
+        Disposition {
+            kind,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Returns the filename as sent.
+    ///
+    /// The filename must not be used as a path, see
+    /// [`sanitized_filename`](#method.sanitized_filename).
+    pub fn filename(&self) -> Option<&str> {
+        find_parameter(&self.parameters, "filename").map(|x| &x.value[..])
+    }
+
+    /// Returns the filename without path components.
+    pub fn sanitized_filename(&self) -> Option<String> {
+        self.filename().and_then(sanitize_filename)
+    }
+
+    /// Sets the filename.
+    ///
+    /// The `filename` parameter always contains an ASCII fallback for
+    /// old recipients. If the name is not plain ASCII or a language is
+    /// given, it is sent as an extended `filename*` parameter as well.
+    pub fn set_filename(&mut self, filename: &str, language: Option<LanguageTag>) {
+        self.parameters.retain(|x| x.name != "filename");
+        let fallback: String = filename.chars()
+            .map(|c| if (' '..='~').contains(&c) && c != '"' && c != '\\' { c } else { '_' })
+            .collect();
+        if fallback != filename || language.is_some() {
+            self.parameters.push(Parameter::new("filename", fallback));
+            self.parameters.push(Parameter::extended("filename", filename, language));
+        } else {
+            self.parameters.push(Parameter::new("filename", fallback));
+        }
+    }
+
+    /// Returns the field name of a `form-data` disposition.
+    pub fn name(&self) -> Option<&str> {
+        find_parameter(&self.parameters, "name").map(|x| &x.value[..])
+    }
+}
+
+impl Display for Disposition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)?;
+        fmt_parameters(f, &self.parameters)
+    }
+}
+
+impl FromStr for Disposition {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Disposition, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let kind = match tokens.next() {
+            Some(Ok(Token::Token(kind))) => kind.parse()?,
+            _ => return Err(()),
+        };
+        let parameters = parse_parameters(&mut tokens)?;
+        if tokens.next().is_some() {
+            return Err(());
+        }
+        Ok(Disposition { kind, parameters })
+    }
+}
+
+/// Makes a received filename safe to use in a directory.
+///
+/// Everything up to the last slash or backslash is removed as well
+/// as a drive prefix like `C:`, control characters, leading dots and
+/// whitespace and trailing dots and whitespace. Characters Windows
+/// does not allow in names are replaced with `_` and reserved device
+/// names like `CON` or `nul.txt` are prefixed with `_`.
+/// Returns `None` if nothing remains.
+pub fn sanitize_filename(filename: &str) -> Option<String> {
+    let mut name = filename.rsplit(['/', '\\']).next().unwrap_or("");
+    let bytes = name.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        name = &name[2..];
+    }
+    let name: String = name.chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let name = name.trim_start_matches(|c: char| c == '.' || c.is_whitespace())
+        .trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    if name.is_empty() {
+        return None;
+    }
+    if is_reserved_name(name) {
+        return Some(format!("_{}", name));
+    }
+    Some(name.to_owned())
+}
+
+// Device names are reserved on Windows, even with an extension.
+fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or("").trim_end();
+    ["CON", "PRN", "AUX", "NUL"].iter().any(|x| stem.eq_ignore_ascii_case(x)) ||
+    (stem.len() == 4 &&
+     (stem[..3].eq_ignore_ascii_case("COM") || stem[..3].eq_ignore_ascii_case("LPT")) &&
+     (b'1'..=b'9').contains(&stem.as_bytes()[3]))
+}
+
+/// An extension in `Sec-WebSocket-Extensions` like
+/// `permessage-deflate; client_max_window_bits`, [RFC6455 Section 9.1]
+///
+/// Parameters may have no value. Names and values are tokens,
+/// characters of invalid ones are percent-encoded when serialized.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WebSocketExtension {
+    /// The extension name.
+    pub name: String,
+    /// The parameters in order.
+    pub parameters: Vec<(String, Option<String>)>,
+}
+
+impl WebSocketExtension {
+    /// Constructs an extension without parameters.
+    ///
+    /// Names that are not tokens are rejected.
+    pub fn new<N: Into<String>>(name: N) -> Result<WebSocketExtension, ()> {
+        let name = name.into();
+        if !is_token(&name) {
+            return Err(());
+        }
+        Ok(WebSocketExtension {
+            name,
+            parameters: Vec::new(),
+        })
+    }
+
+    /// Adds a parameter to the extension.
+    ///
+    /// Names and values that are not tokens are rejected.
+    pub fn with_parameter<N: Into<String>>(mut self, name: N, value: Option<String>)
+        -> Result<WebSocketExtension, ()> {
+        let name = name.into();
+        if !is_token(&name) || value.as_ref().is_some_and(|x| !is_token(x)) {
+            return Err(());
+        }
+        self.parameters.push((name, value));
+        Ok(self)
+    }
+
+    /// Returns the parameter with the name.
+    ///
+    /// The inner option is `None` for parameters without a value.
+    pub fn parameter(&self, name: &str) -> Option<Option<&str>> {
+        self.parameters
+            .iter()
+            .find(|x| x.0.eq_ignore_ascii_case(name))
+            .map(|x| x.1.as_ref().map(|x| &x[..]))
+    }
+}
+
+impl Display for WebSocketExtension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&encode_token(&self.name))?;
+        for (name, value) in &self.parameters {
+            write!(f, "; {}", encode_token(name))?;
+            if let Some(value) = value {
+                write!(f, "={}", encode_token(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for WebSocketExtension {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<WebSocketExtension, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let name = match tokens.next() {
+            Some(Ok(Token::Token(name))) => name.to_owned(),
+            _ => return Err(()),
+        };
+        let mut parameters = Vec::new();
+        while tokens.peek().is_some() {
+            if !tokens.eat(';') {
+                return Err(());
+            }
+            let name = match tokens.next() {
+                Some(Ok(Token::Token(name))) => name.to_owned(),
+                _ => return Err(()),
+            };
+            let value = if tokens.eat('=') {
+                match tokens.next() {
+                    Some(Ok(Token::Token(value))) => Some(value.to_owned()),
+                    // Quoted values must be tokens after unescaping.
+                    Some(Ok(Token::Quoted(ref value))) if is_token(value) => Some(value.clone()),
+                    _ => return Err(()),
+                }
+            } else {
+                None
+            };
+            parameters.push((name, value));
+        }
+        Ok(WebSocketExtension { name, parameters })
+    }
+}
+
+/// An alternative service in the `Alt-Svc` header, [RFC7838 Section 3]
+///
+/// The same resources are available with the protocol at the
+/// alternative authority.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AltService {
+    /// The ALPN protocol identifier like `h2` or `h3`.
+    pub protocol: String,
+    /// The alternative host, `None` for the host of the origin.
+    pub host: Option<HostName>,
+    /// The alternative port.
+    pub port: u16,
+    /// Seconds the alternative stays fresh, 24 hours by default.
+    pub max_age: u64,
+    /// The alternative is kept when the network changes.
+    pub persist: bool,
+}
+
+// The freshness lifetime if `ma` is absent.
+const DEFAULT_MAX_AGE: u64 = 86_400;
+
+impl AltService {
+    /// Constructs an alternative service with default parameters.
+    pub fn new(protocol: &str, host: Option<HostName>, port: u16) -> AltService {
+        AltService {
+            protocol: protocol.to_owned(),
+            host,
+            port,
+            max_age: DEFAULT_MAX_AGE,
+            persist: false,
+        }
+    }
+
+    /// Returns the protocol version of the alternative, if known.
+    pub fn version(&self) -> Option<Version> {
+        Version::from_alpn_id(self.protocol.as_bytes())
+    }
+}
+
+impl Display for AltService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Characters other than tchar in the protocol are percent-encoded.
+        for c in self.protocol.bytes() {
+            if c != b'%' && is_tchar(c as char) {
+                write!(f, "{}", c as char)?;
+            } else {
+                write!(f, "%{:02X}", c)?;
+            }
+        }
+        f.write_str("=\"")?;
+        if let Some(ref host) = self.host {
+            write!(f, "{}", host)?;
+        }
+        write!(f, ":{}\"", self.port)?;
+        if self.max_age != DEFAULT_MAX_AGE {
+            write!(f, "; ma={}", self.max_age)?;
+        }
+        if self.persist {
+            f.write_str("; persist=1")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AltService {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<AltService, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let protocol = match tokens.next() {
+            Some(Ok(Token::Token(protocol))) => {
+                String::from_utf8(percent_decode(protocol.as_bytes()).collect()).map_err(|_| ())?
+            }
+            _ => return Err(()),
+        };
+        if !tokens.eat('=') {
+            return Err(());
+        }
+        let authority = match tokens.next() {
+            Some(Ok(Token::Quoted(authority))) => authority,
+            _ => return Err(()),
+        };
+        let colon = authority.rfind(':').ok_or(())?;
+        let host = match &authority[..colon] {
+            "" => None,
+            host => Some(host.parse()?),
+        };
+        let port = authority[colon + 1..].parse().map_err(|_| ())?;
+        let mut service = AltService::new(&protocol, host, port);
+        // Unknown parameters are ignored. [RFC7838 Section 3]
+        for parameter in parse_parameters(&mut tokens)? {
+            match &parameter.name[..] {
+                "ma" => service.max_age = parameter.value.parse().map_err(|_| ())?,
+                "persist" => service.persist = parameter.value == "1",
+                _ => (),
+            }
+        }
+        if tokens.peek().is_some() {
+            return Err(());
+        }
+        Ok(service)
+    }
+}
+
+/// A timestamp in an HTTP header field, [RFC7231 Section 7.1.1.1]
+///
+/// HTTP dates have a resolution of one second, sub-second parts of
+/// a `SystemTime` are truncated. This way a file modified at 12.5
+/// seconds is not newer than a date of 12 seconds sent before.
+///
+/// Only dates from 1970 to 9999 can be sent. Converting a `SystemTime`
+/// with `From` clamps earlier times to 1970-01-01 00:00:00 and later
+/// times to 9999-12-31 23:59:59, use `HttpDate::new` to reject them.
+///
+/// The IMF-fixdate format is sent, the obsolete RFC 850 and asctime
+/// formats are accepted as well.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct HttpDate(httpdate::HttpDate);
+
+impl HttpDate {
+    /// Converts a time to a date.
+    ///
+    /// Fails for times before 1970 or after 9999.
+    pub fn new(time: SystemTime) -> Result<HttpDate, ()> {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(x) if x.as_secs() <= MAX_DATE => Ok(HttpDate(time.into())),
+            _ => Err(()),
+        }
+    }
+
+    /// Returns the current time.
+    pub fn now() -> HttpDate {
+        SystemTime::now().into()
+    }
+}
+
+impl From<SystemTime> for HttpDate {
+    fn from(time: SystemTime) -> HttpDate {
+        let secs = time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+        HttpDate((UNIX_EPOCH + Duration::from_secs(secs.min(MAX_DATE))).into())
+    }
+}
+
+impl From<HttpDate> for SystemTime {
+    fn from(date: HttpDate) -> SystemTime {
+        date.0.into()
+    }
+}
+
+impl Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for HttpDate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<HttpDate, ()> {
+        s.parse().map(HttpDate).map_err(|_| ())
+    }
+}
+
+// 9999-12-31 23:59:59, the last date with a four digit year.
+const MAX_DATE: u64 = 253_402_300_799;
+
+/// The value of the `return` preference, [RFC7240 Section 4.2]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReturnPreference {
+    /// `minimal`: only a status and few header fields.
+    Minimal,
+    /// `representation`: the current representation of the resource.
+    Representation,
+}
+
+/// The value of the `handling` preference, [RFC7240 Section 4.4]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HandlingPreference {
+    /// `strict`: reject requests with any error.
+    Strict,
+    /// `lenient`: process as much of the request as possible.
+    Lenient,
+}
+
+/// A preference name with its value, [RFC7240 Section 2]
+///
+/// Names and the values of known preferences are case-insensitive.
+/// An empty value is the same as no value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PreferenceKind {
+    /// `respond-async`: answer with `202 Accepted` and process the
+    /// request asynchronously.
+    RespondAsync,
+    /// `return`: the content of a successful response.
+    Return(ReturnPreference),
+    /// `wait`: the time the client waits for a synchronous response.
+    Wait(Duration),
+    /// `handling`: how strictly the request is checked.
+    Handling(HandlingPreference),
+    /// Any other preference with its lowercased name and value.
+    ///
+    /// Use [`PreferenceKind::new`](#method.new) to check the name and
+    /// value. Characters of invalid names are percent-encoded when
+    /// serialized.
+    Extension(String, Option<String>),
+}
+
+impl PreferenceKind {
+    /// Constructs a preference from its name and value.
+    ///
+    /// Known preferences get their own variant. The name must be a
+    /// token and the value must not contain control characters.
+    pub fn new<N: Into<String>>(name: N, value: Option<String>) -> Result<PreferenceKind, ()> {
+        let name = name.into();
+        if !is_token(&name) || value.as_ref().is_some_and(|x| !is_quotable(x)) {
+            return Err(());
+        }
+        Ok(PreferenceKind::from_pair(name.to_ascii_lowercase(), value.filter(|x| !x.is_empty())))
+    }
+
+    /// Returns the lowercased name of the preference.
+    pub fn name(&self) -> &str {
+        match *self {
+            PreferenceKind::RespondAsync => "respond-async",
+            PreferenceKind::Return(_) => "return",
+            PreferenceKind::Wait(_) => "wait",
+            PreferenceKind::Handling(_) => "handling",
+            PreferenceKind::Extension(ref name, _) => name,
+        }
+    }
+
+    fn from_pair(name: String, value: Option<String>) -> PreferenceKind {
+        let known = match (&name[..], value.as_ref().map(|x| x.to_ascii_lowercase())) {
+            ("respond-async", None) => Some(PreferenceKind::RespondAsync),
+            ("return", Some(ref value)) if value == "minimal" => {
+                Some(PreferenceKind::Return(ReturnPreference::Minimal))
+            }
+            ("return", Some(ref value)) if value == "representation" => {
+                Some(PreferenceKind::Return(ReturnPreference::Representation))
+            }
+            ("wait", Some(ref value)) => value.parse().ok().map(|x| PreferenceKind::Wait(Duration::from_secs(x))),
+            ("handling", Some(ref value)) if value == "strict" => {
+                Some(PreferenceKind::Handling(HandlingPreference::Strict))
+            }
+            ("handling", Some(ref value)) if value == "lenient" => {
+                Some(PreferenceKind::Handling(HandlingPreference::Lenient))
+            }
+            _ => None,
+        };
+        known.unwrap_or(PreferenceKind::Extension(name, value))
+    }
+}
+
+impl Display for PreferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&encode_token(self.name()))?;
+        match *self {
+            PreferenceKind::RespondAsync => Ok(()),
+            PreferenceKind::Return(ReturnPreference::Minimal) => f.write_str("=minimal"),
+            PreferenceKind::Return(ReturnPreference::Representation) => f.write_str("=representation"),
+            PreferenceKind::Wait(duration) => write!(f, "={}", duration.as_secs()),
+            PreferenceKind::Handling(HandlingPreference::Strict) => f.write_str("=strict"),
+            PreferenceKind::Handling(HandlingPreference::Lenient) => f.write_str("=lenient"),
+            PreferenceKind::Extension(_, Some(ref value)) => write!(f, "={}", quote_if_needed(value)),
+            PreferenceKind::Extension(_, None) => Ok(()),
+        }
+    }
+}
+
+impl FromStr for PreferenceKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<PreferenceKind, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let (name, value) = parse_preference_pair(&mut tokens)?;
+        if tokens.next().is_some() {
+            return Err(());
+        }
+        Ok(PreferenceKind::from_pair(name, value))
+    }
+}
+
+/// A preference in the `Prefer` header field like `wait=10`,
+/// [RFC7240 Section 2]
+///
+/// Parameters may have no value. Empty values are stored as `None`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Preference {
+    /// The preference.
+    pub kind: PreferenceKind,
+    /// The parameters in order with lowercased names.
+    pub parameters: Vec<(String, Option<String>)>,
+}
+
+impl Preference {
+    /// Constructs a preference without parameters.
+    ///
+    /// Extension preferences with a name that is not a token or a
+    /// value with control characters are rejected.
+    pub fn new(kind: PreferenceKind) -> Result<Preference, ()> {
+        if let PreferenceKind::Extension(ref name, ref value) = kind {
+            PreferenceKind::new(&name[..], value.clone())?;
+        }
+        Ok(Preference {
+            kind,
+            parameters: Vec::new(),
+        })
+    }
+
+    /// Adds a parameter to the preference.
+    ///
+    /// The name must be a token and the value must not contain
+    /// control characters.
+    pub fn with_parameter<N: Into<String>>(mut self, name: N, value: Option<String>)
+        -> Result<Preference, ()> {
+        let name = name.into();
+        if !is_token(&name) || value.as_ref().is_some_and(|x| !is_quotable(x)) {
+            return Err(());
+        }
+        self.parameters.push((name.to_ascii_lowercase(), value.filter(|x| !x.is_empty())));
+        Ok(self)
+    }
+
+    /// Returns the parameter with the name.
+    ///
+    /// The inner option is `None` for parameters without a value.
+    pub fn parameter(&self, name: &str) -> Option<Option<&str>> {
+        self.parameters
+            .iter()
+            .find(|x| x.0.eq_ignore_ascii_case(name))
+            .map(|x| x.1.as_ref().map(|x| &x[..]))
+    }
+}
+
+impl Display for Preference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)?;
+        for (name, value) in &self.parameters {
+            write!(f, "; {}", encode_token(name))?;
+            if let Some(value) = value {
+                write!(f, "={}", quote_if_needed(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Preference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Preference, ()> {
+        let mut tokens = Tokenizer::new(s);
+        let (name, value) = parse_preference_pair(&mut tokens)?;
+        let mut parameters = Vec::new();
+        while tokens.peek().is_some() {
+            if !tokens.eat(';') {
+                return Err(());
+            }
+            // Empty parameters are allowed.
+            match tokens.peek() {
+                None | Some(Ok(Token::Delimiter(';'))) => continue,
+                _ => parameters.push(parse_preference_pair(&mut tokens)?),
+            }
+        }
+        Ok(Preference {
+            kind: PreferenceKind::from_pair(name, value),
+            parameters,
+        })
+    }
+}
+
+// Parses `token [ BWS "=" BWS word ]` with a lowercased name.
+fn parse_preference_pair(tokens: &mut Tokenizer) -> Result<(String, Option<String>), ()> {
+    let (name, value) = parse_optional_parameter(tokens)?;
+    Ok((name.to_ascii_lowercase(), value.filter(|x| !x.is_empty())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use header::util::{Tokenizer, parse_parameter};
+    use super::{ForwardedElement, HandlingPreference, HttpDate, Parameter, Preference,
+                PreferenceKind, Quality, ReturnPreference, decode_ext_value, sanitize_filename};
+
+    #[test]
+    fn test_decode_ext_value() {
+        let (value, language) = decode_ext_value("UTF-8'en'%e2%82%ac%20rates").unwrap();
+        assert_eq!(value, "\u{20ac} rates");
+        assert_eq!(language.unwrap().to_string(), "en");
+        assert_eq!(decode_ext_value("iso-8859-1''%A3").unwrap().0, "\u{a3}");
+        assert!(decode_ext_value("UTF-8''%+1").is_err());
+        assert!(decode_ext_value("UTF-8''%-1").is_err());
+        assert!(decode_ext_value("UTF-8''%4").is_err());
+        assert!(decode_ext_value("UTF-8''a b").is_err());
+        assert!(decode_ext_value("KOI8-R''a").is_err());
+    }
+
+    #[test]
+    fn test_parameter() {
+        let parameter = parse_parameter(&mut Tokenizer::new("Title*=UTF-8''%c2%a3")).unwrap();
+        assert_eq!(parameter, Parameter::extended("title", "\u{a3}", None));
+        assert_eq!(parameter.to_string(), "title*=UTF-8''%C2%A3");
+        assert_eq!(Parameter::new("q", "a b").to_string(), "q=\"a b\"");
+        let mut invalid = Parameter::new("q", "x");
+        invalid.name = "a b".to_owned();
+        assert_eq!(invalid.to_string(), "a%20b=x");
+        assert_eq!(Parameter::new("q", "a\r\nb").to_string(), "q*=UTF-8''a%0D%0Ab");
+        let element = ForwardedElement {
+            host: Some("a\nb".to_owned()),
+            ..ForwardedElement::default()
+        };
+        assert_eq!(element.to_string(), "host=\"a%0Ab\"");
+    }
+
+    #[test]
+    fn test_quality() {
+        let quality: Quality<String> = "text/html;q=0.5;level=1".parse().unwrap();
+        assert_eq!(quality.item, "text/html");
+        assert_eq!(quality.to_string(), "text/html; q=0.5; level=1");
+        assert!("text/html;q=0.5, text/plain".parse::<Quality<String>>().is_err());
+        assert!("text/html;q=0.5 x".parse::<Quality<String>>().is_err());
+        assert!("text/html;q=2".parse::<Quality<String>>().is_err());
+    }
+
+    #[test]
+    fn test_forwarded_element() {
+        let element: ForwardedElement = "for=\"[2001:db8::1]:4711\";proto=https".parse().unwrap();
+        assert_eq!(element.proto.as_ref().unwrap(), "https");
+        assert_eq!(element.to_string(), "for=\"[2001:db8::1]:4711\";proto=https");
+        assert!("for=192.0.2.60, for=198.51.100.17".parse::<ForwardedElement>().is_err());
+        assert!("for=192.0.2.60;for=198.51.100.17".parse::<ForwardedElement>().is_err());
+        assert!("for=192.0.2.60 x".parse::<ForwardedElement>().is_err());
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("report.pdf").unwrap(), "report.pdf");
+        assert_eq!(sanitize_filename("../../etc/passwd").unwrap(), "passwd");
+        assert_eq!(sanitize_filename("C:\\Windows\\win.ini").unwrap(), "win.ini");
+        assert_eq!(sanitize_filename("C:x.txt").unwrap(), "x.txt");
+        assert_eq!(sanitize_filename("ab:c?.txt").unwrap(), "ab_c_.txt");
+        assert_eq!(sanitize_filename("CON").unwrap(), "_CON");
+        assert_eq!(sanitize_filename("nul.txt").unwrap(), "_nul.txt");
+        assert_eq!(sanitize_filename("com1.tar.gz").unwrap(), "_com1.tar.gz");
+        assert_eq!(sanitize_filename("console.log").unwrap(), "console.log");
+        assert_eq!(sanitize_filename("COM0").unwrap(), "COM0");
+        assert_eq!(sanitize_filename("report.pdf. . ").unwrap(), "report.pdf");
+        assert_eq!(sanitize_filename(" .hidden\u{0}\n").unwrap(), "hidden");
+        assert_eq!(sanitize_filename("..."), None);
+        assert_eq!(sanitize_filename("dir/"), None);
+        assert_eq!(sanitize_filename("C:"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_parameter_name_not_token() {
+        Parameter::new("a=b", "x");
+    }
+
+    #[test]
+    fn test_http_date() {
+        let date: HttpDate = "Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap();
+        assert_eq!(SystemTime::from(date), UNIX_EPOCH + Duration::from_secs(784_111_777));
+        assert_eq!(date.to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!("Sunday, 06-Nov-94 08:49:37 GMT".parse(), Ok(date));
+        assert_eq!("Sun Nov  6 08:49:37 1994".parse(), Ok(date));
+        assert_eq!("Sun Nov 06 08:49:37 1994".parse(), Ok(date));
+        assert_eq!(" Sun, 06 Nov 1994 08:49:37 GMT ".parse(), Ok(date));
+
+        let may: HttpDate = "Fri, 31 May 2024 23:59:59 GMT".parse().unwrap();
+        assert_eq!(may.to_string(), "Fri, 31 May 2024 23:59:59 GMT");
+        assert_eq!(HttpDate::from(UNIX_EPOCH).to_string(), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!("Thu, 29 Feb 2024 12:00:00 GMT".parse::<HttpDate>().unwrap().to_string(),
+                   "Thu, 29 Feb 2024 12:00:00 GMT");
+        assert_eq!("Tuesday, 01-Jan-69 00:00:00 GMT".parse::<HttpDate>().unwrap().to_string(),
+                   "Tue, 01 Jan 2069 00:00:00 GMT");
+        assert_eq!(HttpDate::from(UNIX_EPOCH + Duration::from_secs(u64::MAX / 2)).to_string(),
+                   "Fri, 31 Dec 9999 23:59:59 GMT");
+        assert_eq!(HttpDate::from(UNIX_EPOCH - Duration::from_secs(1)), HttpDate::from(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_http_date_new() {
+        let time = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+        assert_eq!(HttpDate::new(time), Ok(HttpDate::from(time)));
+        assert_eq!(HttpDate::new(time).unwrap().to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert!(HttpDate::new(UNIX_EPOCH).is_ok());
+        assert!(HttpDate::new(UNIX_EPOCH + Duration::from_secs(253_402_300_799)).is_ok());
+        assert!(HttpDate::new(UNIX_EPOCH + Duration::from_secs(253_402_300_800)).is_err());
+        assert!(HttpDate::new(UNIX_EPOCH - Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_http_date() {
+        for s in &["Sat, 01 Jan 1960 00:00:00 GMT",
+                   "Sun Jan  1 00:00:00 1961",
+                   "Sun, 06 Mai 1994 08:49:37 GMT",
+                   "Sun, 00 Nov 1994 08:49:37 GMT",
+                   "Tue, 29 Feb 2022 08:49:37 GMT",
+                   "Sun, 31 Nov 1994 08:49:37 GMT",
+                   "Sun, 06 Nov 1994 24:00:00 GMT",
+                   "Sun, 06 Nov 1994 08:60:00 GMT",
+                   "Mon, 06 Nov 1994 08:49:37 GMT",
+                   "Friday, 01-Jan-69 00:00:00 GMT",
+                   "Sun, 06 Nov 1994 08:49:37 UTC",
+                   "Sun, +6 Nov 1994 08:49:37 GMT",
+                   "Xyz, 06 Nov 1994 08:49:37 GMT",
+                   "Sun, 06 Nov 1994 08-49-37 GMT",
+                   "Sun, 06 Nov 1994 08:49:\u{e9} GMT",
+                   "Sunday, 06-Nov-1994 08:49:37 GMT",
+                   "Sun Nov  6 08:49:37 94",
+                   ""] {
+            assert!(s.parse::<HttpDate>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_preference() {
+        let preference: Preference = "Return=Minimal; Foo; bar=\"a b\"; ; baz=\"\"".parse().unwrap();
+        assert_eq!(preference.kind, PreferenceKind::Return(ReturnPreference::Minimal));
+        assert_eq!(preference.parameters,
+                   vec![("foo".to_owned(), None),
+                        ("bar".to_owned(), Some("a b".to_owned())),
+                        ("baz".to_owned(), None)]);
+        assert_eq!(preference.parameter("BAR"), Some(Some("a b")));
+        assert_eq!(preference.parameter("foo"), Some(None));
+        assert_eq!(preference.to_string(), "return=minimal; foo; bar=\"a b\"; baz");
+
+        assert_eq!("wait=10".parse(), Ok(PreferenceKind::Wait(Duration::from_secs(10))));
+        assert_eq!("handling=LENIENT".parse(),
+                   Ok(PreferenceKind::Handling(HandlingPreference::Lenient)));
+        assert_eq!("respond-async=\"\"".parse(), Ok(PreferenceKind::RespondAsync));
+        assert_eq!("wait=soon".parse(),
+                   Ok(PreferenceKind::Extension("wait".to_owned(), Some("soon".to_owned()))));
+        assert!("=1".parse::<Preference>().is_err());
+        assert!("a b".parse::<Preference>().is_err());
+        assert!("a; b".parse::<PreferenceKind>().is_err());
+        assert!("a; b=".parse::<Preference>().is_err());
+    }
+
+    #[test]
+    fn test_preference_name_not_token() {
+        assert_eq!(PreferenceKind::new("Wait", Some("10".to_owned())),
+                   Ok(PreferenceKind::Wait(Duration::from_secs(10))));
+        assert_eq!(PreferenceKind::new("X", Some("".to_owned())),
+                   Ok(PreferenceKind::Extension("x".to_owned(), None)));
+        assert!(PreferenceKind::new("a b", None).is_err());
+        assert!(PreferenceKind::new("foo", Some("a\x01".to_owned())).is_err());
+        let kind = PreferenceKind::Extension("foo".to_owned(), Some("a\x01".to_owned()));
+        assert!(Preference::new(kind).is_err());
+        assert!(Preference::new(PreferenceKind::Extension("a,b".to_owned(), None)).is_err());
+
+        let preference = Preference::new(PreferenceKind::RespondAsync).unwrap()
+            .with_parameter("X", Some("a b".to_owned())).unwrap();
+        assert_eq!(preference.to_string(), "respond-async; x=\"a b\"");
+        assert!(preference.clone().with_parameter("x;y", None).is_err());
+        assert!(preference.clone().with_parameter("y", Some("\r\n".to_owned())).is_err());
+
+        // Values built directly are encoded instead of failing.
+        let kind = PreferenceKind::Extension("a b".to_owned(), Some("a\x01".to_owned()));
+        assert_eq!(kind.to_string(), "a%20b=\"a%01\"");
+        let mut preference = preference;
+        preference.parameters.push(("x;y".to_owned(), Some("1".to_owned())));
+        assert_eq!(preference.to_string(), "respond-async; x=\"a b\"; x%3By=1");
+    }
+}
@@ -2,7 +2,7 @@ use std::str;
 
 use header::{RequestHeader, ResponseHeader, parse_value, serialize_value, parse_list1,
              serialize_list};
-use header::item::{MediaType, Coding, LanguageTag, Url};
+use header::item::{MediaType, Coding, Disposition, LanguageTag, Url};
 
 header!{
     /// `Content-Type` header, [RFC7231 Section 3.1.1.5]
@@ -63,3 +63,67 @@ header!{
         serialize_value(iter, &self.0)
     }
 }
+
+header!{
+    /// `Content-Disposition` header, [RFC6266 Section 4]
+    ///
+    /// Also used for the parts of `multipart/form-data` bodies.
+    pub struct ContentDisposition(Disposition);
+    (RequestHeader ResponseHeader);
+    NAME = "Content-Disposition";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<Disposition>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, &self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Headers, base, headers};
+    use header::item::{Disposition, DispositionType};
+    use super::ContentDisposition;
+
+    fn disposition(value: &str) -> Result<Disposition, ()> {
+        headers("Content-Disposition", value)
+            .get::<ContentDisposition>(base()).unwrap().map(Into::into)
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        let d = disposition("Attachment; filename=\"EURO rates.txt\"; \
+                             filename*=utf-8''%e2%82%ac%20rates.txt").unwrap();
+        assert_eq!(d.kind, DispositionType::Attachment);
+        assert_eq!(d.filename(), Some("\u{20ac} rates.txt"));
+
+        let d = disposition("form-data; name=\"field\"; filename=\"../../etc/passwd\"").unwrap();
+        assert_eq!(d.kind, DispositionType::FormData);
+        assert_eq!(d.name(), Some("field"));
+        assert_eq!(d.sanitized_filename(), Some("passwd".to_owned()));
+
+        assert!(disposition("inline; filename=a; filename=b").is_err());
+        assert!(disposition("attachment filename=a").is_err());
+        assert!(disposition("").is_err());
+    }
+
+    #[test]
+    fn test_set_filename() {
+        let mut d = Disposition::new(DispositionType::Attachment);
+        d.set_filename("\u{20ac} \"rates\".txt", None);
+        let mut headers = Headers::new();
+        headers.set(&ContentDisposition::from(d.clone())).unwrap();
+        assert_eq!(headers.get_raw("Content-Disposition").unwrap(),
+                   &[b"attachment; filename=\"_ _rates_.txt\"; \
+                       filename*=UTF-8''%E2%82%AC%20%22rates%22.txt".to_vec()][..]);
+        assert_eq!(disposition("attachment; filename=\"_ _rates_.txt\"; \
+                                filename*=UTF-8''%E2%82%AC%20%22rates%22.txt"),
+                   Ok(d));
+
+        let mut d = Disposition::new(DispositionType::Inline);
+        d.set_filename("report.pdf", None);
+        d.set_filename("summary.pdf", None);
+        assert_eq!(d.to_string(), "inline; filename=summary.pdf");
+    }
+}
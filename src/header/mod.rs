@@ -16,12 +16,20 @@
 //!
 //! Information about the resource and the endpoints.
 //!
+//! * [`Host`](struct.Host.html): host and port of the target resource
 //! * [`From`](struct.From.html): client email address
 //! * [`Referer`](struct.Referer.html): page visited before and
 //!     linking to current resource
 //! * [`User-Agent`](struct.UserAgent.html): client software used
 //! * [`Allow`](struct.Allow.html): methods allowed on resource
 //! * [`Server`](struct.Server.html): server software used
+//! * [`Link`](struct.Link.html): links to related resources,
+//!     e.g. the next page of a collection
+//! * [`Forwarded`](struct.Forwarded.html): client and proxies as seen
+//!     by each proxy, together with the legacy
+//!     [`X-Forwarded-For`](struct.XForwardedFor.html),
+//!     [`X-Forwarded-Proto`](struct.XForwardedProto.html) and
+//!     [`X-Forwarded-Host`](struct.XForwardedHost.html)
 //!
 //! ## Representation Metadata
 //!
@@ -39,9 +47,25 @@
 //!     the resource is intended for
 //! * [`Content-Location`](struct.ContentLocation.html): a link to the
 //!     current representation of the resource
+//! * [`Content-Disposition`](struct.ContentDisposition.html): display inline
+//!     or save as a file with a suggested filename
 //!
 //! ## Control Data
-//! (needs to be written)
+//!
+//! * [`Date`](struct.Date.html): message creation date
+//! * [`Expect`](enum.Expect.html): wait for `100 Continue` before sending
+//!     the content, see [`check_expect`](../fn.check_expect.html)
+//! * [`Max-Forwards`](struct.MaxForwards.html): remaining proxy hops
+//! * [`Location`](struct.Location.html): redirect target or created resource
+//! * [`Retry-After`](enum.RetryAfter.html): when to send the next request
+//! * [`Vary`](struct.Vary.html): request fields the response depends on
+//! * [`Alt-Svc`](enum.AltSvc.html): other protocols and authorities the
+//!     origin is available at, see [`AltSvcCache`](../struct.AltSvcCache.html)
+//! * [`Alt-Svc-Used`](struct.AltSvcUsed.html): marks requests sent to
+//!     an alternative service
+//! * [`Prefer`](struct.Prefer.html) and
+//!     [`Preference-Applied`](struct.PreferenceApplied.html): optional
+//!     behavior requested by the client and honored by the server
 //!
 //! ## Content Negotiation
 //!
@@ -56,15 +80,91 @@
 //! * [`Accept-Language`](struct.AcceptLanguage.html): preferred languages
 //!     of the user
 //!
+//! ## Rate Limits
+//!
+//! Servers announce how many requests a client may send. A
+//! [`TokenBucket`](../struct.TokenBucket.html) creates these header
+//! fields, a [`Throttle`](../struct.Throttle.html) reads them.
+//!
+//! * [`RateLimit-Policy`](struct.RateLimitPolicy.html): quota policies
+//!     of the server
+//! * [`RateLimit`](struct.RateLimit.html): remaining quota and time
+//!     until it resets
+//! * [`X-RateLimit-Limit`](struct.XRateLimitLimit.html),
+//!     [`X-RateLimit-Remaining`](struct.XRateLimitRemaining.html) and
+//!     [`X-RateLimit-Reset`](struct.XRateLimitReset.html): legacy forms
+//!
+//! ## Cross-Origin Resource Sharing
+//!
+//! Browsers only allow scripts to read responses from other origins
+//! if the server permits it. A [`CorsPolicy`](../struct.CorsPolicy.html)
+//! creates the response header fields.
+//!
+//! * [`Origin`](struct.Origin.html): origin that caused the request
+//! * [`Access-Control-Request-Method`](struct.AccessControlRequestMethod.html)
+//!     and [`Access-Control-Request-Headers`](struct.AccessControlRequestHeaders.html):
+//!     method and header fields a preflight request asks for
+//! * [`Access-Control-Allow-Origin`](enum.AccessControlAllowOrigin.html):
+//!     origin allowed to read the response
+//! * [`Access-Control-Allow-Methods`](struct.AccessControlAllowMethods.html),
+//!     [`Access-Control-Allow-Headers`](struct.AccessControlAllowHeaders.html),
+//!     [`Access-Control-Allow-Credentials`](struct.AccessControlAllowCredentials.html) and
+//!     [`Access-Control-Max-Age`](struct.AccessControlMaxAge.html):
+//!     result of a preflight request
+//! * [`Access-Control-Expose-Headers`](struct.AccessControlExposeHeaders.html):
+//!     response header fields readable by scripts
+//!
+//! ## Security
+//!
+//! Servers restrict what browsers may do with their responses. A
+//! [`SecurityHeaders`](../struct.SecurityHeaders.html) preset inserts
+//! a hardened set of these header fields.
+//!
+//! * [`Strict-Transport-Security`](struct.StrictTransportSecurity.html):
+//!     connect only via HTTPS
+//! * [`X-Content-Type-Options`](struct.XContentTypeOptions.html):
+//!     do not guess the media type
+//! * [`X-Frame-Options`](enum.XFrameOptions.html): who may frame the page
+//! * [`Referrer-Policy`](enum.ReferrerPolicy.html): referrer sent by links
+//!     and subresources
+//! * [`Cross-Origin-Opener-Policy`](struct.CrossOriginOpenerPolicy.html),
+//!     [`Cross-Origin-Embedder-Policy`](struct.CrossOriginEmbedderPolicy.html) and
+//!     [`Cross-Origin-Resource-Policy`](enum.CrossOriginResourcePolicy.html):
+//!     isolation from other origins
+//! * [`Permissions-Policy`](struct.PermissionsPolicy.html): browser features
+//!     the page and its frames may use
+//! * [`Content-Security-Policy`](struct.ContentSecurityPolicy.html) and
+//!     [`Content-Security-Policy-Report-Only`](struct.ContentSecurityPolicyReportOnly.html):
+//!     sources the page may load scripts, styles and other resources from
+//!
+//! ## WebSocket
+//!
+//! A WebSocket connection starts with an HTTP/1.1 request upgraded to
+//! the WebSocket protocol. A [`WebSocketRequest`](../struct.WebSocketRequest.html)
+//! validates the request and builds the response.
+//!
+//! * [`Sec-WebSocket-Key`](struct.SecWebSocketKey.html) and
+//!     [`Sec-WebSocket-Accept`](struct.SecWebSocketAccept.html):
+//!     nonce of the client and the proof the server understood it
+//! * [`Sec-WebSocket-Version`](struct.SecWebSocketVersion.html): protocol version
+//! * [`Sec-WebSocket-Protocol`](struct.SecWebSocketProtocol.html):
+//!     subprotocols like `graphql-ws`
+//! * [`Sec-WebSocket-Extensions`](struct.SecWebSocketExtensions.html):
+//!     extensions like `permessage-deflate`
+//!
+//! ## HTTP/2
+//!
+//! Clients without prior knowledge of HTTP/2 support upgrade cleartext
+//! HTTP/1.1 connections, see [`H2cRequest`](../http2/struct.H2cRequest.html).
+//!
+//! * [`HTTP2-Settings`](struct.Http2Settings.html): connection settings
+//!     of the client
+//!
 //! ## Omitted header fields
 //! While *httptypes* aims to support the common header fields some are
 //! intentionally excluded. They usually can be better handled at a lower
 //! protocol level.
 //!
-//! * `Date`: message creation date. Created automatically on the server.
-//!     Unsure about common client usage.
-//! * `Expect: 100-continue`: better handled at the syntax and
-//!     routing layer.
 //! * `MIME-Version`: unsure about usage and placement.
 
 use std::fmt::Debug;
@@ -76,14 +176,40 @@ use url::Url;
 
 #[cfg(feature="conditional")]
 pub use self::conditional::{ETag, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince, LastModified};
+#[cfg(feature="cors")]
+pub use self::cors::{Origin, AccessControlAllowOrigin, AccessControlAllowMethods,
+                     AccessControlAllowHeaders, AccessControlAllowCredentials,
+                     AccessControlExposeHeaders, AccessControlMaxAge,
+                     AccessControlRequestMethod, AccessControlRequestHeaders};
+#[cfg(feature="security")]
+pub use self::csp::{ContentSecurityPolicy, ContentSecurityPolicyReportOnly, CspPolicy,
+                    CspDirective, CspValue, CspSource, CspKeyword, CspHostSource, CspPort,
+                    CspHashAlgorithm};
 #[cfg(feature="context")]
-pub use self::context::{From, Referer, UserAgent, Allow, Server};
+pub use self::context::{From, Referer, UserAgent, Allow, Server, Forwarded, XForwardedFor,
+                        XForwardedProto, XForwardedHost, Host, Link};
 #[cfg(feature="control")]
-pub use self::control::{MaxForwards, Location, RetryAfter, Vary};
+pub use self::control::{Date, DateCache, Expect, MaxForwards, Location, RetryAfter, Vary,
+                        AltSvc, AltSvcUsed, Prefer, PreferenceApplied};
+#[cfg(feature="http2")]
+pub use self::http2::Http2Settings;
 #[cfg(feature="metadata")]
-pub use self::metadata::{ContentType, ContentEncoding, ContentLanguage, ContentLocation};
+pub use self::metadata::{ContentType, ContentEncoding, ContentLanguage, ContentLocation,
+                         ContentDisposition};
 #[cfg(feature="negotiation")]
 pub use self::negotiation::{Accept, AcceptCharset, AcceptEncoding, AcceptLanguage};
+#[cfg(feature="ratelimit")]
+pub use self::ratelimit::{QuotaPolicy, RateLimit, RateLimitPolicy, ServiceLimit, XRateLimitLimit,
+                          XRateLimitRemaining, XRateLimitReset};
+#[cfg(feature="security")]
+pub use self::security::{StrictTransportSecurity, XContentTypeOptions, XFrameOptions,
+                         ReferrerPolicy, OpenerPolicy, CrossOriginOpenerPolicy, EmbedderPolicy,
+                         CrossOriginEmbedderPolicy, CrossOriginResourcePolicy, Allowlist,
+                         PermissionsPolicy};
+#[cfg(feature="websocket")]
+pub use self::websocket::{SecWebSocketKey, SecWebSocketAccept, SecWebSocketVersion,
+                          SecWebSocketProtocol, SecWebSocketExtensions};
+pub use self::headers::{Headers, Iter};
 use self::util::*;
 
 macro_rules! header {
@@ -136,12 +262,26 @@ mod conditional;
 mod context;
 #[cfg(feature="control")]
 mod control;
+#[cfg(feature="cors")]
+mod cors;
+#[cfg(feature="security")]
+mod csp;
+mod headers;
+#[cfg(feature="http2")]
+mod http2;
 pub mod item;
 #[cfg(feature="metadata")]
 mod metadata;
 #[cfg(feature="negotiation")]
 mod negotiation;
+#[cfg(feature="ratelimit")]
+mod ratelimit;
+#[cfg(feature="security")]
+mod security;
+pub mod structured;
 pub mod util;
+#[cfg(feature="websocket")]
+mod websocket;
 
 /// A HTTP header field.
 ///
@@ -174,6 +314,8 @@ pub trait Header: Clone + Debug + Sized {
     /// The base URL is the effective request URL and is used
     /// to parse relative URLs as commonly found in `Referer`
     /// and `Content-Location` headers to their absolute form.
+    /// It can be computed with
+    /// [`effective_request_url`](../fn.effective_request_url.html).
     fn parse(s: &[Vec<u8>], base: Url) -> Result<Self, ()>;
 
     /// Serializes a header field value.
@@ -199,3 +341,17 @@ pub trait RequestHeader: Header {}
 /// With this trait users of this library can statically
 /// assure that a header is used correctly.
 pub trait ResponseHeader: Header {}
+
+/// Builds a header map holding a single raw field value.
+#[cfg(test)]
+fn headers(name: &str, value: &str) -> Headers {
+    let mut headers = Headers::new();
+    headers.set_raw(name, vec![value.as_bytes().to_vec()]);
+    headers
+}
+
+/// Base URL for parsing header fields in tests.
+#[cfg(test)]
+fn base() -> Url {
+    Url::parse("http://example.com/").unwrap()
+}
@@ -0,0 +1,291 @@
+use std::time::Duration;
+
+use header::{ResponseHeader, serialize_value, parse_value};
+use header::structured::{BareItem, Item, List, MAX_INTEGER, Member, Parameters, StructuredHeader,
+                         lookup};
+
+/// A quota policy in the `RateLimit-Policy` header field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QuotaPolicy {
+    /// The name of the policy, referenced by `RateLimit`.
+    pub name: String,
+    /// `q`: the quota allocated per window.
+    pub quota: u64,
+    /// `w`: the time window the quota applies to.
+    pub window: Option<Duration>,
+    /// `qu`: the unit of the quota like `requests` or `content-bytes`.
+    pub quota_unit: Option<String>,
+    /// `pk`: the partition key, like a hash of the client identifier.
+    pub partition_key: Option<Vec<u8>>,
+}
+
+impl QuotaPolicy {
+    /// Constructs a policy with a quota per window.
+    pub fn new<N: Into<String>>(name: N, quota: u64, window: Duration) -> QuotaPolicy {
+        QuotaPolicy {
+            name: name.into(),
+            quota,
+            window: Some(window),
+            quota_unit: None,
+            partition_key: None,
+        }
+    }
+}
+
+/// `RateLimit-Policy` header, [draft-ietf-httpapi-ratelimit-headers]
+///
+/// Announces the quota policies applied by the server.
+/// Malformed policies are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateLimitPolicy {
+    /// The policies.
+    pub policies: Vec<QuotaPolicy>,
+}
+
+impl ResponseHeader for RateLimitPolicy {}
+
+impl StructuredHeader for RateLimitPolicy {
+    const NAME: &'static str = "RateLimit-Policy";
+    const SENSITIVE: bool = false;
+    type Field = List;
+
+    fn from_field(field: List) -> Result<Self, ()> {
+        let policies = field.iter()
+            .filter_map(|member| {
+                let (name, parameters) = named_item(member)?;
+                Some(QuotaPolicy {
+                    name,
+                    quota: integer(parameters, "q")?,
+                    window: optional(parameters, "w", integer)?.map(Duration::from_secs),
+                    quota_unit: optional(parameters, "qu", token)?,
+                    partition_key: optional(parameters, "pk", partition_key)?,
+                })
+            })
+            .collect();
+        Ok(RateLimitPolicy { policies })
+    }
+
+    fn to_field(&self) -> List {
+        self.policies
+            .iter()
+            .map(|policy| {
+                let mut item = Item::new(BareItem::String(policy.name.clone()));
+                push_integer(&mut item.parameters, "q", policy.quota);
+                if let Some(ref unit) = policy.quota_unit {
+                    item.parameters.push(("qu".to_owned(), BareItem::Token(unit.clone())));
+                }
+                if let Some(window) = policy.window {
+                    push_integer(&mut item.parameters, "w", window.as_secs());
+                }
+                if let Some(ref key) = policy.partition_key {
+                    item.parameters.push(("pk".to_owned(), BareItem::ByteSeq(key.clone())));
+                }
+                Member::Item(item)
+            })
+            .collect()
+    }
+}
+
+/// The state of a quota policy in the `RateLimit` header field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ServiceLimit {
+    /// The name of the policy from `RateLimit-Policy`.
+    pub policy: String,
+    /// `r`: the remaining quota units.
+    pub remaining: u64,
+    /// `t`: the time until the quota resets.
+    pub reset: Option<Duration>,
+    /// `pk`: the partition key, like a hash of the client identifier.
+    pub partition_key: Option<Vec<u8>>,
+}
+
+/// `RateLimit` header, [draft-ietf-httpapi-ratelimit-headers]
+///
+/// The remaining quota of the client for each policy.
+/// Malformed limits are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    /// The limits.
+    pub limits: Vec<ServiceLimit>,
+}
+
+impl ResponseHeader for RateLimit {}
+
+impl StructuredHeader for RateLimit {
+    const NAME: &'static str = "RateLimit";
+    const SENSITIVE: bool = false;
+    type Field = List;
+
+    fn from_field(field: List) -> Result<Self, ()> {
+        let limits = field.iter()
+            .filter_map(|member| {
+                let (policy, parameters) = named_item(member)?;
+                Some(ServiceLimit {
+                    policy,
+                    remaining: integer(parameters, "r")?,
+                    reset: optional(parameters, "t", integer)?.map(Duration::from_secs),
+                    partition_key: optional(parameters, "pk", partition_key)?,
+                })
+            })
+            .collect();
+        Ok(RateLimit { limits })
+    }
+
+    fn to_field(&self) -> List {
+        self.limits
+            .iter()
+            .map(|limit| {
+                let mut item = Item::new(BareItem::String(limit.policy.clone()));
+                push_integer(&mut item.parameters, "r", limit.remaining);
+                if let Some(reset) = limit.reset {
+                    push_integer(&mut item.parameters, "t", reset.as_secs());
+                }
+                if let Some(ref key) = limit.partition_key {
+                    item.parameters.push(("pk".to_owned(), BareItem::ByteSeq(key.clone())));
+                }
+                Member::Item(item)
+            })
+            .collect()
+    }
+}
+
+fn named_item(member: &Member) -> Option<(String, &Parameters)> {
+    match *member {
+        Member::Item(Item { bare_item: BareItem::String(ref name), ref parameters }) => {
+            Some((name.clone(), parameters))
+        }
+        _ => None,
+    }
+}
+
+fn integer(parameters: &Parameters, key: &str) -> Option<u64> {
+    match lookup(parameters, key) {
+        Some(&BareItem::Integer(n)) if n >= 0 => Some(n as u64),
+        _ => None,
+    }
+}
+
+fn token(parameters: &Parameters, key: &str) -> Option<String> {
+    match lookup(parameters, key) {
+        Some(BareItem::Token(token)) => Some(token.clone()),
+        _ => None,
+    }
+}
+
+fn partition_key(parameters: &Parameters, key: &str) -> Option<Vec<u8>> {
+    match lookup(parameters, key) {
+        Some(BareItem::ByteSeq(key)) => Some(key.clone()),
+        _ => None,
+    }
+}
+
+// Returns `Some(None)` for a missing parameter and `None` for an
+// invalid one.
+fn optional<T, F>(parameters: &Parameters, key: &str, f: F) -> Option<Option<T>>
+    where F: Fn(&Parameters, &str) -> Option<T>
+{
+    match lookup(parameters, key) {
+        None => Some(None),
+        Some(_) => f(parameters, key).map(Some),
+    }
+}
+
+// Larger values are sent as the largest integer of structured fields.
+fn push_integer(parameters: &mut Parameters, key: &str, n: u64) {
+    parameters.push((key.to_owned(), BareItem::Integer(n.min(MAX_INTEGER as u64) as i64)));
+}
+
+header!{
+    /// `X-RateLimit-Limit` header
+    ///
+    /// The legacy form of the quota in `RateLimit-Policy`.
+    pub struct XRateLimitLimit(u64);
+    (ResponseHeader);
+    NAME = "X-RateLimit-Limit";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<u64>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, self.0)
+    }
+}
+
+header!{
+    /// `X-RateLimit-Remaining` header
+    ///
+    /// The legacy form of the remaining quota in `RateLimit`.
+    pub struct XRateLimitRemaining(u64);
+    (ResponseHeader);
+    NAME = "X-RateLimit-Remaining";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<u64>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, self.0)
+    }
+}
+
+header!{
+    /// `X-RateLimit-Reset` header
+    ///
+    /// The legacy form of the reset time in `RateLimit`. Some servers
+    /// send seconds until the reset, others a Unix timestamp.
+    pub struct XRateLimitReset(u64);
+    (ResponseHeader);
+    NAME = "X-RateLimit-Reset";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_value::<u64>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use header::Headers;
+    use super::{QuotaPolicy, RateLimit, RateLimitPolicy, ServiceLimit};
+
+    #[test]
+    fn test_rate_limit_policy() {
+        let mut headers = Headers::new();
+        headers.set_raw("RateLimit-Policy",
+                        vec![b"\"default\";q=100;w=60;qu=requests, \"bad\";q=-1, \"day\";q=1000".to_vec()]);
+        let policy: RateLimitPolicy = headers.get_without_base().unwrap().unwrap();
+        let mut default = QuotaPolicy::new("default", 100, Duration::from_secs(60));
+        default.quota_unit = Some("requests".to_owned());
+        let day = QuotaPolicy {
+            window: None,
+            ..QuotaPolicy::new("day", 1000, Duration::from_secs(0))
+        };
+        assert_eq!(policy.policies, vec![default, day]);
+
+        headers.set(&policy).unwrap();
+        assert_eq!(headers.get_raw("RateLimit-Policy").unwrap(),
+                   &[b"\"default\";q=100;qu=requests;w=60, \"day\";q=1000".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit {
+            limits: vec![ServiceLimit {
+                policy: "default".to_owned(),
+                remaining: u64::MAX,
+                reset: Some(Duration::from_secs(u64::MAX)),
+                partition_key: Some(b"abc".to_vec()),
+            }],
+        };
+        let mut headers = Headers::new();
+        headers.set(&limit).unwrap();
+        assert_eq!(headers.get_raw("RateLimit").unwrap(),
+                   &[b"\"default\";r=999999999999999;t=999999999999999;pk=:YWJj:".to_vec()][..]);
+        let parsed: RateLimit = headers.get_without_base().unwrap().unwrap();
+        assert_eq!(parsed.limits[0].remaining, 999_999_999_999_999);
+        assert_eq!(parsed.limits[0].partition_key, Some(b"abc".to_vec()));
+    }
+}
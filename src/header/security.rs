@@ -0,0 +1,657 @@
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::str::{self, FromStr};
+use std::time::Duration;
+
+use url::Origin as UrlOrigin;
+
+use header::{Header, ResponseHeader, ListElements, Token, Tokenizer, serialize_value};
+use header::item::Url;
+use header::structured::{BareItem, Dictionary, InnerList, Item, Member, StructuredHeader, is_key, lookup};
+
+/// `Strict-Transport-Security` header, [RFC6797 Section 6.1]
+///
+/// Browsers only connect to the host via HTTPS for the given time.
+/// Unknown directives are ignored, only the first field line is used.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StrictTransportSecurity {
+    /// The time the host is known as HTTPS-only.
+    ///
+    /// Zero removes the host from the list of known hosts.
+    pub max_age: Duration,
+    /// Apply the policy to all subdomains.
+    pub include_subdomains: bool,
+    /// Consent to inclusion in the browser preload lists.
+    ///
+    /// The preload lists require at least a year and `includeSubDomains`.
+    pub preload: bool,
+}
+
+impl StrictTransportSecurity {
+    /// Constructs a policy for the host only.
+    pub fn new(max_age: Duration) -> StrictTransportSecurity {
+        StrictTransportSecurity {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+}
+
+impl ResponseHeader for StrictTransportSecurity {}
+
+impl Header for StrictTransportSecurity {
+    const NAME: &'static str = "Strict-Transport-Security";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        let raw = str::from_utf8(s.first().ok_or(())?).map_err(|_| ())?;
+        let mut tokens = Tokenizer::new(raw);
+        let mut seen: Vec<String> = Vec::new();
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut preload = false;
+        loop {
+            let name = match tokens.next() {
+                None => break,
+                Some(Ok(Token::Delimiter(';'))) => continue,
+                Some(Ok(Token::Token(name))) => name.to_ascii_lowercase(),
+                _ => return Err(()),
+            };
+            let value = if tokens.eat('=') {
+                match tokens.next() {
+                    Some(Ok(Token::Token(value))) => Some(value.to_owned()),
+                    Some(Ok(Token::Quoted(value))) => Some(value),
+                    _ => return Err(()),
+                }
+            } else {
+                None
+            };
+            match tokens.next() {
+                None | Some(Ok(Token::Delimiter(';'))) => (),
+                _ => return Err(()),
+            }
+            if seen.contains(&name) {
+                return Err(());
+            }
+            match (&name[..], value) {
+                ("max-age", Some(value)) => {
+                    if !value.bytes().all(|c| c.is_ascii_digit()) {
+                        return Err(());
+                    }
+                    max_age = Some(Duration::from_secs(value.parse().map_err(|_| ())?));
+                }
+                ("max-age", None) => return Err(()),
+                ("includesubdomains", None) => include_subdomains = true,
+                ("preload", None) => preload = true,
+                ("includesubdomains", Some(_)) | ("preload", Some(_)) => return Err(()),
+                _ => (),
+            }
+            seen.push(name);
+        }
+        Ok(StrictTransportSecurity {
+            max_age: max_age.ok_or(())?,
+            include_subdomains,
+            preload,
+        })
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, mut iter: I) -> io::Result<()> {
+        let mut w = iter.next().unwrap();
+        write!(w, "max-age={}", self.max_age.as_secs())?;
+        if self.include_subdomains {
+            w.write_all(b"; includeSubDomains")?;
+        }
+        if self.preload {
+            w.write_all(b"; preload")?;
+        }
+        Ok(())
+    }
+}
+
+/// `X-Content-Type-Options` header, [Fetch Section 3.5]
+///
+/// The only value is `nosniff`, so the header field is either
+/// present or absent. Browsers do not guess the media type of
+/// responses with this header field.
+#[derive(Clone, Copy, Debug)]
+pub struct XContentTypeOptions;
+
+impl ResponseHeader for XContentTypeOptions {}
+
+impl Header for XContentTypeOptions {
+    const NAME: &'static str = "X-Content-Type-Options";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        match ListElements::new(s).next() {
+            Some(Ok(value)) if value.eq_ignore_ascii_case(b"nosniff") => Ok(XContentTypeOptions),
+            _ => Err(()),
+        }
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter, "nosniff")
+    }
+}
+
+/// `X-Frame-Options` header, [RFC7034 Section 2]
+///
+/// The obsolete `ALLOW-FROM` is not supported, use the
+/// `frame-ancestors` directive of the Content Security Policy instead.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum XFrameOptions {
+    /// The page must not be displayed in a frame.
+    Deny,
+    /// The page may only be framed by pages of the same origin.
+    SameOrigin,
+}
+
+impl ResponseHeader for XFrameOptions {}
+
+impl Header for XFrameOptions {
+    const NAME: &'static str = "X-Frame-Options";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        // Browsers accept repeated values if they are all the same.
+        let mut value = None;
+        for element in ListElements::new(s) {
+            let element = element?;
+            let option = if element.eq_ignore_ascii_case(b"DENY") {
+                XFrameOptions::Deny
+            } else if element.eq_ignore_ascii_case(b"SAMEORIGIN") {
+                XFrameOptions::SameOrigin
+            } else {
+                return Err(());
+            };
+            if value.is_some_and(|x| x != option) {
+                return Err(());
+            }
+            value = Some(option);
+        }
+        value.ok_or(())
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter,
+                        match *self {
+                            XFrameOptions::Deny => "DENY",
+                            XFrameOptions::SameOrigin => "SAMEORIGIN",
+                        })
+    }
+}
+
+/// `Referrer-Policy` header, [Referrer Policy Section 4.1]
+///
+/// Senders may list several policies for older browsers, the last
+/// known policy is used. Unknown policies are skipped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReferrerPolicy {
+    /// `no-referrer`
+    NoReferrer,
+    /// `no-referrer-when-downgrade`
+    NoReferrerWhenDowngrade,
+    /// `same-origin`
+    SameOrigin,
+    /// `origin`
+    Origin,
+    /// `strict-origin`
+    StrictOrigin,
+    /// `origin-when-cross-origin`
+    OriginWhenCrossOrigin,
+    /// `strict-origin-when-cross-origin`, the default of browsers
+    StrictOriginWhenCrossOrigin,
+    /// `unsafe-url`
+    UnsafeUrl,
+}
+
+const REFERRER_POLICIES: [(ReferrerPolicy, &str); 8] =
+    [(ReferrerPolicy::NoReferrer, "no-referrer"),
+     (ReferrerPolicy::NoReferrerWhenDowngrade, "no-referrer-when-downgrade"),
+     (ReferrerPolicy::SameOrigin, "same-origin"),
+     (ReferrerPolicy::Origin, "origin"),
+     (ReferrerPolicy::StrictOrigin, "strict-origin"),
+     (ReferrerPolicy::OriginWhenCrossOrigin, "origin-when-cross-origin"),
+     (ReferrerPolicy::StrictOriginWhenCrossOrigin, "strict-origin-when-cross-origin"),
+     (ReferrerPolicy::UnsafeUrl, "unsafe-url")];
+
+impl FromStr for ReferrerPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<ReferrerPolicy, ()> {
+        REFERRER_POLICIES.iter().find(|x| x.1.eq_ignore_ascii_case(s)).map(|x| x.0).ok_or(())
+    }
+}
+
+impl Display for ReferrerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(REFERRER_POLICIES.iter().find(|x| x.0 == *self).unwrap().1)
+    }
+}
+
+impl ResponseHeader for ReferrerPolicy {}
+
+impl Header for ReferrerPolicy {
+    const NAME: &'static str = "Referrer-Policy";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        let mut policy = None;
+        for element in ListElements::new(s) {
+            let element = str::from_utf8(element?).map_err(|_| ())?;
+            if let Ok(known) = element.parse() {
+                policy = Some(known);
+            }
+        }
+        policy.ok_or(())
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter, self)
+    }
+}
+
+/// The value of the `Cross-Origin-Opener-Policy` header field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpenerPolicy {
+    /// `unsafe-none`, the default
+    UnsafeNone,
+    /// `same-origin-allow-popups`
+    SameOriginAllowPopups,
+    /// `same-origin`
+    SameOrigin,
+    /// `noopener-allow-popups`
+    NoopenerAllowPopups,
+}
+
+const OPENER_POLICIES: [(OpenerPolicy, &str); 4] =
+    [(OpenerPolicy::UnsafeNone, "unsafe-none"),
+     (OpenerPolicy::SameOriginAllowPopups, "same-origin-allow-popups"),
+     (OpenerPolicy::SameOrigin, "same-origin"),
+     (OpenerPolicy::NoopenerAllowPopups, "noopener-allow-popups")];
+
+/// `Cross-Origin-Opener-Policy` header, [HTML Section 7.1.3]
+///
+/// Isolates the browsing context group from cross-origin documents.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrossOriginOpenerPolicy {
+    /// The policy.
+    pub value: OpenerPolicy,
+    /// The reporting endpoint for violations.
+    pub report_to: Option<String>,
+}
+
+impl ResponseHeader for CrossOriginOpenerPolicy {}
+
+impl StructuredHeader for CrossOriginOpenerPolicy {
+    const NAME: &'static str = "Cross-Origin-Opener-Policy";
+    const SENSITIVE: bool = false;
+    type Field = Item;
+
+    fn from_field(field: Item) -> Result<Self, ()> {
+        Ok(CrossOriginOpenerPolicy {
+            value: from_token(&field, &OPENER_POLICIES)?,
+            report_to: report_to(&field)?,
+        })
+    }
+
+    fn to_field(&self) -> Item {
+        to_token(self.value, &OPENER_POLICIES, &self.report_to)
+    }
+}
+
+/// The value of the `Cross-Origin-Embedder-Policy` header field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EmbedderPolicy {
+    /// `unsafe-none`, the default
+    UnsafeNone,
+    /// `require-corp`
+    RequireCorp,
+    /// `credentialless`
+    Credentialless,
+}
+
+const EMBEDDER_POLICIES: [(EmbedderPolicy, &str); 3] =
+    [(EmbedderPolicy::UnsafeNone, "unsafe-none"),
+     (EmbedderPolicy::RequireCorp, "require-corp"),
+     (EmbedderPolicy::Credentialless, "credentialless")];
+
+/// `Cross-Origin-Embedder-Policy` header, [HTML Section 7.1.4]
+///
+/// Restricts the cross-origin resources a document can load.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrossOriginEmbedderPolicy {
+    /// The policy.
+    pub value: EmbedderPolicy,
+    /// The reporting endpoint for violations.
+    pub report_to: Option<String>,
+}
+
+impl ResponseHeader for CrossOriginEmbedderPolicy {}
+
+impl StructuredHeader for CrossOriginEmbedderPolicy {
+    const NAME: &'static str = "Cross-Origin-Embedder-Policy";
+    const SENSITIVE: bool = false;
+    type Field = Item;
+
+    fn from_field(field: Item) -> Result<Self, ()> {
+        Ok(CrossOriginEmbedderPolicy {
+            value: from_token(&field, &EMBEDDER_POLICIES)?,
+            report_to: report_to(&field)?,
+        })
+    }
+
+    fn to_field(&self) -> Item {
+        to_token(self.value, &EMBEDDER_POLICIES, &self.report_to)
+    }
+}
+
+fn from_token<T: Copy>(field: &Item, values: &[(T, &str)]) -> Result<T, ()> {
+    match field.bare_item {
+        BareItem::Token(ref token) => values.iter().find(|x| x.1 == token).map(|x| x.0).ok_or(()),
+        _ => Err(()),
+    }
+}
+
+fn report_to(field: &Item) -> Result<Option<String>, ()> {
+    match lookup(&field.parameters, "report-to") {
+        Some(BareItem::String(endpoint)) => Ok(Some(endpoint.clone())),
+        Some(_) => Err(()),
+        None => Ok(None),
+    }
+}
+
+fn to_token<T: Copy + PartialEq>(value: T, values: &[(T, &str)], report_to: &Option<String>) -> Item {
+    let mut item = Item::new(BareItem::Token(values.iter()
+        .find(|x| x.0 == value)
+        .unwrap()
+        .1
+        .to_owned()));
+    if let Some(ref endpoint) = *report_to {
+        item.parameters.push(("report-to".to_owned(), BareItem::String(endpoint.clone())));
+    }
+    item
+}
+
+/// `Cross-Origin-Resource-Policy` header, [Fetch Section 3.7]
+///
+/// Restricts which sites may embed the resource.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CrossOriginResourcePolicy {
+    /// `same-site`
+    SameSite,
+    /// `same-origin`
+    SameOrigin,
+    /// `cross-origin`
+    CrossOrigin,
+}
+
+impl ResponseHeader for CrossOriginResourcePolicy {}
+
+impl Header for CrossOriginResourcePolicy {
+    const NAME: &'static str = "Cross-Origin-Resource-Policy";
+    const SENSITIVE: bool = false;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        if s.len() != 1 {
+            return Err(());
+        }
+        match str::from_utf8(&s[0]).map_err(|_| ())?.trim() {
+            "same-site" => Ok(CrossOriginResourcePolicy::SameSite),
+            "same-origin" => Ok(CrossOriginResourcePolicy::SameOrigin),
+            "cross-origin" => Ok(CrossOriginResourcePolicy::CrossOrigin),
+            _ => Err(()),
+        }
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, iter: I) -> io::Result<()> {
+        serialize_value(iter,
+                        match *self {
+                            CrossOriginResourcePolicy::SameSite => "same-site",
+                            CrossOriginResourcePolicy::SameOrigin => "same-origin",
+                            CrossOriginResourcePolicy::CrossOrigin => "cross-origin",
+                        })
+    }
+}
+
+/// A member of an allowlist in a `Permissions-Policy`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Allowlist {
+    /// `*`, all origins
+    Any,
+    /// `self`, the origin of the document
+    Self_,
+    /// `src`, the origin of the `src` attribute of an iframe
+    Src,
+    /// A listed origin.
+    Origin(UrlOrigin),
+}
+
+/// `Permissions-Policy` header, [Permissions Policy Section 5.2]
+///
+/// Maps features like `camera` or `geolocation` to the origins
+/// allowed to use them. An empty allowlist disables the feature.
+/// Invalid allowlist members are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PermissionsPolicy {
+    /// The features with their allowlists in order.
+    pub features: Vec<(String, Vec<Allowlist>)>,
+}
+
+impl PermissionsPolicy {
+    /// Constructs a policy without features.
+    pub fn new() -> PermissionsPolicy {
+        PermissionsPolicy::default()
+    }
+
+    /// Returns the allowlist of a feature.
+    pub fn get(&self, feature: &str) -> Option<&[Allowlist]> {
+        self.features.iter().find(|x| x.0 == feature).map(|x| &x.1[..])
+    }
+
+    /// Sets the allowlist of a feature.
+    ///
+    /// Fails if the feature name is not a lowercase key like `camera`.
+    pub fn set<F: Into<String>>(&mut self, feature: F, allowlist: Vec<Allowlist>) -> Result<(), ()> {
+        let feature = feature.into();
+        if !is_key(&feature) {
+            return Err(());
+        }
+        match self.features.iter_mut().find(|x| x.0 == feature) {
+            Some(entry) => entry.1 = allowlist,
+            None => self.features.push((feature, allowlist)),
+        }
+        Ok(())
+    }
+}
+
+impl ResponseHeader for PermissionsPolicy {}
+
+impl StructuredHeader for PermissionsPolicy {
+    const NAME: &'static str = "Permissions-Policy";
+    const SENSITIVE: bool = false;
+    type Field = Dictionary;
+
+    fn from_field(field: Dictionary) -> Result<Self, ()> {
+        let features = field.into_iter()
+            .map(|(feature, member)| {
+                let allowlist = match member {
+                    Member::Item(item) => allowlist_member(&item.bare_item).into_iter().collect(),
+                    Member::InnerList(list) => {
+                        list.items.iter().filter_map(|x| allowlist_member(&x.bare_item)).collect()
+                    }
+                };
+                (feature, allowlist)
+            })
+            .collect();
+        Ok(PermissionsPolicy { features })
+    }
+
+    fn to_field(&self) -> Dictionary {
+        self.features
+            .iter()
+            .map(|(feature, allowlist)| {
+                let member = match allowlist[..] {
+                    [ref single @ Allowlist::Any] | [ref single @ Allowlist::Self_] => {
+                        Member::Item(Item::new(allowlist_item(single)))
+                    }
+                    _ => {
+                        Member::InnerList(InnerList {
+                            items: allowlist.iter().map(|x| Item::new(allowlist_item(x))).collect(),
+                            parameters: Vec::new(),
+                        })
+                    }
+                };
+                (feature.clone(), member)
+            })
+            .collect()
+    }
+}
+
+fn allowlist_member(item: &BareItem) -> Option<Allowlist> {
+    match *item {
+        BareItem::Token(ref token) if token == "*" => Some(Allowlist::Any),
+        BareItem::Token(ref token) if token == "self" => Some(Allowlist::Self_),
+        BareItem::Token(ref token) if token == "src" => Some(Allowlist::Src),
+        BareItem::String(ref origin) => {
+            let origin = Url::parse(origin).ok()?.origin();
+            if origin.is_tuple() {
+                Some(Allowlist::Origin(origin))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn allowlist_item(member: &Allowlist) -> BareItem {
+    match *member {
+        Allowlist::Any => BareItem::Token("*".to_owned()),
+        Allowlist::Self_ => BareItem::Token("self".to_owned()),
+        Allowlist::Src => BareItem::Token("src".to_owned()),
+        Allowlist::Origin(ref origin) => BareItem::String(origin.ascii_serialization()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use std::time::Duration;
+
+    use url::Url;
+
+    use header::{Header, Headers, headers};
+    use super::{Allowlist, CrossOriginEmbedderPolicy, CrossOriginOpenerPolicy,
+                CrossOriginResourcePolicy, EmbedderPolicy, OpenerPolicy, PermissionsPolicy,
+                ReferrerPolicy, StrictTransportSecurity, XContentTypeOptions, XFrameOptions};
+
+    /// Parses the value, serializes it into `expected` and parses it again.
+    fn round_trip<H: Header + Debug + PartialEq>(value: &str, expected: &str) -> H {
+        let header = headers(H::NAME, value).get_without_base::<H>().unwrap().unwrap();
+        let mut output = Headers::new();
+        output.set(&header).unwrap();
+        assert_eq!(output.get_raw(H::NAME).unwrap(), &[expected.as_bytes().to_vec()][..]);
+        assert_eq!(output.get_without_base::<H>().unwrap(), Ok(header.clone()));
+        header
+    }
+
+    fn parse<H: Header>(value: &str) -> Result<H, ()> {
+        headers(H::NAME, value).get_without_base().unwrap()
+    }
+
+    #[test]
+    fn test_strict_transport_security() {
+        let sts: StrictTransportSecurity =
+            round_trip("max-age=31536000 ; includeSubDomains;PRELOAD;unknown=\"x\"",
+                       "max-age=31536000; includeSubDomains; preload");
+        assert_eq!(sts.max_age, Duration::from_secs(31_536_000));
+        assert!(sts.include_subdomains && sts.preload);
+        assert_eq!(round_trip::<StrictTransportSecurity>("max-age=\"0\"", "max-age=0"),
+                   StrictTransportSecurity::new(Duration::from_secs(0)));
+        assert!(parse::<StrictTransportSecurity>("max-age=1; max-age=2").is_err());
+        assert!(parse::<StrictTransportSecurity>("max-age=1; preload; Preload").is_err());
+        assert!(parse::<StrictTransportSecurity>("max-age=\"\"").is_err());
+        assert!(parse::<StrictTransportSecurity>("max-age=\"1 2\"").is_err());
+        assert!(parse::<StrictTransportSecurity>("max-age=-1").is_err());
+        assert!(parse::<StrictTransportSecurity>("max-age").is_err());
+        assert!(parse::<StrictTransportSecurity>("includeSubDomains").is_err());
+        assert!(parse::<StrictTransportSecurity>("max-age=1; preload=1").is_err());
+    }
+
+    #[test]
+    fn test_x_content_type_options() {
+        let header = parse::<XContentTypeOptions>("NoSniff").unwrap();
+        let mut output = Headers::new();
+        output.set(&header).unwrap();
+        assert_eq!(output.get_raw("X-Content-Type-Options").unwrap(), &[b"nosniff".to_vec()][..]);
+        assert!(output.get_without_base::<XContentTypeOptions>().unwrap().is_ok());
+        assert!(parse::<XContentTypeOptions>("sniff").is_err());
+        assert!(parse::<XContentTypeOptions>("").is_err());
+    }
+
+    #[test]
+    fn test_x_frame_options() {
+        assert_eq!(round_trip::<XFrameOptions>("deny", "DENY"), XFrameOptions::Deny);
+        assert_eq!(round_trip::<XFrameOptions>("SAMEORIGIN, sameorigin", "SAMEORIGIN"),
+                   XFrameOptions::SameOrigin);
+        assert!(parse::<XFrameOptions>("DENY, SAMEORIGIN").is_err());
+        assert!(parse::<XFrameOptions>("ALLOW-FROM https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_referrer_policy() {
+        assert_eq!(round_trip::<ReferrerPolicy>("no-referrer, Strict-Origin-When-Cross-Origin",
+                                                "strict-origin-when-cross-origin"),
+                   ReferrerPolicy::StrictOriginWhenCrossOrigin);
+        assert_eq!(round_trip::<ReferrerPolicy>("origin, unknown-policy", "origin"),
+                   ReferrerPolicy::Origin);
+        assert!(parse::<ReferrerPolicy>("unknown-policy").is_err());
+        assert!(parse::<ReferrerPolicy>("").is_err());
+    }
+
+    #[test]
+    fn test_cross_origin_policies() {
+        let coop: CrossOriginOpenerPolicy = round_trip("same-origin;report-to=\"coop\"",
+                                                       "same-origin;report-to=\"coop\"");
+        assert_eq!(coop.value, OpenerPolicy::SameOrigin);
+        assert_eq!(coop.report_to, Some("coop".to_owned()));
+        assert!(parse::<CrossOriginOpenerPolicy>("\"same-origin\"").is_err());
+        assert!(parse::<CrossOriginOpenerPolicy>("same-origin;report-to=coop").is_err());
+        let coep: CrossOriginEmbedderPolicy = round_trip("require-corp", "require-corp");
+        assert_eq!(coep.value, EmbedderPolicy::RequireCorp);
+        assert_eq!(coep.report_to, None);
+        assert!(parse::<CrossOriginEmbedderPolicy>("require-corp, credentialless").is_err());
+        assert_eq!(round_trip::<CrossOriginResourcePolicy>(" same-site ", "same-site"),
+                   CrossOriginResourcePolicy::SameSite);
+        assert!(parse::<CrossOriginResourcePolicy>("Same-Site").is_err());
+    }
+
+    #[test]
+    fn test_permissions_policy() {
+        let policy: PermissionsPolicy =
+            round_trip("camera=(), geolocation=(self \"https://example.com:443\"), fullscreen=*",
+                       "camera=(), geolocation=(self \"https://example.com\"), fullscreen=*");
+        let origin = Url::parse("https://example.com").unwrap().origin();
+        assert_eq!(policy.get("camera"), Some(&[][..]));
+        assert_eq!(policy.get("geolocation"),
+                   Some(&[Allowlist::Self_, Allowlist::Origin(origin)][..]));
+        assert_eq!(policy.get("fullscreen"), Some(&[Allowlist::Any][..]));
+        assert!(parse::<PermissionsPolicy>("camera=(self").is_err());
+        assert!(parse::<PermissionsPolicy>("camera=self,,fullscreen=*").is_err());
+        assert!(parse::<PermissionsPolicy>("Camera=*").is_err());
+    }
+
+    #[test]
+    fn test_permissions_policy_set() {
+        let mut policy = PermissionsPolicy::new();
+        assert_eq!(policy.set("camera", vec![Allowlist::Self_]), Ok(()));
+        assert_eq!(policy.set("camera", Vec::new()), Ok(()));
+        assert_eq!(policy.features, vec![("camera".to_owned(), Vec::new())]);
+        assert_eq!(policy.set("Camera", Vec::new()), Err(()));
+        assert_eq!(policy.set("", Vec::new()), Err(()));
+        assert_eq!(policy.set("a b", Vec::new()), Err(()));
+        assert_eq!(policy.features.len(), 1);
+    }
+}
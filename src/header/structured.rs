@@ -0,0 +1,847 @@
+//! Structured Field Values for HTTP.
+//!
+//! Many newer header fields are defined as structured fields.
+//! They are either a single *item*, a *list* of members or a
+//! *dictionary* mapping keys to members. A member is an item or an
+//! inner list of items. Items and inner lists carry parameters.
+//! [RFC8941](https://tools.ietf.org/html/rfc8941)
+//!
+//! Parsing is strict and follows the algorithms of the specification.
+//! Serialization always produces the canonical form.
+//!
+//! A header field defined as a structured field implements
+//! [`StructuredHeader`](trait.StructuredHeader.html) and gets an
+//! implementation of `Header` for free.
+
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::str;
+
+use url::Url;
+
+use header::Header;
+use util::{BASE64, decode_base64, encode_base64, is_tchar};
+
+/// A bare item is the value of an item or a parameter.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BareItem {
+    /// An integer with at most 15 digits.
+    Integer(i64),
+    /// A decimal with at most 12 integer and 3 fractional digits.
+    Decimal(Decimal),
+    /// A string of printable ASCII characters.
+    String(String),
+    /// A token like `foo` or `text/html`.
+    Token(String),
+    /// An arbitrary sequence of bytes.
+    ByteSeq(Vec<u8>),
+    /// A boolean.
+    Boolean(bool),
+    /// A date as seconds since the Unix epoch.
+    Date(i64),
+    /// A string of Unicode characters.
+    DisplayString(String),
+}
+
+/// A decimal number with three fractional digits.
+///
+/// The number is stored as an integer count of thousandths
+/// so it can be compared and serialized exactly.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Decimal(i64);
+
+impl Decimal {
+    /// Constructs a decimal from thousandths, `1500` is `1.5`.
+    pub fn from_thousandths(n: i64) -> Decimal {
+        Decimal(n)
+    }
+
+    /// Returns the decimal in thousandths.
+    pub fn thousandths(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Parameters of an item or an inner list.
+///
+/// Keys are unique and kept in their order.
+pub type Parameters = Vec<(String, BareItem)>;
+
+/// An item is a bare item with parameters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Item {
+    /// The value of the item.
+    pub bare_item: BareItem,
+    /// The parameters of the item.
+    pub parameters: Parameters,
+}
+
+impl Item {
+    /// Constructs a new item without parameters.
+    pub fn new(bare_item: BareItem) -> Item {
+        Item {
+            bare_item,
+            parameters: Vec::new(),
+        }
+    }
+}
+
+/// An inner list is a list of items with parameters.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InnerList {
+    /// The items of the inner list.
+    pub items: Vec<Item>,
+    /// The parameters of the inner list.
+    pub parameters: Parameters,
+}
+
+/// A member of a list or dictionary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Member {
+    /// A single item.
+    Item(Item),
+    /// An inner list of items.
+    InnerList(InnerList),
+}
+
+/// A list of members.
+pub type List = Vec<Member>;
+
+/// A dictionary maps keys to members.
+///
+/// Keys are unique and kept in their order.
+pub type Dictionary = Vec<(String, Member)>;
+
+/// Looks up the value for a key in a dictionary or in parameters.
+pub fn lookup<'a, V>(map: &'a [(String, V)], key: &str) -> Option<&'a V> {
+    map.iter().find(|x| x.0 == key).map(|x| &x.1)
+}
+
+/// The three top-level types of structured fields.
+pub trait FieldType: Sized {
+    /// Parses the field from possibly multiple lines.
+    ///
+    /// Lines are combined with commas before parsing.
+    fn parse_field(s: &[Vec<u8>]) -> Result<Self, ()>;
+
+    /// Serializes the field in its canonical form.
+    ///
+    /// An error is returned if the value cannot be represented,
+    /// e.g. an integer out of range or an invalid key.
+    fn serialize_field(&self) -> Result<String, ()>;
+}
+
+impl FieldType for Item {
+    fn parse_field(s: &[Vec<u8>]) -> Result<Item, ()> {
+        let input = combine(s);
+        let mut parser = Parser::new(&input);
+        let item = parser.item()?;
+        parser.end()?;
+        Ok(item)
+    }
+
+    fn serialize_field(&self) -> Result<String, ()> {
+        let mut output = String::new();
+        write_item(&mut output, self)?;
+        Ok(output)
+    }
+}
+
+impl FieldType for List {
+    fn parse_field(s: &[Vec<u8>]) -> Result<List, ()> {
+        let input = combine(s);
+        let mut parser = Parser::new(&input);
+        let mut list = Vec::new();
+        while !parser.is_empty() {
+            list.push(parser.member()?);
+            if !parser.next_member()? {
+                break;
+            }
+        }
+        parser.end()?;
+        Ok(list)
+    }
+
+    fn serialize_field(&self) -> Result<String, ()> {
+        let mut output = String::new();
+        for (i, member) in self.iter().enumerate() {
+            if i != 0 {
+                output.push_str(", ");
+            }
+            write_member(&mut output, member)?;
+        }
+        Ok(output)
+    }
+}
+
+impl FieldType for Dictionary {
+    fn parse_field(s: &[Vec<u8>]) -> Result<Dictionary, ()> {
+        let input = combine(s);
+        let mut parser = Parser::new(&input);
+        let mut dictionary = Vec::new();
+        while !parser.is_empty() {
+            let key = parser.key()?;
+            let member = if parser.eat(b'=') {
+                parser.member()?
+            } else {
+                Member::Item(Item {
+                    bare_item: BareItem::Boolean(true),
+                    parameters: parser.parameters()?,
+                })
+            };
+            insert(&mut dictionary, key, member);
+            if !parser.next_member()? {
+                break;
+            }
+        }
+        parser.end()?;
+        Ok(dictionary)
+    }
+
+    fn serialize_field(&self) -> Result<String, ()> {
+        let mut output = String::new();
+        for (i, (key, member)) in self.iter().enumerate() {
+            if i != 0 {
+                output.push_str(", ");
+            }
+            write_key(&mut output, key)?;
+            match *member {
+                Member::Item(Item { bare_item: BareItem::Boolean(true), ref parameters }) => {
+                    write_parameters(&mut output, parameters)?;
+                }
+                _ => {
+                    output.push('=');
+                    write_member(&mut output, member)?;
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// A header field defined as a structured field.
+///
+/// The implementing type declares its structured type and converts
+/// from and to it. `Header` is implemented for all such types.
+pub trait StructuredHeader: Clone + Debug + Sized {
+    /// The name of the header field, see `Header::NAME`.
+    const NAME: &'static str;
+    /// Marks the header field as sensitive, see `Header::SENSITIVE`.
+    const SENSITIVE: bool;
+
+    /// The structured type of the field: `Item`, `List` or `Dictionary`.
+    type Field: FieldType;
+
+    /// Converts a parsed structured field.
+    fn from_field(field: Self::Field) -> Result<Self, ()>;
+
+    /// Converts the header into a structured field.
+    fn to_field(&self) -> Self::Field;
+}
+
+impl<T: StructuredHeader> Header for T {
+    const NAME: &'static str = T::NAME;
+    const SENSITIVE: bool = T::SENSITIVE;
+
+    fn parse(s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+        T::from_field(T::Field::parse_field(s)?)
+    }
+
+    fn serialize<I: Iterator<Item = W>, W: Write>(&self, mut iter: I) -> io::Result<()> {
+        let value = self.to_field()
+            .serialize_field()
+            .map_err(|()| io::Error::new(io::ErrorKind::InvalidInput, "invalid structured field"))?;
+        iter.next().unwrap().write_all(value.as_bytes())
+    }
+}
+
+fn combine(s: &[Vec<u8>]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for (i, line) in s.iter().enumerate() {
+        if i != 0 {
+            input.extend_from_slice(b", ");
+        }
+        input.extend_from_slice(line);
+    }
+    input
+}
+
+fn insert<V>(map: &mut Vec<(String, V)>, key: String, value: V) {
+    if let Some(entry) = map.iter_mut().find(|x| x.0 == key) {
+        entry.1 = value;
+        return;
+    }
+    map.push((key, value));
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a [u8]) -> Parser<'a> {
+        let mut parser = Parser { input, position: 0 };
+        parser.skip_spaces();
+        while parser.input.last() == Some(&b' ') {
+            parser.input = &parser.input[..parser.input.len() - 1];
+        }
+        parser
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).cloned()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn end(&self) -> Result<(), ()> {
+        if self.is_empty() { Ok(()) } else { Err(()) }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.position += 1;
+        }
+    }
+
+    fn skip_ows(&mut self) {
+        while self.peek() == Some(b' ') || self.peek() == Some(b'\t') {
+            self.position += 1;
+        }
+    }
+
+    // Consumes the comma between list or dictionary members.
+    // Returns `false` at the end of the input.
+    fn next_member(&mut self) -> Result<bool, ()> {
+        self.skip_ows();
+        if self.is_empty() {
+            return Ok(false);
+        }
+        if !self.eat(b',') {
+            return Err(());
+        }
+        self.skip_ows();
+        if self.is_empty() {
+            return Err(());
+        }
+        Ok(true)
+    }
+
+    fn member(&mut self) -> Result<Member, ()> {
+        if self.peek() == Some(b'(') {
+            self.inner_list().map(Member::InnerList)
+        } else {
+            self.item().map(Member::Item)
+        }
+    }
+
+    fn inner_list(&mut self) -> Result<InnerList, ()> {
+        if !self.eat(b'(') {
+            return Err(());
+        }
+        let mut items = Vec::new();
+        loop {
+            self.skip_spaces();
+            if self.eat(b')') {
+                return Ok(InnerList {
+                    items,
+                    parameters: self.parameters()?,
+                });
+            }
+            items.push(self.item()?);
+            match self.peek() {
+                Some(b' ') | Some(b')') => (),
+                _ => return Err(()),
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, ()> {
+        Ok(Item {
+            bare_item: self.bare_item()?,
+            parameters: self.parameters()?,
+        })
+    }
+
+    fn parameters(&mut self) -> Result<Parameters, ()> {
+        let mut parameters = Vec::new();
+        while self.eat(b';') {
+            self.skip_spaces();
+            let key = self.key()?;
+            let value = if self.eat(b'=') {
+                self.bare_item()?
+            } else {
+                BareItem::Boolean(true)
+            };
+            insert(&mut parameters, key, value);
+        }
+        Ok(parameters)
+    }
+
+    fn key(&mut self) -> Result<String, ()> {
+        match self.peek() {
+            Some(c) if c.is_ascii_lowercase() || c == b'*' => (),
+            _ => return Err(()),
+        }
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if !is_key_char(c) {
+                break;
+            }
+            self.position += 1;
+        }
+        Ok(String::from_utf8(self.input[start..self.position].to_vec()).unwrap())
+    }
+
+    fn bare_item(&mut self) -> Result<BareItem, ()> {
+        match self.peek() {
+            Some(b'-') => self.number(),
+            Some(c) if c.is_ascii_digit() => self.number(),
+            Some(b'"') => self.string().map(BareItem::String),
+            Some(b'*') => self.token(),
+            Some(c) if c.is_ascii_alphabetic() => self.token(),
+            Some(b':') => self.byte_seq(),
+            Some(b'?') => self.boolean(),
+            Some(b'@') => self.date(),
+            Some(b'%') => self.display_string(),
+            _ => Err(()),
+        }
+    }
+
+    fn number(&mut self) -> Result<BareItem, ()> {
+        let negative = self.eat(b'-');
+        let start = self.position;
+        let mut point = None;
+        while let Some(c) = self.peek() {
+            if c == b'.' && point.is_none() {
+                if self.position - start > 12 {
+                    return Err(());
+                }
+                point = Some(self.position);
+            } else if !c.is_ascii_digit() {
+                break;
+            }
+            self.position += 1;
+            let len = self.position - start;
+            if (point.is_none() && len > 15) || (point.is_some() && len > 16) {
+                return Err(());
+            }
+        }
+        let raw = str::from_utf8(&self.input[start..self.position]).unwrap();
+        let sign = if negative { -1 } else { 1 };
+        match point {
+            None => {
+                if raw.is_empty() {
+                    return Err(());
+                }
+                Ok(BareItem::Integer(sign * raw.parse::<i64>().map_err(|_| ())?))
+            }
+            Some(point) => {
+                let point = point - start;
+                let (integer, fraction) = (&raw[..point], &raw[point + 1..]);
+                if integer.is_empty() || fraction.is_empty() || fraction.len() > 3 {
+                    return Err(());
+                }
+                let integer = integer.parse::<i64>().map_err(|_| ())?;
+                let fraction = format!("{:0<3}", fraction).parse::<i64>().map_err(|_| ())?;
+                Ok(BareItem::Decimal(Decimal(sign * (integer * 1000 + fraction))))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ()> {
+        if !self.eat(b'"') {
+            return Err(());
+        }
+        let mut output = String::new();
+        loop {
+            match self.peek() {
+                Some(b'\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some(c) if c == b'"' || c == b'\\' => output.push(c as char),
+                        _ => return Err(()),
+                    }
+                }
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some(c) if (0x20..0x7f).contains(&c) => output.push(c as char),
+                _ => return Err(()),
+            }
+            self.position += 1;
+        }
+    }
+
+    fn token(&mut self) -> Result<BareItem, ()> {
+        let start = self.position;
+        self.position += 1;
+        while let Some(c) = self.peek() {
+            if !is_tchar(c as char) && c != b':' && c != b'/' {
+                break;
+            }
+            self.position += 1;
+        }
+        let token = str::from_utf8(&self.input[start..self.position]).unwrap();
+        Ok(BareItem::Token(token.to_owned()))
+    }
+
+    fn byte_seq(&mut self) -> Result<BareItem, ()> {
+        if !self.eat(b':') {
+            return Err(());
+        }
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c == b':' {
+                break;
+            }
+            if !c.is_ascii_alphanumeric() && c != b'+' && c != b'/' && c != b'=' {
+                return Err(());
+            }
+            self.position += 1;
+        }
+        let data = &self.input[start..self.position];
+        if !self.eat(b':') {
+            return Err(());
+        }
+        decode_base64(data, BASE64).map(BareItem::ByteSeq).ok_or(())
+    }
+
+    fn boolean(&mut self) -> Result<BareItem, ()> {
+        if !self.eat(b'?') {
+            return Err(());
+        }
+        if self.eat(b'1') {
+            Ok(BareItem::Boolean(true))
+        } else if self.eat(b'0') {
+            Ok(BareItem::Boolean(false))
+        } else {
+            Err(())
+        }
+    }
+
+    fn date(&mut self) -> Result<BareItem, ()> {
+        if !self.eat(b'@') {
+            return Err(());
+        }
+        match self.number()? {
+            BareItem::Integer(n) => Ok(BareItem::Date(n)),
+            _ => Err(()),
+        }
+    }
+
+    fn display_string(&mut self) -> Result<BareItem, ()> {
+        if !self.eat(b'%') || !self.eat(b'"') {
+            return Err(());
+        }
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'%') => {
+                    let hex = self.input.get(self.position + 1..self.position + 3).ok_or(())?;
+                    if !hex.iter().all(|&c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c)) {
+                        return Err(());
+                    }
+                    let hex = str::from_utf8(hex).unwrap();
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                    self.position += 2;
+                }
+                Some(b'"') => {
+                    self.position += 1;
+                    return String::from_utf8(bytes).map(BareItem::DisplayString).map_err(|_| ());
+                }
+                Some(c) if (0x20..0x7f).contains(&c) => bytes.push(c),
+                _ => return Err(()),
+            }
+            self.position += 1;
+        }
+    }
+}
+
+fn is_key_char(c: u8) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, b'_' | b'-' | b'.' | b'*')
+}
+
+pub(crate) const MAX_INTEGER: i64 = 999_999_999_999_999;
+
+fn write_member(output: &mut String, member: &Member) -> Result<(), ()> {
+    match *member {
+        Member::Item(ref item) => write_item(output, item),
+        Member::InnerList(ref inner_list) => {
+            output.push('(');
+            for (i, item) in inner_list.items.iter().enumerate() {
+                if i != 0 {
+                    output.push(' ');
+                }
+                write_item(output, item)?;
+            }
+            output.push(')');
+            write_parameters(output, &inner_list.parameters)
+        }
+    }
+}
+
+fn write_item(output: &mut String, item: &Item) -> Result<(), ()> {
+    write_bare_item(output, &item.bare_item)?;
+    write_parameters(output, &item.parameters)
+}
+
+fn write_parameters(output: &mut String, parameters: &[(String, BareItem)]) -> Result<(), ()> {
+    for (key, value) in parameters {
+        output.push(';');
+        write_key(output, key)?;
+        if *value != BareItem::Boolean(true) {
+            output.push('=');
+            write_bare_item(output, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_key(output: &mut String, key: &str) -> Result<(), ()> {
+    if !is_key(key) {
+        return Err(());
+    }
+    output.push_str(key);
+    Ok(())
+}
+
+// Checks if a string is a valid dictionary or parameter key.
+pub(crate) fn is_key(key: &str) -> bool {
+    match key.bytes().next() {
+        Some(c) if c.is_ascii_lowercase() || c == b'*' => key.bytes().all(is_key_char),
+        _ => false,
+    }
+}
+
+fn write_bare_item(output: &mut String, bare_item: &BareItem) -> Result<(), ()> {
+    match *bare_item {
+        BareItem::Integer(n) => {
+            if !(-MAX_INTEGER..=MAX_INTEGER).contains(&n) {
+                return Err(());
+            }
+            output.push_str(&n.to_string());
+        }
+        BareItem::Decimal(Decimal(n)) => {
+            let abs = n.unsigned_abs();
+            if abs / 1000 > 999_999_999_999 {
+                return Err(());
+            }
+            if n < 0 {
+                output.push('-');
+            }
+            let fraction = format!("{:03}", abs % 1000);
+            let fraction = fraction.trim_end_matches('0');
+            output.push_str(&(abs / 1000).to_string());
+            output.push('.');
+            output.push_str(if fraction.is_empty() { "0" } else { fraction });
+        }
+        BareItem::String(ref s) => {
+            output.push('"');
+            for c in s.chars() {
+                if !(' '..='~').contains(&c) {
+                    return Err(());
+                }
+                if c == '"' || c == '\\' {
+                    output.push('\\');
+                }
+                output.push(c);
+            }
+            output.push('"');
+        }
+        BareItem::Token(ref s) => {
+            match s.chars().next() {
+                Some(c) if c.is_ascii_alphabetic() || c == '*' => (),
+                _ => return Err(()),
+            }
+            if !s.chars().all(|c| is_tchar(c) || c == ':' || c == '/') {
+                return Err(());
+            }
+            output.push_str(s);
+        }
+        BareItem::ByteSeq(ref bytes) => {
+            output.push(':');
+            output.push_str(&encode_base64(bytes, BASE64, true));
+            output.push(':');
+        }
+        BareItem::Boolean(b) => output.push_str(if b { "?1" } else { "?0" }),
+        BareItem::Date(n) => {
+            output.push('@');
+            write_bare_item(output, &BareItem::Integer(n))?;
+        }
+        BareItem::DisplayString(ref s) => {
+            output.push_str("%\"");
+            for &c in s.as_bytes() {
+                if c == b'%' || c == b'"' || !(0x20..0x7f).contains(&c) {
+                    output.push_str(&format!("%{:02x}", c));
+                } else {
+                    output.push(c as char);
+                }
+            }
+            output.push('"');
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Map, Value};
+
+    use super::{BareItem, Decimal, Dictionary, FieldType, InnerList, Item, List, Member, Parameters};
+
+    const BASE32: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    fn base32(bytes: &[u8]) -> String {
+        let mut output = String::new();
+        for chunk in bytes.chunks(5) {
+            let mut buffer = [0u8; 5];
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            let n = buffer.iter().fold(0u64, |n, &c| n << 8 | u64::from(c));
+            let digits = (chunk.len() * 8).div_ceil(5);
+            for i in 0..8 {
+                if i < digits {
+                    output.push(BASE32[(n >> (35 - 5 * i) & 31) as usize] as char);
+                } else {
+                    output.push('=');
+                }
+            }
+        }
+        output
+    }
+
+    fn typed(kind: &str, value: Value) -> Value {
+        let mut map = Map::new();
+        map.insert("__type".to_owned(), Value::from(kind));
+        map.insert("value".to_owned(), value);
+        Value::Object(map)
+    }
+
+    fn bare_item(bare_item: &BareItem) -> Value {
+        match *bare_item {
+            BareItem::Integer(n) => Value::from(n),
+            BareItem::Decimal(n) => Value::from(n.thousandths() as f64 / 1000.0),
+            BareItem::String(ref s) => Value::from(s.clone()),
+            BareItem::Token(ref s) => typed("token", Value::from(s.clone())),
+            BareItem::ByteSeq(ref bytes) => typed("binary", Value::from(base32(bytes))),
+            BareItem::Boolean(b) => Value::from(b),
+            BareItem::Date(n) => typed("date", Value::from(n)),
+            BareItem::DisplayString(ref s) => typed("displaystring", Value::from(s.clone())),
+        }
+    }
+
+    fn parameters(parameters: &Parameters) -> Value {
+        parameters.iter()
+            .map(|(key, value)| Value::Array(vec![Value::from(key.clone()), bare_item(value)]))
+            .collect()
+    }
+
+    fn item(item: &Item) -> Value {
+        Value::Array(vec![bare_item(&item.bare_item), parameters(&item.parameters)])
+    }
+
+    fn member(member: &Member) -> Value {
+        match *member {
+            Member::Item(ref x) => item(x),
+            Member::InnerList(InnerList { ref items, parameters: ref p }) => {
+                Value::Array(vec![items.iter().map(item).collect(), parameters(p)])
+            }
+        }
+    }
+
+    fn check<T: FieldType, F: Fn(&T) -> Value>(test: &Value, raw: &[Vec<u8>], to_json: F) {
+        let name = test["name"].as_str().unwrap();
+        let must_fail = test["must_fail"].as_bool().unwrap_or(false);
+        let can_fail = test["can_fail"].as_bool().unwrap_or(false);
+        let field = match T::parse_field(raw) {
+            Ok(field) => field,
+            Err(()) => {
+                assert!(must_fail || can_fail, "{}: failed to parse", name);
+                return;
+            }
+        };
+        assert!(!must_fail, "{}: parsed invalid input", name);
+        assert_eq!(to_json(&field), test["expected"], "{}", name);
+        let canonical = match test.get("canonical") {
+            Some(canonical) => canonical.as_array().unwrap().iter().map(|x| x.as_str().unwrap()).collect(),
+            None => vec![test["raw"][0].as_str().unwrap()],
+        };
+        assert_eq!(field.serialize_field().unwrap(), canonical.join(", "), "{}", name);
+    }
+
+    fn run(fixture: &str) {
+        let tests: Value = ::serde_json::from_str(fixture).unwrap();
+        for test in tests.as_array().unwrap() {
+            let raw: Vec<Vec<u8>> = test["raw"].as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_str().unwrap().as_bytes().to_vec())
+                .collect();
+            match test["header_type"].as_str().unwrap() {
+                "item" => check::<Item, _>(test, &raw, item),
+                "list" => check::<List, _>(test, &raw, |x| x.iter().map(member).collect()),
+                "dictionary" => check::<Dictionary, _>(test, &raw, |x| {
+                    x.iter()
+                        .map(|(key, value)| Value::Array(vec![Value::from(key.clone()), member(value)]))
+                        .collect()
+                }),
+                kind => panic!("unknown header type {}", kind),
+            }
+        }
+    }
+
+    macro_rules! fixtures {
+        ($($name:ident => $file:expr,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    run(include_str!(concat!("../../tests/structured-field-tests/", $file)));
+                }
+            )*
+        }
+    }
+
+    fixtures! {
+        test_binary => "binary.json",
+        test_boolean => "boolean.json",
+        test_date => "date.json",
+        test_dictionary => "dictionary.json",
+        test_display_string => "display-string.json",
+        test_examples => "examples.json",
+        test_item => "item.json",
+        test_list => "list.json",
+        test_listlist => "listlist.json",
+        test_number => "number.json",
+        test_param_dict => "param-dict.json",
+        test_param_list => "param-list.json",
+        test_param_listlist => "param-listlist.json",
+        test_string => "string.json",
+        test_token => "token.json",
+    }
+
+    #[test]
+    fn test_serialize_out_of_range() {
+        for &n in &[i64::MIN, i64::MAX, 1_000_000_000_000_000] {
+            let item = Item::new(BareItem::Integer(n));
+            assert_eq!(item.serialize_field(), Err(()));
+            let item = Item::new(BareItem::Decimal(Decimal::from_thousandths(n)));
+            assert_eq!(item.serialize_field(), Err(()));
+        }
+        let item = Item::new(BareItem::Decimal(Decimal::from_thousandths(-999_999_999_999_999)));
+        assert_eq!(item.serialize_field().unwrap(), "-999999999999.999");
+        let item = Item::new(BareItem::Token("1a".to_owned()));
+        assert_eq!(item.serialize_field(), Err(()));
+    }
+}
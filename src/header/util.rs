@@ -1,9 +1,13 @@
 #![allow(missing_docs)]
 
-use std::fmt::Display;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
 use std::io::{self, Write};
 use std::str::{self, FromStr};
 
+use header::item::{Parameter, decode_ext_value};
+use util::{is_tchar, is_token};
+
 pub fn parse_value<T: FromStr>(s: &[Vec<u8>]) -> Result<T, ()> {
     if s.len() != 1 {
         return Err(());
@@ -19,66 +23,372 @@ pub fn serialize_value<I, W, T>(mut iter: I, v: T) -> Result<(), io::Error>
           W: Write,
           T: Display
 {
-    write!(iter.next().unwrap(), "{}", v)
+    write_display(&mut iter.next().unwrap(), v)
+}
+
+// Formats the value before writing it, `write!` on an `io::Write`
+// panics if the `Display` implementation fails.
+fn write_display<W: Write, T: Display>(w: &mut W, v: T) -> io::Result<()> {
+    let mut s = String::new();
+    fmt::Write::write_fmt(&mut s, format_args!("{}", v))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid header field value"))?;
+    w.write_all(s.as_bytes())
+}
+
+/// Lexical elements of a header field value, [RFC7230 Section 3.2.6]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token<'a> {
+    /// A sequence of `tchar` like `gzip` or `max-age`.
+    Token(&'a str),
+    /// The unescaped content of a quoted-string.
+    Quoted(String),
+    /// The unescaped content of a comment. Nested comments are
+    /// included with their parentheses.
+    Comment(String),
+    /// Any other visible character like `,`, `;` or `=`.
+    Delimiter(char),
+}
+
+/// Splits a header field value into tokens.
+///
+/// Optional whitespace between tokens is skipped. An unterminated
+/// quoted-string or comment is returned as an error.
+#[derive(Clone, Debug)]
+pub struct Tokenizer<'a> {
+    s: &'a str,
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Constructs a new tokenizer for a header field value.
+    pub fn new(s: &'a str) -> Tokenizer<'a> {
+        Tokenizer { s, position: 0 }
+    }
+
+    /// Returns the unconsumed rest of the value.
+    pub fn rest(&self) -> &'a str {
+        &self.s[self.position..]
+    }
+
+    /// Returns the next token without consuming it.
+    pub fn peek(&self) -> Option<Result<Token<'a>, ()>> {
+        self.clone().next()
+    }
+
+    /// Consumes the next token if it is the given delimiter.
+    pub fn eat(&mut self, delimiter: char) -> bool {
+        if let Some(Ok(Token::Delimiter(c))) = self.peek() {
+            if c == delimiter {
+                self.next();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Consumes the raw value up to the given character.
+    ///
+    /// The character itself is consumed but not returned. This is used
+    /// for values like URI references in angle brackets.
+    pub fn take_until(&mut self, c: char) -> Result<&'a str, ()> {
+        let rest = self.rest();
+        let end = rest.find(c).ok_or(())?;
+        self.position += end + c.len_utf8();
+        Ok(&rest[..end])
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    fn delimited(&mut self, open: u8, close: u8) -> Result<String, ()> {
+        let bytes = self.s.as_bytes();
+        let mut value = Vec::new();
+        let mut depth = 0;
+        let mut i = self.position + 1;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' if i + 1 < bytes.len() => {
+                    i += 1;
+                    value.push(bytes[i]);
+                }
+                b'\\' => return Err(()),
+                c if c == close && depth == 0 => {
+                    self.position = i + 1;
+                    return String::from_utf8(value).map_err(|_| ());
+                }
+                c if c == close => {
+                    depth -= 1;
+                    value.push(c);
+                }
+                c if c == open && open != close => {
+                    depth += 1;
+                    value.push(c);
+                }
+                c => value.push(c),
+            }
+            i += 1;
+        }
+        Err(())
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, ()>;
+
+    fn next(&mut self) -> Option<Result<Token<'a>, ()>> {
+        self.skip_whitespace();
+        let c = self.rest().chars().next()?;
+        Some(match c {
+            '"' => self.delimited(b'"', b'"').map(Token::Quoted),
+            '(' => self.delimited(b'(', b')').map(Token::Comment),
+            c if is_tchar(c) => {
+                let rest = self.rest();
+                let len = rest.find(|c| !is_tchar(c)).unwrap_or(rest.len());
+                self.position += len;
+                Ok(Token::Token(&rest[..len]))
+            }
+            c => {
+                self.position += c.len_utf8();
+                Ok(Token::Delimiter(c))
+            }
+        })
+    }
 }
 
-struct IterListHeader<'a> {
+/// Splits the lines of a list header field into its elements,
+/// [RFC7230 Section 7]
+///
+/// Commas inside of quoted-strings and comments do not separate
+/// elements. Empty elements are skipped and whitespace around
+/// elements is removed. An unterminated quoted-string or comment
+/// and a line that is not UTF-8 end the iteration with an error.
+#[derive(Clone, Debug)]
+pub struct ListElements<'a> {
     values: &'a [Vec<u8>],
     line: usize,
-    column: usize,
+    tokens: Option<Tokenizer<'a>>,
 }
 
-impl<'a> IterListHeader<'a> {
-    fn new(values: &[Vec<u8>]) -> IterListHeader {
-        IterListHeader {
-            values: values,
+impl<'a> ListElements<'a> {
+    /// Constructs an iterator over the elements of all lines.
+    pub fn new(values: &'a [Vec<u8>]) -> ListElements<'a> {
+        ListElements {
+            values,
             line: 0,
-            column: 0,
+            tokens: None,
         }
     }
+
+    fn fail(&mut self) -> Option<Result<&'a [u8], ()>> {
+        self.line = self.values.len();
+        self.tokens = None;
+        Some(Err(()))
+    }
 }
 
-impl<'a> Iterator for IterListHeader<'a> {
-    type Item = &'a [u8];
-    fn next(&mut self) -> Option<&'a [u8]> {
-        for line in self.line..self.values.len() {
-            let value = &self.values[line];
-            let mut maybe_start_column = None;
-            let mut end_column = 0;
-            for (column, _) in value.iter().enumerate().skip(self.column) {
-                let byte = value[column];
-                if byte != b' ' && byte != b'\t' && byte != b',' {
-                    end_column = column + 1;
-                    if maybe_start_column.is_none() {
-                        maybe_start_column = Some(column)
+impl<'a> Iterator for ListElements<'a> {
+    type Item = Result<&'a [u8], ()>;
+
+    fn next(&mut self) -> Option<Result<&'a [u8], ()>> {
+        loop {
+            let mut tokens = match self.tokens.take() {
+                Some(tokens) => tokens,
+                None => {
+                    let value = self.values.get(self.line)?;
+                    self.line += 1;
+                    match str::from_utf8(value) {
+                        Ok(value) => Tokenizer::new(value),
+                        Err(_) => return self.fail(),
                     }
-                } else if byte == b',' {
-                    if let Some(start_column) = maybe_start_column {
-                        self.column = column + 1;
-                        return Some(&value[start_column..end_column]);
+                }
+            };
+            let start = tokens.rest();
+            let element = loop {
+                match tokens.next() {
+                    None => break start,
+                    Some(Err(())) => return self.fail(),
+                    Some(Ok(Token::Delimiter(','))) => {
+                        let end = start.len() - tokens.rest().len() - 1;
+                        self.tokens = Some(tokens);
+                        break &start[..end];
                     }
-                    maybe_start_column = None;
+                    Some(Ok(_)) => (),
                 }
+            };
+            let element = trim(element.as_bytes());
+            if !element.is_empty() {
+                return Some(Ok(element));
             }
-            self.line = line + 1;
-            self.column = 0;
-            if let Some(start_column) = maybe_start_column {
-                return Some(&value[start_column..end_column]);
+        }
+    }
+}
+
+fn trim(mut s: &[u8]) -> &[u8] {
+    while let Some((&c, rest)) = s.split_first() {
+        if c != b' ' && c != b'\t' {
+            break;
+        }
+        s = rest;
+    }
+    while let Some((&c, rest)) = s.split_last() {
+        if c != b' ' && c != b'\t' {
+            break;
+        }
+        s = rest;
+    }
+    s
+}
+
+/// Quotes a value as a quoted-string if it is not a valid token.
+///
+/// Double quotes and backslashes are escaped with a backslash.
+/// Control characters other than tab can not be represented in a
+/// quoted-string, they are percent-encoded so values can not inject
+/// other header fields.
+pub fn quote_if_needed(s: &str) -> Cow<'_, str> {
+    if is_token(s) {
+        return Cow::Borrowed(s);
+    }
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => quoted.push('\\'),
+            '\t' => (),
+            c if c.is_ascii_control() => {
+                quoted.push_str(&format!("%{:02X}", c as u8));
+                continue;
             }
+            _ => (),
         }
-        None
+        quoted.push(c);
     }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Checks if a value can be sent as a token or quoted-string.
+///
+/// Only control characters other than tab are not allowed.
+pub fn is_quotable(s: &str) -> bool {
+    !s.chars().any(|c| c.is_ascii_control() && c != '\t')
+}
+
+/// Percent-encodes the characters of a name that are not `tchar`.
+///
+/// Names are never quoted, encoding keeps an invalid name from
+/// breaking the syntax of the header field value.
+pub fn encode_token(s: &str) -> Cow<'_, str> {
+    if is_token(s) {
+        return Cow::Borrowed(s);
+    }
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.bytes() {
+        if c != b'%' && is_tchar(c as char) {
+            encoded.push(c as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", c));
+        }
+    }
+    Cow::Owned(encoded)
+}
+
+/// Parses a single parameter `name=value`.
+///
+/// The value may be a token or a quoted-string. Names ending with an
+/// asterisk are decoded as extended values. [RFC8187 Section 3.2]
+pub fn parse_parameter(tokens: &mut Tokenizer) -> Result<Parameter, ()> {
+    let (name, value) = parse_optional_parameter(tokens)?;
+    let value = value.ok_or(())?;
+    if name.len() > 1 && name.ends_with('*') {
+        let (value, language) = decode_ext_value(&value)?;
+        Ok(Parameter::extended(&name[..name.len() - 1], value, language))
+    } else {
+        Ok(Parameter::new(name, value))
+    }
+}
+
+/// Parses a parameter `name [ "=" value ]` where the value may be
+/// missing, like in `Prefer`.
+///
+/// The value may be a token or a quoted-string. The name is returned
+/// unchanged and extended values are not decoded.
+pub fn parse_optional_parameter(tokens: &mut Tokenizer) -> Result<(String, Option<String>), ()> {
+    let name = match tokens.next() {
+        Some(Ok(Token::Token(name))) => name.to_owned(),
+        _ => return Err(()),
+    };
+    if !tokens.eat('=') {
+        return Ok((name, None));
+    }
+    let value = match tokens.next() {
+        Some(Ok(Token::Token(value))) => value.to_owned(),
+        Some(Ok(Token::Quoted(value))) => value,
+        _ => return Err(()),
+    };
+    Ok((name, Some(value)))
+}
+
+/// Parses parameters `*( OWS ";" OWS [ parameter ] )` until the end
+/// of the value or a comma.
+///
+/// Empty parameters are skipped. A parameter occuring twice is
+/// an error. A regular and an extended parameter with the same
+/// name may both be present.
+pub fn parse_parameters(tokens: &mut Tokenizer) -> Result<Vec<Parameter>, ()> {
+    let mut parameters: Vec<Parameter> = Vec::new();
+    while tokens.peek().is_some() {
+        if let Some(Ok(Token::Delimiter(','))) = tokens.peek() {
+            break;
+        }
+        if !tokens.eat(';') {
+            return Err(());
+        }
+        match tokens.peek() {
+            None | Some(Ok(Token::Delimiter(';'))) | Some(Ok(Token::Delimiter(','))) => continue,
+            _ => (),
+        }
+        let parameter = parse_parameter(tokens)?;
+        if parameters.iter()
+            .any(|x| x.name == parameter.name && x.extended == parameter.extended) {
+            return Err(());
+        }
+        parameters.push(parameter);
+    }
+    Ok(parameters)
+}
+
+/// Finds a parameter by its name.
+///
+/// If both a regular and an extended parameter are present the
+/// extended one is preferred.
+pub fn find_parameter<'a>(parameters: &'a [Parameter], name: &str) -> Option<&'a Parameter> {
+    parameters.iter()
+        .filter(|x| x.name.eq_ignore_ascii_case(name))
+        .max_by_key(|x| x.extended)
+}
+
+/// Formats parameters each preceded by a semicolon.
+pub fn fmt_parameters<W: fmt::Write>(w: &mut W, parameters: &[Parameter]) -> fmt::Result {
+    for parameter in parameters {
+        write!(w, "; {}", parameter)?;
+    }
+    Ok(())
 }
 
 pub fn parse_list0<T: FromStr>(s: &[Vec<u8>]) -> Result<Vec<T>, ()> {
-    let iter = IterListHeader::new(s);
-    let items: Option<Vec<T>> = iter.map(|x| {
-            str::from_utf8(x)
-                .ok()
-                .and_then(|x| x.parse().ok())
+    ListElements::new(s)
+        .map(|x| {
+            x.and_then(|x| {
+                str::from_utf8(x)
+                    .ok()
+                    .and_then(|x| x.parse().ok())
+                    .ok_or(())
+            })
         })
-        .collect();
-    items.ok_or(())
+        .collect()
 }
 
 pub fn parse_list1<T: FromStr>(s: &[Vec<u8>]) -> Result<Vec<T>, ()> {
@@ -99,25 +409,54 @@ pub fn serialize_list<I, W, T>(mut iter: I, values: &[T]) -> Result<(), io::Erro
         if i != 0 {
             w.write_all(b", ")?;
         }
-        write!(w, "{}", v)?;
+        write_display(&mut w, v)?;
     }
     Ok(())
 }
 
-pub fn parse_star(s: &[Vec<u8>]) -> Result<(), ()> {
-    if s.len() != 1 {
+/// Parses a list of words, each a token or a quoted-string.
+///
+/// Quoted-strings are unquoted before the value is parsed.
+pub fn parse_word_list1<T: FromStr>(s: &[Vec<u8>]) -> Result<Vec<T>, ()> {
+    let mut list = Vec::new();
+    for element in ListElements::new(s) {
+        let element = str::from_utf8(element?).map_err(|_| ())?;
+        let mut tokens = Tokenizer::new(element);
+        let word = match tokens.next() {
+            Some(Ok(Token::Token(word))) => word.to_owned(),
+            Some(Ok(Token::Quoted(word))) => word,
+            _ => return Err(()),
+        };
+        if tokens.next().is_some() {
+            return Err(());
+        }
+        list.push(word.parse().map_err(|_| ())?);
+    }
+    if list.is_empty() {
         return Err(());
     }
-    let mut star = false;
-    for x in &s[0] {
-        if *x == b' ' || *x == b'\t' {
-            continue;
-        } else if *x == b'*' {
-            if star {
-                return Err(());
-            }
-            star = true;
+    Ok(list)
+}
+
+/// Serializes a list of words, values that are not tokens are quoted.
+pub fn serialize_word_list<I, W, T>(mut iter: I, values: &[T]) -> Result<(), io::Error>
+    where I: Iterator<Item = W>,
+          W: Write,
+          T: Display
+{
+    let mut w = iter.next().unwrap();
+    for (i, v) in values.iter().enumerate() {
+        if i != 0 {
+            w.write_all(b", ")?;
         }
+        w.write_all(quote_if_needed(&v.to_string()).as_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn parse_star(s: &[Vec<u8>]) -> Result<(), ()> {
+    if s.len() != 1 || trim(&s[0]) != b"*" {
+        return Err(());
     }
     Ok(())
 }
@@ -136,3 +475,82 @@ pub fn serialize_list_star<I, W, T>(mut iter: I, values: &[T]) -> Result<(), io:
     }
     serialize_list(iter, values)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ListElements, Token, Tokenizer, encode_token, is_quotable,
+                parse_optional_parameter, parse_parameter, parse_word_list1, quote_if_needed,
+                serialize_word_list};
+
+    fn elements(values: &[&str]) -> Result<Vec<String>, ()> {
+        let values: Vec<Vec<u8>> = values.iter().map(|x| x.as_bytes().to_vec()).collect();
+        ListElements::new(&values)
+            .map(|x| x.map(|x| String::from_utf8(x.to_vec()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let tokens: Result<Vec<Token>, ()> = Tokenizer::new(r#"a=1; b="x\"y" (c (d))"#).collect();
+        assert_eq!(tokens.unwrap(),
+                   vec![Token::Token("a"),
+                        Token::Delimiter('='),
+                        Token::Token("1"),
+                        Token::Delimiter(';'),
+                        Token::Token("b"),
+                        Token::Delimiter('='),
+                        Token::Quoted("x\"y".to_owned()),
+                        Token::Comment("c (d)".to_owned())]);
+        assert!(Tokenizer::new("\"open").any(|x| x.is_err()));
+    }
+
+    #[test]
+    fn test_list_elements() {
+        assert_eq!(elements(&["a, b", " ,c ,", "\"x, y\", (p, q) z"]).unwrap(),
+                   vec!["a", "b", "c", "\"x, y\"", "(p, q) z"]);
+        assert_eq!(elements(&["a=\"\\\",\", b"]).unwrap(), vec!["a=\"\\\",\"", "b"]);
+        assert_eq!(elements(&["a, \"b"]), Err(()));
+        assert_eq!(elements(&["a, (b"]), Err(()));
+        assert_eq!(elements(&[",", ""]).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_quote_if_needed() {
+        assert_eq!(quote_if_needed("gzip"), "gzip");
+        assert_eq!(quote_if_needed("a b\t\"c\\"), "\"a b\t\\\"c\\\\\"");
+        assert_eq!(quote_if_needed("a\r\nSet-Cookie: x"), "\"a%0D%0ASet-Cookie: x\"");
+        assert_eq!(quote_if_needed("a\0\x7f"), "\"a%00%7F\"");
+        assert!(is_quotable("a b\t\u{e4}"));
+        assert!(!is_quotable("a\x7f"));
+        assert_eq!(encode_token("x-Name"), "x-Name");
+        assert_eq!(encode_token("a b%\r"), "a%20b%25%0D");
+    }
+
+    #[test]
+    fn test_word_list() {
+        let values = vec![b"a, \"b, c\"".to_vec(), b"\"\"".to_vec()];
+        let words: Vec<String> = parse_word_list1(&values).unwrap();
+        assert_eq!(words, vec!["a", "b, c", ""]);
+        let mut output = Vec::new();
+        serialize_word_list(Some(&mut output).into_iter(), &words).unwrap();
+        assert_eq!(output, b"a, \"b, c\", \"\"");
+        assert!(parse_word_list1::<String>(&[b"a b".to_vec()]).is_err());
+        assert!(parse_word_list1::<String>(&[b"a=1".to_vec()]).is_err());
+        assert!(parse_word_list1::<String>(&[b",".to_vec()]).is_err());
+    }
+
+    #[test]
+    fn test_optional_parameter() {
+        let mut tokens = Tokenizer::new("A=1; b; c=\"x y\"; d=");
+        assert_eq!(parse_optional_parameter(&mut tokens), Ok(("A".to_owned(), Some("1".to_owned()))));
+        assert!(tokens.eat(';'));
+        assert_eq!(parse_optional_parameter(&mut tokens), Ok(("b".to_owned(), None)));
+        assert!(tokens.eat(';'));
+        assert_eq!(parse_optional_parameter(&mut tokens),
+                   Ok(("c".to_owned(), Some("x y".to_owned()))));
+        assert!(tokens.eat(';'));
+        assert!(parse_optional_parameter(&mut tokens).is_err());
+        assert!(parse_parameter(&mut Tokenizer::new("b")).is_err());
+        assert!(parse_optional_parameter(&mut Tokenizer::new("=1")).is_err());
+    }
+}
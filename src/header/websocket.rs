@@ -0,0 +1,197 @@
+use header::{RequestHeader, ResponseHeader, parse_list1, serialize_list, serialize_value};
+use header::item::WebSocketExtension;
+use util::{self, BASE64, decode_base64, encode_base64, is_token};
+
+// Appended to the key before hashing. [RFC6455 Section 1.3]
+const GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+fn parse_base64<T: Default + AsMut<[u8]>>(s: &[Vec<u8>]) -> Result<T, ()> {
+    if s.len() != 1 {
+        return Err(());
+    }
+    let encoded = s[0].trim_ascii();
+    let decoded = decode_base64(encoded, BASE64).ok_or(())?;
+    let mut value = T::default();
+    // Only the canonical padded encoding round-trips, as the accept
+    // value is computed from the encoded key.
+    if decoded.len() != value.as_mut().len() ||
+       encode_base64(&decoded, BASE64, true).as_bytes() != encoded {
+        return Err(());
+    }
+    value.as_mut().copy_from_slice(&decoded);
+    Ok(value)
+}
+
+header!{
+    /// `Sec-WebSocket-Key` header, [RFC6455 Section 11.3.1]
+    ///
+    /// A random nonce of 16 bytes sent by the client.
+    pub struct SecWebSocketKey([u8; 16]);
+    (RequestHeader);
+    NAME = "Sec-WebSocket-Key";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_base64::<[u8; 16]>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, encode_base64(&self.0, BASE64, true))
+    }
+}
+
+impl SecWebSocketKey {
+    /// Generates a new random key.
+    pub fn random() -> SecWebSocketKey {
+        let mut key = [0; 16];
+        util::random_bytes(&mut key);
+        SecWebSocketKey(key)
+    }
+
+    /// Computes the `Sec-WebSocket-Accept` value for the key.
+    pub fn accept(&self) -> SecWebSocketAccept {
+        let mut input = encode_base64(&self.0, BASE64, true).into_bytes();
+        input.extend_from_slice(GUID);
+        SecWebSocketAccept(util::sha1(&input))
+    }
+}
+
+header!{
+    /// `Sec-WebSocket-Accept` header, [RFC6455 Section 11.3.3]
+    ///
+    /// The SHA-1 hash of the key sent by the client, proving that
+    /// the server understood the WebSocket handshake.
+    pub struct SecWebSocketAccept([u8; 20]);
+    (ResponseHeader);
+    NAME = "Sec-WebSocket-Accept";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_base64::<[u8; 20]>(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_value(iter, encode_base64(&self.0, BASE64, true))
+    }
+}
+
+impl SecWebSocketAccept {
+    /// Checks if the value was computed from the key.
+    pub fn matches(&self, key: &SecWebSocketKey) -> bool {
+        key.accept().0 == self.0
+    }
+}
+
+header!{
+    /// `Sec-WebSocket-Version` header, [RFC6455 Section 11.3.5]
+    ///
+    /// Clients send the version they use, currently `13`. Servers
+    /// list the versions they support when rejecting a handshake.
+    pub struct SecWebSocketVersion(Vec<u8>);
+    (RequestHeader ResponseHeader);
+    NAME = "Sec-WebSocket-Version";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Sec-WebSocket-Protocol` header, [RFC6455 Section 11.3.4]
+    ///
+    /// Clients list the subprotocols they support in order of
+    /// preference, servers respond with the one selected.
+    pub struct SecWebSocketProtocol(Vec<String>);
+    (RequestHeader ResponseHeader);
+    NAME = "Sec-WebSocket-Protocol";
+    SENSITIVE = false;
+    parse(s, _base) {
+        let protocols: Vec<String> = parse_list1(s)?;
+        if !protocols.iter().all(|x| is_token(x)) {
+            return Err(());
+        }
+        Ok(protocols.into())
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+header!{
+    /// `Sec-WebSocket-Extensions` header, [RFC6455 Section 11.3.2]
+    ///
+    /// Clients offer extensions, possibly the same extension with
+    /// different parameters, servers respond with those accepted.
+    pub struct SecWebSocketExtensions(Vec<WebSocketExtension>);
+    (RequestHeader ResponseHeader);
+    NAME = "Sec-WebSocket-Extensions";
+    SENSITIVE = false;
+    parse(s, _base) {
+        parse_list1(s).map(Into::into)
+    }
+    serialize(self, iter) {
+        serialize_list(iter, &self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use header::{Header, Headers};
+    use header::item::WebSocketExtension;
+    use super::{SecWebSocketAccept, SecWebSocketExtensions, SecWebSocketKey};
+
+    fn parse<H: Header>(s: &str) -> Result<H, ()> {
+        H::parse(&[s.as_bytes().to_vec()], Url::parse("http://example.com/").unwrap())
+    }
+
+    #[test]
+    fn test_accept() {
+        // [RFC6455 Section 1.3]
+        let key: SecWebSocketKey = parse("dGhlIHNhbXBsZSBub25jZQ==").unwrap();
+        let accept: SecWebSocketAccept = parse("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=").unwrap();
+        assert!(accept.matches(&key));
+        assert_eq!(<[u8; 20]>::from(key.accept()), <[u8; 20]>::from(accept));
+    }
+
+    #[test]
+    fn test_non_canonical_key() {
+        assert!(parse::<SecWebSocketKey>("dGhlIHNhbXBsZSBub25jZR==").is_err());
+        assert!(parse::<SecWebSocketKey>("dGhlIHNhbXBsZSBub25jZQ").is_err());
+        assert!(parse::<SecWebSocketKey>("dGhlIHNhbXBsZQ==").is_err());
+        assert!(parse::<SecWebSocketKey>(" dGhlIHNhbXBsZSBub25jZQ== ").is_ok());
+    }
+
+    #[test]
+    fn test_random_key() {
+        assert_ne!(<[u8; 16]>::from(SecWebSocketKey::random()),
+                   <[u8; 16]>::from(SecWebSocketKey::random()));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let extensions: Vec<WebSocketExtension> =
+            parse::<SecWebSocketExtensions>("permessage-deflate; client_max_window_bits=\"10\", x")
+                .unwrap()
+                .into();
+        assert_eq!(extensions[0].parameter("client_max_window_bits"), Some(Some("10")));
+        assert!(parse::<SecWebSocketExtensions>("x; a=\"b c\"").is_err());
+
+        let extension = WebSocketExtension::new("permessage-deflate").unwrap()
+            .with_parameter("server_no_context_takeover", None).unwrap()
+            .with_parameter("server_max_window_bits", Some("10".to_owned())).unwrap();
+        assert_eq!(extension.to_string(),
+                   "permessage-deflate; server_no_context_takeover; server_max_window_bits=10");
+        assert!(WebSocketExtension::new("a b").is_err());
+        assert!(extension.clone().with_parameter("a;b", None).is_err());
+        assert!(extension.clone().with_parameter("a", Some("1\n".to_owned())).is_err());
+
+        // Extensions built directly are encoded instead of failing.
+        let mut extension = extension;
+        extension.parameters.push(("a".to_owned(), Some("1\r\nX: y".to_owned())));
+        let mut headers = Headers::new();
+        headers.set(&SecWebSocketExtensions::from(vec![extension])).unwrap();
+        let raw = headers.get_raw("Sec-WebSocket-Extensions").unwrap();
+        assert!(raw[0].ends_with(b"; a=1%0D%0AX%3A%20y"));
+    }
+}
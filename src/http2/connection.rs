@@ -0,0 +1,804 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use super::{Error, ErrorCode, Frame, Role, Settings};
+use super::frame::DEFAULT_MAX_FRAME_SIZE;
+
+// The initial flow-control window of the connection and of streams.
+// [RFC7540 Section 6.9.2]
+const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+const MAX_WINDOW_SIZE: i64 = 0x7FFF_FFFF;
+const MAX_STREAM_ID: u32 = 0x7FFF_FFFF;
+
+// The number of locally reset streams remembered to ignore frames
+// the peer sent before it received the reset.
+const MAX_RESET_STREAMS: usize = 64;
+
+/// The state of a stream, [RFC7540 Section 5.1]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StreamState {
+    /// The stream was not used yet.
+    Idle,
+    /// This endpoint promised the stream with `PUSH_PROMISE`.
+    ReservedLocal,
+    /// The peer promised the stream with `PUSH_PROMISE`.
+    ReservedRemote,
+    /// Both endpoints may send frames.
+    Open,
+    /// This endpoint ended the stream, the peer may still send.
+    HalfClosedLocal,
+    /// The peer ended the stream, this endpoint may still send.
+    HalfClosedRemote,
+    /// Both endpoints ended the stream or it was reset.
+    Closed,
+}
+
+/// The settings of an endpoint in effect, [RFC7540 Section 6.5.2]
+///
+/// Starts with the initial values and changes with each
+/// acknowledged `SETTINGS` frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Parameters {
+    /// The maximum size of the HPACK dynamic table.
+    pub header_table_size: u32,
+    /// If server push is allowed.
+    pub enable_push: bool,
+    /// The number of streams the peer may open, `None` if unlimited.
+    pub max_concurrent_streams: Option<u32>,
+    /// The initial flow-control window of streams.
+    pub initial_window_size: u32,
+    /// The largest frame payload accepted.
+    pub max_frame_size: u32,
+    /// The advisory limit of the header list size, `None` if unlimited.
+    pub max_header_list_size: Option<u32>,
+}
+
+impl Parameters {
+    fn apply(&mut self, settings: &Settings) {
+        if let Some(value) = settings.header_table_size {
+            self.header_table_size = value;
+        }
+        if let Some(value) = settings.enable_push {
+            self.enable_push = value;
+        }
+        if let Some(value) = settings.max_concurrent_streams {
+            self.max_concurrent_streams = Some(value);
+        }
+        if let Some(value) = settings.initial_window_size {
+            self.initial_window_size = value;
+        }
+        if let Some(value) = settings.max_frame_size {
+            self.max_frame_size = value;
+        }
+        if let Some(value) = settings.max_header_list_size {
+            self.max_header_list_size = Some(value);
+        }
+    }
+}
+
+impl Default for Parameters {
+    fn default() -> Parameters {
+        Parameters {
+            header_table_size: 4096,
+            enable_push: true,
+            max_concurrent_streams: None,
+            initial_window_size: DEFAULT_WINDOW_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_header_list_size: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Stream {
+    state: StreamState,
+    // Windows may become negative when the initial window size
+    // is reduced. [RFC7540 Section 6.9.2]
+    send_window: i64,
+    recv_window: i64,
+}
+
+/// The stream states and flow-control windows of a connection.
+///
+/// All frames received are passed to [`recv_frame`](#method.recv_frame)
+/// and all frames sent to [`send_frame`](#method.send_frame), in order.
+/// Header blocks must be complete, as returned by a
+/// [`FrameReader`](struct.FrameReader.html). Frames the connection
+/// must send, like acknowledgements of `SETTINGS` and `PING`, are
+/// taken with [`take_frames`](#method.take_frames).
+///
+/// Protocol violations by the peer are returned as errors with the
+/// code to send in `RST_STREAM` or `GOAWAY`. Frames this endpoint is
+/// not allowed to send are rejected without changing the state.
+///
+/// Received data is counted against the windows of this endpoint,
+/// the application sends `WINDOW_UPDATE` frames as it consumes data.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    role: Role,
+    local: Parameters,
+    remote: Parameters,
+    // Sent settings waiting for acknowledgement.
+    pending: VecDeque<Settings>,
+    streams: HashMap<u32, Stream>,
+    reset: VecDeque<u32>,
+    last_local_id: u32,
+    last_remote_id: u32,
+    send_window: i64,
+    recv_window: i64,
+    settings_received: bool,
+    goaway_received: Option<u32>,
+    goaway_sent: Option<u32>,
+    output: Vec<Frame>,
+}
+
+impl Connection {
+    /// Constructs a connection and queues the initial `SETTINGS`
+    /// frame with the settings of this endpoint.
+    pub fn new(role: Role, settings: Settings) -> Connection {
+        let mut connection = Connection {
+            role,
+            local: Parameters::default(),
+            remote: Parameters::default(),
+            pending: VecDeque::new(),
+            streams: HashMap::new(),
+            reset: VecDeque::new(),
+            last_local_id: 0,
+            last_remote_id: 0,
+            send_window: DEFAULT_WINDOW_SIZE as i64,
+            recv_window: DEFAULT_WINDOW_SIZE as i64,
+            settings_received: false,
+            goaway_received: None,
+            goaway_sent: None,
+            output: Vec::new(),
+        };
+        let frame = Frame::Settings { ack: false, settings };
+        connection.send_frame(&frame).expect("initial settings are valid");
+        connection.output.push(frame);
+        connection
+    }
+
+    /// Constructs a connection upgraded from HTTP/1.1.
+    ///
+    /// The `request` settings are those of the `HTTP2-Settings` header,
+    /// they are in effect without acknowledgement. The upgraded request
+    /// is on stream 1, which the client already ended.
+    pub fn upgraded(role: Role, settings: Settings, request: &Settings) -> Connection {
+        let mut connection = Connection::new(role, settings);
+        let state = match role {
+            Role::Client => {
+                connection.local.apply(request);
+                connection.last_local_id = 1;
+                StreamState::HalfClosedLocal
+            }
+            Role::Server => {
+                connection.remote.apply(request);
+                connection.last_remote_id = 1;
+                StreamState::HalfClosedRemote
+            }
+        };
+        connection.insert(1, state);
+        connection
+    }
+
+    /// Returns the settings of this endpoint acknowledged by the peer.
+    pub fn local_settings(&self) -> &Parameters {
+        &self.local
+    }
+
+    /// Returns the settings received from the peer.
+    pub fn remote_settings(&self) -> &Parameters {
+        &self.remote
+    }
+
+    /// Returns the state of a stream.
+    pub fn stream_state(&self, stream_id: u32) -> StreamState {
+        match self.streams.get(&stream_id) {
+            Some(stream) => stream.state,
+            None if stream_id <= self.last_id(stream_id) => StreamState::Closed,
+            None => StreamState::Idle,
+        }
+    }
+
+    /// Returns the identifier for the next stream opened by this
+    /// endpoint, or `None` if the identifiers are exhausted.
+    pub fn next_stream_id(&self) -> Option<u32> {
+        let next = match (self.last_local_id, self.role) {
+            (0, Role::Client) => 1,
+            (0, Role::Server) => 2,
+            (id, _) => id + 2,
+        };
+        if next > MAX_STREAM_ID { None } else { Some(next) }
+    }
+
+    /// Checks if this endpoint may open another stream.
+    ///
+    /// Streams are limited by the peer's `MAX_CONCURRENT_STREAMS`
+    /// and no new streams are opened after a `GOAWAY`.
+    pub fn can_open_stream(&self) -> bool {
+        self.goaway_received.is_none() && self.goaway_sent.is_none() &&
+        self.next_stream_id().is_some() &&
+        self.remote.max_concurrent_streams.is_none_or(|max| self.active_streams(true) < max as usize)
+    }
+
+    /// Returns the number of bytes of data that may be sent on the
+    /// stream, limited by the stream and connection windows.
+    pub fn send_capacity(&self, stream_id: u32) -> u32 {
+        match self.streams.get(&stream_id) {
+            Some(stream) => stream.send_window.min(self.send_window).max(0) as u32,
+            None => 0,
+        }
+    }
+
+    /// Returns the remaining receive window of the stream, or of the
+    /// connection for stream `0`.
+    pub fn recv_window(&self, stream_id: u32) -> i64 {
+        match stream_id {
+            0 => self.recv_window,
+            id => self.streams.get(&id).map_or(0, |x| x.recv_window),
+        }
+    }
+
+    /// Takes the frames the connection must send.
+    pub fn take_frames(&mut self) -> Vec<Frame> {
+        mem::take(&mut self.output)
+    }
+
+    /// Updates the state with a received frame.
+    ///
+    /// The frame is ignored after a stream error, after a connection
+    /// error the connection must be closed.
+    pub fn recv_frame(&mut self, frame: &Frame) -> Result<(), Error> {
+        let protocol_error = Error::Connection(ErrorCode::PROTOCOL_ERROR);
+        // The first frame must be the peer's settings. [RFC7540 Section 3.5]
+        if !self.settings_received {
+            match *frame {
+                Frame::Settings { ack: false, .. } => self.settings_received = true,
+                _ => return Err(protocol_error),
+            }
+        }
+        if let Frame::Data { .. } = *frame {
+            // Data on any stream counts against the connection window.
+            let len = frame.flow_controlled_len() as i64;
+            if len > self.recv_window {
+                return Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR));
+            }
+            self.recv_window -= len;
+        }
+        let stream_id = frame.stream_id();
+        if stream_id != 0 && self.reset.contains(&stream_id) {
+            return Ok(());
+        }
+        match *frame {
+            Frame::Data { end_stream, .. } => {
+                let len = frame.flow_controlled_len() as i64;
+                let stream = self.recv_stream(stream_id)?;
+                if !matches!(stream.state, StreamState::Open | StreamState::HalfClosedLocal) {
+                    return Err(Error::Stream(stream_id, ErrorCode::STREAM_CLOSED));
+                }
+                if len > stream.recv_window {
+                    return Err(Error::Stream(stream_id, ErrorCode::FLOW_CONTROL_ERROR));
+                }
+                stream.recv_window -= len;
+                if end_stream {
+                    stream.state = close_remote(stream.state);
+                }
+                self.prune(stream_id);
+            }
+            Frame::Headers { end_stream, priority, .. } => {
+                if self.stream_state(stream_id) == StreamState::Idle {
+                    self.open_remote(stream_id)?;
+                }
+                let stream = self.recv_stream(stream_id)?;
+                stream.state = match stream.state {
+                    StreamState::ReservedRemote => StreamState::HalfClosedLocal,
+                    StreamState::Open | StreamState::HalfClosedLocal => stream.state,
+                    _ => return Err(Error::Stream(stream_id, ErrorCode::STREAM_CLOSED)),
+                };
+                if end_stream {
+                    stream.state = close_remote(stream.state);
+                }
+                self.prune(stream_id);
+                // A stream cannot depend on itself. [RFC7540 Section 5.3.1]
+                if priority.is_some_and(|x| x.dependency == stream_id) {
+                    return Err(Error::Stream(stream_id, ErrorCode::PROTOCOL_ERROR));
+                }
+            }
+            Frame::Priority { .. } => (),
+            Frame::RstStream { .. } => {
+                // The peer may reset a stream that just closed normally.
+                if self.stream_state(stream_id) == StreamState::Closed {
+                    return Ok(());
+                }
+                self.recv_stream(stream_id)?.state = StreamState::Closed;
+                self.prune(stream_id);
+            }
+            Frame::Settings { ack: false, ref settings } => {
+                if let Some(size) = settings.initial_window_size {
+                    let delta = size as i64 - self.remote.initial_window_size as i64;
+                    for stream in self.streams.values_mut() {
+                        stream.send_window += delta;
+                        if stream.send_window > MAX_WINDOW_SIZE {
+                            return Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR));
+                        }
+                    }
+                }
+                self.remote.apply(settings);
+                self.output.push(Frame::Settings {
+                    ack: true,
+                    settings: Settings::default(),
+                });
+            }
+            Frame::Settings { ack: true, .. } => {
+                let settings = self.pending.pop_front().ok_or(protocol_error)?;
+                if let Some(size) = settings.initial_window_size {
+                    let delta = size as i64 - self.local.initial_window_size as i64;
+                    for stream in self.streams.values_mut() {
+                        stream.recv_window += delta;
+                    }
+                }
+                self.local.apply(&settings);
+            }
+            Frame::PushPromise { promised_stream_id, .. } => {
+                if self.role == Role::Server || !self.local.enable_push {
+                    return Err(protocol_error);
+                }
+                if !matches!(self.stream_state(stream_id),
+                             StreamState::Open | StreamState::HalfClosedLocal) {
+                    return Err(protocol_error);
+                }
+                if promised_stream_id % 2 != 0 || promised_stream_id <= self.last_remote_id {
+                    return Err(protocol_error);
+                }
+                self.last_remote_id = promised_stream_id;
+                self.insert(promised_stream_id, StreamState::ReservedRemote);
+            }
+            Frame::Ping { ack: false, data } => {
+                self.output.push(Frame::Ping { ack: true, data });
+            }
+            Frame::Ping { ack: true, .. } => (),
+            Frame::GoAway { last_stream_id, .. } => {
+                self.goaway_received = Some(last_stream_id);
+            }
+            Frame::WindowUpdate { increment, .. } => {
+                if stream_id == 0 {
+                    self.send_window += increment as i64;
+                    if self.send_window > MAX_WINDOW_SIZE {
+                        return Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR));
+                    }
+                    return Ok(());
+                }
+                // Updates may arrive shortly after a stream closed.
+                if self.stream_state(stream_id) == StreamState::Closed {
+                    return Ok(());
+                }
+                let stream = self.recv_stream(stream_id)?;
+                stream.send_window += increment as i64;
+                if stream.send_window > MAX_WINDOW_SIZE {
+                    return Err(Error::Stream(stream_id, ErrorCode::FLOW_CONTROL_ERROR));
+                }
+            }
+            // Header blocks must be complete.
+            Frame::Continuation { .. } => return Err(protocol_error),
+            Frame::Unknown { .. } => (),
+        }
+        Ok(())
+    }
+
+    /// Updates the state with a frame this endpoint sends.
+    ///
+    /// `DATA` frames must fit into the [send capacity](#method.send_capacity),
+    /// streams can only be opened if [allowed](#method.can_open_stream).
+    pub fn send_frame(&mut self, frame: &Frame) -> Result<(), Error> {
+        let stream_id = frame.stream_id();
+        let protocol_error = Error::Connection(ErrorCode::PROTOCOL_ERROR);
+        match *frame {
+            Frame::Data { ref data, end_stream, .. } => {
+                if data.len() > self.remote.max_frame_size as usize {
+                    return Err(Error::Stream(stream_id, ErrorCode::FRAME_SIZE_ERROR));
+                }
+                let len = frame.flow_controlled_len() as i64;
+                let state = self.stream_state(stream_id);
+                if !matches!(state, StreamState::Open | StreamState::HalfClosedRemote) {
+                    return Err(Error::Stream(stream_id, ErrorCode::STREAM_CLOSED));
+                }
+                if len > self.send_capacity(stream_id) as i64 {
+                    return Err(Error::Stream(stream_id, ErrorCode::FLOW_CONTROL_ERROR));
+                }
+                self.send_window -= len;
+                let stream = self.streams.get_mut(&stream_id).expect("stream is open");
+                stream.send_window -= len;
+                if end_stream {
+                    stream.state = close_local(stream.state);
+                }
+                self.prune(stream_id);
+            }
+            Frame::Headers { end_stream, .. } => {
+                let state = match self.stream_state(stream_id) {
+                    // Servers open streams with `PUSH_PROMISE`.
+                    StreamState::Idle if self.role == Role::Server => return Err(protocol_error),
+                    StreamState::Idle => {
+                        if !self.can_open_stream() || Some(stream_id) != self.next_stream_id() {
+                            return Err(Error::Stream(stream_id, ErrorCode::REFUSED_STREAM));
+                        }
+                        self.last_local_id = stream_id;
+                        StreamState::Open
+                    }
+                    StreamState::ReservedLocal => StreamState::HalfClosedRemote,
+                    state @ StreamState::Open |
+                    state @ StreamState::HalfClosedRemote => state,
+                    _ => return Err(Error::Stream(stream_id, ErrorCode::STREAM_CLOSED)),
+                };
+                let state = if end_stream { close_local(state) } else { state };
+                if self.streams.contains_key(&stream_id) {
+                    self.streams.get_mut(&stream_id).expect("stream exists").state = state;
+                } else {
+                    self.insert(stream_id, state);
+                }
+                self.prune(stream_id);
+            }
+            Frame::Priority { .. } => (),
+            Frame::RstStream { .. } => {
+                if self.stream_state(stream_id) == StreamState::Idle {
+                    return Err(protocol_error);
+                }
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.state = StreamState::Closed;
+                }
+                self.prune(stream_id);
+                if self.reset.len() == MAX_RESET_STREAMS {
+                    self.reset.pop_front();
+                }
+                self.reset.push_back(stream_id);
+            }
+            Frame::Settings { ack: false, settings } => {
+                self.pending.push_back(settings);
+            }
+            Frame::Settings { ack: true, .. } => (),
+            Frame::PushPromise { promised_stream_id, .. } => {
+                if self.role == Role::Client || !self.remote.enable_push {
+                    return Err(protocol_error);
+                }
+                if !matches!(self.stream_state(stream_id),
+                             StreamState::Open | StreamState::HalfClosedRemote) ||
+                   Some(promised_stream_id) != self.next_stream_id() {
+                    return Err(protocol_error);
+                }
+                self.last_local_id = promised_stream_id;
+                self.insert(promised_stream_id, StreamState::ReservedLocal);
+            }
+            Frame::Ping { .. } => (),
+            Frame::GoAway { last_stream_id, .. } => {
+                self.goaway_sent = Some(last_stream_id);
+            }
+            Frame::WindowUpdate { increment, .. } => {
+                let window = match stream_id {
+                    0 => &mut self.recv_window,
+                    id => match self.streams.get_mut(&id) {
+                        Some(stream) => &mut stream.recv_window,
+                        None => return Ok(()),
+                    },
+                };
+                if *window + increment as i64 > MAX_WINDOW_SIZE {
+                    return Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR));
+                }
+                *window += increment as i64;
+            }
+            Frame::Continuation { .. } | Frame::Unknown { .. } => (),
+        }
+        Ok(())
+    }
+
+    // Returns the highest identifier used by the endpoint that opens
+    // streams with the parity of the identifier.
+    fn last_id(&self, stream_id: u32) -> u32 {
+        let local = (stream_id % 2 == 1) == (self.role == Role::Client);
+        if local { self.last_local_id } else { self.last_remote_id }
+    }
+
+    // Counts the open streams opened by this endpoint or by the peer.
+    fn active_streams(&self, local: bool) -> usize {
+        self.streams
+            .iter()
+            .filter(|&(&id, stream)| {
+                let opened_locally = (id % 2 == 1) == (self.role == Role::Client);
+                opened_locally == local &&
+                matches!(stream.state,
+                         StreamState::Open | StreamState::HalfClosedLocal |
+                         StreamState::HalfClosedRemote)
+            })
+            .count()
+    }
+
+    // Returns a stream that a received frame refers to. Frames on
+    // idle streams are a connection error, frames on closed streams
+    // a stream error.
+    fn recv_stream(&mut self, stream_id: u32) -> Result<&mut Stream, Error> {
+        match self.stream_state(stream_id) {
+            StreamState::Idle => Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+            StreamState::Closed => Err(Error::Stream(stream_id, ErrorCode::STREAM_CLOSED)),
+            _ => Ok(self.streams.get_mut(&stream_id).expect("stream exists")),
+        }
+    }
+
+    // Opens a stream for received `HEADERS`. Only clients open
+    // streams this way, servers reserve them with `PUSH_PROMISE`.
+    fn open_remote(&mut self, stream_id: u32) -> Result<(), Error> {
+        if self.role != Role::Server || stream_id.is_multiple_of(2) {
+            return Err(Error::Connection(ErrorCode::PROTOCOL_ERROR));
+        }
+        // Streams above the last identifier of a sent GOAWAY are ignored.
+        if self.goaway_sent.is_some_and(|last| stream_id > last) {
+            return Err(Error::Stream(stream_id, ErrorCode::REFUSED_STREAM));
+        }
+        self.last_remote_id = stream_id;
+        if self.local.max_concurrent_streams.is_some_and(|max| self.active_streams(false) >= max as usize) {
+            return Err(Error::Stream(stream_id, ErrorCode::REFUSED_STREAM));
+        }
+        self.insert(stream_id, StreamState::Open);
+        Ok(())
+    }
+
+    fn insert(&mut self, stream_id: u32, state: StreamState) {
+        self.streams.insert(stream_id, Stream {
+            state,
+            send_window: self.remote.initial_window_size as i64,
+            recv_window: self.local.initial_window_size as i64,
+        });
+    }
+
+    // Closed streams are forgotten, their state follows from the
+    // last stream identifiers.
+    fn prune(&mut self, stream_id: u32) {
+        if self.streams.get(&stream_id).is_some_and(|x| x.state == StreamState::Closed) {
+            self.streams.remove(&stream_id);
+        }
+    }
+}
+
+fn close_local(state: StreamState) -> StreamState {
+    match state {
+        StreamState::Open => StreamState::HalfClosedLocal,
+        _ => StreamState::Closed,
+    }
+}
+
+fn close_remote(state: StreamState) -> StreamState {
+    match state {
+        StreamState::Open => StreamState::HalfClosedRemote,
+        _ => StreamState::Closed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Error, ErrorCode, Frame, Priority, Role, Settings};
+    use super::{Connection, StreamState};
+
+    // A connection after exchanging the initial settings.
+    fn connection(role: Role) -> Connection {
+        let mut connection = Connection::new(role, Settings::default());
+        connection.recv_frame(&settings(false)).unwrap();
+        connection.recv_frame(&settings(true)).unwrap();
+        connection.take_frames();
+        connection
+    }
+
+    fn settings(ack: bool) -> Frame {
+        Frame::Settings {
+            ack,
+            settings: Settings::default(),
+        }
+    }
+
+    fn headers(stream_id: u32, end_stream: bool) -> Frame {
+        Frame::Headers {
+            stream_id,
+            priority: None,
+            block: Vec::new(),
+            end_stream,
+            end_headers: true,
+            padding: None,
+        }
+    }
+
+    fn data(stream_id: u32, len: usize, end_stream: bool) -> Frame {
+        Frame::Data {
+            stream_id,
+            data: vec![0; len],
+            end_stream,
+            padding: None,
+        }
+    }
+
+    #[test]
+    fn test_settings_first() {
+        let mut connection = Connection::new(Role::Server, Settings::default());
+        assert_eq!(connection.take_frames(), vec![settings(false)]);
+        assert_eq!(connection.recv_frame(&headers(1, true)),
+                   Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+        let mut connection = Connection::new(Role::Server, Settings::default());
+        connection.take_frames();
+        connection.recv_frame(&settings(false)).unwrap();
+        assert_eq!(connection.take_frames(), vec![settings(true)]);
+        connection.recv_frame(&settings(true)).unwrap();
+        // Only sent settings can be acknowledged.
+        assert_eq!(connection.recv_frame(&settings(true)),
+                   Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+
+    #[test]
+    fn test_request_response() {
+        let mut client = connection(Role::Client);
+        let mut server = connection(Role::Server);
+        assert_eq!(client.next_stream_id(), Some(1));
+        let request = headers(1, false);
+        client.send_frame(&request).unwrap();
+        server.recv_frame(&request).unwrap();
+        assert_eq!(client.stream_state(1), StreamState::Open);
+        let body = data(1, 10, true);
+        client.send_frame(&body).unwrap();
+        server.recv_frame(&body).unwrap();
+        assert_eq!(client.stream_state(1), StreamState::HalfClosedLocal);
+        assert_eq!(server.stream_state(1), StreamState::HalfClosedRemote);
+        assert_eq!(server.recv_window(0), 65_525);
+        let response = headers(1, true);
+        server.send_frame(&response).unwrap();
+        client.recv_frame(&response).unwrap();
+        assert_eq!(client.stream_state(1), StreamState::Closed);
+        assert_eq!(server.stream_state(1), StreamState::Closed);
+        assert_eq!(client.next_stream_id(), Some(3));
+        // Frames on the closed stream are stream errors.
+        assert_eq!(server.recv_frame(&data(1, 1, false)),
+                   Err(Error::Stream(1, ErrorCode::STREAM_CLOSED)));
+        // Resets of streams that closed normally are ignored.
+        let reset = Frame::RstStream {
+            stream_id: 1,
+            error_code: ErrorCode::CANCEL,
+        };
+        assert_eq!(server.recv_frame(&reset), Ok(()));
+        assert_eq!(client.recv_frame(&reset), Ok(()));
+        assert_eq!(server.stream_state(1), StreamState::Closed);
+        assert_eq!(client.recv_frame(&Frame::RstStream {
+                       stream_id: 3,
+                       error_code: ErrorCode::CANCEL,
+                   }),
+                   Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+
+    #[test]
+    fn test_headers_on_idle_streams() {
+        let protocol_error = Err(Error::Connection(ErrorCode::PROTOCOL_ERROR));
+        // Servers cannot open streams with HEADERS.
+        let mut client = connection(Role::Client);
+        assert_eq!(client.recv_frame(&headers(2, false)), protocol_error);
+        assert_eq!(client.recv_frame(&headers(1, false)), protocol_error);
+        let mut server = connection(Role::Server);
+        assert_eq!(server.send_frame(&headers(2, false)), protocol_error);
+        assert_eq!(server.stream_state(2), StreamState::Idle);
+        assert_eq!(server.recv_frame(&headers(2, false)), protocol_error);
+        // Clients open streams with increasing identifiers.
+        server.recv_frame(&headers(5, false)).unwrap();
+        assert_eq!(server.stream_state(3), StreamState::Closed);
+        assert_eq!(server.recv_frame(&headers(3, false)),
+                   Err(Error::Stream(3, ErrorCode::STREAM_CLOSED)));
+        let mut client = connection(Role::Client);
+        assert_eq!(client.send_frame(&headers(3, false)),
+                   Err(Error::Stream(3, ErrorCode::REFUSED_STREAM)));
+        // Other frames on idle streams are connection errors.
+        assert_eq!(connection(Role::Server).recv_frame(&data(1, 0, false)), protocol_error);
+    }
+
+    #[test]
+    fn test_push_promise() {
+        let mut client = connection(Role::Client);
+        let mut server = connection(Role::Server);
+        client.send_frame(&headers(1, true)).unwrap();
+        server.recv_frame(&headers(1, true)).unwrap();
+        let promise = Frame::PushPromise {
+            stream_id: 1,
+            promised_stream_id: 2,
+            block: Vec::new(),
+            end_headers: true,
+            padding: None,
+        };
+        server.send_frame(&promise).unwrap();
+        client.recv_frame(&promise).unwrap();
+        assert_eq!(server.stream_state(2), StreamState::ReservedLocal);
+        assert_eq!(client.stream_state(2), StreamState::ReservedRemote);
+        server.send_frame(&headers(2, false)).unwrap();
+        client.recv_frame(&headers(2, false)).unwrap();
+        assert_eq!(client.stream_state(2), StreamState::HalfClosedLocal);
+        assert_eq!(server.stream_state(2), StreamState::HalfClosedRemote);
+        // Clients cannot push.
+        assert_eq!(server.recv_frame(&promise), Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+
+    #[test]
+    fn test_flow_control() {
+        let mut server = connection(Role::Server);
+        server.recv_frame(&headers(1, false)).unwrap();
+        assert_eq!(server.send_capacity(1), 65_535);
+        for _ in 0..3 {
+            server.recv_frame(&data(1, 16_384, false)).unwrap();
+        }
+        assert_eq!(server.recv_frame(&data(1, 16_384, false)),
+                   Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR)));
+        let update = Frame::WindowUpdate {
+            stream_id: 0,
+            increment: 0x7FFF_FFFF,
+        };
+        assert_eq!(server.recv_frame(&update),
+                   Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR)));
+        // A smaller initial window makes stream windows negative.
+        let mut client = connection(Role::Client);
+        client.send_frame(&headers(1, false)).unwrap();
+        client.send_frame(&data(1, 1000, false)).unwrap();
+        let settings = Frame::Settings {
+            ack: false,
+            settings: Settings {
+                initial_window_size: Some(0),
+                ..Settings::default()
+            },
+        };
+        client.recv_frame(&settings).unwrap();
+        assert_eq!(client.send_capacity(1), 0);
+        assert_eq!(client.send_frame(&data(1, 1, false)),
+                   Err(Error::Stream(1, ErrorCode::FLOW_CONTROL_ERROR)));
+    }
+
+    #[test]
+    fn test_reset_and_priority() {
+        let mut server = connection(Role::Server);
+        let headers = Frame::Headers {
+            stream_id: 1,
+            priority: Some(Priority {
+                exclusive: false,
+                dependency: 1,
+                weight: 0,
+            }),
+            block: Vec::new(),
+            end_stream: false,
+            end_headers: true,
+            padding: None,
+        };
+        assert_eq!(server.recv_frame(&headers),
+                   Err(Error::Stream(1, ErrorCode::PROTOCOL_ERROR)));
+        let reset = Frame::RstStream {
+            stream_id: 1,
+            error_code: ErrorCode::PROTOCOL_ERROR,
+        };
+        server.send_frame(&reset).unwrap();
+        assert_eq!(server.stream_state(1), StreamState::Closed);
+        // Frames the peer sent before receiving the reset are ignored.
+        assert_eq!(server.recv_frame(&data(1, 10, false)), Ok(()));
+        assert_eq!(server.recv_window(0), 65_525);
+        assert_eq!(server.send_frame(&Frame::RstStream {
+                       stream_id: 3,
+                       error_code: ErrorCode::CANCEL,
+                   }),
+                   Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+
+    #[test]
+    fn test_goaway() {
+        let mut client = connection(Role::Client);
+        assert!(client.can_open_stream());
+        client.recv_frame(&Frame::GoAway {
+                last_stream_id: 0,
+                error_code: ErrorCode::NO_ERROR,
+                debug_data: Vec::new(),
+            })
+            .unwrap();
+        assert!(!client.can_open_stream());
+        let mut server = connection(Role::Server);
+        server.send_frame(&Frame::GoAway {
+                last_stream_id: 1,
+                error_code: ErrorCode::NO_ERROR,
+                debug_data: Vec::new(),
+            })
+            .unwrap();
+        assert_eq!(server.recv_frame(&headers(3, true)),
+                   Err(Error::Stream(3, ErrorCode::REFUSED_STREAM)));
+    }
+}
@@ -0,0 +1,955 @@
+use super::{Error, ErrorCode, PREFACE, Preface, Role, detect_preface};
+
+/// The initial maximum frame payload size. [RFC7540 Section 4.2]
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 16_384;
+/// The largest maximum frame payload size that can be set.
+pub const MAX_FRAME_SIZE: u32 = 16_777_215;
+
+// The largest flow-control window. [RFC7540 Section 6.9.1]
+const MAX_WINDOW_SIZE: u32 = 0x7FFF_FFFF;
+
+const HEADER_LEN: usize = 9;
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PRIORITY: u8 = 0x2;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY_FLAG: u8 = 0x20;
+
+/// The dependency and weight of a stream, [RFC7540 Section 5.3]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Priority {
+    /// The stream becomes the only child of its parent.
+    pub exclusive: bool,
+    /// The identifier of the parent stream, `0` for the root.
+    pub dependency: u32,
+    /// The weight minus one, from 0 to 255.
+    pub weight: u8,
+}
+
+impl Priority {
+    fn encode(&self, output: &mut Vec<u8>) {
+        let exclusive = if self.exclusive { 0x8000_0000 } else { 0 };
+        output.extend_from_slice(&(exclusive | self.dependency).to_be_bytes());
+        output.push(self.weight);
+    }
+
+    fn decode(input: &[u8]) -> Priority {
+        let dependency = read_u32(input);
+        Priority {
+            exclusive: dependency & 0x8000_0000 != 0,
+            dependency: dependency & MAX_WINDOW_SIZE,
+            weight: input[4],
+        }
+    }
+}
+
+/// The parameters in a `SETTINGS` frame, [RFC7540 Section 6.5.2]
+///
+/// Only the parameters that change are sent, the others are `None`.
+/// Unknown parameters are ignored.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Settings {
+    /// `SETTINGS_HEADER_TABLE_SIZE`: the maximum size of the HPACK
+    /// dynamic table used by the receiver to decode header blocks.
+    pub header_table_size: Option<u32>,
+    /// `SETTINGS_ENABLE_PUSH`: if server push is allowed.
+    pub enable_push: Option<bool>,
+    /// `SETTINGS_MAX_CONCURRENT_STREAMS`: the number of streams the
+    /// sender allows the receiver to open.
+    pub max_concurrent_streams: Option<u32>,
+    /// `SETTINGS_INITIAL_WINDOW_SIZE`: the initial flow-control
+    /// window of new streams.
+    pub initial_window_size: Option<u32>,
+    /// `SETTINGS_MAX_FRAME_SIZE`: the largest frame payload the
+    /// sender accepts.
+    pub max_frame_size: Option<u32>,
+    /// `SETTINGS_MAX_HEADER_LIST_SIZE`: an advisory limit of the
+    /// uncompressed header list size.
+    pub max_header_list_size: Option<u32>,
+}
+
+impl Settings {
+    /// Serializes the parameters as the payload of a `SETTINGS` frame.
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        let parameters = [(0x1, self.header_table_size),
+                          (0x2, self.enable_push.map(u32::from)),
+                          (0x3, self.max_concurrent_streams),
+                          (0x4, self.initial_window_size),
+                          (0x5, self.max_frame_size),
+                          (0x6, self.max_header_list_size)];
+        for &(id, value) in &parameters {
+            if let Some(value) = value {
+                output.extend_from_slice(&(id as u16).to_be_bytes());
+                output.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        output
+    }
+
+    /// Parses the payload of a `SETTINGS` frame.
+    pub fn from_payload(payload: &[u8]) -> Result<Settings, Error> {
+        if !payload.len().is_multiple_of(6) {
+            return Err(Error::Connection(ErrorCode::FRAME_SIZE_ERROR));
+        }
+        let mut settings = Settings::default();
+        for parameter in payload.chunks(6) {
+            let value = read_u32(&parameter[2..]);
+            match u16::from_be_bytes([parameter[0], parameter[1]]) {
+                0x1 => settings.header_table_size = Some(value),
+                0x2 => {
+                    if value > 1 {
+                        return Err(Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                    }
+                    settings.enable_push = Some(value == 1);
+                }
+                0x3 => settings.max_concurrent_streams = Some(value),
+                0x4 => {
+                    if value > MAX_WINDOW_SIZE {
+                        return Err(Error::Connection(ErrorCode::FLOW_CONTROL_ERROR));
+                    }
+                    settings.initial_window_size = Some(value);
+                }
+                0x5 => {
+                    if !(DEFAULT_MAX_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&value) {
+                        return Err(Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                    }
+                    settings.max_frame_size = Some(value);
+                }
+                0x6 => settings.max_header_list_size = Some(value),
+                _ => (),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// A single frame, [RFC7540 Section 6]
+///
+/// Header blocks are kept HPACK-encoded. Padding is removed when
+/// decoding, `padding` is the number of padding bytes for frames
+/// with the `PADDED` flag.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Frame {
+    /// `DATA`: request or response content.
+    Data {
+        /// The stream identifier, never `0`.
+        stream_id: u32,
+        /// The application data.
+        data: Vec<u8>,
+        /// The last frame sent on the stream.
+        end_stream: bool,
+        /// The number of padding bytes.
+        padding: Option<u8>,
+    },
+    /// `HEADERS`: opens a stream and carries a header block.
+    Headers {
+        /// The stream identifier, never `0`.
+        stream_id: u32,
+        /// The priority of the stream.
+        priority: Option<Priority>,
+        /// The encoded header block or its first fragment.
+        block: Vec<u8>,
+        /// The last frame sent on the stream.
+        end_stream: bool,
+        /// The block is complete, no `CONTINUATION` follows.
+        end_headers: bool,
+        /// The number of padding bytes.
+        padding: Option<u8>,
+    },
+    /// `PRIORITY`: changes the priority of a stream.
+    Priority {
+        /// The stream identifier, never `0`.
+        stream_id: u32,
+        /// The new priority.
+        priority: Priority,
+    },
+    /// `RST_STREAM`: terminates a stream.
+    RstStream {
+        /// The stream identifier, never `0`.
+        stream_id: u32,
+        /// Why the stream was terminated.
+        error_code: ErrorCode,
+    },
+    /// `SETTINGS`: changes connection parameters or acknowledges
+    /// the peer's change.
+    Settings {
+        /// Acknowledges received settings, `settings` are empty.
+        ack: bool,
+        /// The changed parameters.
+        settings: Settings,
+    },
+    /// `PUSH_PROMISE`: reserves a stream for a pushed response.
+    PushPromise {
+        /// The stream of the request the push is associated with.
+        stream_id: u32,
+        /// The reserved stream.
+        promised_stream_id: u32,
+        /// The encoded header block of the promised request.
+        block: Vec<u8>,
+        /// The block is complete, no `CONTINUATION` follows.
+        end_headers: bool,
+        /// The number of padding bytes.
+        padding: Option<u8>,
+    },
+    /// `PING`: measures the round-trip time or checks liveness.
+    Ping {
+        /// Answers a received ping.
+        ack: bool,
+        /// Opaque data echoed in the answer.
+        data: [u8; 8],
+    },
+    /// `GOAWAY`: starts shutting down the connection.
+    GoAway {
+        /// The highest stream identifier that may have been processed.
+        last_stream_id: u32,
+        /// Why the connection is closed.
+        error_code: ErrorCode,
+        /// Opaque diagnostic data.
+        debug_data: Vec<u8>,
+    },
+    /// `WINDOW_UPDATE`: increases a flow-control window.
+    WindowUpdate {
+        /// The stream identifier, `0` for the connection window.
+        stream_id: u32,
+        /// The number of bytes added, from 1 to 2^31-1.
+        increment: u32,
+    },
+    /// `CONTINUATION`: continues a header block.
+    Continuation {
+        /// The stream identifier, never `0`.
+        stream_id: u32,
+        /// The next fragment of the header block.
+        block: Vec<u8>,
+        /// The block is complete, no `CONTINUATION` follows.
+        end_headers: bool,
+    },
+    /// A frame of an unknown type, it must be ignored.
+    Unknown {
+        /// The frame type.
+        kind: u8,
+        /// The frame flags.
+        flags: u8,
+        /// The stream identifier.
+        stream_id: u32,
+        /// The frame payload.
+        payload: Vec<u8>,
+    },
+}
+
+impl Frame {
+    /// Returns the stream identifier, `0` for connection frames.
+    pub fn stream_id(&self) -> u32 {
+        match *self {
+            Frame::Data { stream_id, .. } |
+            Frame::Headers { stream_id, .. } |
+            Frame::Priority { stream_id, .. } |
+            Frame::RstStream { stream_id, .. } |
+            Frame::PushPromise { stream_id, .. } |
+            Frame::WindowUpdate { stream_id, .. } |
+            Frame::Continuation { stream_id, .. } |
+            Frame::Unknown { stream_id, .. } => stream_id,
+            Frame::Settings { .. } | Frame::Ping { .. } | Frame::GoAway { .. } => 0,
+        }
+    }
+
+    /// Returns the number of bytes counted against flow-control
+    /// windows.
+    ///
+    /// Only `DATA` frames are flow-controlled, their whole payload
+    /// including padding counts.
+    pub fn flow_controlled_len(&self) -> u32 {
+        match *self {
+            Frame::Data { ref data, padding, .. } => {
+                data.len() as u32 + padding.map_or(0, |x| x as u32 + 1)
+            }
+            _ => 0,
+        }
+    }
+}
+
+fn read_u32(input: &[u8]) -> u32 {
+    u32::from_be_bytes([input[0], input[1], input[2], input[3]])
+}
+
+fn write_header(output: &mut Vec<u8>, len: usize, kind: u8, flags: u8, stream_id: u32) {
+    output.extend_from_slice(&(len as u32).to_be_bytes()[1..]);
+    output.push(kind);
+    output.push(flags);
+    output.extend_from_slice(&(stream_id & MAX_WINDOW_SIZE).to_be_bytes());
+}
+
+fn padding_len(padding: Option<u8>) -> usize {
+    padding.map_or(0, |x| x as usize + 1)
+}
+
+fn write_padded(output: &mut Vec<u8>,
+                kind: u8,
+                flags: u8,
+                stream_id: u32,
+                padding: Option<u8>,
+                prefix: &[u8],
+                data: &[u8]) {
+    let len = padding_len(padding) + prefix.len() + data.len();
+    let flags = if padding.is_some() { flags | PADDED } else { flags };
+    write_header(output, len, kind, flags, stream_id);
+    if let Some(padding) = padding {
+        output.push(padding);
+    }
+    output.extend_from_slice(prefix);
+    output.extend_from_slice(data);
+    output.resize(output.len() + padding.unwrap_or(0) as usize, 0);
+}
+
+// Writes the first fragment of a header block with the frame
+// header, the rest is sent in `CONTINUATION` frames.
+#[allow(clippy::too_many_arguments)]
+fn write_block(output: &mut Vec<u8>,
+               kind: u8,
+               flags: u8,
+               stream_id: u32,
+               padding: Option<u8>,
+               prefix: &[u8],
+               block: &[u8],
+               max_frame_size: u32) {
+    let max = max_frame_size as usize;
+    let end_headers = flags & END_HEADERS != 0;
+    let first = block.len().min(max - padding_len(padding) - prefix.len());
+    let mut rest = &block[first..];
+    let flags = if rest.is_empty() { flags } else { flags & !END_HEADERS };
+    write_padded(output, kind, flags, stream_id, padding, prefix, &block[..first]);
+    while !rest.is_empty() {
+        let (fragment, remaining) = rest.split_at(rest.len().min(max));
+        rest = remaining;
+        let flags = if end_headers && rest.is_empty() { END_HEADERS } else { 0 };
+        write_header(output, fragment.len(), CONTINUATION, flags, stream_id);
+        output.extend_from_slice(fragment);
+    }
+}
+
+/// Writes a frame to the output.
+///
+/// `DATA` frames larger than the maximum frame size are split into
+/// several frames, only the last one ends the stream and is padded.
+/// Header blocks that do not fit are continued in `CONTINUATION`
+/// frames. Other frames must fit into a single frame. The maximum
+/// frame size is clamped to the range allowed in `SETTINGS`.
+pub fn encode_frame(frame: &Frame, max_frame_size: u32, output: &mut Vec<u8>) {
+    let max_frame_size = max_frame_size.clamp(DEFAULT_MAX_FRAME_SIZE, MAX_FRAME_SIZE);
+    let max = max_frame_size as usize;
+    match *frame {
+        Frame::Data { stream_id, ref data, end_stream, padding } => {
+            let mut data = &data[..];
+            while data.len() + padding_len(padding) > max {
+                let (chunk, rest) = data.split_at(max.min(data.len()));
+                write_header(output, chunk.len(), DATA, 0, stream_id);
+                output.extend_from_slice(chunk);
+                data = rest;
+            }
+            let flags = if end_stream { END_STREAM } else { 0 };
+            write_padded(output, DATA, flags, stream_id, padding, &[], data);
+        }
+        Frame::Headers { stream_id, priority, ref block, end_stream, end_headers, padding } => {
+            let mut prefix = Vec::new();
+            let mut flags = if end_headers { END_HEADERS } else { 0 };
+            if end_stream {
+                flags |= END_STREAM;
+            }
+            if let Some(priority) = priority {
+                priority.encode(&mut prefix);
+                flags |= PRIORITY_FLAG;
+            }
+            write_block(output,
+                        HEADERS,
+                        flags,
+                        stream_id,
+                        padding,
+                        &prefix,
+                        block,
+                        max_frame_size);
+        }
+        Frame::Priority { stream_id, priority } => {
+            write_header(output, 5, PRIORITY, 0, stream_id);
+            priority.encode(output);
+        }
+        Frame::RstStream { stream_id, error_code } => {
+            write_header(output, 4, RST_STREAM, 0, stream_id);
+            output.extend_from_slice(&error_code.to_raw().to_be_bytes());
+        }
+        Frame::Settings { ack, ref settings } => {
+            let payload = settings.to_payload();
+            write_header(output, payload.len(), SETTINGS, if ack { ACK } else { 0 }, 0);
+            output.extend_from_slice(&payload);
+        }
+        Frame::PushPromise { stream_id, promised_stream_id, ref block, end_headers, padding } => {
+            let prefix = (promised_stream_id & MAX_WINDOW_SIZE).to_be_bytes();
+            write_block(output,
+                        PUSH_PROMISE,
+                        if end_headers { END_HEADERS } else { 0 },
+                        stream_id,
+                        padding,
+                        &prefix,
+                        block,
+                        max_frame_size);
+        }
+        Frame::Ping { ack, ref data } => {
+            write_header(output, 8, PING, if ack { ACK } else { 0 }, 0);
+            output.extend_from_slice(data);
+        }
+        Frame::GoAway { last_stream_id, error_code, ref debug_data } => {
+            write_header(output, 8 + debug_data.len(), GOAWAY, 0, 0);
+            output.extend_from_slice(&(last_stream_id & MAX_WINDOW_SIZE).to_be_bytes());
+            output.extend_from_slice(&error_code.to_raw().to_be_bytes());
+            output.extend_from_slice(debug_data);
+        }
+        Frame::WindowUpdate { stream_id, increment } => {
+            write_header(output, 4, WINDOW_UPDATE, 0, stream_id);
+            output.extend_from_slice(&(increment & MAX_WINDOW_SIZE).to_be_bytes());
+        }
+        Frame::Continuation { stream_id, ref block, end_headers } => {
+            write_header(output,
+                         block.len(),
+                         CONTINUATION,
+                         if end_headers { END_HEADERS } else { 0 },
+                         stream_id);
+            output.extend_from_slice(block);
+        }
+        Frame::Unknown { kind, flags, stream_id, ref payload } => {
+            write_header(output, payload.len(), kind, flags, stream_id);
+            output.extend_from_slice(payload);
+        }
+    }
+}
+
+// Removes the padding from the payload of a frame with the
+// `PADDED` flag.
+fn unpad(payload: &[u8], flags: u8) -> Result<(&[u8], Option<u8>), Error> {
+    if flags & PADDED == 0 {
+        return Ok((payload, None));
+    }
+    // The padding must be shorter than the rest of the payload.
+    match payload.split_first() {
+        Some((&padding, rest)) if (padding as usize) <= rest.len() => {
+            Ok((&rest[..rest.len() - padding as usize], Some(padding)))
+        }
+        _ => Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+    }
+}
+
+/// Reads a frame from the start of the input.
+///
+/// Returns the frame and the number of bytes consumed, or `None` if
+/// the input does not contain a complete frame. Frames with a payload
+/// larger than `max_frame_size`, the value this endpoint advertised,
+/// are rejected. After a stream error the complete frame of
+/// `9 + length` bytes must be skipped.
+pub fn decode_frame(input: &[u8], max_frame_size: u32) -> Result<Option<(Frame, usize)>, Error> {
+    if input.len() < HEADER_LEN {
+        return Ok(None);
+    }
+    let len = read_u32(&[0, input[0], input[1], input[2]]) as usize;
+    let kind = input[3];
+    let flags = input[4];
+    let stream_id = read_u32(&input[5..]) & MAX_WINDOW_SIZE;
+    if len > max_frame_size as usize {
+        return Err(Error::Connection(ErrorCode::FRAME_SIZE_ERROR));
+    }
+    if input.len() < HEADER_LEN + len {
+        return Ok(None);
+    }
+    let payload = &input[HEADER_LEN..HEADER_LEN + len];
+    let protocol_error = Error::Connection(ErrorCode::PROTOCOL_ERROR);
+    let frame_size_error = Error::Connection(ErrorCode::FRAME_SIZE_ERROR);
+    let on_stream = match kind {
+        DATA | HEADERS | PRIORITY | RST_STREAM | PUSH_PROMISE | CONTINUATION => Some(true),
+        SETTINGS | PING | GOAWAY => Some(false),
+        _ => None,
+    };
+    if on_stream.is_some_and(|x| x != (stream_id != 0)) {
+        return Err(protocol_error);
+    }
+    let frame = match kind {
+        DATA => {
+            let (data, padding) = unpad(payload, flags)?;
+            Frame::Data {
+                stream_id,
+                data: data.to_vec(),
+                end_stream: flags & END_STREAM != 0,
+                padding,
+            }
+        }
+        HEADERS => {
+            let (mut block, padding) = unpad(payload, flags)?;
+            let priority = if flags & PRIORITY_FLAG != 0 {
+                if block.len() < 5 {
+                    return Err(frame_size_error);
+                }
+                // A dependency on the stream itself is checked by the
+                // connection, the block must be decoded anyway.
+                let priority = Priority::decode(block);
+                block = &block[5..];
+                Some(priority)
+            } else {
+                None
+            };
+            Frame::Headers {
+                stream_id,
+                priority,
+                block: block.to_vec(),
+                end_stream: flags & END_STREAM != 0,
+                end_headers: flags & END_HEADERS != 0,
+                padding,
+            }
+        }
+        PRIORITY => {
+            if len != 5 {
+                return Err(Error::Stream(stream_id, ErrorCode::FRAME_SIZE_ERROR));
+            }
+            let priority = Priority::decode(payload);
+            if priority.dependency == stream_id {
+                return Err(Error::Stream(stream_id, ErrorCode::PROTOCOL_ERROR));
+            }
+            Frame::Priority { stream_id, priority }
+        }
+        RST_STREAM => {
+            if len != 4 {
+                return Err(frame_size_error);
+            }
+            Frame::RstStream {
+                stream_id,
+                error_code: ErrorCode::from_raw(read_u32(payload)),
+            }
+        }
+        SETTINGS => {
+            let ack = flags & ACK != 0;
+            if ack && len != 0 {
+                return Err(frame_size_error);
+            }
+            Frame::Settings {
+                ack,
+                settings: Settings::from_payload(payload)?,
+            }
+        }
+        PUSH_PROMISE => {
+            let (block, padding) = unpad(payload, flags)?;
+            if block.len() < 4 {
+                return Err(frame_size_error);
+            }
+            Frame::PushPromise {
+                stream_id,
+                promised_stream_id: read_u32(block) & MAX_WINDOW_SIZE,
+                block: block[4..].to_vec(),
+                end_headers: flags & END_HEADERS != 0,
+                padding,
+            }
+        }
+        PING => {
+            if len != 8 {
+                return Err(frame_size_error);
+            }
+            let mut data = [0; 8];
+            data.copy_from_slice(payload);
+            Frame::Ping {
+                ack: flags & ACK != 0,
+                data,
+            }
+        }
+        GOAWAY => {
+            if len < 8 {
+                return Err(frame_size_error);
+            }
+            Frame::GoAway {
+                last_stream_id: read_u32(payload) & MAX_WINDOW_SIZE,
+                error_code: ErrorCode::from_raw(read_u32(&payload[4..])),
+                debug_data: payload[8..].to_vec(),
+            }
+        }
+        WINDOW_UPDATE => {
+            if len != 4 {
+                return Err(frame_size_error);
+            }
+            let increment = read_u32(payload) & MAX_WINDOW_SIZE;
+            if increment == 0 {
+                return Err(match stream_id {
+                    0 => protocol_error,
+                    id => Error::Stream(id, ErrorCode::PROTOCOL_ERROR),
+                });
+            }
+            Frame::WindowUpdate { stream_id, increment }
+        }
+        CONTINUATION => {
+            Frame::Continuation {
+                stream_id,
+                block: payload.to_vec(),
+                end_headers: flags & END_HEADERS != 0,
+            }
+        }
+        kind => {
+            Frame::Unknown {
+                kind,
+                flags,
+                stream_id,
+                payload: payload.to_vec(),
+            }
+        }
+    };
+    Ok(Some((frame, HEADER_LEN + len)))
+}
+
+/// A sans-IO reader of frames from a connection.
+///
+/// Received bytes are passed to [`receive`](#method.receive) and
+/// frames are read with [`next_frame`](#method.next_frame). A server
+/// checks the connection preface first.
+///
+/// `CONTINUATION` frames are joined to the preceding `HEADERS` or
+/// `PUSH_PROMISE` frame, which is returned once the header block is
+/// complete. Pass the block to an
+/// [`HpackDecoder`](struct.HpackDecoder.html) in the order the
+/// frames are returned.
+#[derive(Clone, Debug)]
+pub struct FrameReader {
+    input: Vec<u8>,
+    preface: bool,
+    max_frame_size: u32,
+    max_block_size: usize,
+    // A header block waiting for `CONTINUATION` frames.
+    partial: Option<Frame>,
+}
+
+impl FrameReader {
+    /// Constructs a reader for the frames sent to the endpoint.
+    pub fn new(role: Role) -> FrameReader {
+        FrameReader {
+            input: Vec::new(),
+            preface: role == Role::Server,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_block_size: 64 * 1024,
+            partial: None,
+        }
+    }
+
+    /// Sets the largest accepted frame payload.
+    ///
+    /// Call this once the peer acknowledged `SETTINGS_MAX_FRAME_SIZE`.
+    pub fn set_max_frame_size(&mut self, size: u32) {
+        self.max_frame_size = size.clamp(DEFAULT_MAX_FRAME_SIZE, MAX_FRAME_SIZE);
+    }
+
+    /// Sets the largest accepted header block split across
+    /// `CONTINUATION` frames, 64 KiB by default.
+    pub fn set_max_header_block_size(&mut self, size: usize) {
+        self.max_block_size = size;
+    }
+
+    /// Appends bytes received from the transport.
+    pub fn receive(&mut self, data: &[u8]) {
+        self.input.extend_from_slice(data);
+    }
+
+    /// Returns the next received frame.
+    ///
+    /// `None` is returned if more data is needed. After an error the
+    /// connection must be closed, except for stream errors.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        if self.preface {
+            match detect_preface(&self.input) {
+                Preface::Complete => {
+                    self.input.drain(..PREFACE.len());
+                    self.preface = false;
+                }
+                Preface::Incomplete => return Ok(None),
+                Preface::Invalid => return Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+            }
+        }
+        loop {
+            let (frame, len) = match decode_frame(&self.input, self.max_frame_size) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(None),
+                Err(error @ Error::Stream(..)) => {
+                    let len = read_u32(&[0, self.input[0], self.input[1], self.input[2]]) as usize;
+                    self.input.drain(..HEADER_LEN + len);
+                    // No other frame may interrupt a header block.
+                    if self.partial.is_some() {
+                        return Err(Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                    }
+                    return Err(error);
+                }
+                Err(error) => return Err(error),
+            };
+            self.input.drain(..len);
+            if let Some(frame) = self.continue_block(frame)? {
+                return Ok(Some(frame));
+            }
+        }
+    }
+
+    // Joins `CONTINUATION` frames to the header block, returns
+    // `None` while the block is incomplete.
+    fn continue_block(&mut self, frame: Frame) -> Result<Option<Frame>, Error> {
+        let protocol_error = Error::Connection(ErrorCode::PROTOCOL_ERROR);
+        let mut partial = match self.partial.take() {
+            Some(partial) => partial,
+            None => {
+                return match frame {
+                    Frame::Continuation { .. } => Err(protocol_error),
+                    Frame::Headers { end_headers: false, .. } |
+                    Frame::PushPromise { end_headers: false, .. } => {
+                        self.partial = Some(frame);
+                        Ok(None)
+                    }
+                    frame => Ok(Some(frame)),
+                };
+            }
+        };
+        // No other frame may be sent until the block is complete.
+        // [RFC7540 Section 6.10]
+        let (fragment, end) = match frame {
+            Frame::Continuation { stream_id, block, end_headers }
+                if stream_id == partial.stream_id() => (block, end_headers),
+            _ => return Err(protocol_error),
+        };
+        match partial {
+            Frame::Headers { ref mut block, ref mut end_headers, .. } |
+            Frame::PushPromise { ref mut block, ref mut end_headers, .. } => {
+                if block.len() + fragment.len() > self.max_block_size {
+                    return Err(Error::Connection(ErrorCode::ENHANCE_YOUR_CALM));
+                }
+                block.extend_from_slice(&fragment);
+                *end_headers = end;
+            }
+            _ => unreachable!(),
+        }
+        if end {
+            Ok(Some(partial))
+        } else {
+            self.partial = Some(partial);
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Error, ErrorCode, PREFACE, Role};
+    use super::{DEFAULT_MAX_FRAME_SIZE, Frame, FrameReader, Priority, Settings, decode_frame,
+                encode_frame};
+
+    fn encode(frame: &Frame) -> Vec<u8> {
+        let mut output = Vec::new();
+        encode_frame(frame, DEFAULT_MAX_FRAME_SIZE, &mut output);
+        output
+    }
+
+    fn read(input: &[u8]) -> Vec<Result<Option<Frame>, Error>> {
+        let mut reader = FrameReader::new(Role::Client);
+        reader.receive(input);
+        let mut results = Vec::new();
+        loop {
+            let result = reader.next_frame();
+            let done = matches!(result, Ok(None) | Err(Error::Connection(_)));
+            results.push(result);
+            if done {
+                return results;
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let priority = Priority {
+            exclusive: true,
+            dependency: 3,
+            weight: 15,
+        };
+        let frames = [Frame::Data {
+                          stream_id: 1,
+                          data: b"hello".to_vec(),
+                          end_stream: true,
+                          padding: Some(3),
+                      },
+                      Frame::Headers {
+                          stream_id: 5,
+                          priority: Some(priority),
+                          block: vec![0x82],
+                          end_stream: false,
+                          end_headers: true,
+                          padding: None,
+                      },
+                      Frame::Priority { stream_id: 5, priority },
+                      Frame::RstStream {
+                          stream_id: 5,
+                          error_code: ErrorCode::CANCEL,
+                      },
+                      Frame::Settings {
+                          ack: false,
+                          settings: Settings {
+                              enable_push: Some(false),
+                              max_frame_size: Some(1 << 20),
+                              ..Settings::default()
+                          },
+                      },
+                      Frame::PushPromise {
+                          stream_id: 1,
+                          promised_stream_id: 2,
+                          block: vec![0x82, 0x84],
+                          end_headers: true,
+                          padding: Some(0),
+                      },
+                      Frame::Ping {
+                          ack: true,
+                          data: *b"12345678",
+                      },
+                      Frame::GoAway {
+                          last_stream_id: 7,
+                          error_code: ErrorCode::NO_ERROR,
+                          debug_data: b"bye".to_vec(),
+                      },
+                      Frame::WindowUpdate {
+                          stream_id: 0,
+                          increment: 1000,
+                      },
+                      Frame::Unknown {
+                          kind: 0xfa,
+                          flags: 0x3,
+                          stream_id: 9,
+                          payload: b"x".to_vec(),
+                      }];
+        for frame in &frames {
+            let encoded = encode(frame);
+            assert_eq!(decode_frame(&encoded, DEFAULT_MAX_FRAME_SIZE),
+                       Ok(Some((frame.clone(), encoded.len()))));
+            assert_eq!(decode_frame(&encoded[..encoded.len() - 1], DEFAULT_MAX_FRAME_SIZE), Ok(None));
+        }
+        assert_eq!(encode(&Frame::Ping {
+                       ack: false,
+                       data: [0; 8],
+                   }),
+                   b"\x00\x00\x08\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let protocol_error = Err(Error::Connection(ErrorCode::PROTOCOL_ERROR));
+        let frame_size_error = Err(Error::Connection(ErrorCode::FRAME_SIZE_ERROR));
+        // Too large, on the wrong stream and with too much padding.
+        assert_eq!(decode_frame(b"\x00\x40\x01\x00\x00\x00\x00\x00\x01", DEFAULT_MAX_FRAME_SIZE),
+                   frame_size_error);
+        assert_eq!(decode_frame(b"\x00\x00\x00\x00\x00\x00\x00\x00\x00", DEFAULT_MAX_FRAME_SIZE),
+                   protocol_error);
+        assert_eq!(decode_frame(b"\x00\x00\x08\x06\x00\x00\x00\x00\x01\0\0\0\0\0\0\0\0",
+                                DEFAULT_MAX_FRAME_SIZE),
+                   protocol_error);
+        assert_eq!(decode_frame(b"\x00\x00\x02\x00\x08\x00\x00\x00\x01\x02x", DEFAULT_MAX_FRAME_SIZE),
+                   protocol_error);
+        assert_eq!(decode_frame(b"\x00\x00\x07\x04\x00\x00\x00\x00\x00\0\0\0\0\0\0\0",
+                                DEFAULT_MAX_FRAME_SIZE),
+                   frame_size_error);
+        assert_eq!(decode_frame(b"\x00\x00\x04\x03\x00\x00\x00\x00\x01\0\0\0\0", DEFAULT_MAX_FRAME_SIZE)
+                       .map(|x| x.map(|x| x.1)),
+                   Ok(Some(13)));
+    }
+
+    #[test]
+    fn test_split_frames() {
+        let data = Frame::Data {
+            stream_id: 1,
+            data: vec![0; DEFAULT_MAX_FRAME_SIZE as usize + 10],
+            end_stream: true,
+            padding: None,
+        };
+        let output = encode(&data);
+        let (first, len) = decode_frame(&output, DEFAULT_MAX_FRAME_SIZE).unwrap().unwrap();
+        assert_eq!(first.flow_controlled_len(), DEFAULT_MAX_FRAME_SIZE);
+        assert!(matches!(first, Frame::Data { end_stream: false, .. }));
+        let (last, _) = decode_frame(&output[len..], DEFAULT_MAX_FRAME_SIZE).unwrap().unwrap();
+        assert!(matches!(last, Frame::Data { end_stream: true, .. }));
+        // The maximum frame size is clamped to the allowed range.
+        let mut small = Vec::new();
+        encode_frame(&data, 0, &mut small);
+        assert_eq!(small, output);
+        let headers = Frame::Headers {
+            stream_id: 1,
+            priority: None,
+            block: vec![0x82; 2 * DEFAULT_MAX_FRAME_SIZE as usize + 1],
+            end_stream: true,
+            end_headers: true,
+            padding: None,
+        };
+        assert_eq!(read(&encode(&headers)), vec![Ok(Some(headers)), Ok(None)]);
+    }
+
+    #[test]
+    fn test_reader_continuation() {
+        let mut input = b"\x00\x00\x01\x01\x01\x00\x00\x00\x01\x82".to_vec();
+        input.extend_from_slice(b"\x00\x00\x01\x09\x04\x00\x00\x00\x01\x84");
+        let headers = Frame::Headers {
+            stream_id: 1,
+            priority: None,
+            block: vec![0x82, 0x84],
+            end_stream: true,
+            end_headers: true,
+            padding: None,
+        };
+        assert_eq!(read(&input), vec![Ok(Some(headers)), Ok(None)]);
+        // Other frames must not interrupt the block.
+        let mut input = b"\x00\x00\x01\x01\x00\x00\x00\x00\x01\x82".to_vec();
+        input.extend_from_slice(b"\x00\x00\x08\x06\x00\x00\x00\x00\x00\0\0\0\0\0\0\0\0");
+        assert_eq!(read(&input), vec![Err(Error::Connection(ErrorCode::PROTOCOL_ERROR))]);
+        assert_eq!(read(b"\x00\x00\x01\x09\x04\x00\x00\x00\x01\x84"),
+                   vec![Err(Error::Connection(ErrorCode::PROTOCOL_ERROR))]);
+    }
+
+    #[test]
+    fn test_reader_stream_error() {
+        // A PRIORITY frame of the wrong size is skipped.
+        let mut input = b"\x00\x00\x04\x02\x00\x00\x00\x00\x03\x00\x00\x00\x00".to_vec();
+        // A HEADERS frame depending on its own stream is still returned.
+        input.extend_from_slice(b"\x00\x00\x06\x01\x24\x00\x00\x00\x03\x00\x00\x00\x03\x0f\x82");
+        let headers = Frame::Headers {
+            stream_id: 3,
+            priority: Some(Priority {
+                exclusive: false,
+                dependency: 3,
+                weight: 15,
+            }),
+            block: vec![0x82],
+            end_stream: false,
+            end_headers: true,
+            padding: None,
+        };
+        assert_eq!(read(&input),
+                   vec![Err(Error::Stream(3, ErrorCode::FRAME_SIZE_ERROR)),
+                        Ok(Some(headers)),
+                        Ok(None)]);
+    }
+
+    #[test]
+    fn test_reader_preface() {
+        let mut reader = FrameReader::new(Role::Server);
+        reader.receive(&PREFACE[..10]);
+        assert_eq!(reader.next_frame(), Ok(None));
+        reader.receive(&PREFACE[10..]);
+        reader.receive(b"\x00\x00\x00\x04\x00\x00\x00\x00\x00");
+        assert_eq!(reader.next_frame(),
+                   Ok(Some(Frame::Settings {
+                       ack: false,
+                       settings: Settings::default(),
+                   })));
+        let mut reader = FrameReader::new(Role::Server);
+        reader.receive(b"GET / HTTP/1.1\r\n");
+        assert_eq!(reader.next_frame(), Err(Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+}
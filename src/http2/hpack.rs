@@ -0,0 +1,821 @@
+use std::collections::VecDeque;
+
+use header::Headers;
+use super::{Error, ErrorCode};
+
+// The table size both endpoints start with. [RFC7540 Section 6.5.2]
+const DEFAULT_TABLE_SIZE: usize = 4096;
+
+// The overhead of a table entry in addition to the name and value
+// length. [RFC7541 Section 4.1]
+const ENTRY_OVERHEAD: usize = 32;
+
+// The header list size accepted by default, the size of a field is
+// counted like a table entry. [RFC7540 Section 6.5.2]
+const DEFAULT_MAX_HEADER_LIST_SIZE: usize = 64 * 1024;
+
+// Names of fields that are never added to a compression table so
+// their values cannot be guessed by probing. [RFC7541 Section 7.1.3]
+const SENSITIVE: &[&str] = &["authorization", "cookie", "proxy-authorization", "set-cookie"];
+
+// [RFC7541 Appendix A]
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// The static table followed by the dynamic table. Indices start at 1
+// and new entries are inserted at the front of the dynamic table.
+// [RFC7541 Section 2.3]
+#[derive(Clone, Debug)]
+struct Table {
+    entries: VecDeque<(String, Vec<u8>)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Table {
+    fn new() -> Table {
+        Table {
+            entries: VecDeque::new(),
+            size: 0,
+            max_size: DEFAULT_TABLE_SIZE,
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<(&str, &[u8])> {
+        match index {
+            0 => None,
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Some((name, value.as_bytes()))
+            }
+            _ => self.entries.get(index - 62).map(|x| (&x.0[..], &x.1[..])),
+        }
+    }
+
+    // Returns the index of an entry with the name and value, or else
+    // the index of an entry with the name and `false`.
+    fn find(&self, name: &str, value: &[u8]) -> Option<(usize, bool)> {
+        let entries = STATIC_TABLE.iter()
+            .map(|&(name, value)| (name, value.as_bytes()))
+            .chain(self.entries.iter().map(|x| (&x.0[..], &x.1[..])));
+        let mut found = None;
+        for (index, (entry_name, entry_value)) in entries.enumerate() {
+            if entry_name == name {
+                if entry_value == value {
+                    return Some((index + 1, true));
+                }
+                found = found.or(Some((index + 1, false)));
+            }
+        }
+        found
+    }
+
+    fn insert(&mut self, name: String, value: Vec<u8>) {
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        self.evict(self.max_size.saturating_sub(size));
+        // An entry larger than the table empties it.
+        if size <= self.max_size {
+            self.size += size;
+            self.entries.push_front((name, value));
+        }
+    }
+
+    fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        self.evict(max_size);
+    }
+
+    fn evict(&mut self, size: usize) {
+        while self.size > size {
+            let (name, value) = self.entries.pop_back().expect("table size matches entries");
+            self.size -= name.len() + value.len() + ENTRY_OVERHEAD;
+        }
+    }
+}
+
+fn decode_integer(input: &[u8], position: &mut usize, prefix: u8) -> Result<usize, ()> {
+    let mask = ((1u16 << prefix) - 1) as u8;
+    let first = *input.get(*position).ok_or(())? & mask;
+    *position += 1;
+    if first < mask {
+        return Ok(first as usize);
+    }
+    let mut value = mask as u64;
+    let mut shift = 0;
+    loop {
+        let byte = *input.get(*position).ok_or(())?;
+        *position += 1;
+        value += ((byte & 0x7F) as u64) << shift;
+        shift += 7;
+        // Larger values are never needed and may overflow.
+        if value > u32::MAX as u64 || shift > 35 {
+            return Err(());
+        }
+        if byte & 0x80 == 0 {
+            return Ok(value as usize);
+        }
+    }
+}
+
+fn encode_integer(output: &mut Vec<u8>, flags: u8, prefix: u8, value: usize) {
+    let mask = ((1u16 << prefix) - 1) as usize;
+    if value < mask {
+        output.push(flags | value as u8);
+        return;
+    }
+    output.push(flags | mask as u8);
+    let mut value = value - mask;
+    while value >= 0x80 {
+        output.push(0x80 | (value & 0x7F) as u8);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn decode_string(input: &[u8], position: &mut usize) -> Result<Vec<u8>, ()> {
+    let huffman = *input.get(*position).ok_or(())? & 0x80 != 0;
+    let len = decode_integer(input, position, 7)?;
+    let end = position.checked_add(len).filter(|&end| end <= input.len()).ok_or(())?;
+    let data = &input[*position..end];
+    *position = end;
+    if huffman { huffman_decode(data) } else { Ok(data.to_vec()) }
+}
+
+fn encode_string(output: &mut Vec<u8>, data: &[u8]) {
+    let len = huffman_len(data);
+    if len < data.len() {
+        encode_integer(output, 0x80, 7, len);
+        huffman_encode(output, data);
+    } else {
+        encode_integer(output, 0, 7, data.len());
+        output.extend_from_slice(data);
+    }
+}
+
+fn huffman_len(data: &[u8]) -> usize {
+    let bits: usize = data.iter().map(|&c| HUFFMAN_CODES[c as usize].1 as usize).sum();
+    bits.div_ceil(8)
+}
+
+fn huffman_encode(output: &mut Vec<u8>, data: &[u8]) {
+    let mut bits = 0u64;
+    let mut len = 0;
+    for &c in data {
+        let (code, code_len) = HUFFMAN_CODES[c as usize];
+        bits = bits << code_len | code as u64;
+        len += code_len;
+        while len >= 8 {
+            len -= 8;
+            output.push((bits >> len) as u8);
+        }
+    }
+    // The last byte is padded with the most significant bits of EOS.
+    if len > 0 {
+        output.push((bits << (8 - len)) as u8 | (0xFF >> len));
+    }
+}
+
+fn huffman_decode(data: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut output = Vec::new();
+    let mut code = 0u32;
+    let mut len = 0;
+    for &byte in data {
+        for shift in (0..8).rev() {
+            code = code << 1 | (byte >> shift) as u32 & 1;
+            len += 1;
+            // Codes of the same length are consecutive.
+            let (first, offset, count) = HUFFMAN_LENGTHS[len];
+            if code >= first && code - first < count as u32 {
+                match HUFFMAN_SYMBOLS[(offset as u32 + code - first) as usize] {
+                    256 => return Err(()),
+                    symbol => output.push(symbol as u8),
+                }
+                code = 0;
+                len = 0;
+            } else if len == 30 {
+                return Err(());
+            }
+        }
+    }
+    // Padding longer than 7 bits or not matching EOS is an error.
+    if len > 7 || code != (1 << len) - 1 {
+        return Err(());
+    }
+    Ok(output)
+}
+
+/// Decodes HPACK header blocks, [RFC7541]
+///
+/// A connection uses one decoder for all header blocks it receives,
+/// they must be decoded in the order they arrive.
+#[derive(Clone, Debug)]
+pub struct HpackDecoder {
+    table: Table,
+    max_table_size: usize,
+    max_list_size: usize,
+}
+
+impl HpackDecoder {
+    /// Constructs a decoder with a table size of 4096 bytes and a
+    /// header list size of 64 KiB.
+    pub fn new() -> HpackDecoder {
+        HpackDecoder {
+            table: Table::new(),
+            max_table_size: DEFAULT_TABLE_SIZE,
+            max_list_size: DEFAULT_MAX_HEADER_LIST_SIZE,
+        }
+    }
+
+    /// Sets the largest table size the encoder may use.
+    ///
+    /// Call this once the peer acknowledged the
+    /// `SETTINGS_HEADER_TABLE_SIZE` sent by this endpoint.
+    pub fn set_max_table_size(&mut self, size: usize) {
+        self.max_table_size = size;
+        if self.table.max_size > size {
+            self.table.set_max_size(size);
+        }
+    }
+
+    /// Sets the largest accepted header list, the sum of the name
+    /// and value lengths plus 32 bytes for each field.
+    ///
+    /// Use the `SETTINGS_MAX_HEADER_LIST_SIZE` sent by this endpoint.
+    pub fn set_max_header_list_size(&mut self, size: usize) {
+        self.max_list_size = size;
+    }
+
+    /// Decodes a complete header block of a stream and appends the
+    /// fields to the collection.
+    ///
+    /// Pseudo-header fields like `:method` are appended as well.
+    /// A malformed block is a connection error as the decoder state
+    /// is lost. A block exceeding the header list size or with an
+    /// uppercase field name is still decoded to keep the state, but
+    /// the remaining fields are dropped and a stream error is
+    /// returned. [RFC7540 Section 8.1.2]
+    pub fn decode(&mut self, stream_id: u32, block: &[u8], headers: &mut Headers) -> Result<(), Error> {
+        match self.decode_fields(block, headers) {
+            Ok(None) => Ok(()),
+            Ok(Some(code)) => Err(Error::Stream(stream_id, code)),
+            Err(()) => Err(Error::Connection(ErrorCode::COMPRESSION_ERROR)),
+        }
+    }
+
+    // Returns the code of a stream error if the header list is too
+    // large or malformed.
+    fn decode_fields(&mut self, block: &[u8], headers: &mut Headers) -> Result<Option<ErrorCode>, ()> {
+        let mut position = 0;
+        let mut first = true;
+        let mut list_size = 0usize;
+        let mut error = None;
+        while position < block.len() {
+            let byte = block[position];
+            let (name, value) = if byte & 0x80 != 0 {
+                // Indexed field.
+                let index = decode_integer(block, &mut position, 7)?;
+                let (name, value) = self.table.get(index).ok_or(())?;
+                (name.to_owned(), value.to_vec())
+            } else if byte & 0xE0 == 0x20 {
+                // Table size updates must come first. [RFC7541 Section 4.2]
+                let size = decode_integer(block, &mut position, 5)?;
+                if !first || size > self.max_table_size {
+                    return Err(());
+                }
+                self.table.set_max_size(size);
+                continue;
+            } else {
+                // Literal field, added to the table if incremental.
+                let indexing = byte & 0xC0 == 0x40;
+                let index = decode_integer(block, &mut position, if indexing { 6 } else { 4 })?;
+                let name = match index {
+                    0 => String::from_utf8_lossy(&decode_string(block, &mut position)?).into_owned(),
+                    index => self.table.get(index).ok_or(())?.0.to_owned(),
+                };
+                let value = decode_string(block, &mut position)?;
+                if indexing {
+                    self.table.insert(name.clone(), value.clone());
+                }
+                (name, value)
+            };
+            first = false;
+            list_size = list_size.saturating_add(name.len() + value.len() + ENTRY_OVERHEAD);
+            if list_size > self.max_list_size {
+                error = error.or(Some(ErrorCode::ENHANCE_YOUR_CALM));
+            }
+            // Names are merged ignoring case once appended.
+            if name.bytes().any(|c| c.is_ascii_uppercase()) {
+                error = error.or(Some(ErrorCode::PROTOCOL_ERROR));
+            }
+            if error.is_none() {
+                headers.append_raw(name, value);
+            }
+        }
+        Ok(error)
+    }
+}
+
+impl Default for HpackDecoder {
+    fn default() -> HpackDecoder {
+        HpackDecoder::new()
+    }
+}
+
+/// Encodes HPACK header blocks, [RFC7541]
+///
+/// Names are sent in lowercase. Fields are added to the dynamic
+/// table, except for credentials and cookies which are never indexed.
+#[derive(Clone, Debug)]
+pub struct HpackEncoder {
+    table: Table,
+    // The smallest and the latest table size since the last block.
+    size_update: Option<(usize, usize)>,
+}
+
+impl HpackEncoder {
+    /// Constructs an encoder with a table size of 4096 bytes.
+    pub fn new() -> HpackEncoder {
+        HpackEncoder {
+            table: Table::new(),
+            size_update: None,
+        }
+    }
+
+    /// Sets the table size to the `SETTINGS_HEADER_TABLE_SIZE`
+    /// received from the peer.
+    ///
+    /// The change is signalled at the start of the next block.
+    pub fn set_max_table_size(&mut self, size: usize) {
+        let smallest = self.size_update.map_or(size, |(smallest, _)| smallest.min(size));
+        self.size_update = Some((smallest, size));
+        self.table.set_max_size(size);
+    }
+
+    /// Encodes the fields of the collection as a header block.
+    ///
+    /// Pseudo-header fields must be the first fields in the collection.
+    pub fn encode(&mut self, headers: &Headers, output: &mut Vec<u8>) {
+        if let Some((smallest, size)) = self.size_update.take() {
+            if smallest < size {
+                encode_integer(output, 0x20, 5, smallest);
+            }
+            encode_integer(output, 0x20, 5, size);
+        }
+        for (name, lines) in headers {
+            let name = name.to_ascii_lowercase();
+            let sensitive = SENSITIVE.contains(&&name[..]);
+            for value in lines {
+                self.encode_field(&name, value, sensitive, output);
+            }
+        }
+    }
+
+    fn encode_field(&mut self, name: &str, value: &[u8], sensitive: bool, output: &mut Vec<u8>) {
+        let found = self.table.find(name, value);
+        if let Some((index, true)) = found {
+            if !sensitive {
+                encode_integer(output, 0x80, 7, index);
+                return;
+            }
+        }
+        // Fields larger than the table would only evict all entries.
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        let (flags, prefix) = if sensitive {
+            (0x10, 4)
+        } else if size * 4 > self.table.max_size * 3 {
+            (0x00, 4)
+        } else {
+            (0x40, 6)
+        };
+        match found {
+            Some((index, _)) => encode_integer(output, flags, prefix, index),
+            None => {
+                encode_integer(output, flags, prefix, 0);
+                encode_string(output, name.as_bytes());
+            }
+        }
+        encode_string(output, value);
+        if flags == 0x40 {
+            self.table.insert(name.to_owned(), value.to_vec());
+        }
+    }
+}
+
+impl Default for HpackEncoder {
+    fn default() -> HpackEncoder {
+        HpackEncoder::new()
+    }
+}
+
+// The Huffman code of each symbol and its length in bits, EOS is 256.
+// [RFC7541 Appendix B]
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28),
+    (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24),
+    (0x3ffffffc, 30), (0xfffffe9, 28), (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28),
+    (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28),
+    (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28), (0xffffff4, 28),
+    (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11), (0x3fa, 10), (0x3fb, 10), (0xf9, 8),
+    (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6), (0x0, 5), (0x1, 5), (0x2, 5),
+    (0x19, 6), (0x1a, 6), (0x1b, 6), (0x1c, 6), (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7),
+    (0xfb, 8), (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13), (0x21, 6),
+    (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7), (0x63, 7), (0x64, 7),
+    (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7), (0x6b, 7), (0x6c, 7),
+    (0x6d, 7), (0x6e, 7), (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6), (0x7ffd, 15),
+    (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6), (0x27, 6), (0x6, 5),
+    (0x74, 7), (0x75, 7), (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7),
+    (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7), (0x79, 7), (0x7a, 7),
+    (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28),
+    (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20), (0x3fffd3, 22), (0x3fffd4, 22),
+    (0x3fffd5, 22), (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23),
+    (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24),
+    (0x7fffe1, 23), (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21),
+    (0x3fffd8, 22), (0x7fffe5, 23), (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23),
+    (0xffffef, 24), (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22),
+    (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21), (0x7fffea, 23), (0x3fffdd, 22),
+    (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23),
+    (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20), (0x3fffe2, 22),
+    (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22),
+    (0x7ffff1, 23), (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19),
+    (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25), (0x3ffffe2, 26),
+    (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27), (0x7ffffdf, 27), (0x3ffffe5, 26),
+    (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21), (0x3ffffe6, 26),
+    (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28),
+    (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20), (0xfffff3, 24),
+    (0xfffed, 20), (0x1fffe6, 21), (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23),
+    (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24),
+    (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23), (0x3ffffeb, 26), (0x7ffffe6, 27),
+    (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27), (0x7ffffe9, 27),
+    (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26), (0x3fffffff, 30)];
+
+// The symbols ordered by code, codes are assigned in canonical order.
+const HUFFMAN_SYMBOLS: [u16; 257] = [
+    48, 49, 50, 97, 99, 101, 105, 111, 115, 116, 32, 37, 45, 46, 47, 51, 52, 53, 54, 55, 56, 57,
+    61, 65, 95, 98, 100, 102, 103, 104, 108, 109, 110, 112, 114, 117, 58, 66, 67, 68, 69, 70, 71,
+    72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 89, 106, 107, 113, 118, 119,
+    120, 121, 122, 38, 42, 44, 59, 88, 90, 33, 34, 40, 41, 63, 39, 43, 124, 35, 62, 0, 36, 64, 91,
+    93, 126, 94, 125, 60, 96, 123, 92, 195, 208, 128, 130, 131, 162, 184, 194, 224, 226, 153, 161,
+    167, 172, 176, 177, 179, 209, 216, 217, 227, 229, 230, 129, 132, 133, 134, 136, 146, 154, 156,
+    160, 163, 164, 169, 170, 173, 178, 181, 185, 186, 187, 189, 190, 196, 198, 228, 232, 233, 1,
+    135, 137, 138, 139, 140, 141, 143, 147, 149, 150, 151, 152, 155, 157, 158, 165, 166, 168, 174,
+    175, 180, 182, 183, 188, 191, 197, 231, 239, 9, 142, 144, 145, 148, 159, 171, 206, 215, 225,
+    236, 237, 199, 207, 234, 235, 192, 193, 200, 201, 202, 205, 210, 213, 218, 219, 238, 240, 242,
+    243, 255, 203, 204, 211, 212, 214, 221, 222, 223, 241, 244, 245, 246, 247, 248, 250, 251, 252,
+    253, 254, 2, 3, 4, 5, 6, 7, 8, 11, 12, 14, 15, 16, 17, 18, 19, 20, 21, 23, 24, 25, 26, 27, 28,
+    29, 30, 31, 127, 220, 249, 10, 13, 22, 256];
+
+// The first code, the offset in `HUFFMAN_SYMBOLS` and the number
+// of symbols for each code length.
+const HUFFMAN_LENGTHS: [(u32, u16, u16); 31] = [
+    (0x0, 0, 0), (0x0, 0, 0), (0x0, 0, 0), (0x0, 0, 0), (0x0, 0, 0), (0x0, 0, 10), (0x14, 10, 26),
+    (0x5c, 36, 32), (0xf8, 68, 6), (0x0, 74, 0), (0x3f8, 74, 5), (0x7fa, 79, 3), (0xffa, 82, 2),
+    (0x1ff8, 84, 6), (0x3ffc, 90, 2), (0x7ffc, 92, 3), (0x0, 95, 0), (0x0, 95, 0), (0x0, 95, 0),
+    (0x7fff0, 95, 3), (0xfffe6, 98, 8), (0x1fffdc, 106, 13), (0x3fffd2, 119, 26),
+    (0x7fffd8, 145, 29), (0xffffea, 174, 12), (0x1ffffec, 186, 4), (0x3ffffe0, 190, 15),
+    (0x7ffffde, 205, 19), (0xfffffe2, 224, 29), (0x0, 253, 0), (0x3ffffffc, 253, 4)];
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use super::super::{Error, ErrorCode};
+    use super::{HpackDecoder, HpackEncoder, decode_integer, encode_integer, huffman_decode,
+                huffman_encode};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn fields(headers: &Headers) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        for (name, lines) in headers {
+            for line in lines {
+                fields.push((name.to_owned(), String::from_utf8(line.clone()).unwrap()));
+            }
+        }
+        fields
+    }
+
+    type Fields<'a> = &'a [(&'a str, &'a str)];
+
+    // Decodes the blocks in order and checks the fields and the
+    // dynamic table and its size after each block.
+    fn check(decoder: &mut HpackDecoder, blocks: &[(&str, Fields, Fields, usize)]) {
+        for &(block, expected, table, size) in blocks {
+            let mut headers = Headers::new();
+            decoder.decode(1, &hex(block), &mut headers).unwrap();
+            let expected: Vec<_> = expected.iter().map(|x| (x.0.to_owned(), x.1.to_owned())).collect();
+            assert_eq!(fields(&headers), expected);
+            let entries: Vec<_> = decoder.table
+                .entries
+                .iter()
+                .map(|x| (&x.0[..], &x.1[..]))
+                .collect();
+            let table: Vec<_> = table.iter().map(|x| (x.0, x.1.as_bytes())).collect();
+            assert_eq!(entries, table);
+            assert_eq!(decoder.table.size, size);
+        }
+    }
+
+    const REQUEST_1: &[(&str, &str)] = &[(":method", "GET"),
+                                         (":scheme", "http"),
+                                         (":path", "/"),
+                                         (":authority", "www.example.com")];
+    const REQUEST_2: &[(&str, &str)] = &[(":method", "GET"),
+                                         (":scheme", "http"),
+                                         (":path", "/"),
+                                         (":authority", "www.example.com"),
+                                         ("cache-control", "no-cache")];
+    const REQUEST_3: &[(&str, &str)] = &[(":method", "GET"),
+                                         (":scheme", "https"),
+                                         (":path", "/index.html"),
+                                         (":authority", "www.example.com"),
+                                         ("custom-key", "custom-value")];
+
+    #[test]
+    fn test_integer() {
+        // [RFC7541 Appendix C.1]
+        for &(value, prefix, encoded) in &[(10, 5, &[0x0a][..]),
+                                            (1337, 5, &[0x1f, 0x9a, 0x0a][..]),
+                                            (42, 8, &[0x2a][..])] {
+            let mut output = Vec::new();
+            encode_integer(&mut output, 0, prefix, value);
+            assert_eq!(output, encoded);
+            let mut position = 0;
+            assert_eq!(decode_integer(encoded, &mut position, prefix), Ok(value));
+            assert_eq!(position, encoded.len());
+        }
+        assert!(decode_integer(&[0x1f, 0x9a], &mut 0, 5).is_err());
+        assert!(decode_integer(&[0x1f, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+                               &mut 0,
+                               5)
+            .is_err());
+        assert!(decode_integer(&[0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+                               &mut 0,
+                               5)
+            .is_err());
+    }
+
+    #[test]
+    fn test_requests() {
+        // [RFC7541 Appendix C.3]
+        check(&mut HpackDecoder::new(),
+              &[("828684410f7777772e6578616d706c652e636f6d",
+                 REQUEST_1,
+                 &[(":authority", "www.example.com")],
+                 57),
+                ("828684be58086e6f2d6361636865",
+                 REQUEST_2,
+                 &[("cache-control", "no-cache"), (":authority", "www.example.com")],
+                 110),
+                ("828785bf400a637573746f6d2d6b65790c637573746f6d2d76616c7565",
+                 REQUEST_3,
+                 &[("custom-key", "custom-value"),
+                   ("cache-control", "no-cache"),
+                   (":authority", "www.example.com")],
+                 164)]);
+    }
+
+    #[test]
+    fn test_requests_huffman() {
+        // [RFC7541 Appendix C.4]
+        check(&mut HpackDecoder::new(),
+              &[("828684418cf1e3c2e5f23a6ba0ab90f4ff",
+                 REQUEST_1,
+                 &[(":authority", "www.example.com")],
+                 57),
+                ("828684be5886a8eb10649cbf",
+                 REQUEST_2,
+                 &[("cache-control", "no-cache"), (":authority", "www.example.com")],
+                 110),
+                ("828785bf408825a849e95ba97d7f8925a849e95bb8e8b4bf",
+                 REQUEST_3,
+                 &[("custom-key", "custom-value"),
+                   ("cache-control", "no-cache"),
+                   (":authority", "www.example.com")],
+                 164)]);
+    }
+
+    const RESPONSE_1: &[(&str, &str)] = &[(":status", "302"),
+                                          ("cache-control", "private"),
+                                          ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                                          ("location", "https://www.example.com")];
+    const RESPONSE_2: &[(&str, &str)] = &[(":status", "307"),
+                                          ("cache-control", "private"),
+                                          ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                                          ("location", "https://www.example.com")];
+    const RESPONSE_3: &[(&str, &str)] = &[(":status", "200"),
+                                          ("cache-control", "private"),
+                                          ("date", "Mon, 21 Oct 2013 20:13:22 GMT"),
+                                          ("location", "https://www.example.com"),
+                                          ("content-encoding", "gzip"),
+                                          ("set-cookie",
+                                           "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; \
+                                            version=1")];
+    const TABLE_1: &[(&str, &str)] = &[("location", "https://www.example.com"),
+                                       ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                                       ("cache-control", "private"),
+                                       (":status", "302")];
+    const TABLE_2: &[(&str, &str)] = &[(":status", "307"),
+                                       ("location", "https://www.example.com"),
+                                       ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                                       ("cache-control", "private")];
+    const TABLE_3: &[(&str, &str)] = &[("set-cookie",
+                                        "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1"),
+                                       ("content-encoding", "gzip"),
+                                       ("date", "Mon, 21 Oct 2013 20:13:22 GMT")];
+
+    #[test]
+    fn test_responses_eviction() {
+        // [RFC7541 Appendix C.5]
+        let mut decoder = HpackDecoder::new();
+        decoder.set_max_table_size(256);
+        check(&mut decoder,
+              &[("4803333032580770726976617465611d4d6f6e2c203231204f63742032303133203230\
+                  3a31333a323120474d546e1768747470733a2f2f7777772e6578616d706c652e636f6d",
+                 RESPONSE_1,
+                 TABLE_1,
+                 222),
+                ("4803333037c1c0bf", RESPONSE_2, TABLE_2, 222),
+                ("88c1611d4d6f6e2c203231204f637420323031332032303a31333a323220474d54c05a\
+                  04677a69707738666f6f3d4153444a4b48514b425a584f5157454f50495541585157454f\
+                  49553b206d61782d6167653d333630303b2076657273696f6e3d31",
+                 RESPONSE_3,
+                 TABLE_3,
+                 215)]);
+    }
+
+    #[test]
+    fn test_responses_huffman() {
+        // [RFC7541 Appendix C.6]
+        let mut decoder = HpackDecoder::new();
+        decoder.set_max_table_size(256);
+        check(&mut decoder,
+              &[("488264025885aec3771a4b6196d07abe941054d444a8200595040b8166e082a62d1bff6e\
+                  919d29ad171863c78f0b97c8e9ae82ae43d3",
+                 RESPONSE_1,
+                 TABLE_1,
+                 222),
+                ("4883640effc1c0bf", RESPONSE_2, TABLE_2, 222),
+                ("88c16196d07abe941054d444a8200595040b8166e084a62d1bffc05a839bd9ab77ad94e7\
+                  821dd7f2e6c7b335dfdfcd5b3960d5af27087f3672c1ab270fb5291f9587316065c003ed\
+                  4ee5b1063d5007",
+                 RESPONSE_3,
+                 TABLE_3,
+                 215)]);
+    }
+
+    #[test]
+    fn test_huffman() {
+        let data = b"custom-value \x00\xff";
+        let mut encoded = Vec::new();
+        huffman_encode(&mut encoded, data);
+        assert_eq!(huffman_decode(&encoded), Ok(data.to_vec()));
+        let mut encoded = Vec::new();
+        huffman_encode(&mut encoded, b"no-cache");
+        assert_eq!(encoded, hex("a8eb10649cbf"));
+        // Padding longer than 7 bits or not of ones is invalid.
+        assert!(huffman_decode(&hex("a8eb10649cbfff")).is_err());
+        assert!(huffman_decode(&hex("a8eb10649cb0")).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut headers = Headers::new();
+        headers.append_raw(":status", b"200".to_vec());
+        headers.append_raw("cookie", b"a=b".to_vec());
+        headers.append_raw("cookie", b"c=d".to_vec());
+        headers.append_raw("x-long", vec![b'x'; 5000]);
+        let mut encoder = HpackEncoder::new();
+        let mut decoder = HpackDecoder::new();
+        for _ in 0..2 {
+            let mut block = Vec::new();
+            encoder.encode(&headers, &mut block);
+            let mut decoded = Headers::new();
+            decoder.decode(1, &block, &mut decoded).unwrap();
+            assert_eq!(fields(&decoded), fields(&headers));
+        }
+        encoder.set_max_table_size(0);
+        encoder.set_max_table_size(100);
+        let mut block = Vec::new();
+        encoder.encode(&headers, &mut block);
+        assert_eq!(&block[..2], &[0x20, 0x3f]);
+        decoder.decode(3, &block, &mut Headers::new()).unwrap();
+        assert_eq!(decoder.table.max_size, 100);
+    }
+
+    #[test]
+    fn test_header_list_size() {
+        let mut decoder = HpackDecoder::new();
+        decoder.set_max_header_list_size(100);
+        let mut headers = Headers::new();
+        // Each field of C.3.1 is added to the list, the fourth exceeds it.
+        let result = decoder.decode(5, &hex("828684410f7777772e6578616d706c652e636f6d"), &mut headers);
+        assert_eq!(result, Err(Error::Stream(5, ErrorCode::ENHANCE_YOUR_CALM)));
+        assert_eq!(headers.len(), 2);
+        // The table is still updated.
+        let mut headers = Headers::new();
+        decoder.set_max_header_list_size(1000);
+        decoder.decode(7, &hex("828684be58086e6f2d6361636865"), &mut headers).unwrap();
+        assert_eq!(fields(&headers), REQUEST_2.iter()
+            .map(|x| (x.0.to_owned(), x.1.to_owned()))
+            .collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_uppercase_name() {
+        let mut decoder = HpackDecoder::new();
+        let mut headers = Headers::new();
+        // A literal `accept: a` and an incrementally indexed `Accept: b`.
+        let block = [&[0x00, 0x06][..], b"accept", &[0x01, b'a'], &[0x40, 0x06], b"Accept", &[0x01, b'b']]
+            .concat();
+        assert_eq!(decoder.decode(3, &block, &mut headers),
+                   Err(Error::Stream(3, ErrorCode::PROTOCOL_ERROR)));
+        assert_eq!(headers.get_raw("accept"), Some(&[b"a".to_vec()][..]));
+        // The uppercase field was still added to the table.
+        assert_eq!(decoder.table.get(62), Some(("Accept", &b"b"[..])));
+    }
+
+    #[test]
+    fn test_invalid_blocks() {
+        let compression_error = Err(Error::Connection(ErrorCode::COMPRESSION_ERROR));
+        let mut headers = Headers::new();
+        // Index 0 and an index beyond the table.
+        assert_eq!(HpackDecoder::new().decode(1, &[0x80], &mut headers), compression_error);
+        assert_eq!(HpackDecoder::new().decode(1, &[0xbe], &mut headers), compression_error);
+        // A size update after a field and one above the limit.
+        assert_eq!(HpackDecoder::new().decode(1, &[0x82, 0x20], &mut headers), compression_error);
+        assert_eq!(HpackDecoder::new().decode(1, &[0x3f, 0xe2, 0x1f], &mut headers),
+                   compression_error);
+        // A truncated string.
+        assert_eq!(HpackDecoder::new().decode(1, &[0x40, 0x05, b'a'], &mut headers),
+                   compression_error);
+    }
+}
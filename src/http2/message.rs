@@ -0,0 +1,407 @@
+use std::fmt::{self, Display};
+use std::str;
+
+use header::{Header, Headers, Host};
+use header::item::HostPort;
+use util::{is_scheme, is_token};
+use {Method, RequestTarget, Status};
+
+/// The `:method` pseudo-header field.
+pub const METHOD: &str = ":method";
+/// The `:scheme` pseudo-header field.
+pub const SCHEME: &str = ":scheme";
+/// The `:authority` pseudo-header field.
+pub const AUTHORITY: &str = ":authority";
+/// The `:path` pseudo-header field.
+pub const PATH: &str = ":path";
+/// The `:status` pseudo-header field.
+pub const STATUS: &str = ":status";
+
+// Header fields only meaningful for a single HTTP/1.1 connection.
+// [RFC7540 Section 8.1.2.2]
+const CONNECTION_SPECIFIC: &[&str] = &["connection",
+                                       "keep-alive",
+                                       "proxy-connection",
+                                       "transfer-encoding",
+                                       "upgrade"];
+
+/// Returns the name of a typed header field as sent in HTTP/2.
+///
+/// Names are sent in lowercase in HTTP/2.
+pub fn wire_name<H: Header>() -> String {
+    H::NAME.to_ascii_lowercase()
+}
+
+/// A malformed HTTP/2 message, [RFC7540 Section 8.1.2.6]
+///
+/// A malformed message is a stream error of type `PROTOCOL_ERROR`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MessageError {
+    /// A field name contains uppercase characters.
+    UppercaseName,
+    /// A field name is not a token.
+    InvalidName,
+    /// A field value contains NUL, CR or LF.
+    InvalidValue,
+    /// A connection-specific header field like `Connection` is present.
+    ConnectionSpecific,
+    /// `TE` contains a value other than `trailers`.
+    InvalidTe,
+    /// A pseudo-header field follows a regular header field.
+    MisplacedPseudoHeader,
+    /// A pseudo-header field is repeated.
+    RepeatedPseudoHeader,
+    /// A pseudo-header field is unknown or not allowed in the message.
+    UnknownPseudoHeader,
+    /// A required pseudo-header field is missing.
+    MissingPseudoHeader,
+    /// A pseudo-header field has an invalid value.
+    InvalidPseudoHeader,
+}
+
+impl Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            MessageError::UppercaseName => "uppercase header field name",
+            MessageError::InvalidName => "invalid header field name",
+            MessageError::InvalidValue => "invalid header field value",
+            MessageError::ConnectionSpecific => "connection-specific header field",
+            MessageError::InvalidTe => "TE other than trailers",
+            MessageError::MisplacedPseudoHeader => "pseudo-header field after regular field",
+            MessageError::RepeatedPseudoHeader => "repeated pseudo-header field",
+            MessageError::UnknownPseudoHeader => "unknown pseudo-header field",
+            MessageError::MissingPseudoHeader => "missing pseudo-header field",
+            MessageError::InvalidPseudoHeader => "invalid pseudo-header field",
+        })
+    }
+}
+
+// Checks the names and regular fields of a header block and returns
+// the pseudo-header fields in order.
+fn check_fields<'a>(headers: &'a Headers,
+                    allowed: &[&str])
+                    -> Result<Vec<(&'a str, &'a [u8])>, MessageError> {
+    let mut pseudo = Vec::new();
+    let mut regular = false;
+    for (name, lines) in headers {
+        if name.bytes().any(|c| c.is_ascii_uppercase()) {
+            return Err(MessageError::UppercaseName);
+        }
+        // [RFC9113 Section 8.2.1]
+        if lines.iter().any(|x| x.iter().any(|&c| c == b'\0' || c == b'\r' || c == b'\n')) {
+            return Err(MessageError::InvalidValue);
+        }
+        if let Some(pseudo_name) = name.strip_prefix(':') {
+            if !is_token(pseudo_name) {
+                return Err(MessageError::InvalidName);
+            }
+            if regular {
+                return Err(MessageError::MisplacedPseudoHeader);
+            }
+            if !allowed.contains(&name) {
+                return Err(MessageError::UnknownPseudoHeader);
+            }
+            if lines.len() != 1 {
+                return Err(MessageError::RepeatedPseudoHeader);
+            }
+            pseudo.push((name, &lines[0][..]));
+            continue;
+        }
+        if !is_token(name) {
+            return Err(MessageError::InvalidName);
+        }
+        regular = true;
+        if CONNECTION_SPECIFIC.contains(&name) {
+            return Err(MessageError::ConnectionSpecific);
+        }
+        if name == "te" && lines.iter().any(|x| !x.trim_ascii().eq_ignore_ascii_case(b"trailers")) {
+            return Err(MessageError::InvalidTe);
+        }
+    }
+    Ok(pseudo)
+}
+
+fn pseudo_value<'a>(pseudo: &[(&str, &'a [u8])], name: &str) -> Option<&'a [u8]> {
+    pseudo.iter().find(|x| x.0 == name).map(|x| x.1)
+}
+
+fn parse_value<T: str::FromStr>(value: &[u8]) -> Result<T, MessageError> {
+    str::from_utf8(value).ok().and_then(|x| x.parse().ok()).ok_or(MessageError::InvalidPseudoHeader)
+}
+
+// Appends the regular fields of an HTTP/1.1 message with lowercase
+// names, omitting connection-specific fields.
+fn append_fields(output: &mut Headers, fields: &Headers) {
+    let host = wire_name::<Host>();
+    for (name, lines) in fields {
+        let name = name.to_ascii_lowercase();
+        if name.starts_with(':') || name == host || CONNECTION_SPECIFIC.contains(&&name[..]) ||
+           fields.contains_token("Connection", &name) {
+            continue;
+        }
+        if name == "te" {
+            if fields.contains_token("TE", "trailers") {
+                output.append_raw(name, b"trailers".to_vec());
+            }
+            continue;
+        }
+        for line in lines {
+            output.append_raw(name.clone(), line.clone());
+        }
+    }
+}
+
+/// The request pseudo-header fields, [RFC7540 Section 8.1.2.3]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RequestHead {
+    /// The request method from `:method`.
+    pub method: Method,
+    /// The scheme of the target URL from `:scheme`, `None` for
+    /// `CONNECT` requests.
+    pub scheme: Option<String>,
+    /// The authority of the target URL from `:authority`.
+    pub authority: Option<HostPort>,
+    /// The request target, built from `:path` or for `CONNECT`
+    /// requests from `:authority`.
+    pub target: RequestTarget,
+}
+
+impl RequestHead {
+    /// Validates a received request header block and removes the
+    /// pseudo-header fields.
+    ///
+    /// `CONNECT` requests carry only `:method` and `:authority`, all
+    /// other requests need `:method`, `:scheme` and `:path`. A `:path`
+    /// of `*` is only allowed for `OPTIONS` requests.
+    pub fn from_headers(headers: &mut Headers) -> Result<RequestHead, MessageError> {
+        let head = {
+            let pseudo = check_fields(headers, &[METHOD, SCHEME, AUTHORITY, PATH])?;
+            let method: Method = parse_value(pseudo_value(&pseudo, METHOD)
+                .ok_or(MessageError::MissingPseudoHeader)?)?;
+            let authority = match pseudo_value(&pseudo, AUTHORITY) {
+                Some(value) => Some(parse_value(value)?),
+                None => None,
+            };
+            let scheme = pseudo_value(&pseudo, SCHEME);
+            let path = pseudo_value(&pseudo, PATH);
+            if method == Method::Connect {
+                if scheme.is_some() || path.is_some() {
+                    return Err(MessageError::UnknownPseudoHeader);
+                }
+                let authority: HostPort = authority.ok_or(MessageError::MissingPseudoHeader)?;
+                RequestHead {
+                    method,
+                    scheme: None,
+                    authority: Some(authority.clone()),
+                    target: RequestTarget::Authority(authority),
+                }
+            } else {
+                let (scheme, path) = match (scheme, path) {
+                    (Some(scheme), Some(path)) => (scheme, path),
+                    _ => return Err(MessageError::MissingPseudoHeader),
+                };
+                let scheme: String = parse_value(scheme)?;
+                let target = match parse_value(path)? {
+                    RequestTarget::Asterisk if method == Method::Options => RequestTarget::Asterisk,
+                    RequestTarget::Origin(path) => RequestTarget::Origin(path),
+                    _ => return Err(MessageError::InvalidPseudoHeader),
+                };
+                if !is_scheme(&scheme) {
+                    return Err(MessageError::InvalidPseudoHeader);
+                }
+                RequestHead {
+                    method,
+                    scheme: Some(scheme),
+                    authority,
+                    target,
+                }
+            }
+        };
+        for name in &[METHOD, SCHEME, AUTHORITY, PATH] {
+            headers.remove_raw(name);
+        }
+        Ok(head)
+    }
+
+    /// Builds the header block of a request from its pseudo-header
+    /// fields and HTTP/1.1 header fields.
+    ///
+    /// Names are lowercased and connection-specific fields removed.
+    /// `TE` is kept only as `TE: trailers`. If no authority is given
+    /// it is taken from an absolute-form target or the `Host` header
+    /// field. An absolute-form target is sent as its path and query.
+    pub fn to_headers(&self, fields: &Headers) -> Headers {
+        let mut headers = Headers::new();
+        headers.append_raw(METHOD, self.method.to_string().into_bytes());
+        if let Some(ref scheme) = self.scheme {
+            headers.append_raw(SCHEME, scheme.clone().into_bytes());
+        }
+        let authority = match (&self.authority, &self.target) {
+            (Some(authority), _) => Some(authority.clone()),
+            (None, RequestTarget::Absolute(url)) => HostPort::from_url(url),
+            _ => None,
+        };
+        let authority = authority.map(|x| x.to_string().into_bytes())
+            .or_else(|| fields.get_raw(Host::NAME).and_then(|x| x.first().cloned()));
+        if let Some(authority) = authority {
+            headers.append_raw(AUTHORITY, authority);
+        }
+        let path = match self.target {
+            RequestTarget::Authority(_) => None,
+            RequestTarget::Absolute(ref url) => {
+                Some(url.query().map_or_else(|| url.path().to_owned(),
+                                             |query| format!("{}?{}", url.path(), query)))
+            }
+            ref target => Some(target.to_string()),
+        };
+        if let Some(path) = path {
+            headers.append_raw(PATH, path.into_bytes());
+        }
+        append_fields(&mut headers, fields);
+        headers
+    }
+}
+
+/// The response pseudo-header fields, [RFC7540 Section 8.1.2.4]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResponseHead {
+    /// The response status from `:status`.
+    pub status: Status,
+}
+
+impl ResponseHead {
+    /// Validates a received response header block and removes the
+    /// pseudo-header fields.
+    pub fn from_headers(headers: &mut Headers) -> Result<ResponseHead, MessageError> {
+        let status = {
+            let pseudo = check_fields(headers, &[STATUS])?;
+            let value = pseudo_value(&pseudo, STATUS).ok_or(MessageError::MissingPseudoHeader)?;
+            if value.len() != 3 {
+                return Err(MessageError::InvalidPseudoHeader);
+            }
+            let code: u16 = parse_value(value)?;
+            if !(100..=599).contains(&code) {
+                return Err(MessageError::InvalidPseudoHeader);
+            }
+            Status::new(code)
+        };
+        headers.remove_raw(STATUS);
+        Ok(ResponseHead { status })
+    }
+
+    /// Builds the header block of a response from its status and
+    /// HTTP/1.1 header fields.
+    ///
+    /// Names are lowercased and connection-specific fields removed.
+    pub fn to_headers(&self, fields: &Headers) -> Headers {
+        let mut headers = Headers::new();
+        headers.append_raw(STATUS, self.status.to_raw().to_string().into_bytes());
+        append_fields(&mut headers, fields);
+        headers
+    }
+}
+
+/// Validates a received trailer block.
+///
+/// Trailers must not contain pseudo-header fields.
+pub fn validate_trailers(headers: &Headers) -> Result<(), MessageError> {
+    check_fields(headers, &[]).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use {Method, RequestTarget, Status};
+
+    use super::{MessageError, RequestHead, ResponseHead, validate_trailers};
+
+    fn headers(fields: &[(&str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for &(name, value) in fields {
+            headers.append_raw(name, value.as_bytes().to_vec());
+        }
+        headers
+    }
+
+    fn request(fields: &[(&str, &str)]) -> Result<RequestHead, MessageError> {
+        RequestHead::from_headers(&mut headers(fields))
+    }
+
+    #[test]
+    fn test_request_head() {
+        let mut fields = headers(&[(":method", "GET"),
+                                   (":scheme", "https"),
+                                   (":authority", "example.com"),
+                                   (":path", "/a?b"),
+                                   ("accept", "*/*")]);
+        let head = RequestHead::from_headers(&mut fields).unwrap();
+        assert_eq!(head.method, Method::Get);
+        assert_eq!(head.scheme, Some("https".to_owned()));
+        assert_eq!(head.target, RequestTarget::Origin("/a?b".to_owned()));
+        assert_eq!(fields.len(), 1);
+        let head = request(&[(":method", "CONNECT"), (":authority", "example.com:443")]).unwrap();
+        assert!(matches!(head.target, RequestTarget::Authority(_)));
+        assert!(request(&[(":method", "OPTIONS"), (":scheme", "http"), (":path", "*")]).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_requests() {
+        let get = [(":method", "GET"), (":scheme", "https"), (":path", "/")];
+        let with = |field: (&'static str, &'static str)| {
+            let mut fields = get.to_vec();
+            fields.push(field);
+            request(&fields)
+        };
+        assert_eq!(request(&get[..2]), Err(MessageError::MissingPseudoHeader));
+        assert_eq!(request(&[(":method", "GET"), (":scheme", "https"), (":path", "*")]),
+                   Err(MessageError::InvalidPseudoHeader));
+        assert_eq!(request(&[(":method", "CONNECT"), (":authority", "a:1"), (":path", "/")]),
+                   Err(MessageError::UnknownPseudoHeader));
+        assert_eq!(with((":status", "200")), Err(MessageError::UnknownPseudoHeader));
+        assert_eq!(with((":path", "/b")), Err(MessageError::RepeatedPseudoHeader));
+        assert_eq!(with(("Accept", "*/*")), Err(MessageError::UppercaseName));
+        assert_eq!(with(("a b", "c")), Err(MessageError::InvalidName));
+        assert_eq!(with(("connection", "close")), Err(MessageError::ConnectionSpecific));
+        assert_eq!(with(("te", "gzip")), Err(MessageError::InvalidTe));
+        assert_eq!(with(("te", "trailers")).map(|_| ()), Ok(()));
+        assert_eq!(with(("x-a", "b\r\nc: d")), Err(MessageError::InvalidValue));
+        assert_eq!(with(("x-a", "b\0")), Err(MessageError::InvalidValue));
+        assert_eq!(request(&[(":method", "GET"), ("accept", "*/*"), (":scheme", "https"), (":path", "/")]),
+                   Err(MessageError::MisplacedPseudoHeader));
+    }
+
+    #[test]
+    fn test_response_head() {
+        let head = ResponseHead::from_headers(&mut headers(&[(":status", "204")])).unwrap();
+        assert_eq!(head.status, Status::NO_CONTENT);
+        for status in &["20", "2000", "099", "600", "abc"] {
+            assert_eq!(ResponseHead::from_headers(&mut headers(&[(":status", status)])),
+                       Err(MessageError::InvalidPseudoHeader));
+        }
+        assert_eq!(validate_trailers(&headers(&[(":status", "200")])),
+                   Err(MessageError::UnknownPseudoHeader));
+        assert_eq!(validate_trailers(&headers(&[("grpc-status", "0")])), Ok(()));
+    }
+
+    #[test]
+    fn test_to_headers() {
+        let fields = headers(&[("Host", "example.com"),
+                               ("Connection", "close, X-Hop"),
+                               ("X-Hop", "1"),
+                               ("TE", "trailers, gzip"),
+                               ("Accept", "*/*")]);
+        let head = RequestHead {
+            method: Method::Get,
+            scheme: Some("https".to_owned()),
+            authority: None,
+            target: RequestTarget::Origin("/".to_owned()),
+        };
+        let block = head.to_headers(&fields);
+        let names: Vec<_> = block.iter().map(|x| x.0).collect();
+        assert_eq!(names, [":method", ":scheme", ":authority", ":path", "te", "accept"]);
+        assert_eq!(block.get_raw(":authority"), Some(&[b"example.com".to_vec()][..]));
+        assert_eq!(block.get_raw("te"), Some(&[b"trailers".to_vec()][..]));
+        let response = ResponseHead { status: Status::OK }.to_headers(&fields);
+        assert_eq!(response.get_raw(":status"), Some(&[b"200".to_vec()][..]));
+    }
+}
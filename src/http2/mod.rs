@@ -0,0 +1,198 @@
+//! HTTP/2 framing and header compression.
+//!
+//! An HTTP/2 connection starts with the client sending the
+//! [connection preface](fn.detect_preface.html), either right away
+//! or after [upgrading](struct.H2cRequest.html) an HTTP/1.1
+//! connection. Afterwards both
+//! endpoints exchange binary frames, see
+//! [`FrameReader`](struct.FrameReader.html) and
+//! [`encode_frame`](fn.encode_frame.html). Header fields are
+//! compressed with HPACK, see [`HpackDecoder`](struct.HpackDecoder.html)
+//! and [`HpackEncoder`](struct.HpackEncoder.html). Request and
+//! response metadata is carried in pseudo-header fields, see
+//! [`RequestHead`](struct.RequestHead.html) and
+//! [`ResponseHead`](struct.ResponseHead.html). Stream states and
+//! flow control are tracked by a [`Connection`](struct.Connection.html).
+//! [RFC7540]
+
+use std::fmt::{self, Display};
+use std::str;
+
+use Method;
+
+pub use self::connection::{Connection, Parameters, StreamState};
+pub use self::frame::{DEFAULT_MAX_FRAME_SIZE, Frame, FrameReader, MAX_FRAME_SIZE, Priority,
+                      Settings, decode_frame, encode_frame};
+pub use self::hpack::{HpackDecoder, HpackEncoder};
+pub use self::message::{AUTHORITY, METHOD, MessageError, PATH, RequestHead, ResponseHead, SCHEME,
+                        STATUS, validate_trailers, wire_name};
+pub use self::upgrade::{H2cRequest, UpgradeError, UpgradeResponse};
+
+mod connection;
+mod frame;
+mod hpack;
+mod message;
+mod upgrade;
+
+/// The connection preface sent by clients. [RFC7540 Section 3.5]
+///
+/// It looks like an HTTP/1.1 request with the method `PRI` so
+/// HTTP/1.1 servers reject it.
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// The result of looking for the connection preface.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Preface {
+    /// The input starts with the complete preface.
+    Complete,
+    /// The input is a prefix of the preface, more data is needed.
+    Incomplete,
+    /// The input does not start with the preface.
+    Invalid,
+}
+
+/// Checks if the input starts with the connection preface.
+///
+/// Servers that accept both HTTP/1.1 and HTTP/2 "with prior
+/// knowledge" use this on the first bytes of a connection. The input
+/// is rejected as soon as the request method is known not to be `PRI`.
+pub fn detect_preface(input: &[u8]) -> Preface {
+    let len = input.len().min(PREFACE.len());
+    if let Some(end) = input[..len].iter().position(|&c| c == b' ') {
+        let method = str::from_utf8(&input[..end]).ok().and_then(|x| x.parse().ok());
+        if method != Some(Method::Pri) {
+            return Preface::Invalid;
+        }
+    }
+    if input[..len] != PREFACE[..len] {
+        Preface::Invalid
+    } else if len < PREFACE.len() {
+        Preface::Incomplete
+    } else {
+        Preface::Complete
+    }
+}
+
+/// The endpoint of a connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    /// The endpoint that sent the preface.
+    Client,
+    /// The endpoint that received the preface.
+    Server,
+}
+
+/// An error code in `RST_STREAM` and `GOAWAY` frames, [RFC7540 Section 7]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ErrorCode(u32);
+
+impl ErrorCode {
+    /// 0x0: No Error, e.g. for a graceful shutdown
+    pub const NO_ERROR: ErrorCode = ErrorCode(0x0);
+    /// 0x1: Protocol Error
+    pub const PROTOCOL_ERROR: ErrorCode = ErrorCode(0x1);
+    /// 0x2: Internal Error
+    pub const INTERNAL_ERROR: ErrorCode = ErrorCode(0x2);
+    /// 0x3: Flow Control Error
+    pub const FLOW_CONTROL_ERROR: ErrorCode = ErrorCode(0x3);
+    /// 0x4: Settings Timeout, the peer did not acknowledge settings
+    pub const SETTINGS_TIMEOUT: ErrorCode = ErrorCode(0x4);
+    /// 0x5: Stream Closed, a frame was received on a half-closed stream
+    pub const STREAM_CLOSED: ErrorCode = ErrorCode(0x5);
+    /// 0x6: Frame Size Error
+    pub const FRAME_SIZE_ERROR: ErrorCode = ErrorCode(0x6);
+    /// 0x7: Refused Stream, the stream was not processed
+    pub const REFUSED_STREAM: ErrorCode = ErrorCode(0x7);
+    /// 0x8: Cancel, the stream is no longer needed
+    pub const CANCEL: ErrorCode = ErrorCode(0x8);
+    /// 0x9: Compression Error, the HPACK state is lost
+    pub const COMPRESSION_ERROR: ErrorCode = ErrorCode(0x9);
+    /// 0xa: Connect Error, the tunnel of a `CONNECT` request failed
+    pub const CONNECT_ERROR: ErrorCode = ErrorCode(0xa);
+    /// 0xb: Enhance Your Calm, the peer generates excessive load
+    pub const ENHANCE_YOUR_CALM: ErrorCode = ErrorCode(0xb);
+    /// 0xc: Inadequate Security, TLS requirements are not met
+    pub const INADEQUATE_SECURITY: ErrorCode = ErrorCode(0xc);
+    /// 0xd: HTTP/1.1 Required
+    pub const HTTP_1_1_REQUIRED: ErrorCode = ErrorCode(0xd);
+
+    /// Constructs an error code from a `u32` number.
+    ///
+    /// Unknown codes must be treated like `INTERNAL_ERROR`.
+    pub fn from_raw(code: u32) -> ErrorCode {
+        ErrorCode(code)
+    }
+
+    /// Returns the error code as a `u32` number.
+    pub fn to_raw(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.0 {
+            0x0 => "NO_ERROR",
+            0x1 => "PROTOCOL_ERROR",
+            0x2 => "INTERNAL_ERROR",
+            0x3 => "FLOW_CONTROL_ERROR",
+            0x4 => "SETTINGS_TIMEOUT",
+            0x5 => "STREAM_CLOSED",
+            0x6 => "FRAME_SIZE_ERROR",
+            0x7 => "REFUSED_STREAM",
+            0x8 => "CANCEL",
+            0x9 => "COMPRESSION_ERROR",
+            0xa => "CONNECT_ERROR",
+            0xb => "ENHANCE_YOUR_CALM",
+            0xc => "INADEQUATE_SECURITY",
+            0xd => "HTTP_1_1_REQUIRED",
+            code => return write!(f, "{:#x}", code),
+        })
+    }
+}
+
+/// A protocol violation by the peer. [RFC7540 Section 5.4]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The connection must be closed with a `GOAWAY` frame.
+    Connection(ErrorCode),
+    /// The stream with the given identifier must be reset with
+    /// a `RST_STREAM` frame, the connection stays usable.
+    Stream(u32, ErrorCode),
+}
+
+impl Error {
+    /// Returns the code sent to the peer for this error.
+    pub fn code(&self) -> ErrorCode {
+        match *self {
+            Error::Connection(code) | Error::Stream(_, code) => code,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Connection(code) => write!(f, "HTTP/2 connection error {}", code),
+            Error::Stream(id, code) => write!(f, "HTTP/2 stream error {} on stream {}", code, id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PREFACE, Preface, detect_preface};
+
+    #[test]
+    fn test_detect_preface() {
+        assert_eq!(detect_preface(PREFACE), Preface::Complete);
+        assert_eq!(detect_preface(&[PREFACE, b"\x00\x00"].concat()), Preface::Complete);
+        assert_eq!(detect_preface(b""), Preface::Incomplete);
+        assert_eq!(detect_preface(b"PRI * HTTP/2.0\r\n"), Preface::Incomplete);
+        assert_eq!(detect_preface(b"PRI * HTTP/1.1\r\n"), Preface::Invalid);
+        // Other methods are rejected as soon as they are complete.
+        assert_eq!(detect_preface(b"GET "), Preface::Invalid);
+        assert_eq!(detect_preface(b"P"), Preface::Incomplete);
+        assert_eq!(detect_preface(b"POST / HTTP/1.1\r\n"), Preface::Invalid);
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::{self, Display};
+
+use header::{Header, Headers, Http2Settings};
+use {Status, Version};
+use super::Settings;
+
+// The protocol token of HTTP/2 over cleartext TCP. [RFC7540 Section 3.2]
+const H2C: &str = "h2c";
+
+/// An error in an `h2c` upgrade.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpgradeError {
+    /// The request is not HTTP/1.1.
+    InvalidVersion,
+    /// `Upgrade: h2c` or `Connection: Upgrade, HTTP2-Settings` is missing.
+    MissingUpgrade,
+    /// There is not exactly one valid `HTTP2-Settings` header field.
+    InvalidSettings,
+}
+
+impl Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            UpgradeError::InvalidVersion => "h2c upgrade requires HTTP/1.1",
+            UpgradeError::MissingUpgrade => "missing h2c upgrade",
+            UpgradeError::InvalidSettings => "invalid HTTP2-Settings",
+        })
+    }
+}
+
+/// The status and header fields of an upgrade response.
+#[derive(Clone, Debug)]
+pub struct UpgradeResponse {
+    /// The response status.
+    pub status: Status,
+    /// The header fields to add to the response.
+    pub headers: Headers,
+}
+
+/// An HTTP/1.1 request upgrading the connection to cleartext
+/// HTTP/2, [RFC7540 Section 3.2]
+///
+/// After the `101 Switching Protocols` response the request is
+/// answered on stream 1, see
+/// [`Connection::upgraded`](struct.Connection.html#method.upgraded).
+/// A server reads the whole request content before switching.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct H2cRequest {
+    /// The settings of the client sent in `HTTP2-Settings`.
+    pub settings: Settings,
+}
+
+impl H2cRequest {
+    /// Constructs a request with the settings of the client.
+    pub fn new(settings: Settings) -> H2cRequest {
+        H2cRequest { settings }
+    }
+
+    /// Returns the header fields of the request.
+    ///
+    /// `Host` is not included.
+    pub fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw("Upgrade", vec![H2C.as_bytes().to_vec()]);
+        headers.set_raw("Connection", vec![b"Upgrade, HTTP2-Settings".to_vec()]);
+        headers.set_valid(&Http2Settings::from(self.settings));
+        headers
+    }
+
+    /// Validates a received upgrade request.
+    ///
+    /// On an error the server ignores the upgrade and answers the
+    /// request with HTTP/1.1.
+    pub fn validate(version: Version, headers: &Headers) -> Result<H2cRequest, UpgradeError> {
+        if version != Version::Http11 {
+            return Err(UpgradeError::InvalidVersion);
+        }
+        if !headers.contains_token("Upgrade", H2C) ||
+           !headers.contains_token("Connection", "Upgrade") {
+            return Err(UpgradeError::MissingUpgrade);
+        }
+        // The settings are hop-by-hop. [RFC7540 Section 3.2.1]
+        if !headers.contains_token("Connection", Http2Settings::NAME) {
+            return Err(UpgradeError::InvalidSettings);
+        }
+        let settings: Http2Settings = headers.get_without_base()
+            .and_then(Result::ok)
+            .ok_or(UpgradeError::InvalidSettings)?;
+        Ok(H2cRequest::new(settings.into()))
+    }
+
+    /// Builds the `101 Switching Protocols` response.
+    pub fn accept(&self) -> UpgradeResponse {
+        let mut headers = Headers::new();
+        headers.set_raw("Upgrade", vec![H2C.as_bytes().to_vec()]);
+        headers.set_raw("Connection", vec![b"Upgrade".to_vec()]);
+        UpgradeResponse {
+            status: Status::SWITCHING_PROTOCOLS,
+            headers,
+        }
+    }
+
+    /// Checks if the server accepted the upgrade.
+    ///
+    /// Returns `false` if the server answered with HTTP/1.1, an error
+    /// if it switched to a different protocol.
+    pub fn validate_response(&self, status: Status, headers: &Headers) -> Result<bool, UpgradeError> {
+        if status != Status::SWITCHING_PROTOCOLS {
+            return Ok(false);
+        }
+        if !headers.contains_token("Upgrade", H2C) ||
+           !headers.contains_token("Connection", "Upgrade") {
+            return Err(UpgradeError::MissingUpgrade);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use http2::Settings;
+    use {Status, Version};
+    use super::{H2cRequest, UpgradeError};
+
+    fn request() -> H2cRequest {
+        H2cRequest::new(Settings {
+            enable_push: Some(false),
+            initial_window_size: Some(1 << 20),
+            ..Settings::default()
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        let headers = request().headers();
+        assert_eq!(H2cRequest::validate(Version::Http11, &headers), Ok(request()));
+        assert_eq!(H2cRequest::validate(Version::Http10, &headers),
+                   Err(UpgradeError::InvalidVersion));
+
+        let mut headers = request().headers();
+        headers.set_raw("Connection", vec![b"Upgrade".to_vec()]);
+        assert_eq!(H2cRequest::validate(Version::Http11, &headers),
+                   Err(UpgradeError::InvalidSettings));
+        headers.remove_raw("Connection");
+        assert_eq!(H2cRequest::validate(Version::Http11, &headers),
+                   Err(UpgradeError::MissingUpgrade));
+    }
+
+    #[test]
+    fn test_validate_response() {
+        let request = request();
+        let response = request.accept();
+        assert_eq!(request.validate_response(response.status, &response.headers), Ok(true));
+        assert_eq!(request.validate_response(Status::OK, &Headers::new()), Ok(false));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Upgrade", vec![b"h2c".to_vec()]);
+        assert_eq!(request.validate_response(Status::SWITCHING_PROTOCOLS, &headers),
+                   Err(UpgradeError::MissingUpgrade));
+        headers.set_raw("Connection", vec![b"upgrade".to_vec()]);
+        assert_eq!(request.validate_response(Status::SWITCHING_PROTOCOLS, &headers), Ok(true));
+        headers.set_raw("Upgrade", vec![b"websocket".to_vec()]);
+        assert_eq!(request.validate_response(Status::SWITCHING_PROTOCOLS, &headers),
+                   Err(UpgradeError::MissingUpgrade));
+    }
+}
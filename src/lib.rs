@@ -4,8 +4,13 @@
 //! It contains types for
 //!
 //! * [request method](enum.Method.html),
+//! * [request target](enum.RequestTarget.html),
 //! * [response status](struct.Status.html),
-//! * [header fields](header/index.html) and
+//! * [header fields](header/index.html),
+//! * [form bodies](form/index.html),
+//! * [multipart bodies](multipart/index.html),
+//! * [WebSocket connections](websocket/index.html),
+//! * [HTTP/2 framing](http2/index.html) and
 //! * the [protocol version](enum.Version.html).
 //!
 //! Each type has useful methods that help to implement HTTP.
@@ -17,20 +22,72 @@
 
 #[cfg(feature="negotiation")]
 extern crate charsets;
+extern crate getrandom;
 extern crate httpdate;
 extern crate language_tags;
 #[macro_use]
 extern crate matches;
 extern crate media_types;
+#[cfg(test)]
+extern crate serde_json;
 extern crate url;
 
+#[cfg(feature="control")]
+mod altsvc;
+mod clock;
+#[cfg(feature="cors")]
+mod cors;
+#[cfg(feature="control")]
+mod expect;
+pub mod form;
 pub mod header;
+#[cfg(feature="http2")]
+pub mod http2;
 mod method;
+pub mod multipart;
+#[cfg(feature="control")]
+mod prefer;
+mod proxy;
+mod random;
+#[cfg(feature="ratelimit")]
+mod ratelimit;
+#[cfg(feature="control")]
+mod redirect;
+#[cfg(feature="control")]
+mod retry;
+#[cfg(feature="security")]
+mod security;
 mod status;
+mod target;
 mod util;
 mod version;
+#[cfg(feature="websocket")]
+pub mod websocket;
 
+#[cfg(feature="control")]
+pub use altsvc::AltSvcCache;
+pub use clock::{Clock, SystemClock};
+#[cfg(feature="cors")]
+pub use cors::{AllowedOrigins, CorsPolicy, CorsResponse};
+#[cfg(feature="control")]
+pub use expect::{ContinueState, ContinueWait, ExpectAction, check_expect};
 pub use header::Header;
 pub use method::Method;
+#[cfg(feature="control")]
+pub use prefer::{AppliedPreferences, apply_preferences};
+pub use proxy::{Cidr, Endpoint, TrustedProxies};
+pub use random::{Random, SystemRandom};
+#[cfg(feature="ratelimit")]
+pub use ratelimit::{Throttle, TokenBucket};
+#[cfg(feature="control")]
+pub use redirect::{RedirectError, RedirectPlanner, RedirectRequest};
+#[cfg(feature="control")]
+pub use retry::{RetryDecision, RetryPolicy};
+#[cfg(feature="security")]
+pub use security::SecurityHeaders;
 pub use status::{Status, StatusClass};
+pub use target::{RequestTarget, effective_request_url};
 pub use version::Version;
+#[cfg(feature="websocket")]
+pub use websocket::{HandshakeError, Negotiated, PerMessageDeflate, WebSocketRequest,
+                    WebSocketResponse};
@@ -0,0 +1,578 @@
+//! Parser and builder for `multipart/form-data` bodies.
+//!
+//! HTML forms with file uploads are submitted as multipart bodies.
+//! Each part carries a `Content-Disposition` header field with the
+//! name of the form field and optionally a filename and a
+//! `Content-Type`. [RFC7578](https://tools.ietf.org/html/rfc7578)
+//!
+//! The [`Parser`](struct.Parser.html) does no I/O. Data is fed to it in
+//! chunks of any size and it emits events as soon as they are complete.
+//! Limits protect servers against oversized uploads.
+
+use std::fmt::{self, Display};
+use std::mem;
+use std::str;
+
+use media_types::{self, Multipart};
+
+use header::Headers;
+use header::item::{Disposition, DispositionType, MediaType, Parameter};
+use util;
+
+/// Limits enforced by the [`Parser`](struct.Parser.html).
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The maximum number of parts.
+    pub max_parts: usize,
+    /// The maximum size of the header section of a part in bytes.
+    pub max_header_size: usize,
+    /// The maximum size of the content of a part in bytes.
+    pub max_field_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_parts: 128,
+            max_header_size: 8 * 1024,
+            max_field_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// An error encountered while parsing a multipart body.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The content type is not `multipart/form-data` with a valid boundary.
+    InvalidContentType,
+    /// The body does not follow the multipart syntax.
+    Malformed,
+    /// The body ended before the closing delimiter.
+    Incomplete,
+    /// There are more parts than allowed.
+    TooManyParts,
+    /// The header section of a part is too large.
+    HeaderTooLarge,
+    /// The content of a part is too large.
+    FieldTooLarge,
+    /// The content of a part contains the boundary.
+    BoundaryInContent,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::InvalidContentType => "content type is not multipart/form-data",
+            Error::Malformed => "malformed multipart body",
+            Error::Incomplete => "incomplete multipart body",
+            Error::TooManyParts => "too many parts",
+            Error::HeaderTooLarge => "part header section too large",
+            Error::FieldTooLarge => "part content too large",
+            Error::BoundaryInContent => "part content contains the boundary",
+        })
+    }
+}
+
+/// The header section of a part.
+#[derive(Clone, Debug)]
+pub struct Part {
+    /// All header fields of the part.
+    pub headers: Headers,
+    /// The parsed `Content-Disposition` with the field name.
+    pub disposition: Disposition,
+    /// The parsed `Content-Type` if present.
+    ///
+    /// Parts without a content type are `text/plain`.
+    pub content_type: Option<MediaType>,
+}
+
+impl Part {
+    /// Returns the name of the form field.
+    pub fn name(&self) -> &str {
+        self.disposition.name().unwrap_or("")
+    }
+
+    /// Returns the filename for file uploads.
+    pub fn filename(&self) -> Option<&str> {
+        self.disposition.filename()
+    }
+}
+
+/// An event emitted by the [`Parser`](struct.Parser.html).
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A new part starts.
+    Part(Part),
+    /// A chunk of content of the current part.
+    Data(Vec<u8>),
+    /// The current part is complete.
+    PartEnd,
+    /// The closing delimiter was found. The rest of the body is ignored.
+    End,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Preamble,
+    Delimiter,
+    Headers,
+    Body,
+    End,
+}
+
+/// A streaming parser for `multipart/form-data` bodies.
+///
+/// Feed the body with [`feed`](#method.feed) and call
+/// [`next_event`](#method.next_event) until it returns `None`, then
+/// feed more data. After the last chunk call [`finish`](#method.finish).
+#[derive(Clone, Debug)]
+pub struct Parser {
+    delimiter: Vec<u8>,
+    limits: Limits,
+    buffer: Vec<u8>,
+    state: State,
+    parts: usize,
+    field_size: usize,
+    finished: bool,
+}
+
+impl Parser {
+    /// Constructs a parser for the boundary of the media type.
+    pub fn new(media_type: &MediaType, limits: Limits) -> Result<Parser, Error> {
+        if !is_form_data(media_type) {
+            return Err(Error::InvalidContentType);
+        }
+        let boundary = media_type.boundary().map_err(|_| Error::InvalidContentType)?;
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Ok(Parser {
+            delimiter,
+            limits,
+            // The first delimiter is not preceded by a line break.
+            buffer: b"\r\n".to_vec(),
+            state: State::Preamble,
+            parts: 0,
+            field_size: 0,
+            finished: false,
+        })
+    }
+
+    /// Appends a chunk of the body.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Marks the end of the body.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Returns the next event.
+    ///
+    /// `None` is returned if more data is needed. After `finish` was
+    /// called an incomplete body is an error.
+    pub fn next_event(&mut self) -> Result<Option<Event>, Error> {
+        let event = match self.state {
+            State::Preamble => self.preamble(),
+            State::Delimiter => self.delimiter(),
+            State::Headers => self.headers(),
+            State::Body => self.body(),
+            State::End => return Ok(None),
+        }?;
+        if event.is_none() && self.finished {
+            return Err(Error::Incomplete);
+        }
+        Ok(event)
+    }
+
+    fn preamble(&mut self) -> Result<Option<Event>, Error> {
+        match find(&self.buffer, &self.delimiter) {
+            Some(i) => {
+                self.buffer.drain(..i + self.delimiter.len());
+                self.state = State::Delimiter;
+                self.delimiter()
+            }
+            None => {
+                let keep = self.buffer.len().min(self.delimiter.len() - 1);
+                let len = self.buffer.len();
+                self.buffer.drain(..len - keep);
+                Ok(None)
+            }
+        }
+    }
+
+    // Handles the rest of the line after a delimiter.
+    fn delimiter(&mut self) -> Result<Option<Event>, Error> {
+        if self.buffer.len() < 2 {
+            return Ok(None);
+        }
+        if self.buffer.starts_with(b"--") {
+            self.buffer.clear();
+            self.state = State::End;
+            return Ok(Some(Event::End));
+        }
+        let end = match find(&self.buffer, b"\r\n") {
+            Some(end) => end,
+            None if self.buffer.len() > self.limits.max_header_size => {
+                return Err(Error::Malformed)
+            }
+            None => return Ok(None),
+        };
+        if !self.buffer[..end].iter().all(|&c| c == b' ' || c == b'\t') {
+            return Err(Error::Malformed);
+        }
+        self.buffer.drain(..end + 2);
+        if self.parts == self.limits.max_parts {
+            return Err(Error::TooManyParts);
+        }
+        self.parts += 1;
+        self.state = State::Headers;
+        self.headers()
+    }
+
+    fn headers(&mut self) -> Result<Option<Event>, Error> {
+        let end = if self.buffer.starts_with(b"\r\n") {
+            Some(0)
+        } else {
+            find(&self.buffer, b"\r\n\r\n").map(|x| x + 2)
+        };
+        let end = match end {
+            Some(end) if end > self.limits.max_header_size => return Err(Error::HeaderTooLarge),
+            Some(end) => end,
+            None if self.buffer.len() > self.limits.max_header_size => {
+                return Err(Error::HeaderTooLarge)
+            }
+            None => return Ok(None),
+        };
+        let part = parse_part(&self.buffer[..end])?;
+        self.buffer.drain(..end + 2);
+        self.field_size = 0;
+        self.state = State::Body;
+        Ok(Some(Event::Part(part)))
+    }
+
+    fn body(&mut self) -> Result<Option<Event>, Error> {
+        let (len, complete) = match find(&self.buffer, &self.delimiter) {
+            Some(i) => (i, true),
+            None => (self.buffer.len().saturating_sub(self.delimiter.len() - 1), false),
+        };
+        self.field_size += len;
+        if self.field_size > self.limits.max_field_size {
+            return Err(Error::FieldTooLarge);
+        }
+        if len > 0 {
+            let rest = self.buffer.split_off(len);
+            let data = mem::replace(&mut self.buffer, rest);
+            return Ok(Some(Event::Data(data)));
+        }
+        if complete {
+            self.buffer.drain(..self.delimiter.len());
+            self.state = State::Delimiter;
+            return Ok(Some(Event::PartEnd));
+        }
+        Ok(None)
+    }
+}
+
+fn is_form_data(media_type: &MediaType) -> bool {
+    media_type.type_ == Some(Multipart) &&
+    media_type.subtype.as_ref().is_some_and(|x| {
+        x.0 == media_types::Standards && x.1.eq_ignore_ascii_case("form-data") && x.2.is_none()
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|x| x == needle)
+}
+
+fn parse_part(section: &[u8]) -> Result<Part, Error> {
+    let mut headers = Headers::new();
+    for line in section.split(|&c| c == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            return Err(Error::Malformed);
+        }
+        let colon = line.iter().position(|&c| c == b':').ok_or(Error::Malformed)?;
+        let name = str::from_utf8(&line[..colon]).map_err(|_| Error::Malformed)?;
+        if !util::is_token(name) {
+            return Err(Error::Malformed);
+        }
+        let value = line[colon + 1..]
+            .iter()
+            .cloned()
+            .skip_while(|&c| c == b' ' || c == b'\t')
+            .collect::<Vec<u8>>();
+        let end = value.iter().rposition(|&c| c != b' ' && c != b'\t').map_or(0, |x| x + 1);
+        headers.append_raw(name, value[..end].to_vec());
+    }
+    let mut disposition: Disposition = single_value(&headers, "Content-Disposition")
+        .ok_or(Error::Malformed)?
+        .parse()
+        .map_err(|_| Error::Malformed)?;
+    if disposition.kind != DispositionType::FormData || disposition.name().is_none() {
+        return Err(Error::Malformed);
+    }
+    for parameter in &mut disposition.parameters {
+        if !parameter.extended && (parameter.name == "name" || parameter.name == "filename") {
+            parameter.value = unescape(&parameter.value);
+        }
+    }
+    let content_type = match single_value(&headers, "Content-Type") {
+        Some(raw) => Some(raw.parse().map_err(|_| Error::Malformed)?),
+        None => None,
+    };
+    Ok(Part {
+        headers,
+        disposition,
+        content_type,
+    })
+}
+
+fn single_value<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    match headers.get_raw(name) {
+        Some(lines) if lines.len() == 1 => str::from_utf8(&lines[0]).ok(),
+        _ => None,
+    }
+}
+
+/// A builder for `multipart/form-data` bodies.
+///
+/// The boundary is generated randomly. It is long enough that it
+/// is not expected to occur in the content of any part, still every
+/// part is checked and rejected if it contains the boundary.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Constructs a builder with a generated boundary.
+    pub fn new() -> Builder {
+        Builder::with_boundary(generate_boundary())
+    }
+
+    /// Constructs a builder with the given boundary.
+    ///
+    /// # Panics
+    /// If the boundary is not valid.
+    pub fn with_boundary(boundary: String) -> Builder {
+        assert!(!boundary.is_empty() && boundary.len() <= 70 &&
+                boundary.bytes().all(|c| c.is_ascii_alphanumeric() || b"'()+_,-./:=?".contains(&c)),
+                "invalid boundary: {:?}",
+                boundary);
+        Builder {
+            boundary,
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns the boundary.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the media type for the `Content-Type` of the body.
+    pub fn media_type(&self) -> MediaType {
+        let mut media_type = MediaType::new(Multipart, media_types::Standards, "form-data");
+        media_type.parameters.insert("boundary".into(), self.boundary.clone().into());
+        media_type
+    }
+
+    /// Adds a text field.
+    pub fn text(&mut self, name: &str, value: &str) -> Result<&mut Builder, Error> {
+        let mut disposition = Disposition::new(DispositionType::FormData);
+        disposition.parameters.push(Parameter::new("name", escape(name)));
+        self.part(&disposition, None, value.as_bytes())
+    }
+
+    /// Adds a file.
+    pub fn file(&mut self,
+                name: &str,
+                filename: &str,
+                content_type: &MediaType,
+                data: &[u8])
+                -> Result<&mut Builder, Error> {
+        let mut disposition = Disposition::new(DispositionType::FormData);
+        disposition.parameters.push(Parameter::new("name", escape(name)));
+        disposition.parameters.push(Parameter::new("filename", escape(filename)));
+        self.part(&disposition, Some(content_type), data)
+    }
+
+    /// Adds a part with the given header fields.
+    ///
+    /// Nothing is added if the data contains the boundary.
+    pub fn part(&mut self,
+                disposition: &Disposition,
+                content_type: Option<&MediaType>,
+                data: &[u8])
+                -> Result<&mut Builder, Error> {
+        // The data is preceded by an empty line, so a boundary at its
+        // start is a delimiter too.
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(self.boundary.as_bytes());
+        if find(data, &delimiter).is_some() || data.starts_with(&delimiter[2..]) {
+            return Err(Error::BoundaryInContent);
+        }
+        self.body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+        self.body.extend_from_slice(format!("Content-Disposition: {}\r\n", disposition).as_bytes());
+        if let Some(content_type) = content_type {
+            self.body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        self.body.extend_from_slice(b"\r\n");
+        self.body.extend_from_slice(data);
+        self.body.extend_from_slice(b"\r\n");
+        Ok(self)
+    }
+
+    /// Writes the closing delimiter and returns the body.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        self.body
+    }
+}
+
+// Field names and filenames are sent as UTF-8, only the characters
+// breaking the header syntax are percent-encoded. [RFC7578 Section 2]
+fn escape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '"' || (c.is_ascii_control() && c != '\t') {
+            output.push_str(&format!("%{:02X}", c as u8));
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+// Reverses `escape`, other percent signs are kept as they are.
+fn unescape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('%') {
+        output.push_str(&rest[..i]);
+        let c = rest.get(i + 1..i + 3)
+            .filter(|x| x.bytes().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|x| u8::from_str_radix(x, 16).ok())
+            .map(char::from)
+            .filter(|&c| c == '"' || (c.is_ascii_control() && c != '\t'));
+        match c {
+            Some(c) => {
+                output.push(c);
+                rest = &rest[i + 3..];
+            }
+            None => {
+                output.push('%');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn generate_boundary() -> String {
+    let mut bytes = [0; 16];
+    util::random_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|x| format!("{:02x}", x)).collect();
+    format!("----httptypes{}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use header::item::MediaType;
+    use super::{Builder, Error, Event, Limits, Parser, escape, unescape};
+
+    fn parse(media_type: &MediaType, body: &[u8], chunk: usize, limits: Limits) -> Result<Vec<Event>, Error> {
+        let mut parser = Parser::new(media_type, limits)?;
+        let mut events = Vec::new();
+        for data in body.chunks(chunk) {
+            parser.feed(data);
+            while let Some(event) = parser.next_event()? {
+                events.push(event);
+            }
+        }
+        parser.finish();
+        while let Some(event) = parser.next_event()? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    // Joins the data events of each part.
+    fn fields(events: Vec<Event>) -> Vec<(String, Option<String>, Vec<u8>)> {
+        let mut fields = Vec::new();
+        for event in events {
+            match event {
+                Event::Part(part) => {
+                    fields.push((part.name().to_owned(), part.filename().map(str::to_owned), Vec::new()))
+                }
+                Event::Data(data) => fields.last_mut().unwrap().2.extend(data),
+                Event::PartEnd | Event::End => (),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut builder = Builder::with_boundary("AaB03x".to_owned());
+        builder.text("field \"1\"\r\n", "Joe Blow").unwrap()
+            .text("a\x01b\x7f", "").unwrap()
+            .file("pics", "file%1.txt", &"text/plain".parse().unwrap(), b"--AaB03 x\r\n-AaB03x").unwrap();
+        let media_type = builder.media_type();
+        let body = builder.finish();
+        for &chunk in &[1, 7, body.len()] {
+            let fields = fields(parse(&media_type, &body, chunk, Limits::default()).unwrap());
+            assert_eq!(fields,
+                       vec![("field \"1\"\r\n".to_owned(), None, b"Joe Blow".to_vec()),
+                            ("a\x01b\x7f".to_owned(), None, Vec::new()),
+                            ("pics".to_owned(),
+                             Some("file%1.txt".to_owned()),
+                             b"--AaB03 x\r\n-AaB03x".to_vec())]);
+        }
+    }
+
+    #[test]
+    fn test_boundary_in_content() {
+        let mut builder = Builder::with_boundary("AaB03x".to_owned());
+        assert_eq!(builder.text("a", "x\r\n--AaB03x--").err(), Some(Error::BoundaryInContent));
+        assert_eq!(builder.text("a", "--AaB03x").err(), Some(Error::BoundaryInContent));
+        assert!(builder.text("a", "x--AaB03x").is_ok());
+        assert!(Builder::new().boundary() != Builder::new().boundary());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut builder = Builder::with_boundary("b".to_owned());
+        builder.text("a", "12345").unwrap().text("b", "1").unwrap();
+        let media_type = builder.media_type();
+        let body = builder.finish();
+        let limits = Limits { max_field_size: 4, ..Limits::default() };
+        assert_eq!(parse(&media_type, &body, 3, limits).err(), Some(Error::FieldTooLarge));
+        let limits = Limits { max_parts: 1, ..Limits::default() };
+        assert_eq!(parse(&media_type, &body, 3, limits).err(), Some(Error::TooManyParts));
+        assert_eq!(parse(&media_type, &body[..body.len() - 4], 3, Limits::default()).err(),
+                   Some(Error::Incomplete));
+        assert_eq!(Parser::new(&"text/plain".parse().unwrap(), Limits::default()).err(),
+                   Some(Error::InvalidContentType));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a%22b%0d%0A%"), "a\"b\r\n%");
+        assert_eq!(unescape("100%25 %2"), "100%25 %2");
+        assert_eq!(escape("a\x01\tb\x7f\"\r\n"), "a%01\tb%7F%22%0D%0A");
+        assert_eq!(unescape("%01%7f%09%+1"), "\x01\x7f%09%+1");
+    }
+}
@@ -0,0 +1,111 @@
+use std::io;
+
+use header::{Headers, Prefer, PreferenceApplied, Vary};
+use header::item::{Preference, PreferenceKind};
+use Header;
+
+/// The preferences a server honored, see
+/// [`apply_preferences`](fn.apply_preferences.html).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AppliedPreferences {
+    /// The honored preferences in request order.
+    pub applied: Vec<PreferenceKind>,
+    /// Set if the response depends on `Prefer` and must be sent
+    /// with `Vary: Prefer`.
+    pub vary: bool,
+}
+
+impl AppliedPreferences {
+    /// Sets `Preference-Applied` and adds `Prefer` to `Vary` if needed.
+    pub fn set_headers(&self, headers: &mut Headers) -> io::Result<()> {
+        if !self.applied.is_empty() {
+            headers.set(&PreferenceApplied::from(self.applied.clone()))?;
+        }
+        if self.vary && !headers.contains_token(Vary::NAME, Prefer::NAME) &&
+           !headers.contains_token(Vary::NAME, "*") {
+            headers.append_raw(Vary::NAME, Prefer::NAME.as_bytes().to_vec());
+        }
+        Ok(())
+    }
+}
+
+/// Decides which preferences of a request are honored,
+/// [RFC7240 Section 2]
+///
+/// `honor` is called once for the first instance of each preference,
+/// later instances are ignored. All preferences but `wait` change the
+/// response, so honoring them requires `Vary: Prefer`. Servers that
+/// support such preferences send `Vary: Prefer` on responses to requests
+/// without them as well.
+pub fn apply_preferences<F>(prefer: &Prefer, mut honor: F) -> AppliedPreferences
+    where F: FnMut(&Preference) -> bool
+{
+    let preferences: Vec<Preference> = prefer.clone().into();
+    let mut result = AppliedPreferences::default();
+    for (i, preference) in preferences.iter().enumerate() {
+        if preferences[..i].iter().any(|x| x.kind.name() == preference.kind.name()) {
+            continue;
+        }
+        if honor(preference) {
+            result.vary |= !matches!(preference.kind, PreferenceKind::Wait(_));
+            result.applied.push(preference.kind.clone());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Headers, Prefer};
+    use header::item::PreferenceKind;
+    use super::apply_preferences;
+
+    fn prefer(value: &str) -> Prefer {
+        let mut headers = Headers::new();
+        headers.set_raw("Prefer", vec![value.as_bytes().to_vec()]);
+        headers.get_without_base().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_apply_preferences() {
+        let prefer = prefer("wait=5, return=minimal, return=representation, foo");
+        let mut seen = Vec::new();
+        let applied = apply_preferences(&prefer, |x| {
+            seen.push(x.kind.clone());
+            x.kind.name() != "foo"
+        });
+        assert_eq!(seen.len(), 3);
+        assert_eq!(applied.applied.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+                   vec!["wait=5", "return=minimal"]);
+        assert!(applied.vary);
+
+        let mut headers = Headers::new();
+        headers.set_raw("Vary", vec![b"Accept".to_vec()]);
+        applied.set_headers(&mut headers).unwrap();
+        assert_eq!(headers.get_raw("Preference-Applied").unwrap(),
+                   &[b"wait=5, return=minimal".to_vec()][..]);
+        assert_eq!(headers.get_raw("Vary").unwrap(),
+                   &[b"Accept".to_vec(), b"Prefer".to_vec()][..]);
+        applied.set_headers(&mut headers).unwrap();
+        assert_eq!(headers.get_raw("Vary").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_wait_only() {
+        let applied = apply_preferences(&prefer("wait=5"), |_| true);
+        assert!(!applied.vary);
+        let mut headers = Headers::new();
+        applied.set_headers(&mut headers).unwrap();
+        assert!(!headers.contains("Vary"));
+    }
+
+    #[test]
+    fn test_invalid_extension() {
+        let mut applied = apply_preferences(&prefer("respond-async"), |_| true);
+        applied.applied.push(PreferenceKind::Extension("a b".to_owned(), None));
+        let mut headers = Headers::new();
+        applied.set_headers(&mut headers).unwrap();
+        assert_eq!(headers.get_raw("Preference-Applied").unwrap(),
+                   &[b"respond-async, a%20b".to_vec()][..]);
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use header::item::{ForwardedElement, HostPort, Node, Url};
+use util::is_scheme;
+
+/// A range of IP addresses in CIDR notation like `10.0.0.0/8`.
+///
+/// A single address without a prefix length is treated as a
+/// network containing only this address.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Constructs a new network from an address and a prefix length.
+    ///
+    /// # Panics
+    /// If the prefix is longer than the address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Cidr {
+        assert!(prefix <= max_prefix(addr), "prefix is too long");
+        Cidr {
+            addr,
+            prefix,
+        }
+    }
+
+    /// Checks if the address is part of the network.
+    ///
+    /// IPv4-mapped IPv6 addresses are matched against IPv4 networks.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                prefix_eq(&net.octets(), &addr.octets(), self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                prefix_eq(&net.octets(), &addr.octets(), self.prefix)
+            }
+            (IpAddr::V4(_), IpAddr::V6(addr)) => {
+                addr.to_ipv4_mapped().is_some_and(|x| self.contains(IpAddr::V4(x)))
+            }
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Cidr, ()> {
+        let mut iter = s.splitn(2, '/');
+        let addr: IpAddr = iter.next().unwrap().parse().map_err(|_| ())?;
+        let prefix = match iter.next() {
+            Some(raw) => raw.parse().map_err(|_| ())?,
+            None => max_prefix(addr),
+        };
+        if prefix > max_prefix(addr) {
+            return Err(());
+        }
+        Ok(Cidr::new(addr, prefix))
+    }
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn prefix_eq(a: &[u8], b: &[u8], prefix: u8) -> bool {
+    let bytes = (prefix / 8) as usize;
+    let bits = prefix % 8;
+    if a[..bytes] != b[..bytes] {
+        return false;
+    }
+    bits == 0 || (a[bytes] ^ b[bytes]) >> (8 - bits) == 0
+}
+
+/// The client of a request together with the scheme and host it used.
+///
+/// Without proxies this is the peer of the connection. Behind proxies
+/// it is recovered from the `Forwarded` header field by
+/// [`TrustedProxies`](struct.TrustedProxies.html).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Endpoint {
+    /// The address or identifier of the client.
+    pub client: Node,
+    /// The scheme used by the client, e.g. `https`.
+    pub scheme: String,
+    /// The host the client requested including an optional port.
+    pub host: String,
+}
+
+impl Endpoint {
+    /// Constructs the endpoint for a direct connection.
+    pub fn new<S, H>(peer: IpAddr, scheme: S, host: H) -> Endpoint
+        where S: Into<String>,
+              H: Into<String>
+    {
+        Endpoint {
+            client: peer.into(),
+            scheme: scheme.into(),
+            host: host.into(),
+        }
+    }
+
+    /// Builds the base URL seen by the client.
+    ///
+    /// The URL can be passed to `Header::parse`. Forwarded values are
+    /// controlled by the client, so the scheme and the host are
+    /// validated before they are combined.
+    pub fn base_url(&self) -> Result<Url, ()> {
+        if !is_scheme(&self.scheme) {
+            return Err(());
+        }
+        let host: HostPort = self.host.parse()?;
+        let url = Url::parse(&format!("{}://{}/", self.scheme, host)).map_err(|_| ())?;
+        if url.cannot_be_a_base() || url.host().is_none() {
+            return Err(());
+        }
+        Ok(url)
+    }
+}
+
+/// A set of proxies trusted to report the client of a request.
+///
+/// The forwarded elements are walked from the right. Every element
+/// added by a trusted proxy is accepted, the walk ends at the first
+/// client that is not a trusted proxy itself.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    networks: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// Constructs a new set of trusted proxies from networks.
+    pub fn new(networks: Vec<Cidr>) -> TrustedProxies {
+        TrustedProxies { networks }
+    }
+
+    /// Adds a network of trusted proxies.
+    pub fn add(&mut self, network: Cidr) {
+        self.networks.push(network)
+    }
+
+    /// Checks if the address belongs to a trusted proxy.
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        self.networks.iter().any(|x| x.contains(addr))
+    }
+
+    /// Resolves the effective client from the elements of a `Forwarded`
+    /// header field.
+    ///
+    /// The connection describes the peer and the scheme and host
+    /// of the connection to this server.
+    pub fn resolve(&self, connection: Endpoint, elements: &[ForwardedElement]) -> Endpoint {
+        let mut current = connection;
+        for element in elements.iter().rev() {
+            if !current.client.ip().is_some_and(|x| self.is_trusted(x)) {
+                break;
+            }
+            let client = match element.for_ {
+                Some(ref client) => client.clone(),
+                None => break,
+            };
+            current = Endpoint {
+                client,
+                scheme: element.proto.clone().unwrap_or(current.scheme),
+                host: element.host.clone().unwrap_or(current.host),
+            };
+        }
+        current
+    }
+
+    /// Resolves the effective client from the legacy `X-Forwarded-For`,
+    /// `X-Forwarded-Proto` and `X-Forwarded-Host` header fields.
+    ///
+    /// Protocols and hosts are matched with the nodes from the right.
+    /// If there are fewer of them the leftmost value is used for the
+    /// remaining nodes.
+    pub fn resolve_legacy(&self,
+                          connection: Endpoint,
+                          for_: &[Node],
+                          proto: &[String],
+                          host: &[String])
+                          -> Endpoint {
+        let elements: Vec<ForwardedElement> = for_.iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let from_right = for_.len() - 1 - i;
+                ForwardedElement {
+                    by: None,
+                    for_: Some(node.clone()),
+                    host: nth_from_right(host, from_right),
+                    proto: nth_from_right(proto, from_right),
+                }
+            })
+            .collect();
+        self.resolve(connection, &elements)
+    }
+}
+
+fn nth_from_right(values: &[String], n: usize) -> Option<String> {
+    if n < values.len() {
+        Some(values[values.len() - 1 - n].clone())
+    } else {
+        values.first().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use header::item::ForwardedElement;
+    use super::{Cidr, Endpoint, TrustedProxies};
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr() {
+        let net: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(net.contains(ip("10.1.2.3")));
+        assert!(!net.contains(ip("11.0.0.1")));
+        assert!(net.contains(ip("::ffff:10.0.0.1")));
+        let single: Cidr = "2001:db8::1".parse().unwrap();
+        assert_eq!(single.to_string(), "2001:db8::1/128");
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let proxies = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+        let connection = Endpoint::new(ip("10.0.0.1"), "http", "internal");
+        let elements: Vec<ForwardedElement> = vec![
+            "for=192.0.2.60;proto=https;host=example.com".parse().unwrap(),
+        ];
+        let endpoint = proxies.resolve(connection.clone(), &elements);
+        assert_eq!(endpoint.client.ip(), Some(ip("192.0.2.60")));
+        assert_eq!(endpoint.base_url().unwrap().as_str(), "https://example.com/");
+
+        let untrusted = Endpoint::new(ip("192.0.2.1"), "http", "internal");
+        assert_eq!(proxies.resolve(untrusted.clone(), &elements), untrusted);
+    }
+
+    #[test]
+    fn test_base_url_rejects_invalid_host() {
+        for host in &["evil.com/path", "user@evil.com", "evil.com?x", "evil.com#x", ""] {
+            let endpoint = Endpoint::new(ip("192.0.2.1"), "https", *host);
+            assert_eq!(endpoint.base_url(), Err(()), "{}", host);
+        }
+        let endpoint = Endpoint::new(ip("192.0.2.1"), "http://evil.com/#", "example.com");
+        assert_eq!(endpoint.base_url(), Err(()));
+        let endpoint = Endpoint::new(ip("192.0.2.1"), "https", "[::1]:8443");
+        assert_eq!(endpoint.base_url().unwrap().as_str(), "https://[::1]:8443/");
+    }
+}
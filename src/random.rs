@@ -0,0 +1,43 @@
+use util::random_bytes;
+
+/// A source of random numbers.
+///
+/// Types that add jitter take a source so tests can control it.
+/// Closures returning an `f64` are sources too.
+pub trait Random {
+    /// Returns a number in the range `0.0..1.0`.
+    fn fraction(&self) -> f64;
+}
+
+/// The default source, backed by the random number generator of the
+/// operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemRandom;
+
+impl Random for SystemRandom {
+    fn fraction(&self) -> f64 {
+        let mut bytes = [0; 8];
+        random_bytes(&mut bytes);
+        // Use the 53 bits an `f64` can represent exactly.
+        (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl<F: Fn() -> f64> Random for F {
+    fn fraction(&self) -> f64 {
+        self()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Random, SystemRandom};
+
+    #[test]
+    fn test_system_random() {
+        for _ in 0..100 {
+            let x = SystemRandom.fraction();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clock::{Clock, SystemClock};
+use header::{Header, Headers, QuotaPolicy, RateLimit, RateLimitPolicy, RetryAfter, ServiceLimit,
+             XRateLimitLimit, XRateLimitRemaining, XRateLimitReset};
+use header::structured::MAX_INTEGER;
+use Status;
+
+// Larger `X-RateLimit-Reset` values are Unix timestamps, not seconds.
+const TIMESTAMP_THRESHOLD: u64 = 1_000_000_000;
+
+// The largest quota and window the header fields can carry.
+const MAX_QUOTA: u64 = MAX_INTEGER as u64;
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// A token bucket limiting the requests of a client.
+///
+/// The bucket holds up to `quota` tokens and every request takes one.
+/// Tokens are added back one by one so that the bucket fills up again
+/// within `window`. The state is announced to the client in the
+/// `RateLimit-Policy` and `RateLimit` header fields and their legacy
+/// `X-RateLimit-*` forms.
+#[derive(Clone, Debug)]
+pub struct TokenBucket<C: Clock = SystemClock> {
+    clock: C,
+    name: String,
+    quota: u64,
+    window: Duration,
+    tokens: u64,
+    updated: SystemTime,
+}
+
+impl TokenBucket {
+    /// Constructs a full bucket using the system clock.
+    ///
+    /// # Panics
+    /// If `quota` is zero.
+    pub fn new<N: Into<String>>(name: N, quota: u64, window: Duration) -> TokenBucket {
+        TokenBucket::with_clock(name, quota, window, SystemClock)
+    }
+}
+
+impl<C: Clock> TokenBucket<C> {
+    /// Constructs a full bucket using the given clock.
+    ///
+    /// The quota and the window in seconds are capped at
+    /// 999,999,999,999,999, the largest integer the header fields
+    /// can carry.
+    ///
+    /// # Panics
+    /// If `quota` is zero.
+    pub fn with_clock<N: Into<String>>(name: N, quota: u64, window: Duration, clock: C) -> TokenBucket<C> {
+        assert!(quota > 0, "quota must not be zero");
+        let quota = quota.min(MAX_QUOTA);
+        let updated = clock.now();
+        TokenBucket {
+            clock,
+            name: name.into(),
+            quota,
+            window: window.min(Duration::from_secs(MAX_QUOTA)),
+            tokens: quota,
+            updated,
+        }
+    }
+
+    // The time to add `n` tokens.
+    fn intervals(&self, n: u64) -> Duration {
+        let nanos = (self.window.as_nanos() / u128::from(self.quota)).max(1) * u128::from(n);
+        Duration::from_nanos(nanos.min(u128::from(u64::MAX)) as u64)
+    }
+
+    fn refill(&mut self) -> SystemTime {
+        let now = self.clock.now();
+        if self.tokens >= self.quota {
+            self.updated = now;
+            return now;
+        }
+        let elapsed = now.duration_since(self.updated).unwrap_or_default();
+        let added = (elapsed.as_nanos() / self.intervals(1).as_nanos()).min(u128::from(self.quota)) as u64;
+        self.tokens = (self.tokens + added).min(self.quota);
+        if self.tokens == self.quota {
+            self.updated = now;
+        } else {
+            self.updated += self.intervals(added);
+        }
+        now
+    }
+
+    /// Takes a token for a request.
+    ///
+    /// Returns `false` if the bucket is empty, the server responds
+    /// with `429 Too Many Requests`.
+    pub fn acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+
+    /// Returns the number of tokens left.
+    pub fn remaining(&mut self) -> u64 {
+        self.refill();
+        self.tokens
+    }
+
+    /// Returns the time until the bucket is full again.
+    pub fn reset(&mut self) -> Duration {
+        let now = self.refill();
+        let elapsed = now.duration_since(self.updated).unwrap_or_default();
+        self.intervals(self.quota - self.tokens).saturating_sub(elapsed)
+    }
+
+    /// Returns the time until the next token is added, zero if the
+    /// bucket is not empty.
+    pub fn next_token(&mut self) -> Duration {
+        let now = self.refill();
+        if self.tokens > 0 {
+            return Duration::from_secs(0);
+        }
+        self.intervals(1).saturating_sub(now.duration_since(self.updated).unwrap_or_default())
+    }
+
+    /// Sets the rate limit header fields of a response.
+    ///
+    /// If the bucket is empty `Retry-After` tells the client when
+    /// to send the next request.
+    pub fn set_headers(&mut self, headers: &mut Headers) {
+        let remaining = self.remaining();
+        let reset = ceil_secs(self.reset());
+        let policy = RateLimitPolicy {
+            policies: vec![QuotaPolicy::new(self.name.clone(), self.quota, self.window)],
+        };
+        let limit = RateLimit {
+            limits: vec![ServiceLimit {
+                policy: self.name.clone(),
+                remaining,
+                reset: Some(Duration::from_secs(reset)),
+                partition_key: None,
+            }],
+        };
+        headers.set_valid(&policy);
+        headers.set_valid(&limit);
+        headers.set_valid(&XRateLimitLimit::from(self.quota));
+        headers.set_valid(&XRateLimitRemaining::from(remaining));
+        headers.set_valid(&XRateLimitReset::from(reset));
+        if remaining == 0 {
+            let delay = Duration::from_secs(ceil_secs(self.next_token()).max(1));
+            headers.set_valid(&RetryAfter::from(delay));
+        }
+    }
+}
+
+/// Delays the requests of a client to stay within the rate limits
+/// announced by the server.
+///
+/// After a `429 Too Many Requests` or `503 Service Unavailable`
+/// response the client waits for `Retry-After`. Otherwise it waits
+/// until the quota resets once `RateLimit` or `X-RateLimit-Remaining`
+/// reach zero.
+#[derive(Clone, Debug)]
+pub struct Throttle<C: Clock = SystemClock> {
+    clock: C,
+    not_before: Option<SystemTime>,
+}
+
+impl Throttle {
+    /// Constructs a throttle using the system clock.
+    pub fn new() -> Throttle {
+        Throttle::with_clock(SystemClock)
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Throttle {
+        Throttle::new()
+    }
+}
+
+impl<C: Clock> Throttle<C> {
+    /// Constructs a throttle using the given clock.
+    pub fn with_clock(clock: C) -> Throttle<C> {
+        Throttle {
+            clock,
+            not_before: None,
+        }
+    }
+
+    /// Updates the throttle with a response.
+    ///
+    /// Delays too large to represent are ignored.
+    pub fn update(&mut self, status: Status, headers: &Headers) {
+        let now = self.clock.now();
+        let limited = status == Status::TOO_MANY_REQUESTS;
+        let retry_after = match get::<RetryAfter>(headers) {
+            Some(_) if !limited && status != Status::SERVICE_UNAVAILABLE => None,
+            Some(RetryAfter::Delay(delay)) => now.checked_add(delay),
+            Some(RetryAfter::Date(date)) => Some(date.into()),
+            None => None,
+        };
+        let reset = retry_after.or_else(|| {
+            // Wait for the latest reset of an exhausted policy, or any
+            // policy if the request was rejected.
+            get::<RateLimit>(headers).and_then(|x| {
+                x.limits
+                    .iter()
+                    .filter(|x| limited || x.remaining == 0)
+                    .filter_map(|x| x.reset)
+                    .max()
+                    .and_then(|x| now.checked_add(x))
+            })
+        }).or_else(|| {
+            let remaining = get::<XRateLimitRemaining>(headers).map(u64::from);
+            if !limited && remaining != Some(0) {
+                return None;
+            }
+            get::<XRateLimitReset>(headers).and_then(|x| match u64::from(x) {
+                secs if secs >= TIMESTAMP_THRESHOLD => {
+                    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+                }
+                secs => now.checked_add(Duration::from_secs(secs)),
+            })
+        });
+        if let Some(reset) = reset {
+            self.not_before = Some(self.not_before.map_or(reset, |x| x.max(reset)));
+        }
+    }
+
+    /// Returns the time the next request is allowed.
+    pub fn next_request(&self) -> SystemTime {
+        let now = self.clock.now();
+        self.not_before.map_or(now, |x| x.max(now))
+    }
+
+    /// Returns the time to wait before the next request.
+    pub fn delay(&self) -> Duration {
+        let now = self.clock.now();
+        self.not_before.and_then(|x| x.duration_since(now).ok()).unwrap_or_default()
+    }
+}
+
+fn get<H: Header>(headers: &Headers) -> Option<H> {
+    headers.get_without_base().and_then(Result::ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use header::Headers;
+    use Status;
+    use super::{Throttle, TokenBucket};
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Cell::new(UNIX_EPOCH);
+        let mut bucket = TokenBucket::with_clock("default", 2, Duration::from_secs(10), || now.get());
+        assert!(bucket.acquire());
+        assert!(bucket.acquire());
+        assert!(!bucket.acquire());
+        assert_eq!(bucket.reset(), Duration::from_secs(10));
+        assert_eq!(bucket.next_token(), Duration::from_secs(5));
+
+        let mut headers = Headers::new();
+        bucket.set_headers(&mut headers);
+        assert_eq!(headers.get_raw("RateLimit-Policy").unwrap(),
+                   &[b"\"default\";q=2;w=10".to_vec()][..]);
+        assert_eq!(headers.get_raw("RateLimit").unwrap(),
+                   &[b"\"default\";r=0;t=10".to_vec()][..]);
+        assert_eq!(headers.get_raw("Retry-After").unwrap(), &[b"5".to_vec()][..]);
+
+        now.set(UNIX_EPOCH + Duration::from_secs(6));
+        assert_eq!(bucket.remaining(), 1);
+        assert_eq!(bucket.reset(), Duration::from_secs(4));
+        now.set(UNIX_EPOCH + Duration::from_secs(60));
+        assert_eq!(bucket.remaining(), 2);
+    }
+
+    #[test]
+    fn test_token_bucket_limits() {
+        let now = UNIX_EPOCH;
+        let mut bucket = TokenBucket::with_clock("huge", u64::MAX, Duration::from_secs(u64::MAX),
+                                                 move || now);
+        assert!(bucket.acquire());
+        let mut headers = Headers::new();
+        bucket.set_headers(&mut headers);
+        assert_eq!(headers.get_raw("RateLimit-Policy").unwrap(),
+                   &[b"\"huge\";q=999999999999999;w=999999999999999".to_vec()][..]);
+        assert_eq!(headers.get_raw("X-RateLimit-Remaining").unwrap(),
+                   &[b"999999999999998".to_vec()][..]);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let now = Cell::new(UNIX_EPOCH + Duration::from_secs(100));
+        let mut throttle = Throttle::with_clock(|| now.get());
+        let mut headers = Headers::new();
+        headers.set_raw("Retry-After", vec![b"30".to_vec()]);
+        throttle.update(Status::OK, &headers);
+        assert_eq!(throttle.delay(), Duration::from_secs(0));
+        throttle.update(Status::TOO_MANY_REQUESTS, &headers);
+        assert_eq!(throttle.delay(), Duration::from_secs(30));
+
+        let mut headers = Headers::new();
+        headers.set_raw("RateLimit", vec![b"\"a\";r=0;t=60, \"b\";r=5;t=90".to_vec()]);
+        throttle.update(Status::OK, &headers);
+        assert_eq!(throttle.delay(), Duration::from_secs(60));
+
+        let mut headers = Headers::new();
+        headers.set_raw("X-RateLimit-Remaining", vec![b"0".to_vec()]);
+        headers.set_raw("X-RateLimit-Reset", vec![b"1000000200".to_vec()]);
+        throttle.update(Status::OK, &headers);
+        assert_eq!(throttle.next_request(), UNIX_EPOCH + Duration::from_secs(1_000_000_200));
+        now.set(UNIX_EPOCH + Duration::from_secs(1_000_000_300));
+        assert_eq!(throttle.delay(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_throttle_overflow() {
+        let now = UNIX_EPOCH + Duration::from_secs(u64::MAX / 2);
+        let mut throttle = Throttle::with_clock(move || now);
+        let mut headers = Headers::new();
+        headers.set_raw("Retry-After", vec![b"18446744073709551615".to_vec()]);
+        throttle.update(Status::TOO_MANY_REQUESTS, &headers);
+        let mut headers = Headers::new();
+        headers.set_raw("RateLimit", vec![b"\"a\";r=0;t=999999999999999".to_vec()]);
+        throttle.update(Status::OK, &headers);
+        let mut headers = Headers::new();
+        headers.set_raw("X-RateLimit-Remaining", vec![b"0".to_vec()]);
+        headers.set_raw("X-RateLimit-Reset", vec![b"999999999".to_vec()]);
+        throttle.update(Status::OK, &headers);
+        assert_eq!(throttle.next_request(), now);
+    }
+}
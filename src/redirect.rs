@@ -0,0 +1,291 @@
+use std::fmt::{self, Display};
+
+use url::Url;
+
+use header::{Header, Headers, Location};
+use header::item::HeaderField;
+use {Method, Status};
+
+// Header fields describing the request content, removed when the
+// content is dropped.
+const CONTENT_FIELDS: &[&str] = &["Content-Encoding",
+                                  "Content-Language",
+                                  "Content-Length",
+                                  "Content-Location",
+                                  "Content-Type",
+                                  "Transfer-Encoding"];
+
+/// An error in following a redirect.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RedirectError {
+    /// The status is not 301, 302, 303, 307 or 308.
+    NotRedirect,
+    /// The response has no `Location` header field.
+    MissingLocation,
+    /// The `Location` header field is invalid.
+    InvalidLocation,
+    /// The target is neither an `http` nor an `https` URL.
+    UnsupportedScheme,
+    /// The maximum number of redirects was reached.
+    TooManyRedirects,
+    /// The target was already requested with the same method.
+    Loop,
+}
+
+impl Display for RedirectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            RedirectError::NotRedirect => "status is not a redirect",
+            RedirectError::MissingLocation => "missing Location",
+            RedirectError::InvalidLocation => "invalid Location",
+            RedirectError::UnsupportedScheme => "redirect to unsupported scheme",
+            RedirectError::TooManyRedirects => "too many redirects",
+            RedirectError::Loop => "redirect loop",
+        })
+    }
+}
+
+/// A request as seen by a [`RedirectPlanner`](struct.RedirectPlanner.html).
+#[derive(Clone, Debug)]
+pub struct RedirectRequest {
+    /// The request method.
+    pub method: Method,
+    /// The target URL.
+    pub url: Url,
+    /// The header fields of the request.
+    pub headers: Headers,
+    /// Set if the request has content that must be sent again.
+    pub has_body: bool,
+}
+
+/// Follows redirects of a client request, [RFC7231 Section 6.4]
+///
+/// `301 Moved Permanently` and `302 Found` change `POST` to `GET`
+/// like browsers do, `303 See Other` changes every method but `HEAD`
+/// to `GET` and drops the content. `307 Temporary Redirect` and
+/// `308 Permanent Redirect` repeat the request unchanged.
+///
+/// On redirects to another origin the credentials in `Authorization`,
+/// `Cookie` and `Proxy-Authorization` are removed. Typed header fields
+/// marked with `Header::SENSITIVE` are added with
+/// [`add_sensitive`](#method.add_sensitive).
+#[derive(Clone, Debug)]
+pub struct RedirectPlanner {
+    /// Maximum number of redirects to follow.
+    pub max_redirects: usize,
+    /// Header fields removed on redirects to another origin.
+    pub sensitive_headers: Vec<HeaderField>,
+    visited: Vec<(Method, Url)>,
+}
+
+impl RedirectPlanner {
+    /// Constructs a planner following up to 20 redirects.
+    pub fn new() -> RedirectPlanner {
+        RedirectPlanner {
+            max_redirects: 20,
+            sensitive_headers: vec!["Authorization".into(),
+                                    "Cookie".into(),
+                                    "Proxy-Authorization".into()],
+            visited: Vec::new(),
+        }
+    }
+
+    /// Removes a typed header field on redirects to another origin.
+    ///
+    /// Fields not marked with `Header::SENSITIVE` are ignored, push
+    /// them to `sensitive_headers` instead.
+    pub fn add_sensitive<H: Header>(&mut self) {
+        if !H::SENSITIVE {
+            return;
+        }
+        let name = H::NAME.into();
+        if !self.sensitive_headers.contains(&name) {
+            self.sensitive_headers.push(name);
+        }
+    }
+
+    /// Returns the number of redirects followed.
+    pub fn redirects(&self) -> usize {
+        self.visited.len().saturating_sub(1)
+    }
+
+    /// Forgets the followed redirects to plan the redirects of
+    /// another request.
+    pub fn reset(&mut self) {
+        self.visited.clear();
+    }
+
+    /// Builds the next request from a redirect response to the
+    /// previous request.
+    ///
+    /// `Location` is resolved against the URL of the previous request,
+    /// its fragment is kept if `Location` has none.
+    pub fn next(&mut self,
+                previous: &RedirectRequest,
+                status: Status,
+                headers: &Headers)
+                -> Result<RedirectRequest, RedirectError> {
+        let change_to_get = match status.to_raw() {
+            301 | 302 => previous.method == Method::Post,
+            303 => previous.method != Method::Head,
+            307 | 308 => false,
+            _ => return Err(RedirectError::NotRedirect),
+        };
+        let mut url: Url = headers.get::<Location>(previous.url.clone())
+            .ok_or(RedirectError::MissingLocation)?
+            .map_err(|_| RedirectError::InvalidLocation)?
+            .into();
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(RedirectError::UnsupportedScheme);
+        }
+        if url.fragment().is_none() {
+            url.set_fragment(previous.url.fragment());
+        }
+        if self.visited.is_empty() {
+            self.visited.push((previous.method.clone(), previous.url.clone()));
+        }
+        if self.redirects() >= self.max_redirects {
+            return Err(RedirectError::TooManyRedirects);
+        }
+        let mut next = RedirectRequest {
+            method: previous.method.clone(),
+            url,
+            headers: previous.headers.clone(),
+            has_body: previous.has_body,
+        };
+        if change_to_get {
+            next.method = Method::Get;
+            next.has_body = false;
+            for name in CONTENT_FIELDS {
+                next.headers.remove_raw(name);
+            }
+        }
+        if self.visited.iter().any(|x| x.0 == next.method && x.1 == next.url) {
+            return Err(RedirectError::Loop);
+        }
+        if next.url.origin() != previous.url.origin() {
+            // The client sets `Host` for the new target.
+            next.headers.remove_raw("Host");
+            for name in &self.sensitive_headers {
+                next.headers.remove_raw(name.as_str());
+            }
+        }
+        self.visited.push((next.method.clone(), next.url.clone()));
+        Ok(next)
+    }
+}
+
+impl Default for RedirectPlanner {
+    fn default() -> RedirectPlanner {
+        RedirectPlanner::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use url::Url;
+
+    use header::{Header, Headers, Location};
+    use {Method, Status};
+    use super::{RedirectError, RedirectPlanner, RedirectRequest};
+
+    #[derive(Clone, Debug)]
+    struct ApiKey;
+
+    impl Header for ApiKey {
+        const NAME: &'static str = "Api-Key";
+        const SENSITIVE: bool = true;
+
+        fn parse(_s: &[Vec<u8>], _base: Url) -> Result<Self, ()> {
+            Ok(ApiKey)
+        }
+
+        fn serialize<I: Iterator<Item = W>, W: Write>(&self, _iter: I) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(method: Method, url: &str) -> RedirectRequest {
+        let mut headers = Headers::new();
+        headers.set_raw("Host", vec![b"example.com".to_vec()]);
+        headers.set_raw("Authorization", vec![b"Basic YTpi".to_vec()]);
+        headers.set_raw("Proxy-Authorization", vec![b"Basic YTpi".to_vec()]);
+        headers.set_raw("Api-Key", vec![b"secret".to_vec()]);
+        headers.set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+        RedirectRequest {
+            method,
+            url: Url::parse(url).unwrap(),
+            headers,
+            has_body: true,
+        }
+    }
+
+    fn location(location: &str) -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw(Location::NAME, vec![location.as_bytes().to_vec()]);
+        headers
+    }
+
+    #[test]
+    fn test_method_change() {
+        let mut planner = RedirectPlanner::new();
+        let previous = request(Method::Post, "https://example.com/a#top");
+        let next = planner.next(&previous, Status::FOUND, &location("/b")).unwrap();
+        assert_eq!(next.method, Method::Get);
+        assert_eq!(next.url.as_str(), "https://example.com/b#top");
+        assert!(!next.has_body);
+        assert!(!next.headers.contains("Content-Type"));
+        assert!(next.headers.contains("Authorization"));
+
+        let next = planner.next(&previous, Status::PERMANENT_REDIRECT, &location("/c")).unwrap();
+        assert_eq!(next.method, Method::Post);
+        assert!(next.has_body);
+        assert!(next.headers.contains("Content-Type"));
+        assert_eq!(planner.redirects(), 2);
+        assert_eq!(planner.next(&previous, Status::OK, &location("/d")).unwrap_err(),
+                   RedirectError::NotRedirect);
+    }
+
+    #[test]
+    fn test_sensitive_headers() {
+        let mut planner = RedirectPlanner::new();
+        let previous = request(Method::Get, "https://example.com/");
+        let next = planner.next(&previous, Status::FOUND, &location("https://example.org/"))
+            .unwrap();
+        assert!(!next.headers.contains("Host"));
+        assert!(!next.headers.contains("Authorization"));
+        assert!(!next.headers.contains("Proxy-Authorization"));
+        assert!(next.headers.contains("Api-Key"));
+
+        planner.add_sensitive::<ApiKey>();
+        planner.add_sensitive::<Location>();
+        assert!(!planner.sensitive_headers.contains(&Location::NAME.into()));
+        planner.reset();
+        let next = planner.next(&previous, Status::FOUND, &location("https://example.org/"))
+            .unwrap();
+        assert!(!next.headers.contains("Api-Key"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut planner = RedirectPlanner::new();
+        let previous = request(Method::Get, "https://example.com/");
+        assert_eq!(planner.next(&previous, Status::FOUND, &Headers::new()).unwrap_err(),
+                   RedirectError::MissingLocation);
+        assert_eq!(planner.next(&previous, Status::FOUND, &location("ftp://example.com/"))
+                       .unwrap_err(),
+                   RedirectError::UnsupportedScheme);
+        assert_eq!(planner.next(&previous, Status::FOUND, &location("/")).unwrap_err(),
+                   RedirectError::Loop);
+
+        planner.max_redirects = 1;
+        let next = planner.next(&previous, Status::FOUND, &location("/a")).unwrap();
+        assert_eq!(planner.next(&next, Status::FOUND, &location("/b")).unwrap_err(),
+                   RedirectError::TooManyRedirects);
+        planner.reset();
+        assert_eq!(planner.redirects(), 0);
+        assert!(planner.next(&next, Status::FOUND, &location("/b")).is_ok());
+    }
+}
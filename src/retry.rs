@@ -0,0 +1,196 @@
+use std::time::{Duration, SystemTime};
+
+use clock::{Clock, SystemClock};
+use header::RetryAfter;
+use random::{Random, SystemRandom};
+use {Method, Status, StatusClass};
+
+/// The outcome of evaluating a failed request with a
+/// [`RetryPolicy`](struct.RetryPolicy.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetryDecision {
+    /// Repeat the request after the given delay.
+    Retry(Duration),
+    /// Give up and use the response or error.
+    Stop,
+}
+
+/// A policy for repeating failed requests.
+///
+/// A request is repeated if it failed without a response or with one
+/// of the configured statuses, and its method is idempotent. The delay
+/// grows exponentially with each attempt and is randomized with "full
+/// jitter" so clients do not retry in lockstep. A `Retry-After` header
+/// field in the response replaces the computed delay.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy<C: Clock = SystemClock, R: Random = SystemRandom> {
+    /// Maximum number of retries after the first request.
+    pub max_retries: u32,
+    /// Statuses to retry.
+    pub statuses: Vec<Status>,
+    /// Status classes to retry, in addition to `statuses`.
+    pub classes: Vec<StatusClass>,
+    /// Repeat requests with methods that are not idempotent like `POST`.
+    ///
+    /// The server may have processed the request before failing,
+    /// only enable this if the application tolerates duplicates.
+    pub retry_non_idempotent: bool,
+    /// The delay before jitter for the first retry, doubled for every
+    /// following retry.
+    pub base_delay: Duration,
+    /// The maximum delay before jitter.
+    pub max_delay: Duration,
+    /// The longest `Retry-After` delay to wait for, the request is not
+    /// repeated if the server asks for more.
+    pub max_retry_after: Duration,
+    clock: C,
+    random: R,
+}
+
+impl RetryPolicy {
+    /// Constructs a policy using the system clock and random numbers.
+    ///
+    /// It retries up to three times on `408 Request Timeout`, `429 Too
+    /// Many Requests`, `502 Bad Gateway`, `503 Service Unavailable` and
+    /// `504 Gateway Timeout`, starting with a delay of up to 100
+    /// milliseconds and waiting at most 10 seconds, or two minutes if
+    /// the server asks for it.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::with_sources(SystemClock, SystemRandom)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+impl<C: Clock, R: Random> RetryPolicy<C, R> {
+    /// Constructs the default policy with a clock and a source of
+    /// random numbers.
+    pub fn with_sources(clock: C, random: R) -> RetryPolicy<C, R> {
+        RetryPolicy {
+            max_retries: 3,
+            statuses: vec![Status::REQUEST_TIMEOUT,
+                           Status::TOO_MANY_REQUESTS,
+                           Status::BAD_GATEWAY,
+                           Status::SERVICE_UNAVAILABLE,
+                           Status::GATEWAY_TIMEOUT],
+            classes: Vec::new(),
+            retry_non_idempotent: false,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            max_retry_after: Duration::from_secs(120),
+            clock,
+            random,
+        }
+    }
+
+    /// Checks if the status is configured to be retried.
+    pub fn is_retryable(&self, status: Status) -> bool {
+        self.statuses.contains(&status) || self.classes.contains(&status.class())
+    }
+
+    /// Decides whether to repeat a failed request.
+    ///
+    /// `retries` is the number of times the request was already
+    /// repeated. `status` is `None` if the request failed without a
+    /// response, like on a connection reset.
+    pub fn decide(&self,
+                  retries: u32,
+                  method: &Method,
+                  status: Option<Status>,
+                  retry_after: Option<&RetryAfter>)
+                  -> RetryDecision {
+        if retries >= self.max_retries ||
+           !(method.is_idempotent() || self.retry_non_idempotent) ||
+           status.is_some_and(|x| !self.is_retryable(x)) {
+            return RetryDecision::Stop;
+        }
+        match retry_after {
+            Some(retry_after) => {
+                let delay = match *retry_after {
+                    RetryAfter::Delay(delay) => delay,
+                    RetryAfter::Date(date) => {
+                        let date: SystemTime = date.into();
+                        date.duration_since(self.clock.now()).unwrap_or_default()
+                    }
+                };
+                if delay > self.max_retry_after {
+                    RetryDecision::Stop
+                } else {
+                    RetryDecision::Retry(delay)
+                }
+            }
+            None => RetryDecision::Retry(self.backoff(retries)),
+        }
+    }
+
+    /// Returns the randomized delay before the given retry.
+    pub fn backoff(&self, retries: u32) -> Duration {
+        let factor = 1u32.checked_shl(retries).unwrap_or(u32::MAX);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let fraction = self.random.fraction();
+        delay.mul_f64(if fraction.is_nan() { 0.0 } else { fraction.clamp(0.0, 1.0) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use header::RetryAfter;
+    use {Method, Status, StatusClass};
+    use super::{RetryDecision, RetryPolicy};
+
+    fn policy(fraction: f64) -> RetryPolicy<impl Fn() -> SystemTime, impl Fn() -> f64> {
+        RetryPolicy::with_sources(|| UNIX_EPOCH + Duration::from_secs(1000), move || fraction)
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy(1.0);
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff(7), Duration::from_secs(10));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(10));
+        assert_eq!(self::policy(0.5).backoff(1), Duration::from_millis(100));
+        assert_eq!(self::policy(2.0).backoff(0), Duration::from_millis(100));
+        assert_eq!(self::policy(f64::NAN).backoff(0), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_decide() {
+        let mut policy = policy(1.0);
+        let retry = RetryDecision::Retry(Duration::from_millis(200));
+        assert_eq!(policy.decide(1, &Method::Get, None, None), retry);
+        assert_eq!(policy.decide(1, &Method::Put, Some(Status::SERVICE_UNAVAILABLE), None), retry);
+        assert_eq!(policy.decide(3, &Method::Get, None, None), RetryDecision::Stop);
+        assert_eq!(policy.decide(0, &Method::Post, None, None), RetryDecision::Stop);
+        assert_eq!(policy.decide(0, &Method::Get, Some(Status::INTERNAL_SERVER_ERROR), None),
+                   RetryDecision::Stop);
+
+        policy.retry_non_idempotent = true;
+        policy.classes.push(StatusClass::ServerError);
+        assert_eq!(policy.decide(1, &Method::Post, Some(Status::INTERNAL_SERVER_ERROR), None),
+                   retry);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let policy = policy(1.0);
+        let status = Some(Status::TOO_MANY_REQUESTS);
+        let delay = RetryAfter::from(Duration::from_secs(30));
+        assert_eq!(policy.decide(0, &Method::Get, status, Some(&delay)),
+                   RetryDecision::Retry(Duration::from_secs(30)));
+        let date = RetryAfter::from(UNIX_EPOCH + Duration::from_secs(1060));
+        assert_eq!(policy.decide(0, &Method::Get, status, Some(&date)),
+                   RetryDecision::Retry(Duration::from_secs(60)));
+        let past = RetryAfter::from(UNIX_EPOCH);
+        assert_eq!(policy.decide(0, &Method::Get, status, Some(&past)),
+                   RetryDecision::Retry(Duration::from_secs(0)));
+        let long = RetryAfter::from(Duration::from_secs(121));
+        assert_eq!(policy.decide(0, &Method::Get, status, Some(&long)), RetryDecision::Stop);
+    }
+}
@@ -0,0 +1,198 @@
+use std::io;
+use std::time::Duration;
+
+use header::{self, Headers};
+use header::{Allowlist, CrossOriginEmbedderPolicy, CrossOriginOpenerPolicy,
+             CrossOriginResourcePolicy, EmbedderPolicy, OpenerPolicy, PermissionsPolicy,
+             ReferrerPolicy, StrictTransportSecurity, XFrameOptions};
+
+/// A set of security response header fields.
+///
+/// [`new`](#method.new) returns a hardened default set suitable for
+/// HTML pages served via HTTPS. Fields set to `None` or `false` are
+/// not inserted.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    /// `Strict-Transport-Security`, only honored via HTTPS.
+    pub strict_transport_security: Option<StrictTransportSecurity>,
+    /// `X-Content-Type-Options: nosniff`.
+    pub content_type_options: bool,
+    /// `X-Frame-Options`.
+    pub frame_options: Option<XFrameOptions>,
+    /// `Referrer-Policy`.
+    pub referrer_policy: Option<ReferrerPolicy>,
+    /// `Cross-Origin-Opener-Policy`.
+    pub opener_policy: Option<CrossOriginOpenerPolicy>,
+    /// `Cross-Origin-Embedder-Policy`.
+    pub embedder_policy: Option<CrossOriginEmbedderPolicy>,
+    /// `Cross-Origin-Resource-Policy`.
+    pub resource_policy: Option<CrossOriginResourcePolicy>,
+    /// `Permissions-Policy`.
+    pub permissions_policy: Option<PermissionsPolicy>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> SecurityHeaders {
+        SecurityHeaders::new()
+    }
+}
+
+impl SecurityHeaders {
+    /// Constructs the hardened default set.
+    ///
+    /// * HTTPS for two years including subdomains
+    /// * no media type sniffing
+    /// * no framing
+    /// * only the origin is sent as referrer to other origins,
+    ///   nothing on downgrades
+    /// * popups of other origins are isolated
+    /// * only same-origin pages may embed the resources
+    /// * camera, microphone, geolocation, payment and USB access
+    ///   are disabled
+    ///
+    /// `Cross-Origin-Embedder-Policy` is not set as it blocks
+    /// cross-origin resources without CORS or CORP.
+    pub fn new() -> SecurityHeaders {
+        let mut permissions_policy = PermissionsPolicy::new();
+        for feature in &["camera", "geolocation", "microphone", "payment", "usb"] {
+            permissions_policy.set(*feature, Vec::new()).expect("feature names are valid");
+        }
+        SecurityHeaders {
+            strict_transport_security: Some(StrictTransportSecurity {
+                max_age: Duration::from_secs(2 * 365 * 24 * 60 * 60),
+                include_subdomains: true,
+                preload: false,
+            }),
+            content_type_options: true,
+            frame_options: Some(XFrameOptions::Deny),
+            referrer_policy: Some(ReferrerPolicy::StrictOriginWhenCrossOrigin),
+            opener_policy: Some(CrossOriginOpenerPolicy {
+                value: OpenerPolicy::SameOrigin,
+                report_to: None,
+            }),
+            embedder_policy: None,
+            resource_policy: Some(CrossOriginResourcePolicy::SameOrigin),
+            permissions_policy: Some(permissions_policy),
+        }
+    }
+
+    /// Constructs an empty set.
+    pub fn empty() -> SecurityHeaders {
+        SecurityHeaders {
+            strict_transport_security: None,
+            content_type_options: false,
+            frame_options: None,
+            referrer_policy: None,
+            opener_policy: None,
+            embedder_policy: None,
+            resource_policy: None,
+            permissions_policy: None,
+        }
+    }
+
+    /// Additionally requires CORP or CORS for all embedded resources,
+    /// needed for cross-origin isolation.
+    pub fn cross_origin_isolated(mut self) -> SecurityHeaders {
+        self.opener_policy = Some(CrossOriginOpenerPolicy {
+            value: OpenerPolicy::SameOrigin,
+            report_to: None,
+        });
+        self.embedder_policy = Some(CrossOriginEmbedderPolicy {
+            value: EmbedderPolicy::RequireCorp,
+            report_to: None,
+        });
+        self
+    }
+
+    /// Allows a feature of the `Permissions-Policy` for the given origins.
+    ///
+    /// Fails if the feature name is not a lowercase key like `camera`.
+    pub fn allow_feature(mut self, feature: &str, allowlist: Vec<Allowlist>) -> Result<SecurityHeaders, ()> {
+        self.permissions_policy.get_or_insert_with(PermissionsPolicy::new).set(feature, allowlist)?;
+        Ok(self)
+    }
+
+    /// Inserts the header fields into the collection.
+    ///
+    /// Fields already present are kept, so handlers can override
+    /// the defaults for individual responses.
+    ///
+    /// Fails if a field cannot be serialized, like a `report-to`
+    /// endpoint with non-ASCII characters.
+    pub fn apply(&self, headers: &mut Headers) -> io::Result<()> {
+        if let Some(ref sts) = self.strict_transport_security {
+            insert(headers, sts)?;
+        }
+        if self.content_type_options {
+            insert(headers, &header::XContentTypeOptions)?;
+        }
+        if let Some(ref frame_options) = self.frame_options {
+            insert(headers, frame_options)?;
+        }
+        if let Some(ref referrer_policy) = self.referrer_policy {
+            insert(headers, referrer_policy)?;
+        }
+        if let Some(ref opener_policy) = self.opener_policy {
+            insert(headers, opener_policy)?;
+        }
+        if let Some(ref embedder_policy) = self.embedder_policy {
+            insert(headers, embedder_policy)?;
+        }
+        if let Some(ref resource_policy) = self.resource_policy {
+            insert(headers, resource_policy)?;
+        }
+        if let Some(ref permissions_policy) = self.permissions_policy {
+            if !permissions_policy.features.is_empty() {
+                insert(headers, permissions_policy)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn insert<H: header::Header>(headers: &mut Headers, header: &H) -> io::Result<()> {
+    if headers.has::<H>() {
+        return Ok(());
+    }
+    headers.set(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Allowlist, CrossOriginEmbedderPolicy, EmbedderPolicy, Headers};
+
+    use super::SecurityHeaders;
+
+    #[test]
+    fn test_apply_defaults() {
+        let mut headers = Headers::new();
+        headers.set_raw("X-Frame-Options", vec![b"SAMEORIGIN".to_vec()]);
+        SecurityHeaders::new().apply(&mut headers).unwrap();
+        assert_eq!(headers.get_raw("X-Frame-Options"), Some(&[b"SAMEORIGIN".to_vec()][..]));
+        assert_eq!(headers.get_raw("X-Content-Type-Options"), Some(&[b"nosniff".to_vec()][..]));
+        assert!(headers.contains("Strict-Transport-Security"));
+        assert!(!headers.contains("Cross-Origin-Embedder-Policy"));
+        let mut headers = Headers::new();
+        SecurityHeaders::empty().apply(&mut headers).unwrap();
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn test_allow_feature() {
+        let security = SecurityHeaders::empty().allow_feature("camera", vec![Allowlist::Self_]).unwrap();
+        let mut headers = Headers::new();
+        security.apply(&mut headers).unwrap();
+        assert_eq!(headers.get_raw("Permissions-Policy"), Some(&[b"camera=self".to_vec()][..]));
+        assert!(SecurityHeaders::empty().allow_feature("Camera", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_apply_invalid_report_to() {
+        let mut security = SecurityHeaders::empty();
+        security.embedder_policy = Some(CrossOriginEmbedderPolicy {
+            value: EmbedderPolicy::RequireCorp,
+            report_to: Some("ä".to_owned()),
+        });
+        assert!(security.apply(&mut Headers::new()).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use header::item::{HostPort, Url};
+use Version;
+
+/// The request-target identifies the target resource of a request.
+///
+/// There are four distinct forms depending on the method and on
+/// whether the request is sent to a proxy. [RFC7230 Section 5.3]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RequestTarget {
+    /// origin-form: an absolute path with an optional query like `/where?q=now`.
+    Origin(String),
+    /// absolute-form: a complete URL, used for requests to proxies.
+    Absolute(Url),
+    /// authority-form: only host and port, used for `CONNECT` requests.
+    Authority(HostPort),
+    /// asterisk-form: `*`, used for server-wide `OPTIONS` requests.
+    Asterisk,
+}
+
+impl Display for RequestTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::RequestTarget::*;
+        match *self {
+            Origin(ref s) => f.write_str(s),
+            Absolute(ref url) => url.fmt(f),
+            Authority(ref authority) => authority.fmt(f),
+            Asterisk => f.write_str("*"),
+        }
+    }
+}
+
+impl FromStr for RequestTarget {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<RequestTarget, ()> {
+        if s.is_empty() || !s.bytes().all(|c| c > b' ' && c < 0x7f) {
+            return Err(());
+        }
+        if s == "*" {
+            return Ok(RequestTarget::Asterisk);
+        }
+        if s.starts_with('/') {
+            return Ok(RequestTarget::Origin(s.to_owned()));
+        }
+        // authority-form = authority, but only host and port are
+        // allowed and the port is required. [RFC7230 Section 5.3.3]
+        match s.parse::<HostPort>() {
+            Ok(authority) if authority.port.is_some() => {
+                return Ok(RequestTarget::Authority(authority));
+            }
+            _ => (),
+        }
+        Url::parse(s).map(RequestTarget::Absolute).map_err(|_| ())
+    }
+}
+
+/// Reconstructs the effective request URL. [RFC7230 Section 5.5]
+///
+/// The `host` is the value of the `Host` header field: `None` if the
+/// field is missing and `Some(None)` if it is empty. The `scheme` is
+/// the one of the connection, usually `http` or `https`. The
+/// `fallback` authority is the default name of the server and used
+/// if the request has neither an absolute-form target nor a non-empty
+/// `Host` header field.
+///
+/// HTTP/1.1 requests without a `Host` header field are rejected,
+/// a client sends an empty field if the target has no authority.
+/// [RFC7230 Section 5.4]
+///
+/// A proxy request in absolute-form overrides the `Host` header field.
+/// If `strict` is set, the request is rejected if both disagree.
+pub fn effective_request_url(target: &RequestTarget,
+                             version: Version,
+                             host: Option<Option<&HostPort>>,
+                             scheme: &str,
+                             fallback: Option<&HostPort>,
+                             strict: bool)
+                             -> Result<Url, ()> {
+    use self::RequestTarget::*;
+    if version == Version::Http11 && host.is_none() {
+        return Err(());
+    }
+    let host = host.and_then(|x| x);
+    match *target {
+        Absolute(ref url) => {
+            if let (true, Some(host)) = (strict, host) {
+                if !authority_eq(url, host) {
+                    return Err(());
+                }
+            }
+            Ok(url.clone())
+        }
+        Authority(ref authority) => build_url(scheme, authority, ""),
+        Origin(ref path) => build_url(scheme, host.or(fallback).ok_or(())?, path),
+        Asterisk => build_url(scheme, host.or(fallback).ok_or(())?, ""),
+    }
+}
+
+fn build_url(scheme: &str, authority: &HostPort, path: &str) -> Result<Url, ()> {
+    Url::parse(&format!("{}://{}{}", scheme, authority, path)).map_err(|_| ())
+}
+
+fn authority_eq(url: &Url, host: &HostPort) -> bool {
+    let authority = match HostPort::from_url(url) {
+        Some(authority) => authority,
+        None => return false,
+    };
+    let mut origin = url.clone();
+    if origin.set_port(None).is_err() {
+        return false;
+    }
+    let default = origin.port_or_known_default();
+    authority.host == host.host && authority.port.or(default) == host.port.or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use header::item::HostPort;
+    use Version;
+    use super::{RequestTarget, effective_request_url};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("/where?q=now".parse(), Ok(RequestTarget::Origin("/where?q=now".to_owned())));
+        assert_eq!("*".parse(), Ok(RequestTarget::Asterisk));
+        assert_eq!("www.example.com:80".parse::<RequestTarget>().unwrap().to_string(),
+                   "www.example.com:80");
+        match "http://www.example.org/pub/WWW/TheProject.html".parse() {
+            Ok(RequestTarget::Absolute(_)) => (),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!("www.example.com".parse::<RequestTarget>(), Err(()));
+        assert!(!matches!("localhost:".parse(), Ok(RequestTarget::Authority(_))));
+        assert_eq!("".parse::<RequestTarget>(), Err(()));
+        assert_eq!("/a b".parse::<RequestTarget>(), Err(()));
+    }
+
+    #[test]
+    fn test_effective_request_url() {
+        let target = RequestTarget::Origin("/pub?q=1".to_owned());
+        let host: HostPort = "example.com:8080".parse().unwrap();
+        let fallback: HostPort = "server.local".parse().unwrap();
+        let url = effective_request_url(&target, Version::Http11, Some(Some(&host)), "http", Some(&fallback), false);
+        assert_eq!(url.unwrap().as_str(), "http://example.com:8080/pub?q=1");
+        // An empty `Host` uses the default name of the server.
+        let url = effective_request_url(&target, Version::Http11, Some(None), "https", Some(&fallback), false);
+        assert_eq!(url.unwrap().as_str(), "https://server.local/pub?q=1");
+        // A missing `Host` is only allowed before HTTP/1.1.
+        assert!(effective_request_url(&target, Version::Http11, None, "http", Some(&fallback), false).is_err());
+        let url = effective_request_url(&target, Version::Http10, None, "http", Some(&fallback), false);
+        assert_eq!(url.unwrap().as_str(), "http://server.local/pub?q=1");
+        assert!(effective_request_url(&target, Version::Http11, Some(None), "http", None, false).is_err());
+    }
+
+    #[test]
+    fn test_absolute_form_strict() {
+        let target: RequestTarget = "http://example.com/x".parse().unwrap();
+        let same: HostPort = "example.com:80".parse().unwrap();
+        let other: HostPort = "example.org".parse().unwrap();
+        assert!(effective_request_url(&target, Version::Http11, Some(Some(&same)), "http", None, true).is_ok());
+        assert!(effective_request_url(&target, Version::Http11, Some(Some(&other)), "http", None, true).is_err());
+        assert!(effective_request_url(&target, Version::Http11, Some(Some(&other)), "http", None, false).is_ok());
+    }
+}
@@ -1,4 +1,6 @@
-fn is_tchar(c: char) -> bool {
+use getrandom;
+
+pub fn is_tchar(c: char) -> bool {
     matches!(c, '!' | '#' | '$' | '%' | '&' | '\'' | '*'
     | '+' | '-' | '.' | '^' | '_' | '`' | '|' | '~'
     | '0'...'9' | 'A'...'Z' | 'a'...'z')
@@ -10,3 +12,157 @@ pub fn is_token(s: &str) -> bool {
     }
     s.chars().all(is_tchar)
 }
+
+// scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." ) [RFC3986 Section 3.1]
+pub fn is_scheme(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic()) &&
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+}
+
+pub const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+pub const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub fn encode_base64(input: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &c)| n | (c as u32) << (16 - 8 * i));
+        for i in 0..chunk.len() + 1 {
+            output.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if pad {
+            for _ in chunk.len()..3 {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+// Padding is optional, but if present it must be complete.
+pub fn decode_base64(input: &[u8], alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let data = match input.iter().position(|&c| c == b'=') {
+        Some(i) => {
+            if !input.len().is_multiple_of(4) || input.len() - i > 2 || input[i..].iter().any(|&c| c != b'=') {
+                return None;
+            }
+            &input[..i]
+        }
+        None => input,
+    };
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut output = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        let mut n = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = alphabet.iter().position(|&x| x == c)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            output.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(output)
+}
+
+// SHA-1 is broken for signatures but still required by the
+// WebSocket handshake. [RFC3174]
+pub fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((input.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (x, y) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+    let mut digest = [0; 20];
+    for (i, x) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&x.to_be_bytes());
+    }
+    digest
+}
+
+// Cryptographically secure bytes from the operating system for
+// handshake keys, frame masks and boundaries.
+pub fn random_bytes(output: &mut [u8]) {
+    getrandom::getrandom(output).expect("operating system random number generator is available");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BASE64, BASE64_URL, decode_base64, encode_base64, is_scheme, is_token, sha1};
+
+    #[test]
+    fn test_base64() {
+        // [RFC4648 Section 10]
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+                       ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for &(input, output) in &vectors {
+            assert_eq!(encode_base64(input.as_bytes(), BASE64, true), output);
+            assert_eq!(decode_base64(output.as_bytes(), BASE64).unwrap(), input.as_bytes());
+            let unpadded = output.trim_end_matches('=');
+            assert_eq!(encode_base64(input.as_bytes(), BASE64, false), unpadded);
+            assert_eq!(decode_base64(unpadded.as_bytes(), BASE64).unwrap(), input.as_bytes());
+        }
+        assert_eq!(encode_base64(&[0xfb, 0xff], BASE64, false), "+/8");
+        assert_eq!(encode_base64(&[0xfb, 0xff], BASE64_URL, false), "-_8");
+        assert!(decode_base64(b"+/8", BASE64_URL).is_none());
+        assert!(decode_base64(b"Zg=", BASE64).is_none());
+        assert!(decode_base64(b"Zg===", BASE64).is_none());
+        assert!(decode_base64(b"Z=g=", BASE64).is_none());
+        assert!(decode_base64(b"Zm9vY", BASE64).is_none());
+    }
+
+    #[test]
+    fn test_sha1() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|x| format!("{:02x}", x)).collect::<String>();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+                   "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+        assert_eq!(hex(sha1(&[b'a'; 1_000_000])), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+    }
+
+    #[test]
+    fn test_token_and_scheme() {
+        assert!(is_token("gzip"));
+        assert!(is_token("!#$%&'*+-.^_`|~09AZaz"));
+        assert!(!is_token(""));
+        assert!(!is_token("a b"));
+        assert!(!is_token("a\u{e9}"));
+        assert!(is_scheme("https"));
+        assert!(is_scheme("web+coffee-1.0"));
+        assert!(!is_scheme(""));
+        assert!(!is_scheme("1http"));
+        assert!(!is_scheme("ht_tp"));
+    }
+}
@@ -18,6 +18,30 @@ pub enum Version {
     /// HTTP/2 adds a binary transport layer to HTTP but the semantics
     /// stay mostly unchanged.
     Http20,
+    /// HTTP/3 protocol version as described in [RFC9114].
+    ///
+    /// HTTP/3 maps HTTP semantics to the QUIC transport.
+    Http30,
+}
+
+impl Version {
+    /// Returns the ALPN protocol identifier used to negotiate the
+    /// version in TLS. [RFC7301]
+    pub fn alpn_id(&self) -> &'static [u8] {
+        use self::Version::*;
+        match *self {
+            Http10 => b"http/1.0",
+            Http11 => b"http/1.1",
+            Http20 => b"h2",
+            Http30 => b"h3",
+        }
+    }
+
+    /// Returns the version for an ALPN protocol identifier.
+    pub fn from_alpn_id(id: &[u8]) -> Option<Version> {
+        use self::Version::*;
+        [Http10, Http11, Http20, Http30].iter().find(|x| x.alpn_id() == id).cloned()
+    }
 }
 
 impl Display for Version {
@@ -27,6 +51,23 @@ impl Display for Version {
             Http10 => "HTTP/1.0",
             Http11 => "HTTP/1.1",
             Http20 => "HTTP/2",
+            Http30 => "HTTP/3",
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Version;
+
+    #[test]
+    fn test_alpn_id() {
+        for &version in &[Version::Http10, Version::Http11, Version::Http20, Version::Http30] {
+            assert_eq!(Version::from_alpn_id(version.alpn_id()), Some(version));
+        }
+        assert_eq!(Version::Http20.alpn_id(), b"h2");
+        assert_eq!(Version::from_alpn_id(b"h2c"), None);
+        assert_eq!(Version::from_alpn_id(b"H2"), None);
+        assert_eq!(Version::Http30.to_string(), "HTTP/3");
+    }
+}
@@ -0,0 +1,804 @@
+use std::fmt::{self, Display};
+use std::mem;
+
+use util;
+
+/// The type of a frame, [RFC6455 Section 5.2]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpCode {
+    /// Continues a fragmented message.
+    Continuation,
+    /// Starts a text message.
+    Text,
+    /// Starts a binary message.
+    Binary,
+    /// Closes the connection.
+    Close,
+    /// Asks the peer for a pong.
+    Ping,
+    /// Answers a ping or serves as a heartbeat.
+    Pong,
+}
+
+impl OpCode {
+    fn from_raw(raw: u8) -> Option<OpCode> {
+        Some(match raw {
+            0x0 => OpCode::Continuation,
+            0x1 => OpCode::Text,
+            0x2 => OpCode::Binary,
+            0x8 => OpCode::Close,
+            0x9 => OpCode::Ping,
+            0xA => OpCode::Pong,
+            _ => return None,
+        })
+    }
+
+    fn to_raw(self) -> u8 {
+        match self {
+            OpCode::Continuation => 0x0,
+            OpCode::Text => 0x1,
+            OpCode::Binary => 0x2,
+            OpCode::Close => 0x8,
+            OpCode::Ping => 0x9,
+            OpCode::Pong => 0xA,
+        }
+    }
+
+    /// Checks if the frame is a control frame.
+    ///
+    /// Control frames may be sent between the fragments of a message.
+    pub fn is_control(&self) -> bool {
+        matches!(*self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+}
+
+/// A single frame.
+///
+/// The payload is stored unmasked. Only the RSV1 bit used by
+/// `permessage-deflate` is supported, the other reserved bits are
+/// always zero.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// Set on the last frame of a message.
+    pub fin: bool,
+    /// Set on the first frame of a compressed message,
+    /// [RFC7692 Section 6]
+    pub rsv1: bool,
+    /// The type of the frame.
+    pub opcode: OpCode,
+    /// The masking key, clients must mask all frames.
+    pub mask: Option<[u8; 4]>,
+    /// The application data.
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Constructs an unmasked final frame.
+    pub fn new(opcode: OpCode, payload: Vec<u8>) -> Frame {
+        Frame {
+            fin: true,
+            rsv1: false,
+            opcode,
+            mask: None,
+            payload,
+        }
+    }
+}
+
+/// Writes a frame to the output.
+pub fn encode_frame(frame: &Frame, output: &mut Vec<u8>) {
+    output.push(if frame.fin { 0x80 } else { 0 } | if frame.rsv1 { 0x40 } else { 0 } |
+                frame.opcode.to_raw());
+    let mask_bit = if frame.mask.is_some() { 0x80 } else { 0 };
+    let len = frame.payload.len();
+    if len < 126 {
+        output.push(mask_bit | len as u8);
+    } else if len <= 0xFFFF {
+        output.push(mask_bit | 126);
+        output.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        output.push(mask_bit | 127);
+        output.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    match frame.mask {
+        Some(mask) => {
+            output.extend_from_slice(&mask);
+            output.extend(frame.payload.iter().enumerate().map(|(i, &c)| c ^ mask[i % 4]));
+        }
+        None => output.extend_from_slice(&frame.payload),
+    }
+}
+
+/// Reads a frame from the start of the input.
+///
+/// Returns the frame with unmasked payload and the number of bytes
+/// consumed, or `None` if the input does not contain a complete frame.
+/// The RSV1 bit is returned as is, callers reject it unless an
+/// extension using it was negotiated.
+pub fn decode_frame(input: &[u8], max_payload: usize) -> Result<Option<(Frame, usize)>, Error> {
+    if input.len() < 2 {
+        return Ok(None);
+    }
+    if input[0] & 0x30 != 0 {
+        return Err(Error::Protocol);
+    }
+    let fin = input[0] & 0x80 != 0;
+    let rsv1 = input[0] & 0x40 != 0;
+    let opcode = OpCode::from_raw(input[0] & 0x0F).ok_or(Error::Protocol)?;
+    let masked = input[1] & 0x80 != 0;
+    let (len, mut position) = match input[1] & 0x7F {
+        126 => {
+            if input.len() < 4 {
+                return Ok(None);
+            }
+            let len = u16::from_be_bytes([input[2], input[3]]) as u64;
+            // The minimal number of bytes must be used for the length.
+            if len < 126 {
+                return Err(Error::Protocol);
+            }
+            (len, 4)
+        }
+        127 => {
+            if input.len() < 10 {
+                return Ok(None);
+            }
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&input[2..10]);
+            let len = u64::from_be_bytes(bytes);
+            if len <= 0xFFFF || len >> 63 != 0 {
+                return Err(Error::Protocol);
+            }
+            (len, 10)
+        }
+        len => (len as u64, 2),
+    };
+    if opcode.is_control() && (!fin || len > 125) {
+        return Err(Error::Protocol);
+    }
+    if len > max_payload as u64 {
+        return Err(Error::TooBig);
+    }
+    let len = len as usize;
+    let mask = if masked {
+        if input.len() < position + 4 {
+            return Ok(None);
+        }
+        let mut mask = [0; 4];
+        mask.copy_from_slice(&input[position..position + 4]);
+        position += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    if input.len() < position + len {
+        return Ok(None);
+    }
+    let mut payload = input[position..position + len].to_vec();
+    if let Some(mask) = mask {
+        for (i, c) in payload.iter_mut().enumerate() {
+            *c ^= mask[i % 4];
+        }
+    }
+    let frame = Frame {
+        fin,
+        rsv1,
+        opcode,
+        mask,
+        payload,
+    };
+    Ok(Some((frame, position + len)))
+}
+
+/// A status code sent when closing a connection, [RFC6455 Section 7.4]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CloseCode(u16);
+
+impl CloseCode {
+    /// 1000: Normal Closure
+    pub const NORMAL: CloseCode = CloseCode(1000);
+    /// 1001: Going Away, e.g. server shutdown or page navigation
+    pub const GOING_AWAY: CloseCode = CloseCode(1001);
+    /// 1002: Protocol Error
+    pub const PROTOCOL_ERROR: CloseCode = CloseCode(1002);
+    /// 1003: Unsupported Data, e.g. binary for a text-only endpoint
+    pub const UNSUPPORTED_DATA: CloseCode = CloseCode(1003);
+    /// 1007: Invalid Frame Payload Data, e.g. invalid UTF-8
+    pub const INVALID_PAYLOAD: CloseCode = CloseCode(1007);
+    /// 1008: Policy Violation
+    pub const POLICY_VIOLATION: CloseCode = CloseCode(1008);
+    /// 1009: Message Too Big
+    pub const MESSAGE_TOO_BIG: CloseCode = CloseCode(1009);
+    /// 1010: Mandatory Extension, sent by clients
+    pub const MANDATORY_EXTENSION: CloseCode = CloseCode(1010);
+    /// 1011: Internal Error
+    pub const INTERNAL_ERROR: CloseCode = CloseCode(1011);
+
+    /// Constructs a close code from a `u16` number.
+    ///
+    /// This does not check if the code may be sent.
+    pub fn from_raw(code: u16) -> CloseCode {
+        CloseCode(code)
+    }
+
+    /// Returns the close code as a `u16` number.
+    pub fn to_raw(&self) -> u16 {
+        self.0
+    }
+
+    /// Checks if the code may be sent in a close frame.
+    ///
+    /// Codes reserved for local use like 1005 and 1006 and
+    /// unassigned codes below 3000 are invalid.
+    pub fn is_valid(&self) -> bool {
+        matches!(self.0, 1000..=1003 | 1007..=1011 | 3000..=4999)
+    }
+}
+
+impl Display for CloseCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// The payload of a close frame.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CloseFrame {
+    /// The reason for closing.
+    pub code: CloseCode,
+    /// A human readable explanation, at most 123 bytes.
+    pub reason: String,
+}
+
+impl CloseFrame {
+    fn to_payload(&self) -> Vec<u8> {
+        let mut payload = self.code.to_raw().to_be_bytes().to_vec();
+        payload.extend_from_slice(self.reason.as_bytes());
+        payload
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Option<CloseFrame>, Error> {
+        match payload.len() {
+            0 => return Ok(None),
+            1 => return Err(Error::Protocol),
+            _ => (),
+        }
+        let code = CloseCode(u16::from_be_bytes([payload[0], payload[1]]));
+        if !code.is_valid() {
+            return Err(Error::Protocol);
+        }
+        let reason = String::from_utf8(payload[2..].to_vec()).map_err(|_| Error::InvalidPayload)?;
+        Ok(Some(CloseFrame { code, reason }))
+    }
+}
+
+/// A complete message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Message {
+    /// A text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping with application data.
+    Ping(Vec<u8>),
+    /// A pong with application data.
+    Pong(Vec<u8>),
+    /// A close frame, `None` if it contains no status code.
+    Close(Option<CloseFrame>),
+    /// A text or binary message compressed by `permessage-deflate`.
+    ///
+    /// Only used if [`Config::per_message_deflate`](struct.Config.html)
+    /// is set. The data is passed through unchanged, it must be
+    /// compressed and decompressed with an external DEFLATE codec.
+    /// [RFC7692 Section 7.2]
+    Compressed(OpCode, Vec<u8>),
+}
+
+/// An error closing the connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The peer violated the protocol.
+    Protocol,
+    /// A text message or close reason is not valid UTF-8.
+    InvalidPayload,
+    /// A frame or message exceeds the configured limits.
+    TooBig,
+    /// The connection is closed.
+    Closed,
+}
+
+impl Error {
+    /// Returns the code sent to the peer for this error.
+    pub fn close_code(&self) -> CloseCode {
+        match *self {
+            Error::Protocol => CloseCode::PROTOCOL_ERROR,
+            Error::InvalidPayload => CloseCode::INVALID_PAYLOAD,
+            Error::TooBig => CloseCode::MESSAGE_TOO_BIG,
+            Error::Closed => CloseCode::NORMAL,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::Protocol => "WebSocket protocol error",
+            Error::InvalidPayload => "invalid UTF-8 in WebSocket message",
+            Error::TooBig => "WebSocket message too big",
+            Error::Closed => "WebSocket connection closed",
+        })
+    }
+}
+
+/// The endpoint of a connection.
+///
+/// Clients mask the frames they send, servers must not.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    /// The endpoint that sent the handshake request.
+    Client,
+    /// The endpoint that accepted the handshake.
+    Server,
+}
+
+/// Limits and settings of a [`Connection`](struct.Connection.html).
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// The maximum payload size of a received frame.
+    pub max_frame_size: usize,
+    /// The maximum size of a received message after reassembly.
+    pub max_message_size: usize,
+    /// Sent messages are split into frames of at most this size.
+    pub fragment_size: usize,
+    /// Set if `permessage-deflate` was negotiated.
+    ///
+    /// Messages with the RSV1 bit are then received and sent as
+    /// [`Message::Compressed`](enum.Message.html), otherwise the
+    /// bit is a protocol error.
+    pub per_message_deflate: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_frame_size: 16 * 1024 * 1024,
+            max_message_size: 64 * 1024 * 1024,
+            fragment_size: 64 * 1024,
+            per_message_deflate: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Open,
+    // A close frame was sent, waiting for the answer.
+    Closing,
+    Closed,
+}
+
+/// A sans-IO WebSocket connection, [RFC6455 Section 5]
+///
+/// Received bytes are passed to [`receive`](#method.receive) and
+/// messages are read with [`next_message`](#method.next_message).
+/// Messages are sent with [`send`](#method.send) and the bytes to
+/// write to the transport are taken with
+/// [`take_output`](#method.take_output).
+///
+/// Pings are answered and close frames echoed automatically. On an
+/// error a close frame with the matching code is queued and the
+/// connection is closed.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    role: Role,
+    config: Config,
+    state: State,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    // The opcode, RSV1 bit and data of a fragmented message being
+    // received.
+    fragments: Option<(OpCode, bool, Vec<u8>)>,
+}
+
+impl Connection {
+    /// Constructs a connection after a successful handshake.
+    pub fn new(role: Role, config: Config) -> Connection {
+        Connection {
+            role,
+            config,
+            state: State::Open,
+            input: Vec::new(),
+            output: Vec::new(),
+            fragments: None,
+        }
+    }
+
+    /// Appends bytes received from the transport.
+    pub fn receive(&mut self, data: &[u8]) {
+        self.input.extend_from_slice(data);
+    }
+
+    /// Returns the next received message.
+    ///
+    /// `None` is returned if more data is needed.
+    pub fn next_message(&mut self) -> Result<Option<Message>, Error> {
+        // Fragments of a message are consumed until it is complete.
+        while self.state != State::Closed {
+            let frame = match decode_frame(&self.input, self.config.max_frame_size) {
+                Ok(Some((frame, len))) => {
+                    self.input.drain(..len);
+                    frame
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    self.fail(error);
+                    return Err(error);
+                }
+            };
+            match self.handle_frame(frame) {
+                // Data received after sending a close frame is discarded.
+                Ok(Some(Message::Text(_))) |
+                Ok(Some(Message::Binary(_))) |
+                Ok(Some(Message::Compressed(..))) if self.state == State::Closing => (),
+                Ok(Some(message)) => return Ok(Some(message)),
+                Ok(None) => (),
+                Err(error) => {
+                    self.fail(error);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Queues a message for sending.
+    ///
+    /// Text and binary messages larger than the fragment size are
+    /// split into several frames. Sending a close message starts the
+    /// closing handshake.
+    pub fn send(&mut self, message: Message) -> Result<(), Error> {
+        if self.state != State::Open {
+            return Err(Error::Closed);
+        }
+        match message {
+            Message::Text(text) => self.send_data(OpCode::Text, false, text.into_bytes()),
+            Message::Binary(data) => self.send_data(OpCode::Binary, false, data),
+            Message::Compressed(opcode, data) => {
+                if !self.config.per_message_deflate ||
+                   !matches!(opcode, OpCode::Text | OpCode::Binary) {
+                    return Err(Error::Protocol);
+                }
+                self.send_data(opcode, true, data);
+            }
+            Message::Ping(data) => self.send_control(OpCode::Ping, data)?,
+            Message::Pong(data) => self.send_control(OpCode::Pong, data)?,
+            Message::Close(frame) => {
+                let payload = match frame {
+                    Some(ref frame) if !frame.code.is_valid() => return Err(Error::Protocol),
+                    Some(frame) => frame.to_payload(),
+                    None => Vec::new(),
+                };
+                self.send_control(OpCode::Close, payload)?;
+                self.state = State::Closing;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the closing handshake with a status code and reason.
+    pub fn close(&mut self, code: CloseCode, reason: &str) -> Result<(), Error> {
+        self.send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.to_owned(),
+        })))
+    }
+
+    /// Takes the bytes to write to the transport.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        mem::take(&mut self.output)
+    }
+
+    /// Checks if the closing handshake is complete or the connection failed.
+    ///
+    /// A server closes the transport now, a client waits for the
+    /// server to do so.
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    // Returns a message if the frame completes one.
+    fn handle_frame(&mut self, frame: Frame) -> Result<Option<Message>, Error> {
+        // Servers require masked frames, clients unmasked ones.
+        if frame.mask.is_some() != (self.role == Role::Server) {
+            return Err(Error::Protocol);
+        }
+        // Only the first frame of a data message may be compressed.
+        if frame.rsv1 &&
+           (!self.config.per_message_deflate ||
+            !matches!(frame.opcode, OpCode::Text | OpCode::Binary)) {
+            return Err(Error::Protocol);
+        }
+        match frame.opcode {
+            OpCode::Ping => {
+                if self.state == State::Open {
+                    self.send_control(OpCode::Pong, frame.payload.clone())?;
+                }
+                Ok(Some(Message::Ping(frame.payload)))
+            }
+            OpCode::Pong => Ok(Some(Message::Pong(frame.payload))),
+            OpCode::Close => {
+                let close = CloseFrame::from_payload(&frame.payload)?;
+                if self.state == State::Open {
+                    // Echo the status code. [RFC6455 Section 5.5.1]
+                    let payload = close.as_ref()
+                        .map_or(Vec::new(), |x| x.code.to_raw().to_be_bytes().to_vec());
+                    self.send_control(OpCode::Close, payload)?;
+                }
+                self.state = State::Closed;
+                Ok(Some(Message::Close(close)))
+            }
+            OpCode::Text | OpCode::Binary => {
+                if self.fragments.is_some() {
+                    return Err(Error::Protocol);
+                }
+                if frame.payload.len() > self.config.max_message_size {
+                    return Err(Error::TooBig);
+                }
+                if frame.fin {
+                    return message(frame.opcode, frame.rsv1, frame.payload).map(Some);
+                }
+                self.fragments = Some((frame.opcode, frame.rsv1, frame.payload));
+                Ok(None)
+            }
+            OpCode::Continuation => {
+                let (opcode, rsv1, mut data) = self.fragments.take().ok_or(Error::Protocol)?;
+                if data.len() + frame.payload.len() > self.config.max_message_size {
+                    return Err(Error::TooBig);
+                }
+                data.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    return message(opcode, rsv1, data).map(Some);
+                }
+                self.fragments = Some((opcode, rsv1, data));
+                Ok(None)
+            }
+        }
+    }
+
+    fn send_data(&mut self, opcode: OpCode, rsv1: bool, data: Vec<u8>) {
+        let size = self.config.fragment_size.max(1);
+        if data.len() <= size {
+            self.send_frame(opcode, true, rsv1, data);
+            return;
+        }
+        let count = data.len().div_ceil(size);
+        for (i, chunk) in data.chunks(size).enumerate() {
+            if i == 0 {
+                self.send_frame(opcode, false, rsv1, chunk.to_vec());
+            } else {
+                self.send_frame(OpCode::Continuation, i + 1 == count, false, chunk.to_vec());
+            }
+        }
+    }
+
+    fn send_control(&mut self, opcode: OpCode, payload: Vec<u8>) -> Result<(), Error> {
+        if payload.len() > 125 {
+            return Err(Error::TooBig);
+        }
+        self.send_frame(opcode, true, false, payload);
+        Ok(())
+    }
+
+    fn send_frame(&mut self, opcode: OpCode, fin: bool, rsv1: bool, payload: Vec<u8>) {
+        let mask = match self.role {
+            Role::Client => {
+                let mut mask = [0; 4];
+                util::random_bytes(&mut mask);
+                Some(mask)
+            }
+            Role::Server => None,
+        };
+        let frame = Frame {
+            fin,
+            rsv1,
+            opcode,
+            mask,
+            payload,
+        };
+        encode_frame(&frame, &mut self.output);
+    }
+
+    // Fails the connection with a close frame. [RFC6455 Section 7.1.7]
+    fn fail(&mut self, error: Error) {
+        if self.state == State::Open {
+            let frame = CloseFrame {
+                code: error.close_code(),
+                reason: String::new(),
+            };
+            self.send_frame(OpCode::Close, true, false, frame.to_payload());
+        }
+        self.state = State::Closed;
+        self.input.clear();
+        self.fragments = None;
+    }
+}
+
+fn message(opcode: OpCode, rsv1: bool, data: Vec<u8>) -> Result<Message, Error> {
+    if rsv1 {
+        return Ok(Message::Compressed(opcode, data));
+    }
+    match opcode {
+        OpCode::Text => {
+            String::from_utf8(data).map(Message::Text).map_err(|_| Error::InvalidPayload)
+        }
+        _ => Ok(Message::Binary(data)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CloseCode, CloseFrame, Config, Connection, Error, Frame, Message, OpCode, Role,
+                decode_frame, encode_frame};
+
+    // A masked frame as sent by a client.
+    fn client_frame(opcode: OpCode, fin: bool, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Frame::new(opcode, payload.to_vec());
+        frame.fin = fin;
+        frame.mask = Some([0x37, 0xfa, 0x21, 0x3d]);
+        let mut output = Vec::new();
+        encode_frame(&frame, &mut output);
+        output
+    }
+
+    #[test]
+    fn test_decode_examples() {
+        // [RFC6455 Section 5.7]
+        let unmasked = b"\x81\x05\x48\x65\x6c\x6c\x6f";
+        let (frame, len) = decode_frame(unmasked, 125).unwrap().unwrap();
+        assert_eq!((frame.fin, frame.opcode, frame.mask, &frame.payload[..], len),
+                   (true, OpCode::Text, None, &b"Hello"[..], 7));
+        let masked = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+        let (frame, len) = decode_frame(masked, 125).unwrap().unwrap();
+        assert_eq!((&frame.payload[..], len), (&b"Hello"[..], 11));
+        assert_eq!(client_frame(OpCode::Text, true, b"Hello"), &masked[..]);
+        assert_eq!(decode_frame(&masked[..10], 125), Ok(None));
+        assert_eq!(decode_frame(masked, 4), Err(Error::TooBig));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // Reserved bits, reserved opcode, fragmented and long control frames.
+        assert_eq!(decode_frame(b"\xa1\x00", 125), Err(Error::Protocol));
+        assert_eq!(decode_frame(b"\x91\x00", 125), Err(Error::Protocol));
+        assert_eq!(decode_frame(b"\x83\x00", 125), Err(Error::Protocol));
+        assert_eq!(decode_frame(b"\x09\x00", 125), Err(Error::Protocol));
+        assert_eq!(decode_frame(b"\x89\x7e\x00\x7e", 1024), Err(Error::Protocol));
+        // Lengths must use the minimal encoding.
+        assert_eq!(decode_frame(b"\x82\x7e\x00\x05", 1024), Err(Error::Protocol));
+    }
+
+    #[test]
+    fn test_encode_lengths() {
+        for &(len, header) in &[(125, 2), (126, 4), (65535, 4), (65536, 10)] {
+            let mut output = Vec::new();
+            encode_frame(&Frame::new(OpCode::Binary, vec![0; len]), &mut output);
+            assert_eq!(output.len(), header + len);
+            let (frame, used) = decode_frame(&output, 1 << 20).unwrap().unwrap();
+            assert_eq!((frame.payload.len(), used), (len, output.len()));
+        }
+    }
+
+    #[test]
+    fn test_fragmented_message() {
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.receive(&client_frame(OpCode::Text, false, b"Hel"));
+        connection.receive(&client_frame(OpCode::Ping, true, b"x"));
+        connection.receive(&client_frame(OpCode::Continuation, true, b"lo"));
+        assert_eq!(connection.next_message(), Ok(Some(Message::Ping(b"x".to_vec()))));
+        assert_eq!(connection.take_output(), b"\x8a\x01x");
+        assert_eq!(connection.next_message(), Ok(Some(Message::Text("Hello".to_owned()))));
+        assert_eq!(connection.next_message(), Ok(None));
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        // Servers require masked frames.
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.receive(b"\x81\x00");
+        assert_eq!(connection.next_message(), Err(Error::Protocol));
+        assert!(connection.is_closed());
+        assert_eq!(connection.take_output(), b"\x88\x02\x03\xea");
+        // Invalid UTF-8 in a text message.
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.receive(&client_frame(OpCode::Text, true, b"\xff"));
+        assert_eq!(connection.next_message(), Err(Error::InvalidPayload));
+        // A continuation without a started message.
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.receive(&client_frame(OpCode::Continuation, true, b""));
+        assert_eq!(connection.next_message(), Err(Error::Protocol));
+    }
+
+    #[test]
+    fn test_client_masks_frames() {
+        let mut connection = Connection::new(Role::Client, Config::default());
+        connection.send(Message::Binary(vec![0; 4])).unwrap();
+        let output = connection.take_output();
+        let (frame, _) = decode_frame(&output, 125).unwrap().unwrap();
+        assert!(frame.mask.is_some());
+        assert_eq!(frame.payload, vec![0; 4]);
+    }
+
+    #[test]
+    fn test_send_fragments() {
+        let config = Config { fragment_size: 2, ..Config::default() };
+        let mut connection = Connection::new(Role::Server, config);
+        connection.send(Message::Text("abcde".to_owned())).unwrap();
+        assert_eq!(connection.take_output(), b"\x01\x02ab\x00\x02cd\x80\x01e");
+    }
+
+    #[test]
+    fn test_closing_handshake() {
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.close(CloseCode::NORMAL, "bye").unwrap();
+        assert_eq!(connection.take_output(), b"\x88\x05\x03\xe8bye");
+        assert_eq!(connection.send(Message::Text(String::new())), Err(Error::Closed));
+        // Data frames still in flight are discarded.
+        connection.receive(&client_frame(OpCode::Text, true, b"late"));
+        connection.receive(&client_frame(OpCode::Close, true, b"\x03\xe8"));
+        let close = CloseFrame {
+            code: CloseCode::NORMAL,
+            reason: String::new(),
+        };
+        assert_eq!(connection.next_message(), Ok(Some(Message::Close(Some(close)))));
+        assert!(connection.is_closed());
+        assert_eq!(connection.take_output(), b"");
+    }
+
+    #[test]
+    fn test_echo_close() {
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.receive(&client_frame(OpCode::Close, true, b"\x03\xe9gone"));
+        assert!(matches!(connection.next_message(), Ok(Some(Message::Close(Some(_))))));
+        assert_eq!(connection.take_output(), b"\x88\x02\x03\xe9");
+        assert!(connection.is_closed());
+    }
+
+    #[test]
+    fn test_compressed_message() {
+        let mut frame = Frame::new(OpCode::Text, b"\xf2\x48\xcd\xc9\xc9\x07\x00".to_vec());
+        frame.rsv1 = true;
+        frame.fin = false;
+        frame.mask = Some([0x37, 0xfa, 0x21, 0x3d]);
+        let mut input = Vec::new();
+        encode_frame(&frame, &mut input);
+        assert_eq!(input[0], 0x41);
+        assert_eq!(decode_frame(&input, 125).unwrap().unwrap().0, frame);
+        input.extend(client_frame(OpCode::Continuation, true, b""));
+
+        // The RSV1 bit is only allowed after negotiating permessage-deflate.
+        let mut connection = Connection::new(Role::Server, Config::default());
+        connection.receive(&input);
+        assert_eq!(connection.next_message(), Err(Error::Protocol));
+        let config = Config { per_message_deflate: true, ..Config::default() };
+        let mut connection = Connection::new(Role::Server, config);
+        connection.receive(&input);
+        assert_eq!(connection.next_message(),
+                   Ok(Some(Message::Compressed(OpCode::Text, frame.payload.clone()))));
+        // Control frames and continuations are never compressed.
+        let mut ping = Frame::new(OpCode::Ping, Vec::new());
+        ping.rsv1 = true;
+        ping.mask = frame.mask;
+        let mut input = Vec::new();
+        encode_frame(&ping, &mut input);
+        connection.receive(&input);
+        assert_eq!(connection.next_message(), Err(Error::Protocol));
+
+        let mut connection = Connection::new(Role::Server, config);
+        connection.send(Message::Compressed(OpCode::Binary, vec![0; 4])).unwrap();
+        assert_eq!(connection.take_output(), b"\xc2\x04\x00\x00\x00\x00");
+        assert_eq!(connection.send(Message::Compressed(OpCode::Ping, Vec::new())),
+                   Err(Error::Protocol));
+        let mut connection = Connection::new(Role::Server, Config::default());
+        assert_eq!(connection.send(Message::Compressed(OpCode::Binary, Vec::new())),
+                   Err(Error::Protocol));
+    }
+}
@@ -0,0 +1,534 @@
+//! WebSocket opening handshake and frame codec.
+//!
+//! A WebSocket connection starts with an HTTP/1.1 upgrade handshake,
+//! see [`WebSocketRequest`](struct.WebSocketRequest.html). Afterwards
+//! both endpoints exchange frames, see
+//! [`Connection`](struct.Connection.html). [RFC6455]
+
+use std::fmt::{self, Display};
+use std::io;
+
+use header::{Header, Headers, SecWebSocketAccept, SecWebSocketExtensions, SecWebSocketKey,
+             SecWebSocketProtocol, SecWebSocketVersion};
+use header::item::WebSocketExtension;
+use {Method, Status, Version};
+
+pub use self::codec::{CloseCode, CloseFrame, Config, Connection, Error, Frame, Message, OpCode,
+                      Role, decode_frame, encode_frame};
+
+mod codec;
+
+// The only version in use. [RFC6455 Section 4.1]
+const VERSION: u8 = 13;
+
+/// An error in a WebSocket opening handshake.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HandshakeError {
+    /// The request method is not `GET`.
+    InvalidMethod,
+    /// The request is not HTTP/1.1.
+    InvalidVersion,
+    /// The `Host` header field is missing.
+    MissingHost,
+    /// `Upgrade: websocket` or `Connection: Upgrade` is missing.
+    MissingUpgrade,
+    /// The `Sec-WebSocket-Key` is missing or invalid.
+    InvalidKey,
+    /// The `Sec-WebSocket-Version` is not supported.
+    UnsupportedVersion,
+    /// The `Sec-WebSocket-Protocol` is invalid or was not requested.
+    InvalidProtocol,
+    /// The `Sec-WebSocket-Extensions` are invalid or were not offered.
+    InvalidExtensions,
+    /// The response status is not `101 Switching Protocols`.
+    InvalidStatus,
+    /// The `Sec-WebSocket-Accept` does not match the key.
+    InvalidAccept,
+}
+
+impl HandshakeError {
+    /// Returns the response a server sends for a rejected request.
+    ///
+    /// An unsupported version is answered with `426 Upgrade Required`
+    /// listing the supported version, all other errors with
+    /// `400 Bad Request`.
+    pub fn response(&self) -> WebSocketResponse {
+        let mut headers = Headers::new();
+        let status = if *self == HandshakeError::UnsupportedVersion {
+            headers.set_valid(&SecWebSocketVersion::from(vec![VERSION]));
+            Status::UPGRADE_REQUIRED
+        } else {
+            Status::BAD_REQUEST
+        };
+        WebSocketResponse { status, headers }
+    }
+}
+
+impl Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            HandshakeError::InvalidMethod => "WebSocket handshake requires GET",
+            HandshakeError::InvalidVersion => "WebSocket handshake requires HTTP/1.1",
+            HandshakeError::MissingHost => "missing Host",
+            HandshakeError::MissingUpgrade => "missing WebSocket upgrade",
+            HandshakeError::InvalidKey => "invalid Sec-WebSocket-Key",
+            HandshakeError::UnsupportedVersion => "unsupported WebSocket version",
+            HandshakeError::InvalidProtocol => "invalid Sec-WebSocket-Protocol",
+            HandshakeError::InvalidExtensions => "invalid Sec-WebSocket-Extensions",
+            HandshakeError::InvalidStatus => "WebSocket handshake response is not 101",
+            HandshakeError::InvalidAccept => "invalid Sec-WebSocket-Accept",
+        })
+    }
+}
+
+/// The status and header fields of a handshake response.
+#[derive(Clone, Debug)]
+pub struct WebSocketResponse {
+    /// The response status.
+    pub status: Status,
+    /// The header fields to add to the response.
+    pub headers: Headers,
+}
+
+/// The parameters of the `permessage-deflate` extension, [RFC7692]
+///
+/// As server configuration the fields are the strictest parameters
+/// the server wants, after negotiation they are the parameters agreed
+/// on. Window sizes are given in bits from 8 to 15.
+///
+/// Only the negotiation is implemented. After agreeing on the
+/// extension set [`Config::per_message_deflate`](struct.Config.html)
+/// and compress messages with an external DEFLATE codec.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PerMessageDeflate {
+    /// The server resets its compression context for each message.
+    pub server_no_context_takeover: bool,
+    /// The client resets its compression context for each message.
+    pub client_no_context_takeover: bool,
+    /// The LZ77 window size the server compresses with.
+    pub server_max_window_bits: u8,
+    /// The LZ77 window size the client compresses with.
+    pub client_max_window_bits: u8,
+}
+
+impl Default for PerMessageDeflate {
+    fn default() -> PerMessageDeflate {
+        PerMessageDeflate {
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            server_max_window_bits: 15,
+            client_max_window_bits: 15,
+        }
+    }
+}
+
+// The parameters of a client offer. `client_max_window_bits` may
+// be present without a value.
+#[derive(Clone, Copy, Debug)]
+struct DeflateOffer {
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    server_max_window_bits: Option<u8>,
+    client_max_window_bits: Option<Option<u8>>,
+}
+
+const DEFLATE: &str = "permessage-deflate";
+
+impl PerMessageDeflate {
+    /// Returns the extension a client offers.
+    ///
+    /// `client_max_window_bits` is always sent to tell the server
+    /// the client can use a smaller window.
+    pub fn offer(&self) -> WebSocketExtension {
+        let mut extension = self.to_extension();
+        if self.client_max_window_bits == 15 {
+            extension.parameters.push(("client_max_window_bits".to_owned(), None));
+        }
+        extension
+    }
+
+    /// Returns the extension a server responds with.
+    pub fn to_extension(&self) -> WebSocketExtension {
+        let mut extension = WebSocketExtension {
+            name: DEFLATE.to_owned(),
+            parameters: Vec::new(),
+        };
+        if self.server_no_context_takeover {
+            extension.parameters.push(("server_no_context_takeover".to_owned(), None));
+        }
+        if self.client_no_context_takeover {
+            extension.parameters.push(("client_no_context_takeover".to_owned(), None));
+        }
+        if self.server_max_window_bits < 15 {
+            extension.parameters.push(("server_max_window_bits".to_owned(),
+                                       Some(self.server_max_window_bits.to_string())));
+        }
+        if self.client_max_window_bits < 15 {
+            extension.parameters.push(("client_max_window_bits".to_owned(),
+                                       Some(self.client_max_window_bits.to_string())));
+        }
+        extension
+    }
+
+    /// Parses the parameters of an extension the server responded with.
+    pub fn from_extension(extension: &WebSocketExtension) -> Result<PerMessageDeflate, ()> {
+        let offer = parse_offer(extension)?;
+        Ok(PerMessageDeflate {
+            server_no_context_takeover: offer.server_no_context_takeover,
+            client_no_context_takeover: offer.client_no_context_takeover,
+            server_max_window_bits: offer.server_max_window_bits.unwrap_or(15),
+            client_max_window_bits: match offer.client_max_window_bits {
+                Some(Some(bits)) => bits,
+                Some(None) => return Err(()),
+                None => 15,
+            },
+        })
+    }
+
+    // Accepts an offer with this server configuration if possible.
+    // [RFC7692 Section 7.1]
+    fn accept(&self, offer: &DeflateOffer) -> Option<PerMessageDeflate> {
+        let client_max_window_bits = match offer.client_max_window_bits {
+            Some(bits) => bits.unwrap_or(15).min(self.client_max_window_bits),
+            // The server must not limit a client that did not offer it.
+            None if self.client_max_window_bits < 15 => return None,
+            None => 15,
+        };
+        Some(PerMessageDeflate {
+            server_no_context_takeover: offer.server_no_context_takeover ||
+                                        self.server_no_context_takeover,
+            client_no_context_takeover: offer.client_no_context_takeover ||
+                                        self.client_no_context_takeover,
+            // The server may use a smaller window without telling.
+            server_max_window_bits: offer.server_max_window_bits
+                .map_or(15, |x| x.min(self.server_max_window_bits)),
+            client_max_window_bits,
+        })
+    }
+
+    // Checks if this response is a valid answer to the offer.
+    fn answers(&self, offer: &DeflateOffer) -> bool {
+        (self.server_no_context_takeover || !offer.server_no_context_takeover) &&
+        (self.server_max_window_bits == 15 ||
+         offer.server_max_window_bits.is_some_and(|x| self.server_max_window_bits <= x)) &&
+        (self.client_max_window_bits == 15 ||
+         offer.client_max_window_bits
+            .is_some_and(|x| self.client_max_window_bits <= x.unwrap_or(15)))
+    }
+}
+
+fn parse_offer(extension: &WebSocketExtension) -> Result<DeflateOffer, ()> {
+    if !extension.name.eq_ignore_ascii_case(DEFLATE) {
+        return Err(());
+    }
+    let mut offer = DeflateOffer {
+        server_no_context_takeover: false,
+        client_no_context_takeover: false,
+        server_max_window_bits: None,
+        client_max_window_bits: None,
+    };
+    let bits = |value: &Option<String>| -> Result<u8, ()> {
+        let value = value.as_ref().ok_or(())?;
+        match value.parse() {
+            Ok(bits) if (8..=15).contains(&bits) && !value.starts_with('0') => Ok(bits),
+            _ => Err(()),
+        }
+    };
+    for (i, (name, value)) in extension.parameters.iter().enumerate() {
+        // Each parameter may only occur once.
+        if extension.parameters[..i].iter().any(|x| x.0.eq_ignore_ascii_case(name)) {
+            return Err(());
+        }
+        match &name.to_ascii_lowercase()[..] {
+            "server_no_context_takeover" if value.is_none() => {
+                offer.server_no_context_takeover = true
+            }
+            "client_no_context_takeover" if value.is_none() => {
+                offer.client_no_context_takeover = true
+            }
+            "server_max_window_bits" => offer.server_max_window_bits = Some(bits(value)?),
+            "client_max_window_bits" if value.is_none() => {
+                offer.client_max_window_bits = Some(None)
+            }
+            "client_max_window_bits" => offer.client_max_window_bits = Some(Some(bits(value)?)),
+            _ => return Err(()),
+        }
+    }
+    Ok(offer)
+}
+
+/// The outcome of a successful handshake.
+#[derive(Clone, Debug, Default)]
+pub struct Negotiated {
+    /// The selected subprotocol.
+    pub protocol: Option<String>,
+    /// The agreed `permessage-deflate` parameters.
+    pub deflate: Option<PerMessageDeflate>,
+    /// All accepted extensions in order.
+    pub extensions: Vec<WebSocketExtension>,
+}
+
+/// A WebSocket opening handshake request, [RFC6455 Section 4]
+///
+/// Servers validate received requests with
+/// [`validate`](#method.validate) and answer them with
+/// [`accept`](#method.accept). Clients construct requests with
+/// [`new`](#method.new) and validate the response with
+/// [`validate_response`](#method.validate_response).
+#[derive(Clone, Debug)]
+pub struct WebSocketRequest {
+    /// The nonce of the client.
+    pub key: SecWebSocketKey,
+    /// The requested subprotocols in order of preference.
+    pub protocols: Vec<String>,
+    /// The offered extensions in order of preference.
+    pub extensions: Vec<WebSocketExtension>,
+}
+
+impl WebSocketRequest {
+    /// Constructs a request with a random key.
+    pub fn new(protocols: Vec<String>, extensions: Vec<WebSocketExtension>) -> WebSocketRequest {
+        WebSocketRequest {
+            key: SecWebSocketKey::random(),
+            protocols,
+            extensions,
+        }
+    }
+
+    /// Returns the header fields of the request.
+    ///
+    /// `Host` and `Origin` are not included. Fails if a subprotocol or
+    /// extension cannot be serialized.
+    pub fn headers(&self) -> io::Result<Headers> {
+        let mut headers = Headers::new();
+        headers.set_raw("Upgrade", vec![b"websocket".to_vec()]);
+        headers.set_raw("Connection", vec![b"Upgrade".to_vec()]);
+        headers.set_valid(&self.key);
+        headers.set_valid(&SecWebSocketVersion::from(vec![VERSION]));
+        if !self.protocols.is_empty() {
+            headers.set(&SecWebSocketProtocol::from(self.protocols.clone()))?;
+        }
+        if !self.extensions.is_empty() {
+            headers.set(&SecWebSocketExtensions::from(self.extensions.clone()))?;
+        }
+        Ok(headers)
+    }
+
+    /// Validates a received handshake request.
+    pub fn validate(method: &Method,
+                    version: Version,
+                    headers: &Headers)
+                    -> Result<WebSocketRequest, HandshakeError> {
+        if *method != Method::Get {
+            return Err(HandshakeError::InvalidMethod);
+        }
+        if version != Version::Http11 {
+            return Err(HandshakeError::InvalidVersion);
+        }
+        if !headers.contains("Host") {
+            return Err(HandshakeError::MissingHost);
+        }
+        if !headers.contains_token("Upgrade", "websocket") ||
+           !headers.contains_token("Connection", "Upgrade") {
+            return Err(HandshakeError::MissingUpgrade);
+        }
+        let key = get::<SecWebSocketKey>(headers)
+            .and_then(Result::ok)
+            .ok_or(HandshakeError::InvalidKey)?;
+        if get_list::<SecWebSocketVersion, _>(headers) != Ok(vec![VERSION]) {
+            return Err(HandshakeError::UnsupportedVersion);
+        }
+        let protocols = get_list::<SecWebSocketProtocol, _>(headers)
+            .map_err(|()| HandshakeError::InvalidProtocol)?;
+        let extensions = get_list::<SecWebSocketExtensions, _>(headers)
+            .map_err(|()| HandshakeError::InvalidExtensions)?;
+        Ok(WebSocketRequest {
+            key,
+            protocols,
+            extensions,
+        })
+    }
+
+    /// Selects the first of the supported subprotocols the client requested.
+    ///
+    /// The supported subprotocols are given in order of preference.
+    pub fn select_protocol<'a>(&self, supported: &[&'a str]) -> Option<&'a str> {
+        supported.iter().find(|x| self.protocols.iter().any(|y| y == *x)).cloned()
+    }
+
+    /// Accepts the first valid `permessage-deflate` offer compatible
+    /// with the server configuration.
+    pub fn negotiate_deflate(&self, config: &PerMessageDeflate) -> Option<PerMessageDeflate> {
+        self.extensions
+            .iter()
+            .filter_map(|x| parse_offer(x).ok())
+            .filter_map(|x| config.accept(&x))
+            .next()
+    }
+
+    /// Builds the `101 Switching Protocols` response.
+    ///
+    /// The protocol should be selected with
+    /// [`select_protocol`](#method.select_protocol) and the extensions
+    /// negotiated like [`negotiate_deflate`](#method.negotiate_deflate).
+    /// Fails if the subprotocol or an extension cannot be serialized.
+    pub fn accept(&self,
+                  protocol: Option<&str>,
+                  extensions: Vec<WebSocketExtension>)
+                  -> io::Result<WebSocketResponse> {
+        let mut headers = Headers::new();
+        headers.set_raw("Upgrade", vec![b"websocket".to_vec()]);
+        headers.set_raw("Connection", vec![b"Upgrade".to_vec()]);
+        headers.set_valid(&self.key.accept());
+        if let Some(protocol) = protocol {
+            headers.set(&SecWebSocketProtocol::from(vec![protocol.to_owned()]))?;
+        }
+        if !extensions.is_empty() {
+            headers.set(&SecWebSocketExtensions::from(extensions))?;
+        }
+        Ok(WebSocketResponse {
+            status: Status::SWITCHING_PROTOCOLS,
+            headers,
+        })
+    }
+
+    /// Validates the response of the server to this request.
+    pub fn validate_response(&self,
+                             status: Status,
+                             headers: &Headers)
+                             -> Result<Negotiated, HandshakeError> {
+        if status != Status::SWITCHING_PROTOCOLS {
+            return Err(HandshakeError::InvalidStatus);
+        }
+        if !headers.contains_token("Upgrade", "websocket") ||
+           !headers.contains_token("Connection", "Upgrade") {
+            return Err(HandshakeError::MissingUpgrade);
+        }
+        match get::<SecWebSocketAccept>(headers) {
+            Some(Ok(ref accept)) if accept.matches(&self.key) => (),
+            _ => return Err(HandshakeError::InvalidAccept),
+        }
+        let mut protocols = get_list::<SecWebSocketProtocol, _>(headers)
+            .map_err(|()| HandshakeError::InvalidProtocol)?;
+        if protocols.len() > 1 || !protocols.iter().all(|x| self.protocols.contains(x)) {
+            return Err(HandshakeError::InvalidProtocol);
+        }
+        let extensions = get_list::<SecWebSocketExtensions, _>(headers)
+            .map_err(|()| HandshakeError::InvalidExtensions)?;
+        let mut deflate = None;
+        for (i, extension) in extensions.iter().enumerate() {
+            if !self.extensions.iter().any(|x| x.name.eq_ignore_ascii_case(&extension.name)) ||
+               extensions[..i].iter().any(|x| x.name.eq_ignore_ascii_case(&extension.name)) {
+                return Err(HandshakeError::InvalidExtensions);
+            }
+            if extension.name.eq_ignore_ascii_case(DEFLATE) {
+                let params = PerMessageDeflate::from_extension(extension)
+                    .map_err(|()| HandshakeError::InvalidExtensions)?;
+                if !self.extensions.iter().filter_map(|x| parse_offer(x).ok()).any(|x| params.answers(&x)) {
+                    return Err(HandshakeError::InvalidExtensions);
+                }
+                deflate = Some(params);
+            }
+        }
+        Ok(Negotiated {
+            protocol: protocols.pop(),
+            deflate,
+            extensions,
+        })
+    }
+}
+
+// None of the handshake header fields contain URI references.
+fn get<H: Header>(headers: &Headers) -> Option<Result<H, ()>> {
+    headers.get_without_base()
+}
+
+fn get_list<H: Header + Into<Vec<T>>, T>(headers: &Headers) -> Result<Vec<T>, ()> {
+    match get::<H>(headers) {
+        Some(header) => header.map(Into::into),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use header::item::WebSocketExtension;
+    use {Method, Status, Version};
+    use super::{Config, Connection, HandshakeError, Message, OpCode, PerMessageDeflate, Role,
+                WebSocketRequest};
+
+    fn request_headers(request: &WebSocketRequest) -> Headers {
+        let mut headers = request.headers().unwrap();
+        headers.set_raw("Host", vec![b"example.com".to_vec()]);
+        headers
+    }
+
+    #[test]
+    fn test_handshake() {
+        let client = WebSocketRequest::new(vec!["chat".to_owned(), "superchat".to_owned()],
+                                           vec![PerMessageDeflate::default().offer()]);
+        let headers = request_headers(&client);
+        let server = WebSocketRequest::validate(&Method::Get, Version::Http11, &headers).unwrap();
+        assert_eq!(server.protocols, client.protocols);
+        assert_eq!(server.extensions, client.extensions);
+
+        let protocol = server.select_protocol(&["superchat", "chat"]);
+        assert_eq!(protocol, Some("superchat"));
+        let deflate = server.negotiate_deflate(&PerMessageDeflate::default()).unwrap();
+        let response = server.accept(protocol, vec![deflate.to_extension()]).unwrap();
+        let negotiated = client.validate_response(response.status, &response.headers).unwrap();
+        assert_eq!(negotiated.protocol, Some("superchat".to_owned()));
+        assert_eq!(negotiated.deflate, Some(PerMessageDeflate::default()));
+
+        // Both endpoints accept compressed messages after agreeing on them.
+        let config = Config {
+            per_message_deflate: negotiated.deflate.is_some(),
+            ..Config::default()
+        };
+        let mut client = Connection::new(Role::Client, config);
+        let mut server = Connection::new(Role::Server, config);
+        let compressed = b"\xf2\x48\xcd\xc9\xc9\x07\x00".to_vec();
+        client.send(Message::Compressed(OpCode::Text, compressed.clone())).unwrap();
+        server.receive(&client.take_output());
+        assert_eq!(server.next_message(),
+                   Ok(Some(Message::Compressed(OpCode::Text, compressed))));
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let client = WebSocketRequest::new(Vec::new(), Vec::new());
+        let mut headers = request_headers(&client);
+        assert_eq!(WebSocketRequest::validate(&Method::Post, Version::Http11, &headers).unwrap_err(),
+                   HandshakeError::InvalidMethod);
+        headers.set_raw("Sec-WebSocket-Version", vec![b"8".to_vec()]);
+        assert_eq!(WebSocketRequest::validate(&Method::Get, Version::Http11, &headers).unwrap_err(),
+                   HandshakeError::UnsupportedVersion);
+        headers.remove_raw("Host");
+        assert_eq!(WebSocketRequest::validate(&Method::Get, Version::Http11, &headers).unwrap_err(),
+                   HandshakeError::MissingHost);
+    }
+
+    #[test]
+    fn test_invalid_response() {
+        let client = WebSocketRequest::new(Vec::new(), Vec::new());
+        let mut response = client.accept(None, Vec::new()).unwrap();
+        assert!(client.validate_response(response.status, &response.headers).is_ok());
+        assert_eq!(client.validate_response(Status::OK, &response.headers).unwrap_err(),
+                   HandshakeError::InvalidStatus);
+
+        response.headers.remove_raw("Connection");
+        assert_eq!(client.validate_response(response.status, &response.headers).unwrap_err(),
+                   HandshakeError::MissingUpgrade);
+
+        let other = WebSocketRequest::new(Vec::new(), Vec::new());
+        let response = other.accept(None, Vec::new()).unwrap();
+        assert_eq!(client.validate_response(response.status, &response.headers).unwrap_err(),
+                   HandshakeError::InvalidAccept);
+
+        let extension = WebSocketExtension::new("x-foo").unwrap();
+        let response = client.accept(Some("chat"), vec![extension]).unwrap();
+        assert_eq!(client.validate_response(response.status, &response.headers).unwrap_err(),
+                   HandshakeError::InvalidProtocol);
+    }
+}